@@ -0,0 +1,36 @@
+//! Allocate-per-call `encode` vs `encode_into` with a reused buffer, for
+//! the small frames (heartbeats, acks) sent most often.
+//!
+//! Run with `cargo bench --bench ecnp_encode`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use edgeclaw_core::ecnp::EcnpCodec;
+use edgeclaw_core::protocol::MessageType;
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ecnp_encode");
+    for size in [0usize, 16, 64, 1024] {
+        let payload = vec![0x5Au8; size];
+
+        group.bench_with_input(
+            BenchmarkId::new("allocate", size),
+            &payload,
+            |b, payload| {
+                b.iter(|| EcnpCodec::encode(MessageType::Heartbeat, black_box(payload)).unwrap())
+            },
+        );
+
+        let mut buf = Vec::new();
+        group.bench_with_input(BenchmarkId::new("reuse", size), &payload, |b, payload| {
+            b.iter(|| {
+                EcnpCodec::encode_into(MessageType::Heartbeat, black_box(payload), &mut buf)
+                    .unwrap();
+                black_box(&buf);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode);
+criterion_main!(benches);
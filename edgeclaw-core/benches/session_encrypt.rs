@@ -0,0 +1,75 @@
+//! Concurrent session encryption: the engine's old layout (one `Mutex`
+//! around the whole `SessionManager`) vs the current one (a shared
+//! `RwLock` read with a lock per session), each thread on its own session.
+//!
+//! Run with `cargo bench --bench session_encrypt`. The gap only shows on a
+//! multi-core host; on one core both layouts serialize anyway.
+
+use std::sync::{Mutex, RwLock};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use edgeclaw_core::kex;
+use edgeclaw_core::session::SessionManager;
+
+/// Encryptions each thread runs per measured iteration
+const OPS_PER_THREAD: usize = 200;
+
+fn manager_with_sessions(count: usize) -> (SessionManager, Vec<String>) {
+    let kex = kex::x25519();
+    let mut manager = SessionManager::new();
+    let ids = (0..count)
+        .map(|i| {
+            let local = kex.generate_keypair();
+            let remote = kex.generate_keypair();
+            manager
+                .create_session(&format!("peer-{i}"), &local.secret, &remote.public)
+                .unwrap()
+                .session_id
+        })
+        .collect();
+    (manager, ids)
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    let payload = vec![0x5Au8; 1024];
+    let mut group = c.benchmark_group("session_encrypt");
+    for threads in [1usize, 4, 16] {
+        let (manager, ids) = manager_with_sessions(threads);
+
+        let single = Mutex::new(manager);
+        group.bench_with_input(BenchmarkId::new("single_lock", threads), &ids, |b, ids| {
+            b.iter(|| {
+                std::thread::scope(|s| {
+                    for id in ids {
+                        s.spawn(|| {
+                            for _ in 0..OPS_PER_THREAD {
+                                let manager = single.lock().unwrap();
+                                black_box(manager.encrypt(id, black_box(&payload)).unwrap());
+                            }
+                        });
+                    }
+                })
+            })
+        });
+
+        let shared = RwLock::new(single.into_inner().unwrap());
+        group.bench_with_input(BenchmarkId::new("per_session", threads), &ids, |b, ids| {
+            b.iter(|| {
+                std::thread::scope(|s| {
+                    for id in ids {
+                        s.spawn(|| {
+                            for _ in 0..OPS_PER_THREAD {
+                                let manager = shared.read().unwrap();
+                                black_box(manager.encrypt(id, black_box(&payload)).unwrap());
+                            }
+                        });
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encrypt);
+criterion_main!(benches);
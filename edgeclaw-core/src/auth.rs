@@ -0,0 +1,476 @@
+//! Challenge-response peer authentication.
+//!
+//! Proves a peer controls the Ed25519 key we have on file for it: we send a
+//! random nonce, the peer signs it, and we verify the signature. Challenges
+//! are single-use and expire after a short TTL.
+//!
+//! Also provides signed peer attestations, letting a peer that trusts both
+//! sides vouch for one peer's identity key when introducing it to another,
+//! and a ledger of issued attestations with a revocation list.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::ecnp::EcnpCodec;
+use crate::error::EdgeClawError;
+use crate::protocol::MessageType;
+use crate::time::{self, TimeSource};
+
+/// Default lifetime of an issued challenge
+pub const DEFAULT_CHALLENGE_TTL_SECS: i64 = 60;
+
+const NONCE_SIZE: usize = 32;
+const CHALLENGE_CONTEXT: &[u8] = b"edgeclaw-auth-v1:";
+
+/// Default validity of a peer attestation (7 days)
+pub const DEFAULT_ATTESTATION_TTL_SECS: i64 = 7 * 24 * 3600;
+
+const ATTESTATION_CONTEXT: &[u8] = b"edgeclaw-attest-v1:";
+
+/// Issued attestations kept by `AttestationLedger`; older ones are dropped
+/// from the list (revocations are kept separately)
+pub const ISSUED_ATTESTATION_CAPACITY: usize = 256;
+
+/// Challenge handed to the caller for delivery to the peer
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuthChallenge {
+    pub challenge_id: String,
+    /// ECNP Control frame carrying an `AuthChallengePayload`
+    pub nonce_frame: Vec<u8>,
+}
+
+/// JSON payload of a challenge frame
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuthChallengePayload {
+    pub challenge_id: String,
+    /// Hex-encoded random nonce
+    pub nonce: String,
+}
+
+/// Bytes a peer signs to answer a challenge (domain-separated nonce)
+pub fn challenge_message(nonce: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(CHALLENGE_CONTEXT.len() + nonce.len());
+    msg.extend_from_slice(CHALLENGE_CONTEXT);
+    msg.extend_from_slice(nonce);
+    msg
+}
+
+/// Parse a challenge frame produced by `ChallengeStore::issue`
+pub fn parse_challenge_frame(frame: &[u8]) -> Result<AuthChallengePayload, EdgeClawError> {
+    let msg = EcnpCodec::decode(frame)?;
+    if msg.msg_type != MessageType::Control as u8 {
+        return Err(EdgeClawError::InvalidParameter);
+    }
+    Ok(serde_json::from_slice(&msg.payload)?)
+}
+
+/// Statement by `attester_id` that `subject_id` holds `subject_pubkey`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PeerAttestation {
+    pub attester_id: String,
+    pub subject_id: String,
+    /// Hex-encoded Ed25519 identity key of the subject
+    pub subject_pubkey: String,
+    /// RFC 3339 issue time
+    pub timestamp: String,
+    /// RFC 3339 time after which the attestation is void
+    pub expires_at: String,
+    /// Hex-encoded Ed25519 signature over `signing_bytes()`
+    pub signature: String,
+}
+
+impl PeerAttestation {
+    /// Unsigned attestation issued at `now`, valid for `ttl_secs`
+    pub fn new(
+        attester_id: &str,
+        subject_id: &str,
+        subject_pubkey: &str,
+        ttl_secs: i64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            attester_id: attester_id.to_string(),
+            subject_id: subject_id.to_string(),
+            subject_pubkey: subject_pubkey.to_string(),
+            timestamp: now.to_rfc3339(),
+            expires_at: (now + chrono::Duration::seconds(ttl_secs)).to_rfc3339(),
+            signature: String::new(),
+        }
+    }
+
+    /// Bytes the attester signs: every field except the signature,
+    /// length-prefixed so no two attestations share an encoding
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let fields = [
+            &self.attester_id,
+            &self.subject_id,
+            &self.subject_pubkey,
+            &self.timestamp,
+            &self.expires_at,
+        ];
+        let mut bytes = ATTESTATION_CONTEXT.to_vec();
+        for field in fields {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes
+    }
+
+    /// Stable ID: hex of the first 16 bytes of SHA-256 over
+    /// `signing_bytes()`, so it is known before signing
+    pub fn id(&self) -> String {
+        hex::encode(&Sha256::digest(self.signing_bytes())[..16])
+    }
+
+    /// Check the attester's signature, then the expiry as of `now`.
+    ///
+    /// A bad signature returns `CryptoError`, an expired attestation
+    /// `TimeoutError` and an unparsable expiry `InvalidParameter`.
+    pub fn verify(
+        &self,
+        attester_key: &VerifyingKey,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), EdgeClawError> {
+        let signature = hex::decode(&self.signature).map_err(|_| EdgeClawError::CryptoError)?;
+        let signature =
+            Signature::from_slice(&signature).map_err(|_| EdgeClawError::CryptoError)?;
+        attester_key
+            .verify(&self.signing_bytes(), &signature)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+
+        let expires_at = chrono::DateTime::parse_from_rfc3339(&self.expires_at)
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        if now >= expires_at {
+            return Err(EdgeClawError::TimeoutError);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a JSON attestation and verify it against the attester's key
+/// as of `now`
+pub fn verify_attestation(
+    json: &str,
+    attester_key: &VerifyingKey,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<PeerAttestation, EdgeClawError> {
+    let attestation: PeerAttestation = serde_json::from_str(json)?;
+    attestation.verify(attester_key, now)?;
+    Ok(attestation)
+}
+
+/// An attestation this engine issued, as listed by `AttestationLedger`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct IssuedAttestation {
+    /// `PeerAttestation::id`
+    pub id: String,
+    pub subject_id: String,
+    pub timestamp: String,
+    pub expires_at: String,
+    pub revoked: bool,
+}
+
+/// Attestations we issued, plus the IDs of revoked attestations.
+///
+/// Any attestation ID can be revoked, including ones issued by other
+/// peers; `check` rejects revoked attestations with `Revoked`.
+#[derive(Debug, Default)]
+pub struct AttestationLedger {
+    issued: VecDeque<IssuedAttestation>,
+    revoked: HashSet<String>,
+}
+
+impl AttestationLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an attestation we just issued, dropping the oldest one
+    /// beyond `ISSUED_ATTESTATION_CAPACITY`
+    pub fn record(&mut self, attestation: &PeerAttestation) {
+        if self.issued.len() == ISSUED_ATTESTATION_CAPACITY {
+            self.issued.pop_front();
+        }
+        self.issued.push_back(IssuedAttestation {
+            id: attestation.id(),
+            subject_id: attestation.subject_id.clone(),
+            timestamp: attestation.timestamp.clone(),
+            expires_at: attestation.expires_at.clone(),
+            revoked: false,
+        });
+    }
+
+    /// Issued attestations, oldest first (at most
+    /// `ISSUED_ATTESTATION_CAPACITY`)
+    pub fn list_issued(&self) -> Vec<IssuedAttestation> {
+        self.issued
+            .iter()
+            .map(|issued| IssuedAttestation {
+                revoked: self.revoked.contains(&issued.id),
+                ..issued.clone()
+            })
+            .collect()
+    }
+
+    /// Revoke an attestation by ID; returns false if it was already revoked
+    pub fn revoke(&mut self, id: &str) -> bool {
+        self.revoked.insert(id.to_string())
+    }
+
+    pub fn is_revoked(&self, id: &str) -> bool {
+        self.revoked.contains(id)
+    }
+
+    /// Fail with `Revoked` if the attestation has been revoked
+    pub fn check(&self, attestation: &PeerAttestation) -> Result<(), EdgeClawError> {
+        if self.is_revoked(&attestation.id()) {
+            return Err(EdgeClawError::Revoked);
+        }
+        Ok(())
+    }
+
+    /// Revoked IDs, sorted, for persistence
+    pub fn revocations(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.revoked.iter().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Replace the revocation list with a persisted one
+    pub fn set_revocations(&mut self, ids: Vec<String>) {
+        self.revoked = ids.into_iter().collect();
+    }
+}
+
+/// Outstanding challenge
+struct PendingChallenge {
+    peer_id: String,
+    nonce: [u8; NONCE_SIZE],
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Issues and redeems single-use authentication challenges
+pub struct ChallengeStore {
+    pending: HashMap<String, PendingChallenge>,
+    ttl_secs: i64,
+    clock: Arc<dyn TimeSource>,
+}
+
+impl Default for ChallengeStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHALLENGE_TTL_SECS)
+    }
+}
+
+impl ChallengeStore {
+    pub fn new(ttl_secs: i64) -> Self {
+        Self {
+            pending: HashMap::new(),
+            ttl_secs,
+            clock: time::system(),
+        }
+    }
+
+    /// Check challenge expiry against `clock`
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.clock = clock;
+    }
+
+    /// Issue a fresh challenge for `peer_id`
+    pub fn issue(&mut self, peer_id: &str) -> Result<AuthChallenge, EdgeClawError> {
+        let now = self.clock.now();
+        self.pending.retain(|_, c| c.expires_at > now);
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let challenge_id = uuid::Uuid::new_v4().to_string();
+
+        let payload = serde_json::to_vec(&AuthChallengePayload {
+            challenge_id: challenge_id.clone(),
+            nonce: hex::encode(nonce),
+        })?;
+        let nonce_frame = EcnpCodec::encode(MessageType::Control, &payload)?;
+
+        self.pending.insert(
+            challenge_id.clone(),
+            PendingChallenge {
+                peer_id: peer_id.to_string(),
+                nonce,
+                expires_at: now + chrono::Duration::seconds(self.ttl_secs),
+            },
+        );
+
+        Ok(AuthChallenge {
+            challenge_id,
+            nonce_frame,
+        })
+    }
+
+    /// Peer a pending challenge was issued to
+    pub fn peer_for(&self, challenge_id: &str) -> Option<&str> {
+        self.pending.get(challenge_id).map(|c| c.peer_id.as_str())
+    }
+
+    /// Redeem a challenge, consuming it whether or not the signature is valid.
+    ///
+    /// Unknown or already-used challenges return `InvalidParameter`, expired
+    /// ones `TimeoutError`, and a bad signature `CryptoError`.
+    pub fn verify(
+        &mut self,
+        challenge_id: &str,
+        peer_key: &VerifyingKey,
+        signature: &[u8],
+    ) -> Result<(), EdgeClawError> {
+        self.verify_with(challenge_id, signature, |_| Ok(*peer_key))
+    }
+
+    /// Like `verify`, looking up the key for the challenged peer with
+    /// `peer_key`. The challenge is consumed before the lookup, so it is
+    /// single-use even when the lookup fails.
+    pub fn verify_with(
+        &mut self,
+        challenge_id: &str,
+        signature: &[u8],
+        peer_key: impl FnOnce(&str) -> Result<VerifyingKey, EdgeClawError>,
+    ) -> Result<(), EdgeClawError> {
+        let challenge = self
+            .pending
+            .remove(challenge_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+
+        if self.clock.now() >= challenge.expires_at {
+            return Err(EdgeClawError::TimeoutError);
+        }
+        let peer_key = peer_key(&challenge.peer_id)?;
+
+        let signature = Signature::from_slice(signature).map_err(|_| EdgeClawError::CryptoError)?;
+        peer_key
+            .verify(&challenge_message(&challenge.nonce), &signature)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+
+        tracing::info!(peer_id = %challenge.peer_id, "Peer authenticated");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use rand::rngs::OsRng;
+
+    fn answer(key: &SigningKey, frame: &[u8]) -> Vec<u8> {
+        let payload = parse_challenge_frame(frame).unwrap();
+        let nonce = hex::decode(payload.nonce).unwrap();
+        key.sign(&challenge_message(&nonce)).to_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_challenge_single_use() {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut store = ChallengeStore::default();
+        let challenge = store.issue("peer-1").unwrap();
+        assert_eq!(store.peer_for(&challenge.challenge_id), Some("peer-1"));
+
+        let sig = answer(&key, &challenge.nonce_frame);
+        store
+            .verify(&challenge.challenge_id, &key.verifying_key(), &sig)
+            .unwrap();
+        assert!(matches!(
+            store.verify(&challenge.challenge_id, &key.verifying_key(), &sig),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn test_expired_challenge() {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut store = ChallengeStore::new(0);
+        let challenge = store.issue("peer-1").unwrap();
+
+        let sig = answer(&key, &challenge.nonce_frame);
+        assert!(matches!(
+            store.verify(&challenge.challenge_id, &key.verifying_key(), &sig),
+            Err(EdgeClawError::TimeoutError)
+        ));
+    }
+
+    fn signed_attestation(key: &SigningKey, ttl_secs: i64) -> PeerAttestation {
+        let mut attestation = PeerAttestation::new(
+            "alice",
+            "bob",
+            &"ab".repeat(32),
+            ttl_secs,
+            chrono::Utc::now(),
+        );
+        attestation.signature = hex::encode(key.sign(&attestation.signing_bytes()).to_bytes());
+        attestation
+    }
+
+    #[test]
+    fn test_attestation_verify_and_tamper() {
+        let key = SigningKey::generate(&mut OsRng);
+        let attestation = signed_attestation(&key, DEFAULT_ATTESTATION_TTL_SECS);
+        let json = serde_json::to_string(&attestation).unwrap();
+        assert_eq!(
+            verify_attestation(&json, &key.verifying_key(), chrono::Utc::now()).unwrap(),
+            attestation
+        );
+
+        let mut tampered = attestation.clone();
+        tampered.subject_pubkey = "cd".repeat(32);
+        assert!(matches!(
+            tampered.verify(&key.verifying_key(), chrono::Utc::now()),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        let other = SigningKey::generate(&mut OsRng);
+        assert!(matches!(
+            verify_attestation(&json, &other.verifying_key(), chrono::Utc::now()),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_issued_attestations_capped() {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut ledger = AttestationLedger::new();
+        let attestations: Vec<_> = (0..=ISSUED_ATTESTATION_CAPACITY as i64)
+            .map(|ttl| signed_attestation(&key, ttl + 1))
+            .collect();
+        ledger.revoke(&attestations[0].id());
+        for attestation in &attestations {
+            ledger.record(attestation);
+        }
+
+        let issued = ledger.list_issued();
+        assert_eq!(issued.len(), ISSUED_ATTESTATION_CAPACITY);
+        assert_eq!(issued[0].id, attestations[1].id());
+        assert_eq!(issued.last().unwrap().id, attestations.last().unwrap().id());
+        // Dropping the oldest entry keeps its revocation
+        assert!(matches!(
+            ledger.check(&attestations[0]),
+            Err(EdgeClawError::Revoked)
+        ));
+    }
+
+    #[test]
+    fn test_expired_attestation() {
+        let key = SigningKey::generate(&mut OsRng);
+        let attestation = signed_attestation(&key, 60);
+        let issued = chrono::DateTime::parse_from_rfc3339(&attestation.timestamp)
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert!(attestation
+            .verify(&key.verifying_key(), issued + chrono::Duration::seconds(59))
+            .is_ok());
+        assert!(matches!(
+            attestation.verify(&key.verifying_key(), issued + chrono::Duration::seconds(60)),
+            Err(EdgeClawError::TimeoutError)
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
 use crate::error::EdgeClawError;
 use crate::protocol::MessageType;
 
@@ -8,67 +11,407 @@ use crate::protocol::MessageType;
 /// └──────────┴──────────┴──────────────┴──────────────┘
 const ECNP_VERSION: u8 = 0x01;
 const HEADER_SIZE: usize = 6; // 1 + 1 + 4
-const MAX_PAYLOAD_SIZE: usize = 1024 * 1024; // 1 MB max
+pub(crate) const MAX_PAYLOAD_SIZE: usize = 1024 * 1024; // 1 MB max
+
+/// ECNP v1.2 frame format (adds a flags byte):
+/// ┌──────────┬──────────┬──────────┬──────────────┬──────────────┐
+/// │ Version  │  Type    │  Flags   │   Length     │   Payload    │
+/// │  1 byte  │  1 byte  │  1 byte  │   4 bytes   │   N bytes    │
+/// └──────────┴──────────┴──────────┴──────────────┴──────────────┘
+///
+/// Only emitted when at least one flag is set, so peers that never use
+/// flags keep exchanging plain v1.1 frames.
+const ECNP_VERSION_FLAGS: u8 = 0x02;
+const FLAGS_HEADER_SIZE: usize = 7; // 1 + 1 + 1 + 4
+
+/// Flag bit: payload is zstd-compressed
+pub const FLAG_COMPRESSED: u8 = 0x01;
+
+/// Flag bit: payload is followed by a 64-byte Ed25519 signature
+pub const FLAG_SIGNED: u8 = 0x02;
+
+const SIGNATURE_SIZE: usize = 64;
+
+/// Flag bit: payload body is CBOR rather than JSON
+pub const FLAG_CBOR: u8 = 0x04;
+
+/// Flag bit: frame is one piece of a larger transfer (see `fragment`)
+pub const FLAG_FRAGMENT: u8 = 0x08;
+
+/// Payloads shorter than this are sent uncompressed even when compression
+/// is requested: zstd framing overhead outweighs any saving
+pub const DEFAULT_COMPRESSION_MIN_BYTES: usize = 128;
+
+/// Flag bits defined so far; the rest are reserved and must be zero
+pub const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_SIGNED | FLAG_CBOR | FLAG_FRAGMENT;
+
+/// Frame version bytes this build can decode (v1.1 and v1.2)
+pub const SUPPORTED_VERSIONS: [u8; 2] = [ECNP_VERSION, ECNP_VERSION_FLAGS];
 
 /// ECNP message exposed via UniFFI
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EcnpMessage {
     pub version: u8,
     pub msg_type: u8,
+    /// Frame flags (always 0 for v1.1 frames)
+    pub flags: u8,
     pub payload: Vec<u8>,
 }
 
+/// Borrowed view of an ECNP frame; `payload` points into the input buffer
+/// and is exactly as sent on the wire (not decompressed)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EcnpMessageRef<'a> {
+    pub version: u8,
+    pub msg_type: u8,
+    pub flags: u8,
+    pub payload: &'a [u8],
+}
+
+/// Payload compression algorithms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
+impl Compression {
+    /// Every algorithm this build supports
+    pub const ALL: [Compression; 2] = [Compression::None, Compression::Zstd];
+
+    /// Wire name used in handshake negotiation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Parse a wire name; unknown algorithms are rejected
+    pub fn parse(s: &str) -> Result<Self, EdgeClawError> {
+        match s {
+            "none" => Ok(Compression::None),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(EdgeClawError::InvalidParameter),
+        }
+    }
+}
+
 /// ECNP v1.1 codec — binary framing for edge protocol
 pub struct EcnpCodec;
 
 impl EcnpCodec {
+    /// Wire size of a v1.1 frame carrying `payload_len` bytes
+    pub fn encoded_size(payload_len: usize) -> usize {
+        HEADER_SIZE + payload_len
+    }
+
+    /// Wire size of a v1.2 (flagged) frame carrying `payload_len` bytes
+    pub fn encoded_size_with_flags(payload_len: usize) -> usize {
+        FLAGS_HEADER_SIZE + payload_len
+    }
+
     /// Encode a message into ECNP v1.1 wire format
     pub fn encode(msg_type: MessageType, payload: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
-        if payload.len() > MAX_PAYLOAD_SIZE {
-            return Err(EdgeClawError::InvalidParameter);
-        }
+        let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+        Self::encode_into(msg_type, payload, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// `encode` into a caller-owned buffer, which is cleared first. Reusing
+    /// one buffer across calls avoids an allocation per frame in hot loops
+    /// (heartbeats, acks). On error `buf` is left empty.
+    pub fn encode_into(
+        msg_type: MessageType,
+        payload: &[u8],
+        buf: &mut Vec<u8>,
+    ) -> Result<(), EdgeClawError> {
+        buf.clear();
+        check_payload_size(payload.len(), MAX_PAYLOAD_SIZE)?;
 
         let length = payload.len() as u32;
-        let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+        buf.reserve(HEADER_SIZE + payload.len());
+        buf.push(ECNP_VERSION);
+        buf.push(msg_type as u8);
+        buf.extend_from_slice(&length.to_be_bytes());
+        buf.extend_from_slice(payload);
 
-        frame.push(ECNP_VERSION);
+        Ok(())
+    }
+
+    /// Encode a message into an ECNP v1.2 frame carrying the given flags
+    pub fn encode_with_flags(
+        msg_type: MessageType,
+        flags: u8,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        check_payload_size(payload.len(), MAX_PAYLOAD_SIZE)?;
+
+        let length = payload.len() as u32;
+        let mut frame = Vec::with_capacity(FLAGS_HEADER_SIZE + payload.len());
+
+        frame.push(ECNP_VERSION_FLAGS);
         frame.push(msg_type as u8);
+        frame.push(flags);
         frame.extend_from_slice(&length.to_be_bytes());
         frame.extend_from_slice(payload);
 
         Ok(frame)
     }
 
-    /// Decode a message from ECNP v1.1 wire format
+    /// Encode a message with its payload zstd-compressed (sets
+    /// `FLAG_COMPRESSED`) if it is at least `DEFAULT_COMPRESSION_MIN_BYTES`
+    /// long; shorter payloads are stored raw
+    pub fn encode_compressed(
+        msg_type: MessageType,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        Self::encode_flagged(msg_type, payload, Compression::Zstd, 0)
+    }
+
+    /// Encode using the given compression algorithm
+    pub fn encode_with_compression(
+        msg_type: MessageType,
+        payload: &[u8],
+        compression: Compression,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        Self::encode_flagged(msg_type, payload, compression, 0)
+    }
+
+    /// Encode with optional compression plus extra flag bits, using
+    /// `DEFAULT_COMPRESSION_MIN_BYTES` as the compression threshold.
+    ///
+    /// Falls back to a plain v1.1 frame when no flag ends up set.
+    pub fn encode_flagged(
+        msg_type: MessageType,
+        payload: &[u8],
+        compression: Compression,
+        flags: u8,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        Self::encode_flagged_with_threshold(
+            msg_type,
+            payload,
+            compression,
+            flags,
+            DEFAULT_COMPRESSION_MIN_BYTES,
+        )
+    }
+
+    /// `encode_flagged`, compressing only payloads of at least
+    /// `compression_min_bytes`; shorter ones go raw with
+    /// `FLAG_COMPRESSED` clear
+    pub fn encode_flagged_with_threshold(
+        msg_type: MessageType,
+        payload: &[u8],
+        compression: Compression,
+        flags: u8,
+        compression_min_bytes: usize,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let compression = if payload.len() < compression_min_bytes {
+            Compression::None
+        } else {
+            compression
+        };
+        match (compression, flags) {
+            (Compression::None, 0) => Self::encode(msg_type, payload),
+            (Compression::None, _) => Self::encode_with_flags(msg_type, flags, payload),
+            (Compression::Zstd, _) => {
+                check_payload_size(payload.len(), MAX_PAYLOAD_SIZE)?;
+                let compressed =
+                    zstd::bulk::compress(payload, 0).map_err(|_| EdgeClawError::InternalError)?;
+                Self::encode_with_flags(msg_type, flags | FLAG_COMPRESSED, &compressed)
+            }
+        }
+    }
+
+    /// Encode an authenticated (but unencrypted) frame: the payload is
+    /// followed by an Ed25519 signature over type, flags and payload.
+    pub fn encode_signed(
+        signing_key: &SigningKey,
+        msg_type: MessageType,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        check_payload_size(payload.len(), MAX_PAYLOAD_SIZE - SIGNATURE_SIZE)?;
+        let signature = signing_key.sign(&Self::signed_bytes(msg_type as u8, FLAG_SIGNED, payload));
+
+        let mut signed = Vec::with_capacity(payload.len() + SIGNATURE_SIZE);
+        signed.extend_from_slice(payload);
+        signed.extend_from_slice(&signature.to_bytes());
+        Self::encode_with_flags(msg_type, FLAG_SIGNED, &signed)
+    }
+
+    /// Decode a signed frame and verify it against `expected_key`.
+    ///
+    /// Unsigned frames and bad signatures are rejected with `CryptoError`.
+    /// The returned payload has the signature stripped.
+    pub fn decode_verified(
+        expected_key: &VerifyingKey,
+        data: &[u8],
+    ) -> Result<EcnpMessage, EdgeClawError> {
+        let mut msg = Self::decode(data)?;
+        if msg.flags & FLAG_SIGNED == 0 || msg.payload.len() < SIGNATURE_SIZE {
+            return Err(EdgeClawError::CryptoError);
+        }
+
+        let sig_start = msg.payload.len() - SIGNATURE_SIZE;
+        let signature = Signature::from_slice(&msg.payload[sig_start..])
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        msg.payload.truncate(sig_start);
+
+        expected_key
+            .verify(
+                &Self::signed_bytes(msg.msg_type, msg.flags, &msg.payload),
+                &signature,
+            )
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        Ok(msg)
+    }
+
+    /// Bytes covered by a frame signature: type, flags, payload
+    fn signed_bytes(msg_type: u8, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + payload.len());
+        bytes.push(msg_type);
+        bytes.push(flags);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Decode a message from ECNP v1.1 or v1.2 wire format.
+    ///
+    /// Compressed payloads are transparently decompressed; `flags` still
+    /// reports how the frame travelled on the wire.
     pub fn decode(data: &[u8]) -> Result<EcnpMessage, EdgeClawError> {
-        if data.len() < HEADER_SIZE {
+        let frame = Self::decode_borrowed(data)?;
+        Self::finish_message(
+            frame.version,
+            frame.msg_type,
+            frame.flags,
+            frame.payload.to_vec(),
+        )
+    }
+
+    /// Decode, rejecting anything a conforming encoder would not produce.
+    ///
+    /// On top of `decode`'s checks, a strict frame must:
+    /// - be exactly header + declared length bytes (trailing data usually
+    ///   means a mis-encoded length, e.g. little-endian)
+    /// - leave reserved flag bits (outside `KNOWN_FLAGS`) zero
+    /// - set at least one flag if it uses the v1.2 header, since encoders
+    ///   fall back to v1.1 otherwise
+    /// - carry a full signature if `FLAG_SIGNED` is set
+    /// - not be compressed or CBOR if it is a Handshake, since both are
+    ///   negotiated by the handshake itself
+    ///
+    /// Violations fail with `InvalidParameter`; lenient `decode` still
+    /// accepts them.
+    pub fn decode_strict(data: &[u8]) -> Result<EcnpMessage, EdgeClawError> {
+        let frame = Self::decode_borrowed(data)?;
+        Self::check_strict(data.len(), &frame)?;
+        Self::finish_message(
+            frame.version,
+            frame.msg_type,
+            frame.flags,
+            frame.payload.to_vec(),
+        )
+    }
+
+    fn check_strict(data_len: usize, frame: &EcnpMessageRef<'_>) -> Result<(), EdgeClawError> {
+        let header_size = Self::header_size(frame.version);
+        let handshake = frame.msg_type == MessageType::Handshake as u8;
+        if data_len != header_size + frame.payload.len()
+            || frame.flags & !KNOWN_FLAGS != 0
+            || (frame.version == ECNP_VERSION_FLAGS && frame.flags == 0)
+            || (frame.flags & FLAG_SIGNED != 0 && frame.payload.len() < SIGNATURE_SIZE)
+            || (handshake && frame.flags & (FLAG_COMPRESSED | FLAG_CBOR) != 0)
+        {
             return Err(EdgeClawError::InvalidParameter);
         }
+        Ok(())
+    }
 
-        let version = data[0];
-        if version != ECNP_VERSION {
+    /// Check framing (version, type, length) without copying the payload
+    pub fn validate(data: &[u8]) -> Result<(), EdgeClawError> {
+        Self::decode_borrowed(data).map(|_| ())
+    }
+
+    /// Decode a frame without copying: the payload borrows from `data`.
+    ///
+    /// Flag-driven transforms (decompression) are not applied.
+    pub fn decode_borrowed(data: &[u8]) -> Result<EcnpMessageRef<'_>, EdgeClawError> {
+        if data.len() < HEADER_SIZE {
             return Err(EdgeClawError::InvalidParameter);
         }
 
-        let msg_type = data[1];
-        // Validate message type
-        let _ = MessageType::try_from(msg_type)?;
+        let header_size = Self::header_size(data[0]);
+        if data.len() < header_size {
+            return Err(EdgeClawError::InvalidParameter);
+        }
 
-        let length = u32::from_be_bytes([data[2], data[3], data[4], data[5]]) as usize;
+        let (version, msg_type, flags, length) = Self::parse_header(&data[..header_size])?;
 
-        if length > MAX_PAYLOAD_SIZE {
+        if data.len() < header_size + length {
             return Err(EdgeClawError::InvalidParameter);
         }
 
-        if data.len() < HEADER_SIZE + length {
-            return Err(EdgeClawError::InvalidParameter);
+        Ok(EcnpMessageRef {
+            version,
+            msg_type,
+            flags,
+            payload: &data[header_size..header_size + length],
+        })
+    }
+
+    /// Header size implied by the version byte
+    fn header_size(version: u8) -> usize {
+        if version == ECNP_VERSION_FLAGS {
+            FLAGS_HEADER_SIZE
+        } else {
+            HEADER_SIZE
         }
+    }
 
-        let payload = data[HEADER_SIZE..HEADER_SIZE + length].to_vec();
+    /// Validate a v1.1 (6-byte) or v1.2 (7-byte) header, returning
+    /// (version, msg_type, flags, payload length)
+    fn parse_header(header: &[u8]) -> Result<(u8, u8, u8, usize), EdgeClawError> {
+        let version = header[0];
+        let (flags, len_bytes) = match version {
+            ECNP_VERSION => (0, &header[2..6]),
+            ECNP_VERSION_FLAGS => (header[2], &header[3..7]),
+            _ => return Err(EdgeClawError::InvalidParameter),
+        };
+
+        let msg_type = header[1];
+        // Validate message type
+        let _ = MessageType::try_from(msg_type)?;
+
+        let length =
+            u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+
+        check_payload_size(length, MAX_PAYLOAD_SIZE)?;
+
+        Ok((version, msg_type, flags, length))
+    }
+
+    /// Apply flag-driven payload transforms and build the message
+    fn finish_message(
+        version: u8,
+        msg_type: u8,
+        flags: u8,
+        payload: Vec<u8>,
+    ) -> Result<EcnpMessage, EdgeClawError> {
+        let payload = if flags & FLAG_COMPRESSED != 0 {
+            // Bounded decompression guards against zip bombs
+            zstd::bulk::decompress(&payload, MAX_PAYLOAD_SIZE)
+                .map_err(|_| EdgeClawError::InvalidParameter)?
+        } else {
+            payload
+        };
 
         Ok(EcnpMessage {
             version,
             msg_type,
+            flags,
             payload,
         })
     }
@@ -78,19 +421,278 @@ impl EcnpCodec {
         Self::encode(msg_type, text.as_bytes())
     }
 
-    /// Decode and return payload as string
+    /// Decode and return payload as string; `InvalidUtf8` carries the
+    /// payload offset of the first bad byte
     pub fn decode_string(data: &[u8]) -> Result<(u8, String), EdgeClawError> {
         let msg = Self::decode(data)?;
-        let text = String::from_utf8(msg.payload).map_err(|_| EdgeClawError::SerializationError)?;
+        let text = String::from_utf8(msg.payload).map_err(|e| EdgeClawError::InvalidUtf8 {
+            offset: e.utf8_error().valid_up_to() as u32,
+        })?;
         Ok((msg.msg_type, text))
     }
 }
 
+// ─── Async stream adapters ───
+
+/// Map a stream I/O error, treating EOF as a peer disconnect
+fn map_io_error(e: std::io::Error) -> EdgeClawError {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        EdgeClawError::ConnectionClosed
+    } else {
+        EdgeClawError::ConnectionError
+    }
+}
+
+/// Read exactly one ECNP (v1.1 or v1.2) frame from an async stream.
+///
+/// The header is validated (version, type, length ≤ 1 MB) before the
+/// payload buffer is allocated. EOF before any byte of the frame is a clean
+/// close (`ConnectionClosed`); EOF part-way through is `IncompleteFrame`.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<EcnpMessage, EdgeClawError> {
+    into_frame(read_frame_counting(reader, &mut 0, MAX_PAYLOAD_SIZE).await?)
+}
+
+/// `read_frame` for read loops: a clean close between frames is `Ok(None)`
+/// rather than an error, so only truncated frames (`IncompleteFrame`) and
+/// real failures surface as `Err`
+pub async fn read_frame_or_eof<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<EcnpMessage>, EdgeClawError> {
+    match read_frame(reader).await {
+        Ok(frame) => Ok(Some(frame)),
+        Err(EdgeClawError::ConnectionClosed) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `read_frame` with a deadline for the whole frame.
+///
+/// A peer that sends nothing in time gets `TimeoutError`; one that stalls
+/// after part of a frame gets `IncompleteFrame` instead.
+pub async fn read_frame_timeout<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    timeout: std::time::Duration,
+) -> Result<EcnpMessage, EdgeClawError> {
+    into_frame(read_frame_bounded(reader, timeout, MAX_PAYLOAD_SIZE).await?)
+}
+
+/// A frame read under a caller-chosen payload limit
+#[derive(Debug)]
+pub(crate) enum BoundedFrame {
+    Frame(EcnpMessage),
+    /// The payload was over the limit. Its bytes were read and discarded,
+    /// so the stream is positioned at the next frame.
+    Skipped {
+        size: usize,
+    },
+}
+
+/// `read_frame_timeout` that discards (rather than fails on) a payload
+/// larger than `limit` but within the 1 MB protocol maximum
+pub(crate) async fn read_frame_bounded<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    timeout: std::time::Duration,
+    limit: usize,
+) -> Result<BoundedFrame, EdgeClawError> {
+    let mut received = 0;
+    match tokio::time::timeout(timeout, read_frame_counting(reader, &mut received, limit)).await {
+        Ok(result) => result,
+        Err(_) if received == 0 => Err(EdgeClawError::TimeoutError),
+        Err(_) => Err(EdgeClawError::IncompleteFrame {
+            received: received as u32,
+        }),
+    }
+}
+
+fn into_frame(read: BoundedFrame) -> Result<EcnpMessage, EdgeClawError> {
+    match read {
+        BoundedFrame::Frame(frame) => Ok(frame),
+        BoundedFrame::Skipped { size } => Err(EdgeClawError::PayloadTooLarge {
+            size,
+            max: MAX_PAYLOAD_SIZE,
+        }),
+    }
+}
+
+/// Read one frame, keeping `received` up to date so a caller that gives up
+/// (times out) can still tell how far the frame got
+async fn read_frame_counting<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    received: &mut usize,
+    limit: usize,
+) -> Result<BoundedFrame, EdgeClawError> {
+    let mut header = [0u8; FLAGS_HEADER_SIZE];
+    read_full(reader, &mut header[..HEADER_SIZE], received).await?;
+
+    let header_size = EcnpCodec::header_size(header[0]);
+    if header_size > HEADER_SIZE {
+        read_full(reader, &mut header[HEADER_SIZE..header_size], received).await?;
+    }
+
+    let (version, msg_type, flags, length) = EcnpCodec::parse_header(&header[..header_size])?;
+
+    if length > limit {
+        let mut scratch = [0u8; 8192];
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk = remaining.min(scratch.len());
+            read_full(reader, &mut scratch[..chunk], received).await?;
+            remaining -= chunk;
+        }
+        return Ok(BoundedFrame::Skipped { size: length });
+    }
+
+    let mut payload = vec![0u8; length];
+    read_full(reader, &mut payload, received).await?;
+
+    EcnpCodec::finish_message(version, msg_type, flags, payload).map(BoundedFrame::Frame)
+}
+
+/// Reject payloads over `max` bytes, reporting the offending size
+fn check_payload_size(size: usize, max: usize) -> Result<(), EdgeClawError> {
+    if size > max {
+        return Err(EdgeClawError::PayloadTooLarge { size, max });
+    }
+    Ok(())
+}
+
+/// Fill `buf`, counting bytes into `received` as they arrive
+async fn read_full<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+    received: &mut usize,
+) -> Result<(), EdgeClawError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        // Some streams (e.g. TLS) report EOF as an `UnexpectedEof` error
+        // rather than a zero-length read; treat both the same
+        let n = match reader.read(&mut buf[filled..]).await {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => return Err(map_io_error(e)),
+        };
+        if n == 0 {
+            return Err(if *received == 0 {
+                EdgeClawError::ConnectionClosed
+            } else {
+                EdgeClawError::IncompleteFrame {
+                    received: *received as u32,
+                }
+            });
+        }
+        filled += n;
+        *received += n;
+    }
+    Ok(())
+}
+
+/// Encode and write one ECNP frame to an async stream, flushing afterwards
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    msg_type: MessageType,
+    payload: &[u8],
+) -> Result<(), EdgeClawError> {
+    let frame = EcnpCodec::encode(msg_type, payload)?;
+    write_encoded_frame(writer, &frame).await
+}
+
+/// Write an already-encoded ECNP frame to an async stream, flushing afterwards
+pub async fn write_encoded_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    frame: &[u8],
+) -> Result<(), EdgeClawError> {
+    writer.write_all(frame).await.map_err(map_io_error)?;
+    writer.flush().await.map_err(map_io_error)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::protocol::MessageType;
 
+    #[test]
+    fn test_decode_borrowed_points_into_input() {
+        let frame = EcnpCodec::encode(MessageType::Data, b"borrowed").unwrap();
+        let view = EcnpCodec::decode_borrowed(&frame).unwrap();
+        assert_eq!(view.msg_type, MessageType::Data as u8);
+        assert_eq!(view.payload, b"borrowed");
+        assert!(std::ptr::eq(
+            view.payload.as_ptr(),
+            frame[HEADER_SIZE..].as_ptr()
+        ));
+
+        let flagged = EcnpCodec::encode_with_flags(MessageType::Data, 0x80, b"x").unwrap();
+        let view = EcnpCodec::decode_borrowed(&flagged).unwrap();
+        assert_eq!((view.flags, view.payload), (0x80, &b"x"[..]));
+    }
+
+    #[test]
+    fn test_validate_catches_malformed() {
+        let frame = EcnpCodec::encode(MessageType::Heartbeat, b"ping").unwrap();
+        assert!(EcnpCodec::validate(&frame).is_ok());
+
+        assert!(EcnpCodec::validate(&frame[..3]).is_err());
+        assert!(EcnpCodec::validate(&frame[..frame.len() - 1]).is_err());
+
+        let mut bad_version = frame.clone();
+        bad_version[0] = 0x7F;
+        assert!(EcnpCodec::validate(&bad_version).is_err());
+
+        let mut bad_type = frame;
+        bad_type[1] = 0xEE;
+        assert!(EcnpCodec::validate(&bad_type).is_err());
+    }
+
+    #[test]
+    fn test_signed_frame_roundtrip() {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let frame = EcnpCodec::encode_signed(&key, MessageType::Heartbeat, b"uptime=42").unwrap();
+
+        let msg = EcnpCodec::decode_verified(&key.verifying_key(), &frame).unwrap();
+        assert_eq!(msg.flags, FLAG_SIGNED);
+        assert_eq!(msg.payload, b"uptime=42");
+
+        // Signature from a different key fails
+        let other = SigningKey::generate(&mut rand::rngs::OsRng);
+        assert!(matches!(
+            EcnpCodec::decode_verified(&other.verifying_key(), &frame),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_signed_frame_tampered() {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let mut frame =
+            EcnpCodec::encode_signed(&key, MessageType::Heartbeat, b"uptime=42").unwrap();
+        frame[FLAGS_HEADER_SIZE] ^= 0x01;
+        assert!(matches!(
+            EcnpCodec::decode_verified(&key.verifying_key(), &frame),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        let unsigned = EcnpCodec::encode(MessageType::Heartbeat, b"uptime=42").unwrap();
+        assert!(matches!(
+            EcnpCodec::decode_verified(&key.verifying_key(), &unsigned),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_encoded_size_matches() {
+        for len in [0usize, 1, 100, 4096] {
+            let payload = vec![0xAB; len];
+            let plain = EcnpCodec::encode(MessageType::Data, &payload).unwrap();
+            assert_eq!(EcnpCodec::encoded_size(len), plain.len());
+
+            let flagged = EcnpCodec::encode_with_flags(MessageType::Data, 0x80, &payload).unwrap();
+            assert_eq!(EcnpCodec::encoded_size_with_flags(len), flagged.len());
+        }
+    }
+
     #[test]
     fn test_encode_decode_roundtrip() {
         let payload = b"Hello ECNP!";
@@ -106,6 +708,27 @@ mod tests {
         assert_eq!(msg.payload, payload);
     }
 
+    #[test]
+    fn test_encode_into_matches_encode() {
+        let mut buf = Vec::new();
+        for (msg_type, payload) in [
+            (MessageType::Heartbeat, &b""[..]),
+            (MessageType::Ack, &b"ok"[..]),
+            (MessageType::Data, &[0xAB; 300][..]),
+            (MessageType::Control, &b"x"[..]),
+        ] {
+            EcnpCodec::encode_into(msg_type, payload, &mut buf).unwrap();
+            assert_eq!(buf, EcnpCodec::encode(msg_type, payload).unwrap());
+        }
+        // The buffer is cleared, not appended to, and keeps its allocation
+        assert_eq!(buf.len(), EcnpCodec::encoded_size(1));
+        assert!(buf.capacity() >= EcnpCodec::encoded_size(300));
+
+        let oversized = vec![0u8; MAX_PAYLOAD_SIZE + 1];
+        assert!(EcnpCodec::encode_into(MessageType::Data, &oversized, &mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn test_encode_string_roundtrip() {
         let text = "EdgeClaw heartbeat";
@@ -116,6 +739,15 @@ mod tests {
         assert_eq!(decoded, text);
     }
 
+    #[test]
+    fn test_decode_string_invalid_utf8() {
+        let frame = EcnpCodec::encode(MessageType::Data, b"ok\xff\xfe").unwrap();
+        assert!(matches!(
+            EcnpCodec::decode_string(&frame),
+            Err(EdgeClawError::InvalidUtf8 { offset: 2 })
+        ));
+    }
+
     #[test]
     fn test_decode_too_short() {
         assert!(EcnpCodec::decode(&[0x01, 0x02]).is_err());
@@ -148,6 +780,152 @@ mod tests {
         assert_eq!(msg.msg_type, MessageType::Ack as u8);
     }
 
+    #[test]
+    fn test_decode_strict_rejects_nonconforming_frames() {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        for valid in [
+            EcnpCodec::encode(MessageType::Data, b"ok").unwrap(),
+            EcnpCodec::encode(MessageType::Ack, &[]).unwrap(),
+            EcnpCodec::encode_compressed(MessageType::Data, "ok ".repeat(100).as_bytes()).unwrap(),
+            EcnpCodec::encode_signed(&key, MessageType::Handshake, b"hi").unwrap(),
+        ] {
+            assert!(EcnpCodec::decode_strict(&valid).is_ok());
+        }
+
+        let mut trailing = EcnpCodec::encode(MessageType::Data, b"ok").unwrap();
+        trailing.push(0);
+        // A little-endian length of 2 reads back as 32 MiB
+        let little_endian = vec![
+            ECNP_VERSION,
+            MessageType::Data as u8,
+            2,
+            0,
+            0,
+            0,
+            0xAA,
+            0xBB,
+        ];
+        let reserved_flag = EcnpCodec::encode_with_flags(MessageType::Data, 0x80, b"ok").unwrap();
+        let empty_flags = EcnpCodec::encode_with_flags(MessageType::Data, 0, b"ok").unwrap();
+        let short_signature =
+            EcnpCodec::encode_with_flags(MessageType::Data, FLAG_SIGNED, b"ok").unwrap();
+        let cbor_handshake =
+            EcnpCodec::encode_with_flags(MessageType::Handshake, FLAG_CBOR, b"ok").unwrap();
+        let compressed_handshake =
+            EcnpCodec::encode_compressed(MessageType::Handshake, "ok ".repeat(100).as_bytes())
+                .unwrap();
+
+        for invalid in [
+            &trailing,
+            &reserved_flag,
+            &empty_flags,
+            &short_signature,
+            &cbor_handshake,
+            &compressed_handshake,
+        ] {
+            assert!(EcnpCodec::decode(invalid).is_ok());
+            assert!(matches!(
+                EcnpCodec::decode_strict(invalid),
+                Err(EdgeClawError::InvalidParameter)
+            ));
+        }
+        assert!(matches!(
+            EcnpCodec::decode_strict(&little_endian),
+            Err(EdgeClawError::PayloadTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_oversized_payload_reports_size_and_max() {
+        let payload = vec![0u8; MAX_PAYLOAD_SIZE + 1];
+        assert!(matches!(
+            EcnpCodec::encode(MessageType::Data, &payload),
+            Err(EdgeClawError::PayloadTooLarge { size, max })
+                if size == MAX_PAYLOAD_SIZE + 1 && max == MAX_PAYLOAD_SIZE
+        ));
+
+        // Signed frames reserve room for the trailing signature
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let payload = vec![0u8; MAX_PAYLOAD_SIZE];
+        assert!(matches!(
+            EcnpCodec::encode_signed(&key, MessageType::Data, &payload),
+            Err(EdgeClawError::PayloadTooLarge { size, max })
+                if size == MAX_PAYLOAD_SIZE && max == MAX_PAYLOAD_SIZE - SIGNATURE_SIZE
+        ));
+
+        let header = [
+            ECNP_VERSION,
+            MessageType::Data as u8,
+            0x00,
+            0x10,
+            0x00,
+            0x01,
+        ];
+        assert!(matches!(
+            EcnpCodec::decode(&header),
+            Err(EdgeClawError::PayloadTooLarge { size, max })
+                if size == MAX_PAYLOAD_SIZE + 1 && max == MAX_PAYLOAD_SIZE
+        ));
+    }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let payload = "status ".repeat(200);
+        let frame = EcnpCodec::encode_compressed(MessageType::Data, payload.as_bytes()).unwrap();
+
+        assert_eq!(frame[0], ECNP_VERSION_FLAGS);
+        assert_eq!(frame[2], FLAG_COMPRESSED);
+        assert!(frame.len() < payload.len());
+
+        let msg = EcnpCodec::decode(&frame).unwrap();
+        assert_eq!(msg.flags, FLAG_COMPRESSED);
+        assert_eq!(msg.payload, payload.as_bytes());
+    }
+
+    #[test]
+    fn test_compression_threshold() {
+        // Below the threshold the payload is stored raw, flag clear
+        let small = b"{\"cpu\":12.5}";
+        let frame = EcnpCodec::encode_compressed(MessageType::Data, small).unwrap();
+        assert_eq!(frame[0], ECNP_VERSION);
+        assert_eq!(&frame[HEADER_SIZE..], small);
+
+        let payload = "status ".repeat(40);
+        let frame = EcnpCodec::encode_flagged_with_threshold(
+            MessageType::Data,
+            payload.as_bytes(),
+            Compression::Zstd,
+            FLAG_CBOR,
+            payload.len() + 1,
+        )
+        .unwrap();
+        assert_eq!(frame[2], FLAG_CBOR);
+        assert_eq!(&frame[FLAGS_HEADER_SIZE..], payload.as_bytes());
+
+        // At the threshold a compressible payload shrinks
+        let frame = EcnpCodec::encode_flagged_with_threshold(
+            MessageType::Data,
+            payload.as_bytes(),
+            Compression::Zstd,
+            0,
+            payload.len(),
+        )
+        .unwrap();
+        assert_eq!(frame[2], FLAG_COMPRESSED);
+        assert!(frame.len() < payload.len());
+        assert_eq!(
+            EcnpCodec::decode(&frame).unwrap().payload,
+            payload.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_v11_frame_has_no_flags() {
+        let frame = EcnpCodec::encode(MessageType::Data, b"plain").unwrap();
+        let msg = EcnpCodec::decode(&frame).unwrap();
+        assert_eq!(msg.flags, 0);
+    }
+
     #[test]
     fn test_all_message_types() {
         let types = vec![
@@ -165,4 +943,144 @@ mod tests {
             assert_eq!(msg.msg_type, mt as u8);
         }
     }
+
+    #[tokio::test]
+    async fn test_stream_roundtrip_multiple_frames() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let writer = tokio::spawn(async move {
+            write_frame(&mut client, MessageType::Handshake, b"hello")
+                .await
+                .unwrap();
+            write_frame(&mut client, MessageType::Ack, &[])
+                .await
+                .unwrap();
+            write_frame(&mut client, MessageType::Data, &[0xAB; 200])
+                .await
+                .unwrap();
+        });
+
+        let first = read_frame(&mut server).await.unwrap();
+        assert_eq!(first.msg_type, MessageType::Handshake as u8);
+        assert_eq!(first.payload, b"hello");
+
+        let second = read_frame(&mut server).await.unwrap();
+        assert_eq!(second.msg_type, MessageType::Ack as u8);
+        assert!(second.payload.is_empty());
+
+        let third = read_frame(&mut server).await.unwrap();
+        assert_eq!(third.msg_type, MessageType::Data as u8);
+        assert_eq!(third.payload, vec![0xAB; 200]);
+
+        writer.await.unwrap();
+
+        // Writer dropped → EOF
+        assert!(matches!(
+            read_frame(&mut server).await,
+            Err(EdgeClawError::ConnectionClosed)
+        ));
+    }
+
+    /// Yields `data`, then fails with `UnexpectedEof` like a TLS stream
+    struct EofErrorReader {
+        data: std::io::Cursor<Vec<u8>>,
+    }
+
+    impl AsyncRead for EofErrorReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if self.data.position() as usize == self.data.get_ref().len() {
+                return std::task::Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            std::pin::Pin::new(&mut self.data).poll_read(cx, buf)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_or_eof_clean_close_between_frames() {
+        let frame = EcnpCodec::encode(MessageType::Data, b"one").unwrap();
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&frame).await.unwrap();
+        drop(client);
+        let first = read_frame_or_eof(&mut server).await.unwrap().unwrap();
+        assert_eq!(first.payload, b"one");
+        assert!(read_frame_or_eof(&mut server).await.unwrap().is_none());
+
+        // Same for a stream that signals EOF as an error
+        let mut reader = EofErrorReader {
+            data: std::io::Cursor::new(frame.clone()),
+        };
+        assert!(read_frame_or_eof(&mut reader).await.unwrap().is_some());
+        assert!(read_frame_or_eof(&mut reader).await.unwrap().is_none());
+
+        // ...but EOF part-way through a frame is still a protocol error
+        let mut reader = EofErrorReader {
+            data: std::io::Cursor::new(frame[..4].to_vec()),
+        };
+        assert!(matches!(
+            read_frame_or_eof(&mut reader).await,
+            Err(EdgeClawError::IncompleteFrame { received: 4 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_eof_mid_frame() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let header = [ECNP_VERSION, MessageType::Data as u8, 0, 0, 0, 10];
+        client.write_all(&header).await.unwrap();
+        client.write_all(b"abc").await.unwrap();
+        drop(client);
+
+        assert!(matches!(
+            read_frame(&mut server).await,
+            Err(EdgeClawError::IncompleteFrame { received: 9 })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_frame_timeout_distinguishes_silence_from_stall() {
+        let timeout = std::time::Duration::from_secs(5);
+
+        let (_silent, mut server) = tokio::io::duplex(64);
+        assert!(matches!(
+            read_frame_timeout(&mut server, timeout).await,
+            Err(EdgeClawError::TimeoutError)
+        ));
+
+        let (mut stalled, mut server) = tokio::io::duplex(64);
+        stalled
+            .write_all(&[ECNP_VERSION, MessageType::Data as u8, 0, 0, 0, 10])
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_frame_timeout(&mut server, timeout).await,
+            Err(EdgeClawError::IncompleteFrame { received: 6 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_rejects_oversized_length() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let header = [
+            ECNP_VERSION,
+            MessageType::Data as u8,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+        ];
+        client.write_all(&header).await.unwrap();
+
+        assert!(matches!(
+            read_frame(&mut server).await,
+            Err(EdgeClawError::PayloadTooLarge {
+                size: 0xFFFF_FFFF,
+                max: MAX_PAYLOAD_SIZE,
+            })
+        ));
+    }
 }
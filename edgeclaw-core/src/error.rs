@@ -7,6 +7,12 @@ pub enum EdgeClawError {
     #[error("Connection failed")]
     ConnectionError,
 
+    #[error("Connection closed by peer")]
+    ConnectionClosed,
+
+    #[error("Connected to ourselves: remote device ID is our own")]
+    SelfConnection,
+
     #[error("Action denied by policy")]
     PolicyDenied,
 
@@ -16,6 +22,15 @@ pub enum EdgeClawError {
     #[error("Session has expired")]
     SessionExpired,
 
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Session handshake not completed")]
+    SessionNotEstablished,
+
+    #[error("No device identity: call generate_identity first")]
+    IdentityNotInitialized,
+
     #[error("Invalid parameter")]
     InvalidParameter,
 
@@ -25,6 +40,36 @@ pub enum EdgeClawError {
     #[error("Serialization/deserialization error")]
     SerializationError,
 
+    #[error("Invalid UTF-8 in string payload at byte {offset}")]
+    InvalidUtf8 { offset: u32 },
+
+    #[error("Unknown sync message type 0x{code:02x}")]
+    UnknownSyncType { code: u8 },
+
+    #[error("Unsupported sync wire version {version}")]
+    UnsupportedSyncVersion { version: u8 },
+
+    #[error("Incomplete frame: peer stopped after {received} bytes")]
+    IncompleteFrame { received: u32 },
+
+    #[error("Payload of {size} bytes exceeds the {max} byte limit")]
+    PayloadTooLarge { size: usize, max: usize },
+
+    #[error("Integrity check failed: reassembled payload does not match its hash")]
+    IntegrityError,
+
+    #[error("Revoked by its issuer")]
+    Revoked,
+
+    #[error("Replayed frame rejected")]
+    ReplayDetected,
+
+    #[error("Capacity of {max} exceeded")]
+    CapacityExceeded { max: u32 },
+
+    #[error("Rate limit exceeded")]
+    RateLimited,
+
     #[error("Internal engine error")]
     InternalError,
 }
@@ -50,7 +95,64 @@ mod tests {
         let err = EdgeClawError::CryptoError;
         assert_eq!(format!("{err}"), "Cryptographic operation failed");
 
+        let err = EdgeClawError::SelfConnection;
+        assert_eq!(
+            format!("{err}"),
+            "Connected to ourselves: remote device ID is our own"
+        );
+
         let err = EdgeClawError::PolicyDenied;
         assert_eq!(format!("{err}"), "Action denied by policy");
+
+        let err = EdgeClawError::IdentityNotInitialized;
+        assert_eq!(
+            format!("{err}"),
+            "No device identity: call generate_identity first"
+        );
+
+        let err = EdgeClawError::InvalidUtf8 { offset: 3 };
+        assert_eq!(
+            format!("{err}"),
+            "Invalid UTF-8 in string payload at byte 3"
+        );
+
+        let err = EdgeClawError::UnknownSyncType { code: 0x1a };
+        assert_eq!(format!("{err}"), "Unknown sync message type 0x1a");
+
+        let err = EdgeClawError::UnsupportedSyncVersion { version: 2 };
+        assert_eq!(format!("{err}"), "Unsupported sync wire version 2");
+
+        let err = EdgeClawError::IncompleteFrame { received: 6 };
+        assert_eq!(
+            format!("{err}"),
+            "Incomplete frame: peer stopped after 6 bytes"
+        );
+
+        let err = EdgeClawError::PayloadTooLarge {
+            size: 2048,
+            max: 1024,
+        };
+        assert_eq!(
+            format!("{err}"),
+            "Payload of 2048 bytes exceeds the 1024 byte limit"
+        );
+
+        let err = EdgeClawError::IntegrityError;
+        assert_eq!(
+            format!("{err}"),
+            "Integrity check failed: reassembled payload does not match its hash"
+        );
+
+        let err = EdgeClawError::Revoked;
+        assert_eq!(format!("{err}"), "Revoked by its issuer");
+
+        let err = EdgeClawError::ReplayDetected;
+        assert_eq!(format!("{err}"), "Replayed frame rejected");
+
+        let err = EdgeClawError::CapacityExceeded { max: 64 };
+        assert_eq!(format!("{err}"), "Capacity of 64 exceeded");
+
+        let err = EdgeClawError::RateLimited;
+        assert_eq!(format!("{err}"), "Rate limit exceeded");
     }
 }
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::ecnp::{EcnpCodec, EcnpMessage, FLAG_FRAGMENT, MAX_PAYLOAD_SIZE};
+use crate::error::EdgeClawError;
+use crate::protocol::MessageType;
+
+/// Largest payload a transfer may reassemble to
+pub const MAX_TRANSFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// Transfers the reassembler tracks at once
+const MAX_PENDING_TRANSFERS: usize = 8;
+
+/// A transfer with no new fragment for this long is dropped
+const STALLED_TRANSFER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Fragment body header: transfer id (4, BE) + marker (1)
+const FRAGMENT_HEADER_SIZE: usize = 5;
+const HASH_SIZE: usize = 32;
+
+/// Marker bit: first fragment; a SHA-256 of the whole payload follows the
+/// header (the integrity manifest)
+const FRAGMENT_FIRST: u8 = 0x01;
+
+/// Marker bit: last fragment of the transfer
+const FRAGMENT_LAST: u8 = 0x02;
+
+/// Split `payload` into ECNP v1.2 frames flagged `FLAG_FRAGMENT`, each
+/// carrying at most `max_chunk` payload bytes.
+///
+/// The first fragment carries the SHA-256 of the whole payload so
+/// `Reassembler` can prove nothing was dropped or reordered in transit.
+pub fn fragment(
+    msg_type: MessageType,
+    payload: &[u8],
+    max_chunk: usize,
+) -> Result<Vec<Vec<u8>>, EdgeClawError> {
+    if payload.len() > MAX_TRANSFER_SIZE {
+        return Err(EdgeClawError::PayloadTooLarge {
+            size: payload.len(),
+            max: MAX_TRANSFER_SIZE,
+        });
+    }
+    if max_chunk == 0 || max_chunk > MAX_PAYLOAD_SIZE - FRAGMENT_HEADER_SIZE - HASH_SIZE {
+        return Err(EdgeClawError::InvalidParameter);
+    }
+
+    let transfer_id: u32 = rand::random();
+    let hash: [u8; HASH_SIZE] = Sha256::digest(payload).into();
+    let mut chunks: Vec<&[u8]> = payload.chunks(max_chunk).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    let last_index = chunks.len() - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut marker = 0;
+            if i == 0 {
+                marker |= FRAGMENT_FIRST;
+            }
+            if i == last_index {
+                marker |= FRAGMENT_LAST;
+            }
+
+            let mut body = Vec::with_capacity(FRAGMENT_HEADER_SIZE + HASH_SIZE + chunk.len());
+            body.extend_from_slice(&transfer_id.to_be_bytes());
+            body.push(marker);
+            if i == 0 {
+                body.extend_from_slice(&hash);
+            }
+            body.extend_from_slice(chunk);
+            EcnpCodec::encode_with_flags(msg_type, FLAG_FRAGMENT, &body)
+        })
+        .collect()
+}
+
+struct Transfer {
+    msg_type: u8,
+    expected_hash: [u8; HASH_SIZE],
+    data: Vec<u8>,
+    last_fragment_at: std::time::Instant,
+}
+
+/// Rebuilds payloads split by `fragment`.
+///
+/// Fragments of a transfer must arrive in order. When the last one
+/// arrives the reassembled bytes are checked against the first fragment's
+/// hash; a dropped, duplicated or reordered fragment fails with
+/// `IntegrityError` and the transfer is discarded. A transfer that receives
+/// no fragment for 30 seconds is dropped so stalled senders cannot hold
+/// the slots forever.
+#[derive(Default)]
+pub struct Reassembler {
+    transfers: HashMap<u32, Transfer>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one decoded fragment frame. Returns the full payload once the
+    /// last fragment of its transfer is in, `None` while more are expected.
+    pub fn push(&mut self, msg: &EcnpMessage) -> Result<Option<Vec<u8>>, EdgeClawError> {
+        self.push_at(msg, std::time::Instant::now())
+    }
+
+    /// `push` with the current time passed in
+    fn push_at(
+        &mut self,
+        msg: &EcnpMessage,
+        now: std::time::Instant,
+    ) -> Result<Option<Vec<u8>>, EdgeClawError> {
+        self.transfers.retain(|transfer_id, transfer| {
+            let live = now.duration_since(transfer.last_fragment_at) < STALLED_TRANSFER_TIMEOUT;
+            if !live {
+                tracing::debug!(transfer_id, "Dropped stalled fragment transfer");
+            }
+            live
+        });
+        if msg.flags & FLAG_FRAGMENT == 0 || msg.payload.len() < FRAGMENT_HEADER_SIZE {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let (header, mut chunk) = msg.payload.split_at(FRAGMENT_HEADER_SIZE);
+        let transfer_id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let marker = header[4];
+
+        if marker & FRAGMENT_FIRST != 0 {
+            if chunk.len() < HASH_SIZE {
+                return Err(EdgeClawError::InvalidParameter);
+            }
+            // A second "first" fragment means the sender restarted or
+            // fragments were mixed up; either way the old bytes are void
+            if self.transfers.remove(&transfer_id).is_some() {
+                return Err(EdgeClawError::IntegrityError);
+            }
+            if self.transfers.len() >= MAX_PENDING_TRANSFERS {
+                return Err(EdgeClawError::InvalidParameter);
+            }
+            let (hash, rest) = chunk.split_at(HASH_SIZE);
+            let mut expected_hash = [0u8; HASH_SIZE];
+            expected_hash.copy_from_slice(hash);
+            self.transfers.insert(
+                transfer_id,
+                Transfer {
+                    msg_type: msg.msg_type,
+                    expected_hash,
+                    data: Vec::new(),
+                    last_fragment_at: now,
+                },
+            );
+            chunk = rest;
+        }
+
+        // No entry means the first fragment never arrived
+        let transfer = self
+            .transfers
+            .get_mut(&transfer_id)
+            .ok_or(EdgeClawError::IntegrityError)?;
+        if transfer.msg_type != msg.msg_type {
+            self.transfers.remove(&transfer_id);
+            return Err(EdgeClawError::IntegrityError);
+        }
+        let size = transfer.data.len() + chunk.len();
+        if size > MAX_TRANSFER_SIZE {
+            self.transfers.remove(&transfer_id);
+            return Err(EdgeClawError::PayloadTooLarge {
+                size,
+                max: MAX_TRANSFER_SIZE,
+            });
+        }
+        transfer.data.extend_from_slice(chunk);
+        transfer.last_fragment_at = now;
+
+        if marker & FRAGMENT_LAST == 0 {
+            return Ok(None);
+        }
+        let transfer = self
+            .transfers
+            .remove(&transfer_id)
+            .ok_or(EdgeClawError::InternalError)?;
+        let actual: [u8; HASH_SIZE] = Sha256::digest(&transfer.data).into();
+        if actual != transfer.expected_hash {
+            return Err(EdgeClawError::IntegrityError);
+        }
+        Ok(Some(transfer.data))
+    }
+
+    /// Transfers started but not yet complete
+    pub fn pending(&self) -> usize {
+        self.transfers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(frames: &[Vec<u8>]) -> Vec<EcnpMessage> {
+        frames
+            .iter()
+            .map(|f| EcnpCodec::decode_strict(f).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_fragment_reassemble_roundtrip() {
+        let payload: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let frames = fragment(MessageType::Data, &payload, 1024).unwrap();
+        assert_eq!(frames.len(), 10);
+
+        let mut reassembler = Reassembler::new();
+        let messages = decoded(&frames);
+        let (last, rest) = messages.split_last().unwrap();
+        for msg in rest {
+            assert_eq!(msg.flags, FLAG_FRAGMENT);
+            assert_eq!(reassembler.push(msg).unwrap(), None);
+        }
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.push(last).unwrap(), Some(payload));
+        assert_eq!(reassembler.pending(), 0);
+
+        // Empty payloads still travel as a single fragment
+        let frames = fragment(MessageType::Data, b"", 1024).unwrap();
+        assert_eq!(frames.len(), 1);
+        let msg = EcnpCodec::decode(&frames[0]).unwrap();
+        assert_eq!(reassembler.push(&msg).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_dropped_fragment_fails_integrity() {
+        let payload = vec![7u8; 4096];
+        let mut messages = decoded(&fragment(MessageType::Data, &payload, 1000).unwrap());
+
+        let mut reassembler = Reassembler::new();
+        messages.remove(2);
+        let (last, rest) = messages.split_last().unwrap();
+        for msg in rest {
+            reassembler.push(msg).unwrap();
+        }
+        assert!(matches!(
+            reassembler.push(last),
+            Err(EdgeClawError::IntegrityError)
+        ));
+        assert_eq!(reassembler.pending(), 0);
+
+        // Losing the first fragment loses the manifest
+        let messages = decoded(&fragment(MessageType::Data, &payload, 1000).unwrap());
+        assert!(matches!(
+            reassembler.push(&messages[1]),
+            Err(EdgeClawError::IntegrityError)
+        ));
+    }
+
+    #[test]
+    fn test_stalled_transfers_are_evicted() {
+        let start = std::time::Instant::now();
+        let mut reassembler = Reassembler::new();
+        let payload = vec![1u8; 100];
+        for _ in 0..MAX_PENDING_TRANSFERS {
+            let first = decoded(&fragment(MessageType::Data, &payload, 10).unwrap()).remove(0);
+            reassembler.push_at(&first, start).unwrap();
+        }
+
+        let messages = decoded(&fragment(MessageType::Data, &payload, 10).unwrap());
+        assert!(matches!(
+            reassembler.push_at(&messages[0], start),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+
+        // Once the others stall, a new transfer gets through
+        let later = start + STALLED_TRANSFER_TIMEOUT;
+        assert_eq!(reassembler.push_at(&messages[0], later).unwrap(), None);
+        assert_eq!(reassembler.pending(), 1);
+        let (last, rest) = messages[1..].split_last().unwrap();
+        for msg in rest {
+            reassembler.push_at(msg, later).unwrap();
+        }
+        assert_eq!(reassembler.push_at(last, later).unwrap(), Some(payload));
+    }
+
+    #[test]
+    fn test_fragment_rejects_bad_input() {
+        assert!(matches!(
+            fragment(MessageType::Data, b"abc", 0),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(matches!(
+            fragment(MessageType::Data, b"abc", MAX_PAYLOAD_SIZE),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+
+        let plain =
+            EcnpCodec::decode(&EcnpCodec::encode(MessageType::Data, b"x").unwrap()).unwrap();
+        assert!(matches!(
+            Reassembler::new().push(&plain),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+}
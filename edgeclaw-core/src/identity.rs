@@ -1,9 +1,11 @@
-use ed25519_dalek::{SigningKey, VerifyingKey};
-use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use sha2::{Digest, Sha256};
-use x25519_dalek::{PublicKey, StaticSecret};
 
 use crate::error::EdgeClawError;
+use crate::keystore::{InMemoryKeyProvider, KeyProvider, KeySecrets};
+use crate::time::{self, TimeSource};
 
 /// Device identity information exposed via UniFFI
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -14,11 +16,146 @@ pub struct DeviceIdentity {
     pub created_at: String,
 }
 
-/// Manages device identity (Ed25519 signing + X25519 key exchange)
+/// Fingerprint = first 16 hex chars of SHA256(public_key)
+fn fingerprint_of(public_key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(public_key);
+    let hash = hasher.finalize();
+    hex::encode(&hash[..8])
+}
+
+/// Derive the fingerprint for a hex-encoded Ed25519 public key
+pub fn fingerprint_for_key(public_key_hex: &str) -> Result<String, EdgeClawError> {
+    let bytes = hex::decode(public_key_hex).map_err(|_| EdgeClawError::InvalidParameter)?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| EdgeClawError::InvalidParameter)?;
+    Ok(fingerprint_of(&key))
+}
+
+/// Verify that a claimed fingerprint was derived from the given public key.
+///
+/// A mismatch means the key/fingerprint pair was tampered with (or mixed
+/// up) and returns `CryptoError`.
+pub fn verify_claimed_fingerprint(
+    public_key_hex: &str,
+    claimed: &str,
+) -> Result<(), EdgeClawError> {
+    let derived = fingerprint_for_key(public_key_hex)?;
+    if derived.eq_ignore_ascii_case(claimed) {
+        Ok(())
+    } else {
+        tracing::warn!(claimed = %claimed, derived = %derived, "Fingerprint mismatch");
+        Err(EdgeClawError::CryptoError)
+    }
+}
+
+const PAIRING_PIN_CONTEXT: &[u8] = b"edgeclaw-pairing-pin-v1";
+
+/// Longest PIN `pairing_pin` produces; 10^18 still fits in a u64
+pub const MAX_PAIRING_PIN_DIGITS: u8 = 18;
+
+/// Short numeric code both devices derive from their fingerprints, for the
+/// user to compare when pairing.
+///
+/// The fingerprints are lowercased and sorted, so either side gets the same
+/// PIN. SHA-256 of the pair is reduced modulo 10^`digits` and zero-padded;
+/// `digits` is capped at `MAX_PAIRING_PIN_DIGITS`. For full MITM protection
+/// compare `session_confirmation` tags instead.
+pub fn pairing_pin(local_fingerprint: &str, remote_fingerprint: &str, digits: u8) -> String {
+    let digits = digits.min(MAX_PAIRING_PIN_DIGITS);
+    let mut pair = [
+        local_fingerprint.to_ascii_lowercase(),
+        remote_fingerprint.to_ascii_lowercase(),
+    ];
+    pair.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(PAIRING_PIN_CONTEXT);
+    for fingerprint in &pair {
+        hasher.update((fingerprint.len() as u32).to_be_bytes());
+        hasher.update(fingerprint.as_bytes());
+    }
+    let hash = hasher.finalize();
+
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&hash[..8]);
+    let code = u64::from_be_bytes(prefix) % 10u64.pow(digits as u32);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// How `device_id` is assigned to newly generated identities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceIdMode {
+    /// Random UUID v4, unrelated to the keypair (legacy behaviour)
+    #[default]
+    Uuid,
+    /// Base32 of a SHA-256 prefix of the Ed25519 public key
+    Derived,
+}
+
+const DEVICE_ID_CONTEXT: &[u8] = b"edgeclaw-device-id-v1";
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Derive a stable device ID from an Ed25519 public key: lowercase
+/// unpadded base32 (RFC 4648) of the first 20 bytes of
+/// SHA-256(context || public_key), 32 characters long
+pub fn derive_device_id(public_key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(DEVICE_ID_CONTEXT);
+    hasher.update(public_key);
+    let hash = hasher.finalize();
+
+    let mut out = String::with_capacity(32);
+    for chunk in hash[..20].chunks(5) {
+        let bits = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        for i in (0..8).rev() {
+            out.push(BASE32_ALPHABET[((bits >> (i * 5)) & 0x1F) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Label of the identity used when callers never pick one
+pub const DEFAULT_IDENTITY_LABEL: &str = "default";
+
+fn default_label() -> String {
+    DEFAULT_IDENTITY_LABEL.to_string()
+}
+
+/// Exportable identity key material (hex-encoded secrets)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IdentitySnapshot {
+    #[serde(default = "default_label")]
+    pub label: String,
+    pub identity: DeviceIdentity,
+    pub signing_key_hex: String,
+    pub x25519_secret_hex: String,
+}
+
+fn decode_key(hex_str: &str) -> Result<[u8; 32], EdgeClawError> {
+    hex::decode(hex_str)
+        .map_err(|_| EdgeClawError::InvalidParameter)?
+        .try_into()
+        .map_err(|_| EdgeClawError::InvalidParameter)
+}
+
+/// Key provider for one labeled identity
+struct IdentityKeys {
+    provider: Arc<dyn KeyProvider>,
+    identity: DeviceIdentity,
+}
+
+/// Manages device identities (Ed25519 signing + X25519 key exchange).
+///
+/// Identities are keyed by label; the active one is used for sessions,
+/// signing and ECMs. Single-identity callers only ever touch "default".
 pub struct IdentityManager {
-    signing_key: Option<SigningKey>,
-    x25519_secret: Option<StaticSecret>,
-    identity: Option<DeviceIdentity>,
+    identities: HashMap<String, IdentityKeys>,
+    active: String,
+    device_id_mode: DeviceIdMode,
+    clock: Arc<dyn TimeSource>,
 }
 
 impl Default for IdentityManager {
@@ -29,81 +166,249 @@ impl Default for IdentityManager {
 
 impl IdentityManager {
     pub fn new() -> Self {
+        Self::with_device_id_mode(DeviceIdMode::default())
+    }
+
+    /// Create a manager that assigns device IDs using `mode`
+    pub fn with_device_id_mode(mode: DeviceIdMode) -> Self {
         Self {
-            signing_key: None,
-            x25519_secret: None,
-            identity: None,
+            identities: HashMap::new(),
+            active: default_label(),
+            device_id_mode: mode,
+            clock: time::system(),
         }
     }
 
-    /// Generate a new device identity
-    pub fn generate_identity(&mut self) -> Result<DeviceIdentity, EdgeClawError> {
-        // Generate Ed25519 signing key
-        let signing_key = SigningKey::generate(&mut OsRng);
-        let verifying_key: VerifyingKey = signing_key.verifying_key();
-        let public_key_bytes = verifying_key.to_bytes();
-        let public_key_hex = hex::encode(public_key_bytes);
+    /// Stamp `created_at` on new identities using `clock`
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.clock = clock;
+    }
 
-        // Generate X25519 key for key exchange
-        let x25519_secret = StaticSecret::random_from_rng(OsRng);
+    /// Generate a new identity under the active label
+    pub fn generate_identity(&mut self) -> Result<DeviceIdentity, EdgeClawError> {
+        let label = self.active.clone();
+        self.generate_identity_for(&label)
+    }
 
-        // Device ID = UUID v4
-        let device_id = uuid::Uuid::new_v4().to_string();
+    /// Generate a new identity under `label`, replacing any existing one
+    pub fn generate_identity_for(&mut self, label: &str) -> Result<DeviceIdentity, EdgeClawError> {
+        if label.is_empty() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let identity = self.insert_provider(label, Arc::new(InMemoryKeyProvider::generate()));
 
-        // Fingerprint = first 16 chars of SHA256(public_key)
-        let mut hasher = Sha256::new();
-        hasher.update(public_key_bytes);
-        let hash = hasher.finalize();
-        let fingerprint = hex::encode(&hash[..8]);
+        tracing::info!(
+            label = %label,
+            device_id = %identity.device_id,
+            fingerprint = %identity.fingerprint,
+            "Device identity generated"
+        );
 
-        let identity = DeviceIdentity {
-            device_id,
-            public_key_hex,
-            fingerprint,
-            created_at: chrono::Utc::now().to_rfc3339(),
-        };
+        Ok(identity)
+    }
 
-        self.signing_key = Some(signing_key);
-        self.x25519_secret = Some(x25519_secret);
-        self.identity = Some(identity.clone());
+    /// Add an identity under `label` whose keys live behind `provider`
+    /// (e.g. a platform keystore), replacing any existing one
+    pub fn register_key_provider(
+        &mut self,
+        label: &str,
+        provider: Arc<dyn KeyProvider>,
+    ) -> Result<DeviceIdentity, EdgeClawError> {
+        if label.is_empty() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let identity = self.insert_provider(label, provider);
 
         tracing::info!(
+            label = %label,
             device_id = %identity.device_id,
             fingerprint = %identity.fingerprint,
-            "Device identity generated"
+            "Key provider registered"
         );
 
         Ok(identity)
     }
 
-    /// Get current device identity
+    fn insert_provider(&mut self, label: &str, provider: Arc<dyn KeyProvider>) -> DeviceIdentity {
+        let public_key_bytes = provider.signing_public_key();
+        let public_key_hex = hex::encode(public_key_bytes);
+
+        let device_id = match self.device_id_mode {
+            DeviceIdMode::Uuid => uuid::Uuid::new_v4().to_string(),
+            DeviceIdMode::Derived => derive_device_id(&public_key_bytes),
+        };
+
+        let fingerprint = fingerprint_of(&public_key_bytes);
+
+        let identity = DeviceIdentity {
+            device_id,
+            public_key_hex,
+            fingerprint,
+            created_at: self.clock.now().to_rfc3339(),
+        };
+
+        self.identities.insert(
+            label.to_string(),
+            IdentityKeys {
+                provider,
+                identity: identity.clone(),
+            },
+        );
+        identity
+    }
+
+    /// Get the active device identity
     pub fn get_identity(&self) -> Result<DeviceIdentity, EdgeClawError> {
-        self.identity.clone().ok_or(EdgeClawError::InternalError)
+        Ok(self.active_keys()?.identity.clone())
+    }
+
+    /// Get the identity stored under `label`
+    pub fn get_identity_for(&self, label: &str) -> Result<DeviceIdentity, EdgeClawError> {
+        self.identities
+            .get(label)
+            .map(|k| k.identity.clone())
+            .ok_or(EdgeClawError::InvalidParameter)
+    }
+
+    /// Labels of all stored identities, sorted
+    pub fn list_identities(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self.identities.keys().cloned().collect();
+        labels.sort();
+        labels
+    }
+
+    /// Label of the active identity
+    pub fn active_label(&self) -> &str {
+        &self.active
+    }
+
+    /// Switch the active identity; the label must already exist
+    pub fn set_active(&mut self, label: &str) -> Result<(), EdgeClawError> {
+        if !self.identities.contains_key(label) {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        self.active = label.to_string();
+        tracing::info!(label = %label, "Active identity switched");
+        Ok(())
+    }
+
+    fn active_keys(&self) -> Result<&IdentityKeys, EdgeClawError> {
+        self.identities
+            .get(&self.active)
+            .ok_or(EdgeClawError::IdentityNotInitialized)
+    }
+
+    /// Key provider of the active identity
+    pub fn key_provider(&self) -> Result<Arc<dyn KeyProvider>, EdgeClawError> {
+        Ok(Arc::clone(&self.active_keys()?.provider))
     }
 
-    /// Get the X25519 secret key bytes for session creation
+    /// Get the X25519 secret key bytes for session creation.
+    ///
+    /// Fails with `CryptoError` if the active provider does not export keys.
     pub fn get_secret_key(&self) -> Result<[u8; 32], EdgeClawError> {
-        let secret = self
-            .x25519_secret
-            .as_ref()
-            .ok_or(EdgeClawError::InternalError)?;
-        Ok(secret.to_bytes())
+        self.active_keys()?
+            .provider
+            .export_secrets()
+            .map(|secrets| secrets.x25519_secret)
+            .ok_or(EdgeClawError::CryptoError)
+    }
+
+    /// Sign a message with the active identity's Ed25519 key
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+        self.active_keys()?.provider.sign(message)
+    }
+
+    /// Export the active identity and its secret keys, if it exists and its
+    /// provider exports keys
+    pub fn export_identity(&self) -> Option<IdentitySnapshot> {
+        self.identities
+            .get(&self.active)
+            .and_then(|keys| Self::snapshot_of(&self.active, keys))
+    }
+
+    /// Export every stored identity whose provider exports keys.
+    ///
+    /// Platform-backed identities are left out since their keys never leave
+    /// the keystore; `keep_platform_identities` carries them over instead.
+    pub fn export_identities(&self) -> Vec<IdentitySnapshot> {
+        self.identities
+            .iter()
+            .filter_map(|(label, keys)| Self::snapshot_of(label, keys))
+            .collect()
+    }
+
+    fn snapshot_of(label: &str, keys: &IdentityKeys) -> Option<IdentitySnapshot> {
+        let secrets = keys.provider.export_secrets()?;
+        Some(IdentitySnapshot {
+            label: label.to_string(),
+            identity: keys.identity.clone(),
+            signing_key_hex: hex::encode(secrets.signing_key),
+            x25519_secret_hex: hex::encode(secrets.x25519_secret),
+        })
+    }
+
+    /// Restore an identity from a snapshot under its label, replacing any
+    /// identity already stored there.
+    ///
+    /// The stored public key must match the signing key, otherwise the
+    /// snapshot is rejected with `CryptoError` and nothing changes.
+    pub fn import_identity(&mut self, snapshot: IdentitySnapshot) -> Result<(), EdgeClawError> {
+        if snapshot.label.is_empty() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let provider = InMemoryKeyProvider::from_secrets(&KeySecrets {
+            signing_key: decode_key(&snapshot.signing_key_hex)?,
+            x25519_secret: decode_key(&snapshot.x25519_secret_hex)?,
+        });
+
+        let public_key_hex = hex::encode(provider.signing_public_key());
+        if !public_key_hex.eq_ignore_ascii_case(&snapshot.identity.public_key_hex) {
+            return Err(EdgeClawError::CryptoError);
+        }
+
+        self.identities.insert(
+            snapshot.label,
+            IdentityKeys {
+                provider: Arc::new(provider),
+                identity: snapshot.identity,
+            },
+        );
+        Ok(())
     }
 
-    /// Get the X25519 public key bytes
+    /// Take over the identities of `previous` whose provider does not export
+    /// keys, so replacing a manager (e.g. on snapshot import) keeps them.
+    /// They win over identities of the same label already held here.
+    pub fn keep_platform_identities(&mut self, previous: &IdentityManager) {
+        for (label, keys) in &previous.identities {
+            if keys.provider.export_secrets().is_some() {
+                continue;
+            }
+            if self.identities.contains_key(label) {
+                tracing::warn!(label = %label, "Kept platform-backed identity over imported one");
+            }
+            self.identities.insert(
+                label.clone(),
+                IdentityKeys {
+                    provider: Arc::clone(&keys.provider),
+                    identity: keys.identity.clone(),
+                },
+            );
+        }
+    }
+
+    /// Get the active identity's X25519 public key bytes
     pub fn get_public_key(&self) -> Result<[u8; 32], EdgeClawError> {
-        let secret = self
-            .x25519_secret
-            .as_ref()
-            .ok_or(EdgeClawError::InternalError)?;
-        let public = PublicKey::from(secret);
-        Ok(public.to_bytes())
+        Ok(self.active_keys()?.provider.x25519_public_key())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
 
     #[test]
     fn test_generate_identity() {
@@ -116,10 +421,40 @@ mod tests {
         assert!(!id.created_at.is_empty());
     }
 
+    #[test]
+    fn test_derived_device_id() {
+        let key_a = SigningKey::generate(&mut OsRng).verifying_key().to_bytes();
+        let key_b = SigningKey::generate(&mut OsRng).verifying_key().to_bytes();
+
+        let id_a = derive_device_id(&key_a);
+        assert_eq!(id_a, derive_device_id(&key_a));
+        assert_ne!(id_a, derive_device_id(&key_b));
+        assert_eq!(id_a.len(), 32);
+        assert!(id_a.bytes().all(|c| BASE32_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn test_device_id_mode() {
+        let mut mgr = IdentityManager::with_device_id_mode(DeviceIdMode::Derived);
+        let id = mgr.generate_identity().unwrap();
+        let public_key: [u8; 32] = hex::decode(&id.public_key_hex).unwrap().try_into().unwrap();
+        assert_eq!(id.device_id, derive_device_id(&public_key));
+
+        let legacy = IdentityManager::new().generate_identity().unwrap();
+        assert!(uuid::Uuid::parse_str(&legacy.device_id).is_ok());
+    }
+
     #[test]
     fn test_get_identity_before_generate() {
         let mgr = IdentityManager::new();
-        assert!(mgr.get_identity().is_err());
+        assert!(matches!(
+            mgr.get_identity(),
+            Err(EdgeClawError::IdentityNotInitialized)
+        ));
+        assert!(matches!(
+            mgr.get_secret_key(),
+            Err(EdgeClawError::IdentityNotInitialized)
+        ));
     }
 
     #[test]
@@ -134,6 +469,102 @@ mod tests {
         assert_eq!(public.len(), 32);
     }
 
+    #[test]
+    fn test_export_import_identity() {
+        let mut mgr = IdentityManager::new();
+        assert!(mgr.export_identity().is_none());
+        let id = mgr.generate_identity().unwrap();
+        let snapshot = mgr.export_identity().unwrap();
+
+        let mut restored = IdentityManager::new();
+        restored.import_identity(snapshot.clone()).unwrap();
+        assert_eq!(restored.get_identity().unwrap().device_id, id.device_id);
+        assert_eq!(
+            restored.get_secret_key().unwrap(),
+            mgr.get_secret_key().unwrap()
+        );
+
+        let mut tampered = snapshot;
+        tampered.identity.public_key_hex = "00".repeat(32);
+        assert!(matches!(
+            IdentityManager::new().import_identity(tampered),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_key_provider_identity_delegates() {
+        use crate::keystore::mock::MockKeyProvider;
+
+        let provider = Arc::new(MockKeyProvider::new());
+        let mut mgr = IdentityManager::new();
+        let id = mgr
+            .register_key_provider(DEFAULT_IDENTITY_LABEL, provider.clone())
+            .unwrap();
+        assert_eq!(
+            id.public_key_hex,
+            hex::encode(provider.signing_public_key())
+        );
+        assert_eq!(mgr.get_public_key().unwrap(), provider.x25519_public_key());
+
+        let signature = mgr.sign(b"challenge").unwrap();
+        assert_eq!(provider.signs(), 1);
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&provider.signing_public_key()).unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&signature).unwrap();
+        assert!(ed25519_dalek::Verifier::verify(&key, b"challenge", &signature).is_ok());
+
+        // Keys that never leave the provider cannot be exported
+        assert!(matches!(
+            mgr.get_secret_key(),
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert!(mgr.export_identity().is_none());
+        assert!(mgr.export_identities().is_empty());
+        assert!(mgr.register_key_provider("", provider.clone()).is_err());
+
+        // ...but survive being replaced by a restored manager
+        let mut other = IdentityManager::new();
+        other.generate_identity().unwrap();
+        let work = other.generate_identity_for("work").unwrap();
+        let mut restored = IdentityManager::new();
+        for snapshot in other.export_identities() {
+            restored.import_identity(snapshot).unwrap();
+        }
+        restored.keep_platform_identities(&mgr);
+        assert_eq!(
+            restored.get_identity().unwrap().public_key_hex,
+            hex::encode(provider.signing_public_key())
+        );
+        assert_eq!(
+            restored.get_identity_for("work").unwrap().device_id,
+            work.device_id
+        );
+    }
+
+    #[test]
+    fn test_labeled_identities() {
+        let mut mgr = IdentityManager::new();
+        let personal = mgr.generate_identity().unwrap();
+        let work = mgr.generate_identity_for("work").unwrap();
+
+        assert_eq!(mgr.list_identities(), vec!["default", "work"]);
+        assert_eq!(mgr.active_label(), DEFAULT_IDENTITY_LABEL);
+        assert_eq!(mgr.get_identity().unwrap().device_id, personal.device_id);
+        let personal_secret = mgr.get_secret_key().unwrap();
+
+        mgr.set_active("work").unwrap();
+        assert_eq!(mgr.get_identity().unwrap().device_id, work.device_id);
+        assert_ne!(mgr.get_secret_key().unwrap(), personal_secret);
+        assert_eq!(
+            mgr.get_identity_for("default").unwrap().device_id,
+            personal.device_id
+        );
+
+        assert!(mgr.set_active("missing").is_err());
+        assert_eq!(mgr.active_label(), "work");
+        assert_eq!(mgr.export_identities().len(), 2);
+    }
+
     #[test]
     fn test_identity_uniqueness() {
         let mut mgr1 = IdentityManager::new();
@@ -145,4 +576,52 @@ mod tests {
         assert_ne!(id1.device_id, id2.device_id);
         assert_ne!(id1.public_key_hex, id2.public_key_hex);
     }
+
+    #[test]
+    fn test_fingerprint_for_known_key() {
+        // SHA256 of 32 zero bytes = 66687aadf862bd77...
+        let zero_key = "00".repeat(32);
+        assert_eq!(fingerprint_for_key(&zero_key).unwrap(), "66687aadf862bd77");
+
+        let mut mgr = IdentityManager::new();
+        let id = mgr.generate_identity().unwrap();
+        assert_eq!(
+            fingerprint_for_key(&id.public_key_hex).unwrap(),
+            id.fingerprint
+        );
+        assert!(verify_claimed_fingerprint(&id.public_key_hex, &id.fingerprint).is_ok());
+    }
+
+    #[test]
+    fn test_pairing_pin_symmetric_and_stable() {
+        let a = "66687aadf862bd77";
+        let b = "0a1b2c3d4e5f6071";
+        let pin = pairing_pin(a, b, 6);
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(pin, pairing_pin(b, a, 6));
+        assert_eq!(pin, pairing_pin(&a.to_uppercase(), b, 6));
+        assert_eq!(pin, "184255");
+
+        assert_ne!(pin, pairing_pin(a, "ffffffffffffffff", 6));
+        assert_eq!(pairing_pin(a, b, 4).len(), 4);
+        assert_eq!(pairing_pin(a, b, 40).len(), MAX_PAIRING_PIN_DIGITS as usize);
+    }
+
+    #[test]
+    fn test_verify_claimed_fingerprint_mismatch() {
+        let mut mgr = IdentityManager::new();
+        let id = mgr.generate_identity().unwrap();
+
+        assert!(matches!(
+            verify_claimed_fingerprint(&id.public_key_hex, "0000000000000000"),
+            Err(EdgeClawError::CryptoError)
+        ));
+        // Malformed key
+        assert!(matches!(
+            fingerprint_for_key("not-hex"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(fingerprint_for_key("abcd").is_err());
+    }
 }
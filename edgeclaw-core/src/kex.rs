@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use rand::rngs::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::error::EdgeClawError;
+
+/// The key agreement step of the session handshake.
+///
+/// `SessionManager` only asks for a raw shared secret and runs it through
+/// HKDF itself, so a different scheme (e.g. hybrid X25519 + ML-KEM) can be
+/// swapped in with `SessionManager::set_key_exchange`. The engine's
+/// `SessionInit`/`SessionAccept` handshake takes its ephemeral keys from
+/// the same exchange. Keys are byte slices because such schemes don't use
+/// 32-byte keys. `X25519KeyExchange` is the default.
+pub trait KeyExchange: Send + Sync {
+    /// Algorithm name, for logs and diagnostics
+    fn algorithm(&self) -> &'static str;
+
+    /// Fresh local key pair
+    fn generate_keypair(&self) -> KeyExchangeKeypair;
+
+    /// Raw shared secret between our `local_secret` and `remote_public`
+    fn shared_secret(
+        &self,
+        local_secret: &[u8],
+        remote_public: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError>;
+}
+
+/// Key pair produced by a `KeyExchange`. Not `Clone`; the secret is
+/// wiped on drop.
+pub struct KeyExchangeKeypair {
+    pub secret: Vec<u8>,
+    pub public: Vec<u8>,
+}
+
+impl Drop for KeyExchangeKeypair {
+    fn drop(&mut self) {
+        zeroize(&mut self.secret);
+    }
+}
+
+/// Overwrite secret bytes before they are freed (best effort)
+pub(crate) fn zeroize(secret: &mut [u8]) {
+    secret.fill(0);
+    std::hint::black_box(secret);
+}
+
+/// Plain X25519 ECDH (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct X25519KeyExchange;
+
+impl KeyExchange for X25519KeyExchange {
+    fn algorithm(&self) -> &'static str {
+        "x25519"
+    }
+
+    fn generate_keypair(&self) -> KeyExchangeKeypair {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        KeyExchangeKeypair {
+            public: PublicKey::from(&secret).to_bytes().to_vec(),
+            secret: secret.to_bytes().to_vec(),
+        }
+    }
+
+    fn shared_secret(
+        &self,
+        local_secret: &[u8],
+        remote_public: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let local: [u8; 32] = local_secret
+            .try_into()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        let remote: [u8; 32] = remote_public
+            .try_into()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        let shared = StaticSecret::from(local).diffie_hellman(&PublicKey::from(remote));
+        Ok(shared.as_bytes().to_vec())
+    }
+}
+
+/// Shared handle to the default X25519 exchange
+pub fn x25519() -> Arc<dyn KeyExchange> {
+    Arc::new(X25519KeyExchange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_x25519_agreement() {
+        let kex = X25519KeyExchange;
+        let a = kex.generate_keypair();
+        let b = kex.generate_keypair();
+        assert_eq!(a.public.len(), 32);
+        assert_eq!(kex.algorithm(), "x25519");
+
+        let ab = kex.shared_secret(&a.secret, &b.public).unwrap();
+        let ba = kex.shared_secret(&b.secret, &a.public).unwrap();
+        assert_eq!(ab, ba);
+
+        // Same result as calling x25519-dalek directly
+        let secret: [u8; 32] = a.secret.clone().try_into().unwrap();
+        let public: [u8; 32] = b.public.clone().try_into().unwrap();
+        let direct = StaticSecret::from(secret).diffie_hellman(&PublicKey::from(public));
+        assert_eq!(ab, direct.as_bytes());
+    }
+
+    #[test]
+    fn test_x25519_rejects_wrong_key_length() {
+        let kex = X25519KeyExchange;
+        let a = kex.generate_keypair();
+        assert!(matches!(
+            kex.shared_secret(&a.secret, &[0u8; 31]),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(matches!(
+            kex.shared_secret(&[0u8; 64], &a.public),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use ed25519_dalek::{Signer, SigningKey};
+use rand::rngs::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::error::EdgeClawError;
+
+/// Raw identity secrets, for backup of keys that are allowed to leave
+/// the provider
+pub struct KeySecrets {
+    pub signing_key: [u8; 32],
+    pub x25519_secret: [u8; 32],
+}
+
+/// Performs an identity's private-key operations.
+///
+/// Platform integrations (Secure Enclave, StrongBox, ...) implement this so
+/// the private keys never enter Rust-managed memory; `IdentityManager` and
+/// `SessionManager` only ever call through it. `InMemoryKeyProvider` is the
+/// default.
+pub trait KeyProvider: Send + Sync {
+    /// Ed25519 public key
+    fn signing_public_key(&self) -> [u8; 32];
+
+    /// X25519 public key used for session key exchange
+    fn x25519_public_key(&self) -> [u8; 32];
+
+    /// Ed25519 signature over `message`
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EdgeClawError>;
+
+    /// Raw X25519 shared secret with `peer_public`
+    fn diffie_hellman(&self, peer_public: &[u8; 32]) -> Result<[u8; 32], EdgeClawError>;
+
+    /// Secrets for export; hardware-backed providers keep the default `None`
+    fn export_secrets(&self) -> Option<KeySecrets> {
+        None
+    }
+}
+
+/// Keys held in process memory (the default provider)
+pub struct InMemoryKeyProvider {
+    signing_key: SigningKey,
+    x25519_secret: StaticSecret,
+}
+
+impl InMemoryKeyProvider {
+    /// Fresh random Ed25519 and X25519 keys
+    pub fn generate() -> Self {
+        Self {
+            signing_key: SigningKey::generate(&mut OsRng),
+            x25519_secret: StaticSecret::random_from_rng(OsRng),
+        }
+    }
+
+    /// Provider over existing secret key bytes
+    pub fn from_secrets(secrets: &KeySecrets) -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(&secrets.signing_key),
+            x25519_secret: StaticSecret::from(secrets.x25519_secret),
+        }
+    }
+}
+
+impl KeyProvider for InMemoryKeyProvider {
+    fn signing_public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    fn x25519_public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.x25519_secret).to_bytes()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+        Ok(self.signing_key.sign(message).to_bytes().to_vec())
+    }
+
+    fn diffie_hellman(&self, peer_public: &[u8; 32]) -> Result<[u8; 32], EdgeClawError> {
+        let shared = self
+            .x25519_secret
+            .diffie_hellman(&PublicKey::from(*peer_public));
+        Ok(shared.to_bytes())
+    }
+
+    fn export_secrets(&self) -> Option<KeySecrets> {
+        Some(KeySecrets {
+            signing_key: self.signing_key.to_bytes(),
+            x25519_secret: self.x25519_secret.to_bytes(),
+        })
+    }
+}
+
+/// Hardware-keystore stand-in for tests: counts calls and never exports
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub(crate) struct MockKeyProvider {
+        inner: InMemoryKeyProvider,
+        signs: AtomicUsize,
+        exchanges: AtomicUsize,
+    }
+
+    impl MockKeyProvider {
+        pub(crate) fn new() -> Self {
+            Self {
+                inner: InMemoryKeyProvider::generate(),
+                signs: AtomicUsize::new(0),
+                exchanges: AtomicUsize::new(0),
+            }
+        }
+
+        pub(crate) fn signs(&self) -> usize {
+            self.signs.load(Ordering::SeqCst)
+        }
+
+        pub(crate) fn exchanges(&self) -> usize {
+            self.exchanges.load(Ordering::SeqCst)
+        }
+    }
+
+    impl KeyProvider for MockKeyProvider {
+        fn signing_public_key(&self) -> [u8; 32] {
+            self.inner.signing_public_key()
+        }
+
+        fn x25519_public_key(&self) -> [u8; 32] {
+            self.inner.x25519_public_key()
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+            self.signs.fetch_add(1, Ordering::SeqCst);
+            self.inner.sign(message)
+        }
+
+        fn diffie_hellman(&self, peer_public: &[u8; 32]) -> Result<[u8; 32], EdgeClawError> {
+            self.exchanges.fetch_add(1, Ordering::SeqCst);
+            self.inner.diffie_hellman(peer_public)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    #[test]
+    fn test_in_memory_sign_and_exchange() {
+        let a = InMemoryKeyProvider::generate();
+        let b = InMemoryKeyProvider::generate();
+
+        let signature = a.sign(b"hello").unwrap();
+        let key = VerifyingKey::from_bytes(&a.signing_public_key()).unwrap();
+        let signature = Signature::from_slice(&signature).unwrap();
+        assert!(key.verify(b"hello", &signature).is_ok());
+
+        assert_eq!(
+            a.diffie_hellman(&b.x25519_public_key()).unwrap(),
+            b.diffie_hellman(&a.x25519_public_key()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_in_memory_secrets_roundtrip() {
+        let original = InMemoryKeyProvider::generate();
+        let restored = InMemoryKeyProvider::from_secrets(&original.export_secrets().unwrap());
+        assert_eq!(restored.signing_public_key(), original.signing_public_key());
+        assert_eq!(restored.x25519_public_key(), original.x25519_public_key());
+    }
+}
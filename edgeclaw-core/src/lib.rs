@@ -4,26 +4,48 @@
 //! providing identity management, encrypted sessions, ECNP protocol
 //! framing, peer discovery, and policy evaluation.
 
+pub mod auth;
 pub mod ecnp;
 pub mod error;
+pub mod fragment;
 pub mod identity;
+pub mod kex;
+pub mod keystore;
+pub mod logging;
 pub mod peer;
 pub mod policy;
+pub mod pool;
 pub mod protocol;
 pub mod session;
+pub mod snapshot;
 pub mod sync;
+pub mod time;
 pub mod uniffi_bridge;
 
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
+use auth::{AttestationLedger, AuthChallenge, ChallengeStore, IssuedAttestation, PeerAttestation};
 use ecnp::{EcnpCodec, EcnpMessage};
 use error::EdgeClawError;
-use identity::{DeviceIdentity, IdentityManager};
-use peer::{PeerInfo, PeerManager};
-use policy::{PolicyDecision, PolicyEngine};
-use protocol::MessageType;
-use session::{SessionInfo, SessionManager};
-use sync::{SyncClient, SyncClientConfig, SyncMessage};
+use identity::{DeviceIdMode, DeviceIdentity, IdentityManager};
+use kex::KeyExchangeKeypair;
+use keystore::KeyProvider;
+use logging::{LogFormat, LogRing};
+use peer::{PeerInfo, PeerLiveness, PeerManager, PeerManifest, PeerSortKey};
+use policy::{
+    CapabilityInfo, PermissionMatrix, PolicyAuditEntry, PolicyDecision, PolicyEngine, RiskLevel,
+    Role,
+};
+use pool::{ConnectionPool, PoolConfig, PoolStats};
+use protocol::{
+    ControlMessage, DeviceType, EcmPayload, MessageType, SessionAccept, SessionInit,
+    SessionNegotiation, SignedControlMessage,
+};
+use session::{NonceMode, SessionInfo, SessionManager, SessionOptions};
+use snapshot::EngineSnapshot;
+use sync::{CommandRisk, SyncClient, SyncClientConfig, SyncMessage};
+use time::TimeSource;
 
 // ─── Engine config ───
 
@@ -36,6 +58,45 @@ pub struct EngineConfig {
     pub max_connections: u32,
     pub quic_enabled: bool,
     pub log_level: String,
+    /// Number of recent log lines kept in memory for diagnostics (0 = off)
+    #[serde(default)]
+    pub log_ring_capacity: u32,
+    /// How device IDs are assigned to new identities
+    #[serde(default)]
+    pub device_id_mode: DeviceIdMode,
+    /// Default lifetime of new sessions (seconds)
+    #[serde(default = "default_session_duration")]
+    pub session_duration_secs: u64,
+    /// Sessions held at once before new ones are refused (0 = unlimited)
+    #[serde(default = "default_max_active_sessions")]
+    pub max_active_sessions: u32,
+    /// Formatter for the tracing output (JSON unless set)
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// New peers accepted per discovery source address per minute; excess
+    /// announcements are dropped (0 = unlimited)
+    #[serde(default = "default_discovery_rate_limit")]
+    pub discovery_rate_limit: u32,
+    /// New peers accepted per minute across all sources, including
+    /// `add_peer` (0 = unlimited)
+    #[serde(default = "default_discovery_global_rate_limit")]
+    pub discovery_global_rate_limit: u32,
+}
+
+fn default_session_duration() -> u64 {
+    3600
+}
+
+fn default_max_active_sessions() -> u32 {
+    256
+}
+
+fn default_discovery_rate_limit() -> u32 {
+    64
+}
+
+fn default_discovery_global_rate_limit() -> u32 {
+    256
 }
 
 impl Default for EngineConfig {
@@ -47,10 +108,162 @@ impl Default for EngineConfig {
             max_connections: 16,
             quic_enabled: false,
             log_level: "info".to_string(),
+            log_ring_capacity: 0,
+            device_id_mode: DeviceIdMode::Uuid,
+            session_duration_secs: default_session_duration(),
+            max_active_sessions: default_max_active_sessions(),
+            log_format: LogFormat::Json,
+            discovery_rate_limit: default_discovery_rate_limit(),
+            discovery_global_rate_limit: default_discovery_global_rate_limit(),
         }
     }
 }
 
+impl EngineConfig {
+    /// Reject values the engine cannot run with
+    fn validate(&self) -> Result<(), EdgeClawError> {
+        if self.device_name.is_empty()
+            || self.max_connections == 0
+            || self.session_duration_secs == 0
+            || i64::try_from(self.session_duration_secs).is_err()
+            || tracing_subscriber::EnvFilter::try_new(&self.log_level).is_err()
+        {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        Ok(())
+    }
+}
+
+/// What this build supports, for version negotiation and diagnostics
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EngineCapabilities {
+    /// Crate version (`CARGO_PKG_VERSION`)
+    pub crate_version: String,
+    /// Decodable ECNP frame version bytes
+    pub ecnp_versions: Vec<u8>,
+    /// Session ciphers
+    pub ciphers: Vec<String>,
+    /// Payload compression algorithms
+    pub compression: Vec<String>,
+    /// ECNP message type codes
+    pub message_types: Vec<u8>,
+    /// Sync sub-type codes carried in Data frames
+    pub sync_message_types: Vec<u8>,
+    /// Optional protocol features compiled into this build
+    pub features: Vec<String>,
+}
+
+/// Protocol features reported by `EdgeClawEngine::capabilities`
+const ENGINE_FEATURES: &[&str] = &[
+    "signed_frames",
+    "cbor_sync",
+    "session_negotiation",
+    "session_confirmation",
+    "peer_auth_challenge",
+    "sync_desktop_auth",
+    "encrypted_snapshot",
+    "binary_exec_output",
+];
+
+/// Snapshot of engine health for supervisors
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct EngineHealth {
+    /// A lock was found poisoned (a thread panicked while holding it);
+    /// state may be inconsistent and a restart is advisable
+    pub degraded: bool,
+    pub identity_loaded: bool,
+    pub active_sessions: u32,
+    pub peers: u32,
+    pub sync_connected: bool,
+}
+
+/// Aggregate counters for status screens, gathered in one call
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct EngineStats {
+    pub peers: u32,
+    pub connected_peers: u32,
+    pub active_sessions: u32,
+    /// Plaintext bytes encrypted / decrypted through sessions
+    pub bytes_encrypted: u64,
+    pub bytes_decrypted: u64,
+    pub sync_messages_sent: u64,
+    pub sync_messages_received: u64,
+    /// Policy denials, including report-only would-denies
+    pub policy_denials: u64,
+}
+
+/// Outcome of `EdgeClawEngine::update_config`
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ConfigUpdateReport {
+    /// Fields whose new values took effect immediately
+    pub applied: Vec<String>,
+    /// Changed fields that keep their running values until the engine is
+    /// recreated
+    pub restart_required: Vec<String>,
+}
+
+// ─── Session negotiation ───
+
+/// Result of answering a `SessionInit`: the new session plus the
+/// `SessionAccept` frame to send back to the initiator
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionHandshakeAccept {
+    pub session: SessionInfo,
+    pub accept_frame: Vec<u8>,
+}
+
+/// A scheduled rekey: the session being replaced and the `SessionInit`
+/// frame to send its peer
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionRekey {
+    pub session_id: String,
+    pub peer_id: String,
+    pub init_frame: Vec<u8>,
+}
+
+/// A tracked remote exec: the ID to cancel it with plus the frame to send
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteExecRequest {
+    pub request_id: String,
+    pub frame: Vec<u8>,
+}
+
+/// Combined verdict of `EdgeClawEngine::can_remote_exec`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteExecGate {
+    pub allowed: bool,
+    /// "allowed", "capability_not_negotiated", "policy_denied" or
+    /// "command_too_risky"
+    pub code: String,
+    pub reason: String,
+    pub command_risk: CommandRisk,
+}
+
+/// Initiator state kept until the peer's `SessionAccept` arrives
+struct PendingSessionInit {
+    peer_id: String,
+    ephemeral: KeyExchangeKeypair,
+    duration_secs: u64,
+    nonce_mode: NonceMode,
+    max_idle_secs: Option<u64>,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    /// Session retired once this handshake completes (rekeys)
+    replaces: Option<String>,
+}
+
+const SESSION_INIT_TAG: &str = "edgeclaw-session-init-v2";
+const SESSION_ACCEPT_TAG: &str = "edgeclaw-session-accept-v2";
+/// How long a `SessionInit` waits for its `SessionAccept`
+const PENDING_SESSION_INIT_TTL_SECS: i64 = 120;
+/// Cap on handshakes awaiting a `SessionAccept`
+const MAX_PENDING_SESSION_INITS: usize = 64;
+/// How long to wait for a peer's reply on a pooled connection
+const PEER_REPLY_TIMEOUT_SECS: u64 = 10;
+/// Oldest (or furthest-future) `issued_at` accepted on a control message
+const CONTROL_MESSAGE_MAX_AGE_SECS: i64 = 300;
+/// Least peer role whose signed control messages are applied
+const CONTROL_SIGNER_MIN_ROLE: Role = Role::Admin;
+
 // ─── Main Engine ───
 
 /// Create a new EdgeClaw engine instance
@@ -60,40 +273,344 @@ pub fn create_engine(config: EngineConfig) -> Result<EdgeClawEngine, EdgeClawErr
 
 /// Main EdgeClaw engine — thread-safe, composable
 pub struct EdgeClawEngine {
-    config: EngineConfig,
+    config: RwLock<EngineConfig>,
     identity_manager: Mutex<IdentityManager>,
-    session_manager: Mutex<SessionManager>,
+    /// Read-locked for per-session crypto; write-locked to add or remove sessions
+    session_manager: RwLock<SessionManager>,
     peer_manager: Mutex<PeerManager>,
-    policy_engine: PolicyEngine,
+    auth_challenges: Mutex<ChallengeStore>,
+    attestations: Mutex<AttestationLedger>,
+    pending_session_inits: Mutex<std::collections::HashMap<String, PendingSessionInit>>,
+    policy_engine: Mutex<PolicyEngine>,
+    /// Sequence of the last control message we signed
+    control_sequence: Mutex<u64>,
+    /// Last control message sequence applied, per signer
+    control_sequences_seen: Mutex<std::collections::HashMap<String, u64>>,
     sync_client: Mutex<Option<SyncClient>>,
+    /// Outbound peer connections for `establish_session` / `probe_session`
+    connection_pool: ConnectionPool,
+    /// Engine-level timestamps (e.g. pending handshake expiry)
+    clock: RwLock<Arc<dyn TimeSource>>,
+    log_ring: Option<LogRing>,
+    /// Set once any engine lock has been found poisoned
+    degraded: AtomicBool,
+    /// Handle to the global log filter; only live if this engine installed
+    /// the subscriber
+    log_filter: tracing_subscriber::reload::Handle<
+        tracing_subscriber::EnvFilter,
+        tracing_subscriber::Registry,
+    >,
 }
 
 impl EdgeClawEngine {
-    fn new(config: EngineConfig) -> Result<Self, EdgeClawError> {
-        // Initialize tracing (ignore if already set)
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter(
-                tracing_subscriber::EnvFilter::try_new(&config.log_level)
-                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-            )
-            .json()
+    fn new(mut config: EngineConfig) -> Result<Self, EdgeClawError> {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+
+        let log_ring =
+            (config.log_ring_capacity > 0).then(|| LogRing::new(config.log_ring_capacity as usize));
+
+        // Initialize tracing (ignore if already set). The log ring is fed
+        // by the fanout layer of whichever engine installed the subscriber.
+        if let Some(ring) = &log_ring {
+            ring.register_global();
+        }
+        let (filter, log_filter) = tracing_subscriber::reload::Layer::new(
+            tracing_subscriber::EnvFilter::try_new(&config.log_level)
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        );
+        let _ = tracing_subscriber::registry()
+            .with(filter)
+            .with(logging::fmt_layer(config.log_format, std::io::stdout))
+            .with(logging::RingFanout)
             .try_init();
 
         tracing::info!(device_name = %config.device_name, "EdgeClaw engine initializing");
 
+        config.device_type = DeviceType::normalize(&config.device_type);
+        let device_id_mode = config.device_id_mode;
+        let mut session_manager = SessionManager::new();
+        session_manager.set_default_duration_secs(config.session_duration_secs)?;
+        session_manager.set_max_active_sessions(config.max_active_sessions);
+        let mut peer_manager = PeerManager::new();
+        peer_manager.set_discovery_rate_limit(config.discovery_rate_limit);
+        peer_manager.set_discovery_global_rate_limit(config.discovery_global_rate_limit);
+        let connection_pool = ConnectionPool::new(PoolConfig {
+            max_total: config.max_connections as usize,
+            ..Default::default()
+        });
+
         Ok(Self {
-            config,
-            identity_manager: Mutex::new(IdentityManager::new()),
-            session_manager: Mutex::new(SessionManager::new()),
-            peer_manager: Mutex::new(PeerManager::new()),
-            policy_engine: PolicyEngine::new(),
+            config: RwLock::new(config),
+            identity_manager: Mutex::new(IdentityManager::with_device_id_mode(device_id_mode)),
+            session_manager: RwLock::new(session_manager),
+            peer_manager: Mutex::new(peer_manager),
+            auth_challenges: Mutex::new(ChallengeStore::default()),
+            attestations: Mutex::new(AttestationLedger::new()),
+            pending_session_inits: Mutex::new(std::collections::HashMap::new()),
+            policy_engine: Mutex::new(PolicyEngine::new()),
+            control_sequence: Mutex::new(0),
+            control_sequences_seen: Mutex::new(std::collections::HashMap::new()),
             sync_client: Mutex::new(None),
+            connection_pool,
+            clock: RwLock::new(time::system()),
+            log_ring,
+            degraded: AtomicBool::new(false),
+            log_filter,
         })
     }
 
+    fn now(&self) -> Result<chrono::DateTime<chrono::Utc>, EdgeClawError> {
+        Ok(self.clock.read().map_err(|e| self.lock_failed(e))?.now())
+    }
+
+    /// Take the guard from a poisoned lock, marking the engine degraded
+    fn recover_lock<G>(&self, poisoned: PoisonError<G>) -> G {
+        self.mark_degraded();
+        poisoned.into_inner()
+    }
+
+    /// Map a poisoned lock to `InternalError`, marking the engine degraded
+    fn lock_failed<G>(&self, _: PoisonError<G>) -> EdgeClawError {
+        self.mark_degraded();
+        EdgeClawError::InternalError
+    }
+
+    fn mark_degraded(&self) {
+        if !self.degraded.swap(true, Ordering::Relaxed) {
+            tracing::error!("Engine lock poisoned; running degraded");
+        }
+    }
+
+    /// Whether a poisoned lock has been seen since the engine was created
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Read the time from `clock` in every manager: identity and session
+    /// timestamps, session expiry, peer `last_seen`, challenge and pending
+    /// handshake expiry, and policy audit entries
+    pub fn set_time_source(&self, clock: Arc<dyn TimeSource>) -> Result<(), EdgeClawError> {
+        *self.clock.write().map_err(|e| self.lock_failed(e))? = Arc::clone(&clock);
+        self.identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .set_time_source(Arc::clone(&clock));
+        self.session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?
+            .set_time_source(Arc::clone(&clock));
+        self.peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .set_time_source(Arc::clone(&clock));
+        self.auth_challenges
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .set_time_source(Arc::clone(&clock));
+        self.policy_engine
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .set_time_source(clock);
+        Ok(())
+    }
+
+    /// Degraded flag plus basic liveness counters
+    pub fn health(&self) -> EngineHealth {
+        let identity_loaded = self
+            .identity_manager
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .get_identity()
+            .is_ok();
+        let active_sessions = self
+            .session_manager
+            .read()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .active_sessions()
+            .len() as u32;
+        let peers = self
+            .peer_manager
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .list_peers()
+            .len() as u32;
+        let sync_connected = self.sync_is_connected();
+
+        EngineHealth {
+            degraded: self.is_degraded(),
+            identity_loaded,
+            active_sessions,
+            peers,
+            sync_connected,
+        }
+    }
+
+    /// Peer, session, traffic, sync and policy counters in one struct
+    pub fn stats(&self) -> EngineStats {
+        let (peers, connected_peers) = {
+            let peers = self
+                .peer_manager
+                .lock()
+                .unwrap_or_else(|e| self.recover_lock(e));
+            (
+                peers.list_peers().len() as u32,
+                peers.connected_peers().len() as u32,
+            )
+        };
+        let (active_sessions, bytes_encrypted, bytes_decrypted) = {
+            let sessions = self
+                .session_manager
+                .read()
+                .unwrap_or_else(|e| self.recover_lock(e));
+            (
+                sessions.active_sessions().len() as u32,
+                sessions.bytes_encrypted(),
+                sessions.bytes_decrypted(),
+            )
+        };
+        let sync_stats = self
+            .sync_client
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .as_ref()
+            .map(|c| c.stats());
+        let policy_denials = self
+            .policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .denial_count();
+
+        EngineStats {
+            peers,
+            connected_peers,
+            active_sessions,
+            bytes_encrypted,
+            bytes_decrypted,
+            sync_messages_sent: sync_stats.as_ref().map_or(0, |s| s.messages_sent),
+            sync_messages_received: sync_stats.as_ref().map_or(0, |s| s.messages_received),
+            policy_denials,
+        }
+    }
+
     /// Get engine configuration
-    pub fn config(&self) -> &EngineConfig {
-        &self.config
+    pub fn config(&self) -> EngineConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .clone()
+    }
+
+    /// Versions, algorithms and features supported by this build
+    pub fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            ecnp_versions: ecnp::SUPPORTED_VERSIONS.to_vec(),
+            ciphers: vec![protocol::SESSION_CIPHER_AES256GCM.to_string()],
+            compression: ecnp::Compression::ALL
+                .iter()
+                .map(|c| c.as_str().to_string())
+                .collect(),
+            message_types: MessageType::ALL.iter().map(|t| *t as u8).collect(),
+            sync_message_types: sync::SYNC_TYPES.to_vec(),
+            features: ENGINE_FEATURES.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// Apply a new configuration to the running engine.
+    ///
+    /// Device name/type, max connections, session duration and log level
+    /// take effect immediately. Changes to listen port, QUIC, log ring
+    /// capacity and device ID mode are reported in `restart_required` and
+    /// keep their running values; recreate the engine to apply them. The
+    /// log level can only change live if this engine installed the global
+    /// tracing subscriber, otherwise it is reported as restart-required too.
+    pub fn update_config(&self, new: EngineConfig) -> Result<ConfigUpdateReport, EdgeClawError> {
+        new.validate()?;
+
+        let mut config = self.config.write().map_err(|e| self.lock_failed(e))?;
+        let mut report = ConfigUpdateReport::default();
+
+        if new.session_duration_secs != config.session_duration_secs {
+            self.session_manager
+                .write()
+                .map_err(|e| self.lock_failed(e))?
+                .set_default_duration_secs(new.session_duration_secs)?;
+            config.session_duration_secs = new.session_duration_secs;
+            report.applied.push("session_duration_secs".into());
+        }
+        if new.max_active_sessions != config.max_active_sessions {
+            self.session_manager
+                .write()
+                .map_err(|e| self.lock_failed(e))?
+                .set_max_active_sessions(new.max_active_sessions);
+            config.max_active_sessions = new.max_active_sessions;
+            report.applied.push("max_active_sessions".into());
+        }
+        if new.discovery_rate_limit != config.discovery_rate_limit {
+            self.peer_manager
+                .lock()
+                .map_err(|e| self.lock_failed(e))?
+                .set_discovery_rate_limit(new.discovery_rate_limit);
+            config.discovery_rate_limit = new.discovery_rate_limit;
+            report.applied.push("discovery_rate_limit".into());
+        }
+        if new.discovery_global_rate_limit != config.discovery_global_rate_limit {
+            self.peer_manager
+                .lock()
+                .map_err(|e| self.lock_failed(e))?
+                .set_discovery_global_rate_limit(new.discovery_global_rate_limit);
+            config.discovery_global_rate_limit = new.discovery_global_rate_limit;
+            report.applied.push("discovery_global_rate_limit".into());
+        }
+        if new.log_level != config.log_level {
+            let filter = tracing_subscriber::EnvFilter::try_new(&new.log_level)
+                .map_err(|_| EdgeClawError::InvalidParameter)?;
+            if self.log_filter.reload(filter).is_ok() {
+                config.log_level = new.log_level.clone();
+                report.applied.push("log_level".into());
+            } else {
+                report.restart_required.push("log_level".into());
+            }
+        }
+        if new.device_name != config.device_name {
+            config.device_name = new.device_name.clone();
+            report.applied.push("device_name".into());
+        }
+        let device_type = DeviceType::normalize(&new.device_type);
+        if device_type != config.device_type {
+            config.device_type = device_type;
+            report.applied.push("device_type".into());
+        }
+        if new.max_connections != config.max_connections {
+            config.max_connections = new.max_connections;
+            self.connection_pool
+                .set_max_total(new.max_connections as usize);
+            report.applied.push("max_connections".into());
+        }
+
+        if new.listen_port != config.listen_port {
+            report.restart_required.push("listen_port".into());
+        }
+        if new.quic_enabled != config.quic_enabled {
+            report.restart_required.push("quic_enabled".into());
+        }
+        if new.log_ring_capacity != config.log_ring_capacity {
+            report.restart_required.push("log_ring_capacity".into());
+        }
+        if new.device_id_mode != config.device_id_mode {
+            report.restart_required.push("device_id_mode".into());
+        }
+        if new.log_format != config.log_format {
+            report.restart_required.push("log_format".into());
+        }
+
+        tracing::info!(
+            applied = ?report.applied,
+            restart_required = ?report.restart_required,
+            "Engine config updated"
+        );
+        Ok(report)
     }
 
     // ─── Identity ───
@@ -103,7 +620,7 @@ impl EdgeClawEngine {
         let mut mgr = self
             .identity_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
+            .map_err(|e| self.lock_failed(e))?;
         mgr.generate_identity()
     }
 
@@ -112,13 +629,70 @@ impl EdgeClawEngine {
         let mgr = self
             .identity_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
+            .map_err(|e| self.lock_failed(e))?;
         mgr.get_identity()
     }
 
+    /// Generate an identity under a label (e.g. "work"); the active identity
+    /// is unchanged
+    pub fn generate_identity_for(&self, label: &str) -> Result<DeviceIdentity, EdgeClawError> {
+        let mut mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        mgr.generate_identity_for(label)
+    }
+
+    /// Get the identity stored under a label
+    pub fn get_identity_for(&self, label: &str) -> Result<DeviceIdentity, EdgeClawError> {
+        let mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        mgr.get_identity_for(label)
+    }
+
+    /// Add an identity under a label whose keys stay in a platform keystore;
+    /// signing and session key exchange call through `provider`
+    pub fn register_key_provider(
+        &self,
+        label: &str,
+        provider: Arc<dyn KeyProvider>,
+    ) -> Result<DeviceIdentity, EdgeClawError> {
+        let mut mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        mgr.register_key_provider(label, provider)
+    }
+
+    /// Labels of all identities held by this engine
+    pub fn list_identities(&self) -> Vec<String> {
+        let mgr = self
+            .identity_manager
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e));
+        mgr.list_identities()
+    }
+
+    /// Switch the identity used for sessions, signing and ECMs
+    pub fn set_active_identity(&self, label: &str) -> Result<(), EdgeClawError> {
+        let mut mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        mgr.set_active(label)
+    }
+
     // ─── Peers ───
 
-    /// Add or update a discovered peer
+    /// Add or update a discovered peer. A new address for the paired sync
+    /// desktop is offered to the sync client, which adopts it once the
+    /// desktop there proves its pinned key (see `SyncClient::propose_address`).
+    ///
+    /// Unknown peers count against `discovery_global_rate_limit`; over it
+    /// the call fails with `RateLimited`. Use `ingest_discovery` for
+    /// announcements received from the network.
     pub fn add_peer(
         &self,
         peer_id: &str,
@@ -127,325 +701,3147 @@ impl EdgeClawEngine {
         address: &str,
         capabilities: Vec<String>,
     ) -> Result<PeerInfo, EdgeClawError> {
-        let mut mgr = self
+        self.record_discovered_peer(
+            peer_id,
+            device_name,
+            device_type,
+            address,
+            capabilities,
+            None,
+        )
+    }
+
+    /// `add_peer` for an announcement received from `source`, the
+    /// transport address it actually arrived from. Unknown peers also count
+    /// against `discovery_rate_limit` for that source, whatever `address`
+    /// they claim.
+    pub fn ingest_discovery(
+        &self,
+        peer_id: &str,
+        device_name: &str,
+        device_type: &str,
+        address: &str,
+        capabilities: Vec<String>,
+        source: &str,
+    ) -> Result<PeerInfo, EdgeClawError> {
+        self.record_discovered_peer(
+            peer_id,
+            device_name,
+            device_type,
+            address,
+            capabilities,
+            Some(source),
+        )
+    }
+
+    fn record_discovered_peer(
+        &self,
+        peer_id: &str,
+        device_name: &str,
+        device_type: &str,
+        address: &str,
+        capabilities: Vec<String>,
+        source: Option<&str>,
+    ) -> Result<PeerInfo, EdgeClawError> {
+        let info = {
+            let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+            mgr.admit_discovery(peer_id, source)?;
+            mgr.add_peer(peer_id, device_name, device_type, address, capabilities)
+        };
+        self.follow_desktop_address(&info);
+        Ok(info)
+    }
+
+    /// List all known peers, most recently seen first, then by name
+    pub fn get_peers(&self) -> Vec<PeerInfo> {
+        self.get_peers_sorted(PeerSortKey::default())
+    }
+
+    /// List all known peers in the given order
+    pub fn get_peers_sorted(&self, by: PeerSortKey) -> Vec<PeerInfo> {
+        let mgr = self
             .peer_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        Ok(mgr.add_peer(peer_id, device_name, device_type, address, capabilities))
+            .unwrap_or_else(|e| self.recover_lock(e));
+        mgr.list_peers_sorted(by)
     }
 
-    /// List all known peers
-    pub fn get_peers(&self) -> Vec<PeerInfo> {
-        let mgr = self.peer_manager.lock().unwrap_or_else(|e| e.into_inner());
-        mgr.list_peers()
+    /// Connected / recently seen / stale / unknown status of a peer
+    pub fn peer_liveness(&self, peer_id: &str) -> Result<PeerLiveness, EdgeClawError> {
+        self.peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .liveness(peer_id)
     }
 
     /// Remove a peer by ID
     pub fn remove_peer(&self, peer_id: &str) -> Result<(), EdgeClawError> {
-        let mut mgr = self
-            .peer_manager
-            .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
         mgr.remove_peer(peer_id)
     }
 
-    // ─── Sessions ───
+    /// Mark a known peer as freshly seen without re-adding it
+    pub fn touch_peer(&self, peer_id: &str) -> Result<(), EdgeClawError> {
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        mgr.touch(peer_id)
+    }
 
-    /// Create an encrypted session with a peer via X25519 ECDH
-    pub fn create_session(
-        &self,
-        peer_id: &str,
-        peer_public_key: &[u8; 32],
-    ) -> Result<SessionInfo, EdgeClawError> {
-        let id_mgr = self
-            .identity_manager
+    /// Add a group tag to a peer
+    pub fn tag_peer(&self, peer_id: &str, tag: &str) -> Result<(), EdgeClawError> {
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        mgr.tag_peer(peer_id, tag)
+    }
+
+    /// Remove a group tag from a peer
+    pub fn untag_peer(&self, peer_id: &str, tag: &str) -> Result<(), EdgeClawError> {
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        mgr.untag_peer(peer_id, tag)
+    }
+
+    /// List peers carrying a group tag
+    pub fn peers_with_tag(&self, tag: &str) -> Vec<PeerInfo> {
+        let mgr = self
+            .peer_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        let our_secret = id_mgr.get_secret_key()?;
+            .unwrap_or_else(|e| self.recover_lock(e));
+        mgr.peers_with_tag(tag)
+    }
 
-        let mut sess_mgr = self
-            .session_manager
+    /// Peers pinned to the given public key; more than one means a device
+    /// is announcing under several IDs
+    pub fn find_peers_by_public_key(&self, public_key_hex: &str) -> Vec<PeerInfo> {
+        let mgr = self
+            .peer_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        sess_mgr.create_session(peer_id, &our_secret, peer_public_key)
+            .unwrap_or_else(|e| self.recover_lock(e));
+        mgr.find_by_public_key(public_key_hex)
     }
 
-    /// Encrypt data using a session key
-    pub fn encrypt_message(
+    /// Pin a peer's Ed25519 public key (hex) for challenge-response auth
+    pub fn set_peer_public_key(
         &self,
-        session_id: &str,
-        plaintext: &[u8],
-    ) -> Result<Vec<u8>, EdgeClawError> {
-        let mut sess_mgr = self
-            .session_manager
+        peer_id: &str,
+        public_key_hex: &str,
+    ) -> Result<(), EdgeClawError> {
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        mgr.set_public_key(peer_id, public_key_hex)
+    }
+
+    /// Trust a peer's signed control messages at `role`, or not at all
+    /// with `None`; only Admin and above may revoke capabilities
+    pub fn set_peer_role(&self, peer_id: &str, role: Option<&str>) -> Result<(), EdgeClawError> {
+        let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        mgr.set_role(peer_id, role)
+    }
+
+    // ─── Peer authentication ───
+
+    /// Issue a single-use challenge proving `peer_id` holds its pinned key
+    pub fn create_auth_challenge(&self, peer_id: &str) -> Result<AuthChallenge, EdgeClawError> {
+        // Fail early for peers we could never verify
+        self.peer_public_key(peer_id)?;
+        let mut store = self
+            .auth_challenges
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        sess_mgr.encrypt(session_id, plaintext)
+            .map_err(|e| self.lock_failed(e))?;
+        store.issue(peer_id)
     }
 
-    /// Decrypt data using a session key
-    pub fn decrypt_message(
+    /// Verify a peer's signature over a challenge nonce
+    pub fn verify_auth_response(
         &self,
-        session_id: &str,
-        ciphertext: &[u8],
-    ) -> Result<Vec<u8>, EdgeClawError> {
-        let mut sess_mgr = self
-            .session_manager
+        challenge_id: &str,
+        signature: &[u8],
+    ) -> Result<(), EdgeClawError> {
+        let mut store = self
+            .auth_challenges
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        sess_mgr.decrypt(session_id, ciphertext)
+            .map_err(|e| self.lock_failed(e))?;
+        store.verify_with(challenge_id, signature, |peer_id| {
+            self.peer_public_key(peer_id)
+        })
     }
 
-    // ─── Protocol ───
-
-    /// Create an ECM (Edge Capability Manifest) announcement
-    pub fn create_ecm(&self) -> Result<String, EdgeClawError> {
+    /// Answer a challenge frame from a peer by signing its nonce
+    pub fn sign_auth_challenge(&self, nonce_frame: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+        let payload = auth::parse_challenge_frame(nonce_frame)?;
+        let nonce = hex::decode(&payload.nonce).map_err(|_| EdgeClawError::InvalidParameter)?;
         let id_mgr = self
             .identity_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        let identity = id_mgr.get_identity()?;
-
-        protocol::create_ecm(
-            &identity.device_id,
-            &self.config.device_type,
-            vec!["status".into(), "file_read".into(), "heartbeat".into()],
-        )
+            .map_err(|e| self.lock_failed(e))?;
+        id_mgr.sign(&auth::challenge_message(&nonce))
     }
 
-    /// Create a heartbeat message
-    pub fn create_heartbeat(
-        &self,
-        uptime_secs: u64,
-        cpu_usage: f64,
-        memory_usage: f64,
-    ) -> Result<String, EdgeClawError> {
+    /// Vouch for a peer's pinned identity key with a signed attestation
+    /// (JSON), valid for `auth::DEFAULT_ATTESTATION_TTL_SECS`
+    pub fn attest_peer(&self, peer_id: &str) -> Result<String, EdgeClawError> {
+        let subject_key = self.peer_public_key(peer_id)?;
         let id_mgr = self
             .identity_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
+            .map_err(|e| self.lock_failed(e))?;
         let identity = id_mgr.get_identity()?;
 
-        let active = self
-            .session_manager
+        let mut attestation = PeerAttestation::new(
+            &identity.device_id,
+            peer_id,
+            &hex::encode(subject_key.to_bytes()),
+            auth::DEFAULT_ATTESTATION_TTL_SECS,
+            self.now()?,
+        );
+        attestation.signature = hex::encode(id_mgr.sign(&attestation.signing_bytes())?);
+        drop(id_mgr);
+
+        self.attestations
             .lock()
-            .map(|s| s.active_sessions().len() as u32)
-            .unwrap_or(0);
+            .map_err(|e| self.lock_failed(e))?
+            .record(&attestation);
+        Ok(serde_json::to_string(&attestation)?)
+    }
 
-        protocol::create_heartbeat(
-            &identity.device_id,
-            uptime_secs,
-            cpu_usage,
-            memory_usage,
-            active,
-        )
+    /// Attestations issued by `attest_peer`, oldest first (the latest
+    /// `auth::ISSUED_ATTESTATION_CAPACITY`)
+    pub fn list_issued_attestations(&self) -> Vec<IssuedAttestation> {
+        self.attestations
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .list_issued()
     }
 
-    // ─── Policy ───
+    /// Revoke an attestation by ID (`PeerAttestation::id`); from then on
+    /// `verify_attestation` rejects it with `Revoked`. Revocations are
+    /// kept in engine snapshots.
+    pub fn revoke_attestation(&self, attestation_id: &str) -> Result<(), EdgeClawError> {
+        if attestation_id.is_empty() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let mut ledger = self.attestations.lock().map_err(|e| self.lock_failed(e))?;
+        if ledger.revoke(attestation_id) {
+            tracing::info!(attestation_id = %attestation_id, "Attestation revoked");
+        }
+        Ok(())
+    }
 
-    /// Evaluate a capability request
-    pub fn evaluate_capability(
+    /// Verify an attestation against the attester's hex Ed25519 key,
+    /// rejecting revoked ones
+    pub fn verify_attestation(
         &self,
-        capability_name: &str,
-        role: &str,
-    ) -> Result<PolicyDecision, EdgeClawError> {
-        self.policy_engine.evaluate(capability_name, role)
+        attestation_json: &str,
+        attester_public_key_hex: &str,
+    ) -> Result<PeerAttestation, EdgeClawError> {
+        let key = protocol::decode_key_hex(attester_public_key_hex)?;
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&key)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        let attestation = auth::verify_attestation(attestation_json, &key, self.now()?)?;
+        self.attestations
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .check(&attestation)?;
+        Ok(attestation)
     }
 
-    // ─── ECNP ───
-
-    /// Encode a message into ECNP v1.1 wire format
-    pub fn encode_ecnp(
+    /// Known peers (names, addresses, pinned keys) as a manifest (JSON)
+    /// signed with our identity key, for bootstrapping another device
+    pub fn export_peer_manifest(&self) -> Result<String, EdgeClawError> {
+        let peers = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .manifest_entries();
+        let id_mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        let mut manifest = PeerManifest {
+            signer_id: id_mgr.get_identity()?.device_id,
+            created_at: self.now()?.to_rfc3339(),
+            peers,
+            signature: String::new(),
+        };
+        manifest.signature = hex::encode(id_mgr.sign(&manifest.signing_bytes()?)?);
+        Ok(serde_json::to_string(&manifest)?)
+    }
+
+    /// Import peers from a manifest after checking it was signed by
+    /// `expected_signer_key_hex` (`CryptoError` otherwise). Returns how many
+    /// entries were imported; pinned keys are never replaced.
+    pub fn import_peer_manifest(
         &self,
-        msg_type: MessageType,
-        payload: &[u8],
+        manifest_json: &str,
+        expected_signer_key_hex: &str,
+    ) -> Result<u32, EdgeClawError> {
+        let key = protocol::decode_key_hex(expected_signer_key_hex)?;
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&key)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        let manifest: PeerManifest = serde_json::from_str(manifest_json)?;
+        manifest.verify(&key)?;
+
+        let imported = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .import_manifest(&manifest.peers)?;
+        tracing::info!(signer_id = %manifest.signer_id, imported, "Peer manifest imported");
+        Ok(imported)
+    }
+
+    fn peer_public_key(&self, peer_id: &str) -> Result<ed25519_dalek::VerifyingKey, EdgeClawError> {
+        let hex_key = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .get_peer(peer_id)?
+            .public_key_hex
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        let bytes: [u8; 32] = hex::decode(hex_key)
+            .map_err(|_| EdgeClawError::InvalidParameter)?
+            .try_into()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        ed25519_dalek::VerifyingKey::from_bytes(&bytes).map_err(|_| EdgeClawError::CryptoError)
+    }
+
+    // ─── Sessions ───
+
+    /// Create an encrypted session with a peer via X25519 ECDH
+    pub fn create_session(
+        &self,
+        peer_id: &str,
+        peer_public_key: &[u8; 32],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let provider = self.key_provider()?;
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.create_session_via(
+            peer_id,
+            provider.as_ref(),
+            peer_public_key,
+            &SessionOptions::default(),
+        )
+    }
+
+    /// Start a session whose key is derived once the peer's public key
+    /// arrives; returns the pending session ID
+    pub fn begin_session(&self, peer_id: &str) -> Result<String, EdgeClawError> {
+        let provider = self.key_provider()?;
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.begin_session_via(peer_id, provider)
+    }
+
+    /// Complete a pending session with the peer's X25519 public key
+    pub fn complete_session(
+        &self,
+        pending_id: &str,
+        peer_public_key: &[u8; 32],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.complete_session(pending_id, peer_public_key)
+    }
+
+    /// Get the active session with a peer, creating it if none exists.
+    ///
+    /// Concurrent callers for the same peer all receive the same session;
+    /// see `SessionManager::get_or_create_session` for the reuse policy.
+    pub fn get_or_create_session(
+        &self,
+        peer_id: &str,
+        peer_public_key: &[u8; 32],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let provider = self.key_provider()?;
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.get_or_create_session_via(peer_id, provider.as_ref(), peer_public_key)
+    }
+
+    /// Accept a session initiated by a peer (responder side)
+    pub fn accept_session(
+        &self,
+        peer_id: &str,
+        peer_public_key: &[u8; 32],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let provider = self.key_provider()?;
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.accept_session_via(
+            peer_id,
+            provider.as_ref(),
+            peer_public_key,
+            &SessionOptions::default(),
+        )
+    }
+
+    fn key_provider(&self) -> Result<Arc<dyn KeyProvider>, EdgeClawError> {
+        self.identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .key_provider()
+    }
+
+    /// One diagnostic line per session (peer, state, age, message counts,
+    /// time to expiry), oldest first
+    pub fn session_summaries(&self) -> Vec<String> {
+        self.session_manager
+            .read()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .session_summaries()
+    }
+
+    /// List every session, including expired ones awaiting cleanup
+    pub fn all_sessions(&self) -> Vec<SessionInfo> {
+        let mgr = self
+            .session_manager
+            .read()
+            .unwrap_or_else(|e| self.recover_lock(e));
+        mgr.all_sessions()
+    }
+
+    /// Start a session handshake: returns a `SessionInit` Handshake frame
+    /// carrying a fresh ephemeral key signed by our identity
+    pub fn initiate_session_handshake(
+        &self,
+        peer_id: &str,
+        options: SessionOptions,
     ) -> Result<Vec<u8>, EdgeClawError> {
-        EcnpCodec::encode(msg_type, payload)
+        self.start_session_handshake(peer_id, options, None)
+            .map(|(_, frame)| frame)
     }
 
-    /// Decode a message from ECNP v1.1 wire format
-    pub fn decode_ecnp(&self, data: &[u8]) -> Result<EcnpMessage, EdgeClawError> {
-        EcnpCodec::decode(data)
+    /// `initiate_session_handshake`, returning the init ID too. `replaces`
+    /// is closed once the handshake completes.
+    fn start_session_handshake(
+        &self,
+        peer_id: &str,
+        options: SessionOptions,
+        replaces: Option<String>,
+    ) -> Result<(String, Vec<u8>), EdgeClawError> {
+        let duration_secs = match options.duration_secs {
+            Some(secs) => secs,
+            None => self
+                .session_manager
+                .read()
+                .map_err(|e| self.lock_failed(e))?
+                .default_duration_secs(),
+        };
+
+        let ephemeral = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?
+            .generate_keypair();
+        let init_id = uuid::Uuid::new_v4().to_string();
+
+        let (identity, signature) = {
+            let id_mgr = self
+                .identity_manager
+                .lock()
+                .map_err(|e| self.lock_failed(e))?;
+            let identity = id_mgr.get_identity()?;
+            let signature = id_mgr.sign(&protocol::session_key_signing_bytes(
+                SESSION_INIT_TAG,
+                &init_id,
+                &identity.device_id,
+                &ephemeral.public,
+                protocol::SESSION_CIPHER_AES256GCM,
+                duration_secs,
+            ))?;
+            (identity, signature)
+        };
+
+        let frame = SessionNegotiation::SessionInit(SessionInit {
+            init_id: init_id.clone(),
+            device_id: identity.device_id,
+            ephemeral_public_key: hex::encode(&ephemeral.public),
+            identity_public_key: identity.public_key_hex,
+            cipher: protocol::SESSION_CIPHER_AES256GCM.to_string(),
+            duration_secs,
+            signature: hex::encode(signature),
+        })
+        .encode()?;
+
+        let now = self.now()?;
+        let mut pending = self
+            .pending_session_inits
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        pending.retain(|_, init| init.expires_at > now);
+        if pending.len() >= MAX_PENDING_SESSION_INITS {
+            tracing::warn!(
+                max = MAX_PENDING_SESSION_INITS,
+                "Too many session handshakes awaiting a reply"
+            );
+            return Err(EdgeClawError::CapacityExceeded {
+                max: MAX_PENDING_SESSION_INITS as u32,
+            });
+        }
+        pending.insert(
+            init_id.clone(),
+            PendingSessionInit {
+                peer_id: peer_id.to_string(),
+                ephemeral,
+                duration_secs,
+                nonce_mode: options.nonce_mode,
+                max_idle_secs: options.max_idle_secs,
+                expires_at: now + chrono::Duration::seconds(PENDING_SESSION_INIT_TTL_SECS),
+                replaces,
+            },
+        );
+        Ok((init_id, frame))
     }
 
-    // ─── Sync ───
+    /// Start a fresh handshake for every active session, e.g. after the
+    /// identity key changed, so new session keys take effect promptly.
+    ///
+    /// Returns one `SessionRekey` per session scheduled (its length is the
+    /// count). Each replacement keeps the old session's lifetime, nonce mode
+    /// and idle limit. An old session stays usable until the peer's
+    /// `SessionAccept` for its replacement is handled, which closes it.
+    ///
+    /// All or nothing: if any handshake cannot be started (e.g. more than
+    /// 64 would be pending) none stay scheduled.
+    pub fn rekey_all_sessions(&self) -> Result<Vec<SessionRekey>, EdgeClawError> {
+        let sessions = {
+            let mgr = self
+                .session_manager
+                .read()
+                .map_err(|e| self.lock_failed(e))?;
+            mgr.active_sessions()
+                .into_iter()
+                .map(|session| {
+                    let options = mgr.session_options(&session.session_id)?;
+                    Ok((session, options))
+                })
+                .collect::<Result<Vec<_>, EdgeClawError>>()?
+        };
 
-    /// Initialize the sync client for Desktop-Mobile synchronization
-    pub fn init_sync(&self, config: SyncClientConfig) -> Result<(), EdgeClawError> {
-        let client = SyncClient::new(config);
-        let mut guard = self
-            .sync_client
+        let mut rekeys = Vec::with_capacity(sessions.len());
+        let mut started = Vec::with_capacity(sessions.len());
+        for (session, options) in sessions {
+            let result = self.start_session_handshake(
+                &session.peer_id,
+                options,
+                Some(session.session_id.clone()),
+            );
+            match result {
+                Ok((init_id, init_frame)) => {
+                    started.push(init_id);
+                    rekeys.push(SessionRekey {
+                        session_id: session.session_id,
+                        peer_id: session.peer_id,
+                        init_frame,
+                    });
+                }
+                Err(e) => {
+                    let mut pending = self
+                        .pending_session_inits
+                        .lock()
+                        .unwrap_or_else(|e| self.recover_lock(e));
+                    for init_id in &started {
+                        pending.remove(init_id);
+                    }
+                    tracing::warn!(error = %e, "Session rekey aborted");
+                    return Err(e);
+                }
+            }
+        }
+        tracing::info!(sessions = rekeys.len(), "Scheduled session rekey");
+        Ok(rekeys)
+    }
+
+    /// Answer a peer's `SessionInit` frame: verifies its signature, creates
+    /// the responder session, and returns the `SessionAccept` frame.
+    ///
+    /// The session is keyed to the initiator's device ID. If that peer has a
+    /// pinned public key, the init must be signed by it.
+    pub fn handle_session_init(
+        &self,
+        frame: &[u8],
+    ) -> Result<SessionHandshakeAccept, EdgeClawError> {
+        let init = match SessionNegotiation::decode(frame)? {
+            SessionNegotiation::SessionInit(init) => init,
+            _ => return Err(EdgeClawError::InvalidParameter),
+        };
+        if init.cipher != protocol::SESSION_CIPHER_AES256GCM {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+
+        let remote_ephemeral = protocol::decode_kex_key_hex(&init.ephemeral_public_key)?;
+        protocol::verify_negotiation_signature(
+            &init.identity_public_key,
+            &protocol::session_key_signing_bytes(
+                SESSION_INIT_TAG,
+                &init.init_id,
+                &init.device_id,
+                &remote_ephemeral,
+                &init.cipher,
+                init.duration_secs,
+            ),
+            &init.signature,
+        )?;
+        self.check_pinned_key(&init.device_id, &init.identity_public_key)?;
+
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        // Never grant a longer lifetime than we would by default
+        let duration_secs = init.duration_secs.min(sess_mgr.default_duration_secs());
+        let ephemeral = sess_mgr.generate_keypair();
+
+        let id_mgr = self
+            .identity_manager
             .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        *guard = Some(client);
-        tracing::info!("Sync client initialized");
-        Ok(())
+            .map_err(|e| self.lock_failed(e))?;
+        let identity = id_mgr.get_identity()?;
+        let signature = id_mgr.sign(&protocol::session_key_signing_bytes(
+            SESSION_ACCEPT_TAG,
+            &init.init_id,
+            &identity.device_id,
+            &ephemeral.public,
+            &init.cipher,
+            duration_secs,
+        ))?;
+        let session = sess_mgr.accept_session_with(
+            &init.device_id,
+            &ephemeral.secret,
+            &remote_ephemeral,
+            &SessionOptions {
+                duration_secs: Some(duration_secs),
+                ..Default::default()
+            },
+        )?;
+
+        let accept_frame = SessionNegotiation::SessionAccept(SessionAccept {
+            init_id: init.init_id,
+            device_id: identity.device_id,
+            ephemeral_public_key: hex::encode(&ephemeral.public),
+            identity_public_key: identity.public_key_hex,
+            cipher: init.cipher,
+            duration_secs,
+            signature: hex::encode(signature),
+        })
+        .encode()?;
+
+        Ok(SessionHandshakeAccept {
+            session,
+            accept_frame,
+        })
     }
 
-    /// Connect sync client to desktop agent
-    pub async fn sync_connect(&self) -> Result<(), EdgeClawError> {
-        // Extract what we need from the lock, then drop it before await
-        let client_state = {
-            let guard = self
-                .sync_client
+    /// Finish a handshake started with `initiate_session_handshake` using
+    /// the peer's `SessionAccept` frame.
+    ///
+    /// The accept must come from the device the init was sent to, signed
+    /// by its pinned key if it has one. The pending init is only consumed
+    /// once those checks pass, so a forged accept cannot cancel it.
+    pub fn handle_session_accept(&self, frame: &[u8]) -> Result<SessionInfo, EdgeClawError> {
+        self.accept_session_negotiation(&EcnpCodec::decode(frame)?)
+    }
+
+    /// `handle_session_accept` for a reply already read off a connection
+    fn accept_session_negotiation(&self, msg: &EcnpMessage) -> Result<SessionInfo, EdgeClawError> {
+        let accept = match SessionNegotiation::from_message(msg)? {
+            SessionNegotiation::SessionAccept(accept) => accept,
+            _ => return Err(EdgeClawError::InvalidParameter),
+        };
+        if accept.cipher != protocol::SESSION_CIPHER_AES256GCM {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+
+        let now = self.now()?;
+        let peer_id = {
+            let mut pending = self
+                .pending_session_inits
                 .lock()
-                .map_err(|_| EdgeClawError::InternalError)?;
-            guard.as_ref().map(|c| c.desktop_address().to_string())
+                .map_err(|e| self.lock_failed(e))?;
+            let init = pending
+                .get(&accept.init_id)
+                .ok_or(EdgeClawError::InvalidParameter)?;
+            if now >= init.expires_at {
+                pending.remove(&accept.init_id);
+                return Err(EdgeClawError::TimeoutError);
+            }
+            init.peer_id.clone()
+        };
+        if accept.device_id != peer_id {
+            tracing::warn!(
+                peer_id = %peer_id,
+                device_id = %accept.device_id,
+                "Session accept from a device the init was not sent to"
+            );
+            return Err(EdgeClawError::CryptoError);
+        }
+        self.check_pinned_key(&peer_id, &accept.identity_public_key)?;
+
+        let remote_ephemeral = protocol::decode_kex_key_hex(&accept.ephemeral_public_key)?;
+        protocol::verify_negotiation_signature(
+            &accept.identity_public_key,
+            &protocol::session_key_signing_bytes(
+                SESSION_ACCEPT_TAG,
+                &accept.init_id,
+                &accept.device_id,
+                &remote_ephemeral,
+                &accept.cipher,
+                accept.duration_secs,
+            ),
+            &accept.signature,
+        )?;
+
+        // Gone if a concurrent accept for the same init won the race
+        let pending = self
+            .pending_session_inits
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .remove(&accept.init_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+
+        let mut sess_mgr = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        let session = sess_mgr.create_session_with(
+            &pending.peer_id,
+            &pending.ephemeral.secret,
+            &remote_ephemeral,
+            &SessionOptions {
+                duration_secs: Some(accept.duration_secs.min(pending.duration_secs)),
+                nonce_mode: pending.nonce_mode,
+                max_idle_secs: pending.max_idle_secs,
+            },
+        )?;
+        if let Some(old) = pending.replaces {
+            if sess_mgr.close_session(&old).is_ok() {
+                tracing::info!(session_id = %old, "Retired rekeyed session");
+            }
+        }
+        Ok(session)
+    }
+
+    /// Reject a negotiation signed by a key other than the one pinned for the peer
+    fn check_pinned_key(
+        &self,
+        peer_id: &str,
+        identity_public_key: &str,
+    ) -> Result<(), EdgeClawError> {
+        let pinned = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .get_peer(peer_id)
+            .ok()
+            .and_then(|p| p.public_key_hex);
+        match pinned {
+            Some(key) if !key.eq_ignore_ascii_case(identity_public_key) => {
+                tracing::warn!(peer_id = %peer_id, "Session negotiation signed by unpinned key");
+                Err(EdgeClawError::CryptoError)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Encrypt data using a session key
+    pub fn encrypt_message(
+        &self,
+        session_id: &str,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.encrypt(session_id, plaintext)
+    }
+
+    /// Decrypt data using a session key
+    pub fn decrypt_message(
+        &self,
+        session_id: &str,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.decrypt(session_id, ciphertext)
+    }
+
+    /// Encode a sync message as an ECNP frame and encrypt it for a session
+    pub fn encrypt_sync_message(
+        &self,
+        session_id: &str,
+        msg: &SyncMessage,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let frame = msg.encode_ecnp()?;
+        self.encrypt_message(session_id, &frame)
+    }
+
+    /// Decrypt a frame from `encrypt_sync_message` and decode the sync
+    /// message inside it
+    pub fn decrypt_sync_frame(
+        &self,
+        session_id: &str,
+        encrypted_frame: &[u8],
+    ) -> Result<SyncMessage, EdgeClawError> {
+        let frame = self.decrypt_message(session_id, encrypted_frame)?;
+        let (_sync_type, msg) = SyncMessage::decode_ecnp(&frame)?;
+        msg.verify_config_hash()?;
+        Ok(msg)
+    }
+
+    /// Encrypt one plaintext for several sessions, each with its own key
+    /// and nonce. Results are in `session_ids` order; one failing session
+    /// does not affect the others.
+    pub fn encrypt_broadcast(
+        &self,
+        session_ids: &[String],
+        plaintext: &[u8],
+    ) -> Vec<Result<Vec<u8>, EdgeClawError>> {
+        let sess_mgr = match self.session_manager.read() {
+            Ok(mgr) => mgr,
+            Err(_) => {
+                self.mark_degraded();
+                return session_ids
+                    .iter()
+                    .map(|_| Err(EdgeClawError::InternalError))
+                    .collect();
+            }
         };
-        let addr = client_state.ok_or(EdgeClawError::InvalidParameter)?;
+        session_ids
+            .iter()
+            .map(|id| sess_mgr.encrypt(id, plaintext))
+            .collect()
+    }
+
+    /// Active sessions with a peer, newest first
+    pub fn sessions_for_peer(&self, peer_id: &str) -> Vec<SessionInfo> {
+        self.session_manager
+            .read()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .sessions_for_peer(peer_id)
+    }
+
+    /// Encrypt with the peer's most recent active session
+    /// (`SessionNotFound` if it has none)
+    pub fn encrypt_for_peer(
+        &self,
+        peer_id: &str,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        let session = sess_mgr
+            .sessions_for_peer(peer_id)
+            .into_iter()
+            .next()
+            .ok_or(EdgeClawError::SessionNotFound)?;
+        sess_mgr.encrypt(&session.session_id, plaintext)
+    }
+
+    /// Decrypt with the peer's most recent active session
+    /// (`SessionNotFound` if it has none)
+    pub fn decrypt_from_peer(
+        &self,
+        peer_id: &str,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        let session = sess_mgr
+            .sessions_for_peer(peer_id)
+            .into_iter()
+            .next()
+            .ok_or(EdgeClawError::SessionNotFound)?;
+        sess_mgr.decrypt(&session.session_id, ciphertext)
+    }
+
+    /// Key confirmation tag to exchange with the peer for MITM detection
+    pub fn session_confirmation(&self, session_id: &str) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        Ok(sess_mgr.session_confirmation(session_id)?.to_vec())
+    }
+
+    /// Encrypted probe to send the peer before trusting a new session; the
+    /// peer answers via `echo_session_probe`
+    pub fn verify_session(&self, session_id: &str) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.session_probe(session_id)
+    }
+
+    /// Answer a peer's `verify_session` probe with an encrypted echo
+    pub fn echo_session_probe(
+        &self,
+        session_id: &str,
+        probe: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.echo_session_probe(session_id, probe)
+    }
+
+    /// Check the peer's echo; `CryptoError` means key disagreement
+    pub fn confirm_session_echo(&self, session_id: &str, echo: &[u8]) -> Result<(), EdgeClawError> {
+        let sess_mgr = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?;
+        sess_mgr.confirm_session_echo(session_id, echo)
+    }
+
+    // ─── Peer connections ───
+
+    /// Run the session handshake with `peer_id` over a pooled connection to
+    /// its address and return the established session. A peer address
+    /// without a port is dialed on our `listen_port`.
+    pub async fn establish_session(
+        &self,
+        peer_id: &str,
+        options: SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let addr = self.peer_dial_address(peer_id)?;
+        let init = self.initiate_session_handshake(peer_id, options)?;
+        let reply = self.exchange_with_peer(&addr, &init).await?;
+        self.accept_session_negotiation(&reply)
+    }
+
+    /// Confirm both ends of a session share its key: send the
+    /// `verify_session` probe over a pooled connection and check the echo
+    pub async fn probe_session(&self, session_id: &str) -> Result<(), EdgeClawError> {
+        let peer_id = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?
+            .get_session(session_id)?
+            .peer_id;
+        let addr = self.peer_dial_address(&peer_id)?;
+        let probe = EcnpCodec::encode(MessageType::Data, &self.verify_session(session_id)?)?;
+        let reply = self.exchange_with_peer(&addr, &probe).await?;
+        if reply.msg_type != MessageType::Data as u8 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        self.confirm_session_echo(session_id, &reply.payload)
+    }
+
+    /// Counters of the outbound peer connection pool
+    pub fn connection_pool_stats(&self) -> PoolStats {
+        self.connection_pool.stats()
+    }
+
+    /// Send `frame` and read one reply on a pooled connection to `addr`.
+    /// The connection is reused only after a clean exchange.
+    async fn exchange_with_peer(
+        &self,
+        addr: &str,
+        frame: &[u8],
+    ) -> Result<EcnpMessage, EdgeClawError> {
+        let mut conn = self.connection_pool.acquire(addr).await?;
+        ecnp::write_encoded_frame(conn.stream(), frame).await?;
+        let reply = ecnp::read_frame_timeout(
+            conn.stream(),
+            std::time::Duration::from_secs(PEER_REPLY_TIMEOUT_SECS),
+        )
+        .await?;
+        self.connection_pool.release(conn);
+        Ok(reply)
+    }
+
+    fn peer_dial_address(&self, peer_id: &str) -> Result<String, EdgeClawError> {
+        let address = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .get_peer(peer_id)?
+            .address;
+        if address.parse::<std::net::SocketAddr>().is_ok() {
+            return Ok(address);
+        }
+        let ip = address
+            .parse::<std::net::IpAddr>()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        Ok(std::net::SocketAddr::new(ip, self.config().listen_port).to_string())
+    }
+
+    // ─── Protocol ───
+
+    /// Create an ECM (Edge Capability Manifest) announcement
+    pub fn create_ecm(&self) -> Result<String, EdgeClawError> {
+        serde_json::to_string(&self.ecm_payload()?).map_err(EdgeClawError::from)
+    }
+
+    /// The ECM announcement as a typed payload rather than JSON
+    pub fn ecm_payload(&self) -> Result<EcmPayload, EdgeClawError> {
+        let id_mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        let identity = id_mgr.get_identity()?;
+
+        Ok(protocol::ecm_payload(
+            &identity.device_id,
+            &self.config().device_type,
+            vec!["status".into(), "file_read".into(), "heartbeat".into()],
+        ))
+    }
+
+    /// The ECM announcement in the compact binary encoding, for BLE and
+    /// other constrained transports
+    pub fn create_ecm_binary(&self) -> Result<Vec<u8>, EdgeClawError> {
+        Ok(protocol::encode_ecm_binary(&self.ecm_payload()?))
+    }
+
+    /// Record a peer from a received ECM announcement (JSON). `address` is
+    /// where it arrived from and is rate limited like an `ingest_discovery`
+    /// source.
+    pub fn ingest_ecm(&self, ecm_json: &str, address: &str) -> Result<PeerInfo, EdgeClawError> {
+        self.ingest_ecm_payload(&protocol::parse_ecm(ecm_json)?, address)
+    }
+
+    /// Record a peer from a binary ECM announcement
+    pub fn ingest_ecm_binary(&self, ecm: &[u8], address: &str) -> Result<PeerInfo, EdgeClawError> {
+        self.ingest_ecm_payload(&protocol::decode_ecm_binary(ecm)?, address)
+    }
+
+    fn ingest_ecm_payload(
+        &self,
+        ecm: &EcmPayload,
+        address: &str,
+    ) -> Result<PeerInfo, EdgeClawError> {
+        let info = {
+            let mut mgr = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+            mgr.admit_discovery(&ecm.device_id, Some(address))?;
+            mgr.ingest_ecm(ecm, address)
+        };
+        self.follow_desktop_address(&info);
+        Ok(info)
+    }
+
+    /// Create a heartbeat message
+    pub fn create_heartbeat(
+        &self,
+        uptime_secs: u64,
+        cpu_usage: f64,
+        memory_usage: f64,
+    ) -> Result<String, EdgeClawError> {
+        let id_mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        let identity = id_mgr.get_identity()?;
+
+        let active = self
+            .session_manager
+            .read()
+            .map(|s| s.active_sessions().len() as u32)
+            .unwrap_or(0);
+
+        protocol::create_heartbeat(
+            &identity.device_id,
+            uptime_secs,
+            cpu_usage,
+            memory_usage,
+            active,
+        )
+    }
+
+    // ─── Policy ───
+
+    /// Evaluate a capability request
+    pub fn evaluate_capability(
+        &self,
+        capability_name: &str,
+        role: &str,
+    ) -> Result<PolicyDecision, EdgeClawError> {
+        let policy = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+        policy.evaluate(capability_name, role)
+    }
+
+    /// Evaluate a batch of `(capability, role)` requests in one call, in
+    /// input order; an invalid role gives a denied decision for that item
+    pub fn evaluate_capabilities(&self, requests: Vec<(String, String)>) -> Vec<PolicyDecision> {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .evaluate_batch(&requests)
+    }
+
+    /// Structured list of registered capabilities with localization keys
+    pub fn capability_infos(&self) -> Vec<CapabilityInfo> {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .capability_infos()
+    }
+
+    /// Allow/deny for every role × registered capability
+    pub fn permission_matrix(&self) -> PermissionMatrix {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .permission_matrix()
+    }
+
+    /// Registered capabilities at exactly `level`
+    pub fn capabilities_at_risk(&self, level: RiskLevel) -> Vec<CapabilityInfo> {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .capabilities_at_risk(level)
+    }
+
+    /// Registered capabilities at `level` or below
+    pub fn capabilities_up_to(&self, level: RiskLevel) -> Vec<CapabilityInfo> {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .capabilities_up_to(level)
+    }
+
+    /// Globally disable a capability for every role
+    pub fn deny_capability(&self, name: &str) -> Result<(), EdgeClawError> {
+        let mut policy = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+        policy.deny_capability(name);
+        Ok(())
+    }
+
+    /// Lift a global capability deny
+    pub fn allow_capability(&self, name: &str) -> Result<(), EdgeClawError> {
+        let mut policy = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+        policy.allow_capability(name);
+        Ok(())
+    }
+
+    /// Revoke a capability for `affected_roles` (every role if empty) and
+    /// return the Control frame announcing it to peers, signed with our
+    /// identity key
+    pub fn broadcast_capability_revocation(
+        &self,
+        capability: &str,
+        affected_roles: Vec<String>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let now = self.now()?;
+        // Millisecond floor keeps the sequence increasing across restarts
+        let sequence = {
+            let mut last = self
+                .control_sequence
+                .lock()
+                .map_err(|e| self.lock_failed(e))?;
+            *last = (*last + 1).max(now.timestamp_millis().max(0) as u64);
+            *last
+        };
+        let id_mgr = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        let mut signed = SignedControlMessage {
+            signer_id: id_mgr.get_identity()?.device_id,
+            issued_at: now.to_rfc3339(),
+            sequence,
+            message: ControlMessage::CapabilityRevoked {
+                capability: capability.to_string(),
+                affected_roles,
+            },
+            signature: String::new(),
+        };
+        signed.signature = hex::encode(id_mgr.sign(&signed.signing_bytes()?)?);
+        drop(id_mgr);
+        let frame = signed.encode()?;
+        self.apply_control_message(&signed.message)?;
+        Ok(frame)
+    }
+
+    /// Apply a Control frame received from a peer.
+    ///
+    /// The frame must be signed by a known peer (its `signer_id`) whose
+    /// identity key is pinned: `InvalidParameter` if there is no pinned
+    /// key, `CryptoError` if the signature does not match. The signer must
+    /// hold Admin or above (see `set_peer_role`), else `PolicyDenied`.
+    /// Frames issued more than five minutes ago fail with `TimeoutError`,
+    /// and ones not newer than the signer's last applied sequence with
+    /// `ReplayDetected`. Nothing is applied unless all checks pass.
+    pub fn handle_control_frame(&self, frame: &[u8]) -> Result<ControlMessage, EdgeClawError> {
+        let signed = SignedControlMessage::decode(frame)?;
+        signed.verify(&self.peer_public_key(&signed.signer_id)?)?;
+
+        let role = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .get_peer(&signed.signer_id)?
+            .role;
+        match role.as_deref().map(Role::parse_role) {
+            Some(Ok(role)) if role >= CONTROL_SIGNER_MIN_ROLE => {}
+            _ => {
+                tracing::warn!(signer = %signed.signer_id, "control message from unprivileged signer");
+                return Err(EdgeClawError::PolicyDenied);
+            }
+        }
+
+        let issued_at = chrono::DateTime::parse_from_rfc3339(&signed.issued_at)
+            .map_err(|_| EdgeClawError::InvalidParameter)?
+            .with_timezone(&chrono::Utc);
+        let age = self.now()? - issued_at;
+        if age > chrono::Duration::seconds(CONTROL_MESSAGE_MAX_AGE_SECS) {
+            return Err(EdgeClawError::TimeoutError);
+        }
+        if -age > chrono::Duration::seconds(CONTROL_MESSAGE_MAX_AGE_SECS) {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+
+        let mut seen = self
+            .control_sequences_seen
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        if seen
+            .get(&signed.signer_id)
+            .is_some_and(|last| signed.sequence <= *last)
+        {
+            tracing::warn!(signer = %signed.signer_id, sequence = signed.sequence, "replayed control message");
+            return Err(EdgeClawError::ReplayDetected);
+        }
+        self.apply_control_message(&signed.message)?;
+        seen.insert(signed.signer_id.clone(), signed.sequence);
+        Ok(signed.message)
+    }
+
+    fn apply_control_message(&self, msg: &ControlMessage) -> Result<(), EdgeClawError> {
+        match msg {
+            ControlMessage::CapabilityRevoked {
+                capability,
+                affected_roles,
+            } => self
+                .policy_engine
+                .lock()
+                .map_err(|e| self.lock_failed(e))?
+                .revoke_capability(capability, affected_roles),
+        }
+    }
+
+    /// Switch policy report-only mode (denials logged, not enforced)
+    pub fn set_policy_report_only(&self, enabled: bool) -> Result<(), EdgeClawError> {
+        let mut policy = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+        policy.set_report_only(enabled);
+        Ok(())
+    }
+
+    /// Recent policy denials, oldest first
+    pub fn policy_audit_log(&self) -> Vec<PolicyAuditEntry> {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .audit_log()
+    }
+
+    /// Policy audit log as NDJSON, for log pipelines; the log is kept
+    pub fn export_policy_audit_ndjson(&self) -> String {
+        self.policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .export_audit_ndjson()
+    }
+
+    /// Policy audit log as NDJSON, removing the exported entries
+    pub fn drain_policy_audit_ndjson(&self) -> Result<String, EdgeClawError> {
+        let mut out = Vec::new();
+        self.policy_engine
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .drain_audit_ndjson(&mut out)?;
+        String::from_utf8(out).map_err(|_| EdgeClawError::SerializationError)
+    }
+
+    // ─── ECNP ───
+
+    /// Encode a message into ECNP v1.1 wire format
+    pub fn encode_ecnp(
+        &self,
+        msg_type: MessageType,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        EcnpCodec::encode(msg_type, payload)
+    }
+
+    /// Decode a message from ECNP v1.1 wire format
+    pub fn decode_ecnp(&self, data: &[u8]) -> Result<EcnpMessage, EdgeClawError> {
+        EcnpCodec::decode(data)
+    }
+
+    // ─── Sync ───
+
+    /// Initialize the sync client for Desktop-Mobile synchronization
+    pub fn init_sync(&self, config: SyncClientConfig) -> Result<(), EdgeClawError> {
+        let client = SyncClient::new(config);
+        let mut guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        *guard = Some(client);
+        tracing::info!("Sync client initialized");
+        Ok(())
+    }
+
+    /// Connect sync client to desktop agent.
+    ///
+    /// If `desktop_peer_id` is configured, the desktop must authenticate
+    /// with the public key pinned for that peer. The dialed stream is not
+    /// kept; use `sync_connect_over` for a connection to upgrade.
+    pub async fn sync_connect(&self) -> Result<(), EdgeClawError> {
+        self.prepared_sync_client()?.connect().await
+    }
+
+    /// `sync_connect` over an already open stream, which can then be passed
+    /// to `sync_upgrade`
+    pub async fn sync_connect_over<S>(&self, stream: &mut S) -> Result<(), EdgeClawError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        self.prepared_sync_client()?.connect_over(stream).await
+    }
+
+    /// The sync client, with the pinned desktop key and our device ID set
+    /// for a handshake. A clone (shared state), so the lock is not held
+    /// across awaits.
+    fn prepared_sync_client(&self) -> Result<SyncClient, EdgeClawError> {
+        let client = {
+            let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+            guard.clone().ok_or(EdgeClawError::InvalidParameter)?
+        };
+        if let Some(desktop) = &client.config().desktop_peer_id {
+            client.set_desktop_key(self.peer_public_key(desktop)?);
+        }
+        if let Ok(identity) = self.get_identity() {
+            client.set_local_device_id(&identity.device_id);
+        }
+        Ok(client)
+    }
+
+    /// Start a channel upgrade on a transport the caller drives; returns
+    /// the Control frame to send (see `SyncClient::begin_upgrade`)
+    pub fn sync_begin_upgrade(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.begin_upgrade()
+    }
+
+    /// Complete `sync_begin_upgrade` with the desktop's reply frame
+    pub fn sync_finish_upgrade(&self, reply: &[u8]) -> Result<(), EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.finish_upgrade(reply)
+    }
+
+    /// Switch a connected sync stream to the encrypted channel (see
+    /// `SyncClient::upgrade`). Config and remote exec frames are refused
+    /// until this succeeds unless `require_encrypted_channel` is off.
+    pub async fn sync_upgrade<S>(&self, stream: &mut S) -> Result<(), EdgeClawError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let client = {
+            let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+            guard.clone().ok_or(EdgeClawError::InvalidParameter)?
+        };
+        client.upgrade(stream).await
+    }
+
+    /// Point the sync client at a new desktop address; returns whether it
+    /// changed. An open sync connection is dropped.
+    pub fn sync_update_address(&self, address: &str) -> Result<bool, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.update_address(address)
+    }
+
+    /// If `peer` is the paired sync desktop, offer its address to the sync
+    /// client. Discovery is unauthenticated, so the address is only adopted
+    /// after a pinned-key handshake there; without a pinned desktop key, or
+    /// for addresses that are not `host:port`, it is ignored.
+    fn follow_desktop_address(&self, peer: &PeerInfo) {
+        let guard = self
+            .sync_client
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e));
+        let Some(client) = guard.as_ref() else {
+            return;
+        };
+        if client.config().desktop_peer_id.as_deref() != Some(peer.peer_id.as_str()) {
+            return;
+        }
+        if let Ok(key) = self.peer_public_key(&peer.peer_id) {
+            client.set_desktop_key(key);
+        }
+        if let Err(e) = client.propose_address(&peer.address) {
+            tracing::debug!(
+                peer_id = %peer.peer_id,
+                address = %peer.address,
+                error = %e,
+                "Desktop address not usable for sync"
+            );
+        }
+    }
+
+    /// Register a callback for sync connection state transitions
+    pub fn sync_set_connection_listener(
+        &self,
+        listener: Box<dyn Fn(sync::SyncConnectionState) + Send + Sync>,
+    ) -> Result<(), EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.set_connection_listener(listener);
+        Ok(())
+    }
+
+    /// Send a remote execution request to the desktop agent
+    pub fn sync_remote_exec(
+        &self,
+        command: &str,
+        args: Vec<String>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.create_remote_exec(command, args)
+    }
+
+    /// One answer to "may `role` send this remote exec now?".
+    ///
+    /// Checks, in order, that `remote_exec` was agreed in a handshake with
+    /// the desktop, that policy allows `shell_exec` for `role`, and that
+    /// the command is not classified `CommandRisk::High`. The first failure
+    /// decides the verdict. Asking has no side effects: nothing reaches the
+    /// policy audit log or the denial count.
+    pub fn can_remote_exec(&self, command: &str, args: &[String], role: &str) -> RemoteExecGate {
+        let command_risk = sync::classify_command(command, args);
+        let deny = |code: &str, reason: String| RemoteExecGate {
+            allowed: false,
+            code: code.to_string(),
+            reason,
+            command_risk,
+        };
+
+        let negotiated = self
+            .sync_client
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .as_ref()
+            .map(|client| client.agreed_capabilities());
+        match negotiated {
+            None => {
+                return deny(
+                    "capability_not_negotiated",
+                    "Sync client not initialized".into(),
+                )
+            }
+            Some(None) => {
+                return deny(
+                    "capability_not_negotiated",
+                    "No handshake with the desktop yet".into(),
+                )
+            }
+            Some(Some(caps)) if !caps.iter().any(|c| c == sync::CAP_REMOTE_EXEC) => {
+                return deny(
+                    "capability_not_negotiated",
+                    "Desktop did not negotiate remote_exec".into(),
+                )
+            }
+            Some(_) => {}
+        }
+
+        let decision = self
+            .policy_engine
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .verdict("shell_exec", role);
+        if !decision.allowed {
+            return deny("policy_denied", decision.reason);
+        }
+
+        if command_risk == CommandRisk::High {
+            return deny(
+                "command_too_risky",
+                format!("Command '{command}' is classified high risk"),
+            );
+        }
+
+        RemoteExecGate {
+            allowed: true,
+            code: "allowed".into(),
+            reason: format!("Remote exec allowed for role '{role}'"),
+            command_risk,
+        }
+    }
+
+    /// Send a remote execution request, returning its ID for `sync_cancel_exec`
+    pub fn sync_remote_exec_tracked(
+        &self,
+        command: &str,
+        args: Vec<String>,
+    ) -> Result<RemoteExecRequest, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        let (request_id, frame) = client.create_tracked_exec(command, args)?;
+        Ok(RemoteExecRequest { request_id, frame })
+    }
+
+    /// Cancel an in-flight remote exec; returns the cancel frame to send
+    pub fn sync_cancel_exec(&self, request_id: &str) -> Result<Vec<u8>, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.cancel_exec(request_id)
+    }
+
+    /// Process an incoming sync frame from the desktop agent
+    pub fn sync_process_incoming(&self, frame: &[u8]) -> Result<SyncMessage, EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
+        client.process_incoming(frame)
+    }
+
+    /// Shutdown the sync client
+    pub fn sync_shutdown(&self) -> Result<(), EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        if let Some(client) = guard.as_ref() {
+            client.shutdown();
+        }
+        Ok(())
+    }
+
+    /// Pause the sync client (e.g. when the app is backgrounded)
+    pub fn sync_pause(&self) -> Result<(), EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        guard
+            .as_ref()
+            .ok_or(EdgeClawError::InvalidParameter)?
+            .pause();
+        Ok(())
+    }
+
+    /// Resume a sync client paused with `sync_pause`
+    pub fn sync_resume(&self) -> Result<(), EdgeClawError> {
+        let guard = self.sync_client.lock().map_err(|e| self.lock_failed(e))?;
+        guard
+            .as_ref()
+            .ok_or(EdgeClawError::InvalidParameter)?
+            .resume();
+        Ok(())
+    }
+
+    /// Check if sync client is connected
+    pub fn sync_is_connected(&self) -> bool {
+        self.sync_client
+            .lock()
+            .unwrap_or_else(|e| self.recover_lock(e))
+            .as_ref()
+            .is_some_and(|c| c.is_connected())
+    }
+
+    // ─── Backup ───
+
+    /// Export identity, peers, session metadata and policy overrides as one
+    /// passphrase-encrypted blob
+    pub fn export_snapshot(&self, passphrase: &str) -> Result<Vec<u8>, EdgeClawError> {
+        let (identities, active_identity) = {
+            let id_mgr = self
+                .identity_manager
+                .lock()
+                .map_err(|e| self.lock_failed(e))?;
+            (
+                id_mgr.export_identities(),
+                id_mgr.active_label().to_string(),
+            )
+        };
+        let sessions = self
+            .session_manager
+            .read()
+            .map_err(|e| self.lock_failed(e))?
+            .export_sessions();
+        let peers = self
+            .peer_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .export_peers();
+        let (denied_capabilities, revoked_capabilities) = {
+            let policy = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+            (policy.denied_capabilities(), policy.revoked_capabilities())
+        };
+        let revoked_attestations = self
+            .attestations
+            .lock()
+            .map_err(|e| self.lock_failed(e))?
+            .revocations();
+
+        snapshot::seal(
+            &EngineSnapshot {
+                identities,
+                active_identity,
+                peers,
+                sessions,
+                denied_capabilities,
+                revoked_attestations,
+                revoked_capabilities,
+            },
+            passphrase,
+        )
+    }
+
+    /// Restore state from `export_snapshot`, replacing current state.
+    /// Platform-backed identities, which snapshots cannot hold, are kept.
+    /// Snapshot sessions are merged in as expired; a session already held
+    /// under the same ID is kept as is.
+    ///
+    /// Everything is decrypted and validated before any subsystem is
+    /// touched, so a corrupt snapshot leaves the engine unchanged.
+    pub fn import_snapshot(&self, blob: &[u8], passphrase: &str) -> Result<(), EdgeClawError> {
+        let snap = snapshot::open(blob, passphrase)?;
+
+        let config = self.config();
+        let clock = Arc::clone(&*self.clock.read().map_err(|e| self.lock_failed(e))?);
+        let mut identity = IdentityManager::with_device_id_mode(config.device_id_mode);
+        identity.set_time_source(Arc::clone(&clock));
+        for id in snap.identities {
+            identity.import_identity(id)?;
+        }
+        let mut peers = PeerManager::new();
+        peers.set_time_source(clock);
+        peers.set_discovery_rate_limit(config.discovery_rate_limit);
+        peers.set_discovery_global_rate_limit(config.discovery_global_rate_limit);
+        peers.import_peers(snap.peers);
+
+        let mut id_guard = self
+            .identity_manager
+            .lock()
+            .map_err(|e| self.lock_failed(e))?;
+        let mut sess_guard = self
+            .session_manager
+            .write()
+            .map_err(|e| self.lock_failed(e))?;
+        let mut peer_guard = self.peer_manager.lock().map_err(|e| self.lock_failed(e))?;
+        let mut policy_guard = self.policy_engine.lock().map_err(|e| self.lock_failed(e))?;
+        let mut ledger_guard = self.attestations.lock().map_err(|e| self.lock_failed(e))?;
+
+        // Platform-backed identities are never in a snapshot; keep ours
+        identity.keep_platform_identities(&id_guard);
+        if identity.list_identities().contains(&snap.active_identity) {
+            identity.set_active(&snap.active_identity)?;
+        }
+        // Live sessions always win over their keyless snapshot copies
+        sess_guard.merge_sessions(snap.sessions)?;
+        *id_guard = identity;
+        *peer_guard = peers;
+        policy_guard.set_denied_capabilities(snap.denied_capabilities);
+        policy_guard.set_revoked_capabilities(snap.revoked_capabilities);
+        ledger_guard.set_revocations(snap.revoked_attestations);
+
+        tracing::info!("Engine state restored from snapshot");
+        Ok(())
+    }
+
+    // ─── Logging ───
+
+    /// Log an event through the tracing subsystem
+    pub fn log_event(&self, level: &str, message: &str) {
+        match level {
+            "error" => tracing::error!(%message),
+            "warn" => tracing::warn!(%message),
+            "info" => tracing::info!(%message),
+            "debug" => tracing::debug!(%message),
+            _ => tracing::trace!(%message),
+        }
+    }
+
+    /// Recent log lines captured in memory, oldest first.
+    ///
+    /// Empty unless `log_ring_capacity` was set in the engine config. Lines
+    /// arrive through the global subscriber installed by the first engine;
+    /// if the host installed its own subscriber first, it must include
+    /// `logging::RingFanout` for this to fill.
+    pub fn recent_logs(&self) -> Vec<String> {
+        self.log_ring
+            .as_ref()
+            .map(|ring| ring.lines())
+            .unwrap_or_default()
+    }
+
+    /// Clear the in-memory log buffer
+    pub fn clear_logs(&self) {
+        if let Some(ring) = &self.log_ring {
+            ring.clear();
+        }
+    }
+}
+
+// ─── Tests ───
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> EngineConfig {
+        EngineConfig {
+            device_name: "test-device".to_string(),
+            device_type: "smartphone".to_string(),
+            listen_port: 8443,
+            max_connections: 10,
+            quic_enabled: false,
+            log_level: "warn".to_string(),
+            log_ring_capacity: 0,
+            device_id_mode: DeviceIdMode::Uuid,
+            session_duration_secs: 3600,
+            max_active_sessions: 64,
+            log_format: LogFormat::Json,
+            discovery_rate_limit: 64,
+            discovery_global_rate_limit: 256,
+        }
+    }
+
+    #[test]
+    fn test_create_engine() {
+        let engine = create_engine(test_config()).unwrap();
+        assert!(engine.get_peers().is_empty());
+    }
+
+    #[test]
+    fn test_engine_capabilities() {
+        let engine = create_engine(test_config()).unwrap();
+        let caps = engine.capabilities();
+        assert_eq!(caps.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(caps.ecnp_versions.contains(&0x01));
+        assert!(caps.ciphers.contains(&"aes-256-gcm".to_string()));
+        assert!(caps.compression.contains(&"zstd".to_string()));
+        assert_eq!(caps.message_types, vec![1, 2, 3, 4, 5, 6]);
+
+        let json = serde_json::to_value(&caps).unwrap();
+        assert_eq!(json["crate_version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_peer_manifest_roundtrip() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let alice_id = alice.generate_identity().unwrap();
+        let carol_key = hex::encode(
+            ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng)
+                .verifying_key()
+                .to_bytes(),
+        );
+        alice
+            .add_peer("carol", "Carol's PC", "pc", "10.0.0.7:8443", vec![])
+            .unwrap();
+        alice.set_peer_public_key("carol", &carol_key).unwrap();
+        alice
+            .add_peer("dave", "Dave's Tablet", "tablet", "10.0.0.8:8443", vec![])
+            .unwrap();
+
+        let start = chrono::Utc::now() - chrono::Duration::days(3);
+        alice
+            .set_time_source(Arc::new(time::MockTimeSource::new(start)))
+            .unwrap();
+        let manifest = alice.export_peer_manifest().unwrap();
+        let parsed: PeerManifest = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(parsed.created_at, start.to_rfc3339());
+        assert_eq!(
+            bob.import_peer_manifest(&manifest, &alice_id.public_key_hex)
+                .unwrap(),
+            2
+        );
+        let carol = bob
+            .get_peers()
+            .into_iter()
+            .find(|p| p.peer_id == "carol")
+            .unwrap();
+        assert_eq!(carol.address, "10.0.0.7:8443");
+        assert_eq!(carol.public_key_hex.as_deref(), Some(carol_key.as_str()));
+
+        // Tampered manifest or the wrong signer
+        let tampered = manifest.replace("10.0.0.7", "10.6.6.6");
+        let fresh = create_engine(test_config()).unwrap();
+        assert!(matches!(
+            fresh.import_peer_manifest(&tampered, &alice_id.public_key_hex),
+            Err(EdgeClawError::CryptoError)
+        ));
+        let mallory = fresh.generate_identity().unwrap();
+        assert!(matches!(
+            fresh.import_peer_manifest(&manifest, &mallory.public_key_hex),
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert!(fresh.get_peers().is_empty());
+    }
+
+    #[test]
+    fn test_engine_log_formats() {
+        for format in [LogFormat::Json, LogFormat::Compact, LogFormat::Pretty] {
+            let engine = create_engine(EngineConfig {
+                log_format: format,
+                ..test_config()
+            })
+            .unwrap();
+            assert_eq!(engine.config().log_format, format);
+        }
+
+        // The subscriber is installed once, so switching needs a restart
+        let engine = create_engine(test_config()).unwrap();
+        let report = engine
+            .update_config(EngineConfig {
+                log_format: LogFormat::Pretty,
+                ..test_config()
+            })
+            .unwrap();
+        assert_eq!(report.restart_required, vec!["log_format"]);
+    }
+
+    #[test]
+    fn test_engine_stats() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+        engine
+            .add_peer("peer-1", "Laptop", "pc", "10.0.0.2:8443", vec![])
+            .unwrap();
+        engine
+            .add_peer("peer-2", "Phone", "smartphone", "10.0.0.3:8443", vec![])
+            .unwrap();
+        engine
+            .peer_manager
+            .lock()
+            .unwrap()
+            .set_connected("peer-1", true)
+            .unwrap();
+
+        let peer = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let peer_public = x25519_dalek::PublicKey::from(&peer);
+        let session = engine
+            .create_session("peer-1", peer_public.as_bytes())
+            .unwrap();
+        let ciphertext = engine
+            .encrypt_message(&session.session_id, b"hello")
+            .unwrap();
+        engine
+            .decrypt_message(&session.session_id, &ciphertext)
+            .unwrap();
+        engine.evaluate_capability("shell_exec", "viewer").unwrap();
+
+        let stats = engine.stats();
+        assert_eq!(stats.peers, 2);
+        assert_eq!(stats.connected_peers, 1);
+        assert_eq!(stats.active_sessions, 1);
+        assert_eq!(stats.bytes_encrypted, 5);
+        assert_eq!(stats.bytes_decrypted, 5);
+        assert_eq!(stats.policy_denials, 1);
+        // No sync client configured
+        assert_eq!(stats.sync_messages_sent, 0);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["connected_peers"], 1);
+    }
+
+    #[test]
+    fn test_capability_revocation_broadcast() {
+        let sender = create_engine(test_config()).unwrap();
+        let receiver = create_engine(test_config()).unwrap();
+        let impostor = create_engine(test_config()).unwrap();
+        let sender_id = sender.generate_identity().unwrap();
+        impostor.generate_identity().unwrap();
+        receiver
+            .add_peer(&sender_id.device_id, "Hub", "pc", "10.0.0.2", vec![])
+            .unwrap();
+        assert!(
+            receiver
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+
+        let frame = sender
+            .broadcast_capability_revocation("file_write", vec!["admin".into()])
+            .unwrap();
+        // The sender's own policy reflects the revocation
+        assert!(
+            !sender
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+
+        // Nothing is applied until the sender's key is pinned
+        assert!(matches!(
+            receiver.handle_control_frame(&frame),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(
+            receiver
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+        receiver
+            .set_peer_public_key(&sender_id.device_id, &sender_id.public_key_hex)
+            .unwrap();
+
+        // Unsigned frames and frames signed by another key are refused
+        let unsigned = ControlMessage::CapabilityRevoked {
+            capability: "file_write".into(),
+            affected_roles: vec![],
+        }
+        .encode()
+        .unwrap();
+        assert!(receiver.handle_control_frame(&unsigned).is_err());
+        let forged = impostor
+            .broadcast_capability_revocation("file_write", vec![])
+            .unwrap();
+        let mut forged = SignedControlMessage::decode(&forged).unwrap();
+        forged.signer_id = sender_id.device_id.clone();
+        assert!(matches!(
+            receiver.handle_control_frame(&forged.encode().unwrap()),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        // A pinned key alone does not let a peer revoke capabilities
+        for role in [None, Some("operator")] {
+            receiver.set_peer_role(&sender_id.device_id, role).unwrap();
+            assert!(matches!(
+                receiver.handle_control_frame(&frame),
+                Err(EdgeClawError::PolicyDenied)
+            ));
+        }
+        assert!(
+            receiver
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+        receiver
+            .set_peer_role(&sender_id.device_id, Some("admin"))
+            .unwrap();
+
+        let msg = receiver.handle_control_frame(&frame).unwrap();
+        assert!(matches!(msg, ControlMessage::CapabilityRevoked { .. }));
+        // The same frame is not applied twice
+        assert!(matches!(
+            receiver.handle_control_frame(&frame),
+            Err(EdgeClawError::ReplayDetected)
+        ));
+        assert!(
+            !receiver
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            receiver
+                .evaluate_capability("file_write", "owner")
+                .unwrap()
+                .allowed
+        );
+
+        // An empty role list disables the capability everywhere
+        let frame = sender
+            .broadcast_capability_revocation("status_query", vec![])
+            .unwrap();
+        receiver.handle_control_frame(&frame).unwrap();
+        assert!(
+            !receiver
+                .evaluate_capability("status_query", "owner")
+                .unwrap()
+                .allowed
+        );
+
+        // Bad roles are rejected without touching the policy
+        assert!(sender
+            .broadcast_capability_revocation("file_read", vec!["nobody".into()])
+            .is_err());
+        let data = EcnpCodec::encode(MessageType::Data, b"{}").unwrap();
+        assert!(receiver.handle_control_frame(&data).is_err());
+
+        // Per-role revocations survive a snapshot round trip
+        let blob = receiver.export_snapshot("pass").unwrap();
+        let restored = create_engine(test_config()).unwrap();
+        restored.import_snapshot(&blob, "pass").unwrap();
+        assert!(
+            !restored
+                .evaluate_capability("file_write", "admin")
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            restored
+                .evaluate_capability("file_write", "owner")
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_control_frame_freshness() {
+        let sender = create_engine(test_config()).unwrap();
+        let receiver = create_engine(test_config()).unwrap();
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        sender.set_time_source(clock.clone()).unwrap();
+        receiver.set_time_source(clock.clone()).unwrap();
+        let sender_id = sender.generate_identity().unwrap();
+        receiver
+            .add_peer(&sender_id.device_id, "Hub", "pc", "10.0.0.2", vec![])
+            .unwrap();
+        receiver
+            .set_peer_public_key(&sender_id.device_id, &sender_id.public_key_hex)
+            .unwrap();
+        receiver
+            .set_peer_role(&sender_id.device_id, Some("owner"))
+            .unwrap();
+
+        // Frames held back past the freshness window are refused
+        let stale = sender
+            .broadcast_capability_revocation("file_write", vec![])
+            .unwrap();
+        clock.advance(chrono::Duration::seconds(CONTROL_MESSAGE_MAX_AGE_SECS + 1));
+        assert!(matches!(
+            receiver.handle_control_frame(&stale),
+            Err(EdgeClawError::TimeoutError)
+        ));
+        assert!(
+            receiver
+                .evaluate_capability("file_write", "owner")
+                .unwrap()
+                .allowed
+        );
+
+        // Once a newer frame is applied, older ones are replays
+        let older = sender
+            .broadcast_capability_revocation("file_read", vec![])
+            .unwrap();
+        let newer = sender
+            .broadcast_capability_revocation("status_query", vec![])
+            .unwrap();
+        receiver.handle_control_frame(&newer).unwrap();
+        assert!(matches!(
+            receiver.handle_control_frame(&older),
+            Err(EdgeClawError::ReplayDetected)
+        ));
+        assert!(
+            receiver
+                .evaluate_capability("file_read", "owner")
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_encrypt_for_peer() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let alice_id = alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap();
+        let alice_x = alice
+            .identity_manager
+            .lock()
+            .unwrap()
+            .get_public_key()
+            .unwrap();
+        let bob_x = bob
+            .identity_manager
+            .lock()
+            .unwrap()
+            .get_public_key()
+            .unwrap();
+
+        alice.create_session(&bob_id.device_id, &bob_x).unwrap();
+        bob.accept_session(&alice_id.device_id, &alice_x).unwrap();
+
+        let frame = alice
+            .encrypt_for_peer(&bob_id.device_id, b"hi bob")
+            .unwrap();
+        assert_eq!(
+            bob.decrypt_from_peer(&alice_id.device_id, &frame).unwrap(),
+            b"hi bob"
+        );
+
+        assert!(matches!(
+            alice.encrypt_for_peer("stranger", b"hi"),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+        assert!(matches!(
+            alice.decrypt_from_peer("stranger", &frame),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_get_or_create_single_session() {
+        let engine = std::sync::Arc::new(create_engine(test_config()).unwrap());
+        engine.generate_identity().unwrap();
+        let peer = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::random_from_rng(
+            rand::rngs::OsRng,
+        ))
+        .to_bytes();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || engine.get_or_create_session("peer-x", &peer).unwrap())
+            })
+            .collect();
+        let ids: std::collections::HashSet<String> = handles
+            .into_iter()
+            .map(|h| h.join().unwrap().session_id)
+            .collect();
+
+        assert_eq!(ids.len(), 1);
+        assert_eq!(engine.all_sessions().len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_encryption_across_sessions() {
+        let engine = std::sync::Arc::new(create_engine(test_config()).unwrap());
+        engine.generate_identity().unwrap();
+        let session_ids: Vec<String> = (0..8)
+            .map(|i| {
+                let peer = x25519_dalek::PublicKey::from(
+                    &x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng),
+                )
+                .to_bytes();
+                engine
+                    .create_session(&format!("peer-{i}"), &peer)
+                    .unwrap()
+                    .session_id
+            })
+            .collect();
+
+        // Two threads per session, so sessions run in parallel and each
+        // session's nonce counter is also contended
+        let handles: Vec<_> = session_ids
+            .iter()
+            .chain(session_ids.iter())
+            .map(|id| {
+                let engine = engine.clone();
+                let id = id.clone();
+                std::thread::spawn(move || {
+                    let frames: Vec<Vec<u8>> = (0..100u32)
+                        .map(|n| engine.encrypt_message(&id, &n.to_be_bytes()).unwrap())
+                        .collect();
+                    (id, frames)
+                })
+            })
+            .collect();
+
+        let mut nonces: std::collections::HashMap<String, std::collections::HashSet<Vec<u8>>> =
+            std::collections::HashMap::new();
+        for handle in handles {
+            let (id, frames) = handle.join().unwrap();
+            for frame in frames {
+                assert_eq!(engine.decrypt_message(&id, &frame).unwrap().len(), 4);
+                assert!(nonces
+                    .entry(id.clone())
+                    .or_default()
+                    .insert(frame[..12].to_vec()));
+            }
+        }
+
+        for info in engine.all_sessions() {
+            assert_eq!(info.messages_sent, 200);
+            assert_eq!(nonces[&info.session_id].len(), 200);
+        }
+    }
+
+    #[test]
+    fn test_update_config_live_fields() {
+        let engine = create_engine(test_config()).unwrap();
+        let report = engine
+            .update_config(EngineConfig {
+                device_type: "tablet".to_string(),
+                max_connections: 32,
+                session_duration_secs: 120,
+                ..test_config()
+            })
+            .unwrap();
+
+        assert_eq!(
+            report.applied,
+            vec!["session_duration_secs", "device_type", "max_connections"]
+        );
+        assert!(report.restart_required.is_empty());
+        assert_eq!(engine.config().max_connections, 32);
+        assert_eq!(
+            engine
+                .session_manager
+                .read()
+                .unwrap()
+                .default_duration_secs(),
+            120
+        );
+
+        engine.generate_identity().unwrap();
+        assert!(engine.create_ecm().unwrap().contains("tablet"));
+    }
+
+    #[test]
+    fn test_binary_ecm_between_engines() {
+        let announcer = create_engine(test_config()).unwrap();
+        let identity = announcer.generate_identity().unwrap();
+        let ecm = announcer.create_ecm_binary().unwrap();
+        assert!(ecm.len() < announcer.create_ecm().unwrap().len());
+
+        let listener = create_engine(test_config()).unwrap();
+        let peer = listener.ingest_ecm_binary(&ecm, "ble:AA:BB").unwrap();
+        assert_eq!(peer.peer_id, identity.device_id);
+        assert_eq!(peer.address, "ble:AA:BB");
+        assert!(listener.ingest_ecm_binary(b"{}", "ble:AA:BB").is_err());
+    }
+
+    #[test]
+    fn test_update_config_restart_required() {
+        let engine = create_engine(test_config()).unwrap();
+        let report = engine
+            .update_config(EngineConfig {
+                listen_port: 9443,
+                quic_enabled: true,
+                ..test_config()
+            })
+            .unwrap();
+
+        assert!(report.applied.is_empty());
+        assert_eq!(report.restart_required, vec!["listen_port", "quic_enabled"]);
+        // Restart-required fields keep their running values
+        assert_eq!(engine.config().listen_port, 8443);
+        assert!(!engine.config().quic_enabled);
+    }
+
+    #[test]
+    fn test_update_config_rejects_invalid() {
+        let engine = create_engine(test_config()).unwrap();
+        for bad in [
+            EngineConfig {
+                max_connections: 0,
+                ..test_config()
+            },
+            EngineConfig {
+                session_duration_secs: 0,
+                ..test_config()
+            },
+            EngineConfig {
+                log_level: "=[bogus".to_string(),
+                ..test_config()
+            },
+        ] {
+            assert!(matches!(
+                engine.update_config(bad),
+                Err(EdgeClawError::InvalidParameter)
+            ));
+        }
+        assert_eq!(engine.config().max_connections, 10);
+    }
+
+    #[test]
+    fn test_identity_required_before_use() {
+        let engine = create_engine(test_config()).unwrap();
+        let missing =
+            |r: Result<_, EdgeClawError>| matches!(r, Err(EdgeClawError::IdentityNotInitialized));
+
+        assert!(missing(
+            engine.create_session("peer-1", &[9u8; 32]).map(|_| ())
+        ));
+        assert!(missing(engine.begin_session("peer-1").map(|_| ())));
+        assert!(missing(engine.create_ecm().map(|_| ())));
+        assert!(missing(engine.create_heartbeat(60, 10.0, 20.0).map(|_| ())));
+        assert!(missing(engine.get_identity().map(|_| ())));
+
+        engine.generate_identity().unwrap();
+        assert!(engine.create_ecm().is_ok());
+        assert!(engine.create_heartbeat(60, 10.0, 20.0).is_ok());
+    }
+
+    #[test]
+    fn test_identity_lifecycle() {
+        let engine = create_engine(test_config()).unwrap();
+
+        // Before generation, get_identity should fail
+        assert!(engine.get_identity().is_err());
+
+        let identity = engine.generate_identity().unwrap();
+        assert!(!identity.device_id.is_empty());
+        assert_eq!(identity.public_key_hex.len(), 64);
+        assert_eq!(identity.fingerprint.len(), 16);
+
+        let retrieved = engine.get_identity().unwrap();
+        assert_eq!(identity.device_id, retrieved.device_id);
+    }
+
+    #[test]
+    fn test_peer_management() {
+        let engine = create_engine(test_config()).unwrap();
+
+        engine
+            .add_peer(
+                "peer-001",
+                "test-pc",
+                "pc",
+                "192.168.1.10",
+                vec!["gpu".into()],
+            )
+            .unwrap();
+        assert_eq!(engine.get_peers().len(), 1);
+
+        engine.remove_peer("peer-001").unwrap();
+        assert!(engine.get_peers().is_empty());
+    }
+
+    #[test]
+    fn test_discovery_rate_limit() {
+        let engine = create_engine(EngineConfig {
+            discovery_rate_limit: 5,
+            ..test_config()
+        })
+        .unwrap();
+
+        // Claimed addresses vary; the real source does not
+        let results: Vec<_> = (0..20)
+            .map(|i| {
+                engine.ingest_discovery(
+                    &format!("spam-{i}"),
+                    "Spam",
+                    "pc",
+                    &format!("10.0.{i}.1:8443"),
+                    vec![],
+                    "10.9.9.9:5353",
+                )
+            })
+            .collect();
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 5);
+        assert!(matches!(
+            results.last(),
+            Some(Err(EdgeClawError::RateLimited))
+        ));
+        assert_eq!(engine.get_peers().len(), 5);
+
+        let report = engine
+            .update_config(EngineConfig {
+                discovery_rate_limit: 0,
+                ..engine.config()
+            })
+            .unwrap();
+        assert!(report.applied.contains(&"discovery_rate_limit".to_string()));
+        engine
+            .ingest_discovery("spam-99", "Spam", "pc", "10.9.9.9", vec![], "10.9.9.9")
+            .unwrap();
+
+        // The global cap holds across sources and for add_peer
+        let report = engine
+            .update_config(EngineConfig {
+                discovery_global_rate_limit: 1,
+                ..engine.config()
+            })
+            .unwrap();
+        assert!(report
+            .applied
+            .contains(&"discovery_global_rate_limit".to_string()));
+        engine
+            .ingest_discovery("a", "A", "pc", "10.1.1.1", vec![], "10.1.1.1")
+            .unwrap();
+        assert!(matches!(
+            engine.ingest_discovery("b", "B", "pc", "10.1.1.2", vec![], "10.1.1.2"),
+            Err(EdgeClawError::RateLimited)
+        ));
+        assert!(matches!(
+            engine.add_peer("c", "C", "pc", "10.1.1.3", vec![]),
+            Err(EdgeClawError::RateLimited)
+        ));
+    }
+
+    #[test]
+    fn test_session_and_encryption() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+
+        // Simulate a valid X25519 peer public key
+        let peer_key: [u8; 32] = [
+            9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 64,
+        ];
+
+        let session = engine.create_session("peer-001", &peer_key).unwrap();
+        assert_eq!(session.peer_id, "peer-001");
+        assert_eq!(session.state, "established");
+
+        let plaintext = b"EdgeClaw test message";
+        let ciphertext = engine
+            .encrypt_message(&session.session_id, plaintext)
+            .unwrap();
+        assert_ne!(ciphertext, plaintext.to_vec());
+
+        let decrypted = engine
+            .decrypt_message(&session.session_id, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn test_platform_key_provider_backs_engine() {
+        use keystore::mock::MockKeyProvider;
+
+        let engine = create_engine(test_config()).unwrap();
+        let provider = Arc::new(MockKeyProvider::new());
+        let identity = engine
+            .register_key_provider(identity::DEFAULT_IDENTITY_LABEL, provider.clone())
+            .unwrap();
+        assert_eq!(engine.get_identity().unwrap().device_id, identity.device_id);
+
+        let peer = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::random_from_rng(
+            rand::rngs::OsRng,
+        ));
+        let session = engine.create_session("peer-001", peer.as_bytes()).unwrap();
+        engine.accept_session("peer-002", peer.as_bytes()).unwrap();
+        assert_eq!(provider.exchanges(), 2);
+        engine.encrypt_message(&session.session_id, b"hi").unwrap();
+
+        engine
+            .initiate_session_handshake("peer-001", SessionOptions::default())
+            .unwrap();
+        assert_eq!(provider.signs(), 1);
+
+        // The platform identity survives importing a snapshot
+        let other = create_engine(test_config()).unwrap();
+        let work = other.generate_identity_for("work").unwrap();
+        let blob = other.export_snapshot("pass").unwrap();
+        engine.import_snapshot(&blob, "pass").unwrap();
+        assert_eq!(engine.get_identity().unwrap().device_id, identity.device_id);
+        assert_eq!(engine.list_identities(), vec!["default", "work"]);
+        assert_ne!(work.device_id, identity.device_id);
+    }
+
+    #[test]
+    fn test_poisoned_lock_marks_engine_degraded() {
+        let engine = create_engine(test_config()).unwrap();
+        assert!(!engine.is_degraded());
+        assert!(!engine.health().degraded);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = engine.peer_manager.lock().unwrap();
+                panic!("poison the peer manager");
+            })
+            .join()
+            .unwrap_err();
+        });
+
+        // Listing recovers the poisoned lock; mutations report the failure
+        assert!(engine.get_peers().is_empty());
+        assert!(engine.is_degraded());
+        assert!(matches!(
+            engine.remove_peer("peer-001"),
+            Err(EdgeClawError::InternalError)
+        ));
+        assert!(engine.health().degraded);
+    }
+
+    #[test]
+    fn test_encrypt_broadcast() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+
+        let mut receivers = Vec::new();
+        let mut session_ids = Vec::new();
+        for i in 0..3 {
+            let peer_secret = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+            let peer_public = x25519_dalek::PublicKey::from(&peer_secret).to_bytes();
+            let session = engine
+                .create_session(&format!("peer-{i}"), &peer_public)
+                .unwrap();
+
+            // The peer's side of the same session
+            let our_public = engine
+                .identity_manager
+                .lock()
+                .unwrap()
+                .get_public_key()
+                .unwrap();
+            let mut receiver = SessionManager::new();
+            let info = receiver
+                .accept_session("us", &peer_secret.to_bytes(), &our_public)
+                .unwrap();
+            receivers.push((receiver, info.session_id));
+            session_ids.push(session.session_id);
+        }
+        session_ids.push("missing".to_string());
+
+        let results = engine.encrypt_broadcast(&session_ids, b"fan-out");
+        assert_eq!(results.len(), 4);
+        for (result, (receiver, id)) in results.iter().zip(&receivers) {
+            let ciphertext = result.as_ref().unwrap();
+            assert_eq!(receiver.decrypt(id, ciphertext).unwrap(), b"fan-out");
+        }
+        // Each recipient's ciphertext only opens under its own session
+        let (other, other_id) = &receivers[1];
+        assert!(other
+            .decrypt(other_id, results[0].as_ref().unwrap())
+            .is_err());
+        assert!(matches!(results[3], Err(EdgeClawError::SessionNotFound)));
+    }
+
+    #[test]
+    fn test_encrypt_unknown_session() {
+        let engine = create_engine(test_config()).unwrap();
+        assert!(matches!(
+            engine.encrypt_message("missing", b"data"),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let source = create_engine(test_config()).unwrap();
+        let id = source.generate_identity().unwrap();
+        source
+            .add_peer("peer-1", "Laptop", "pc", "10.0.0.2", vec![])
+            .unwrap();
+        source.tag_peer("peer-1", "office").unwrap();
+        let peer_key = [9u8; 32];
+        let session = source.create_session("peer-1", &peer_key).unwrap();
+        let ct = source.encrypt_message(&session.session_id, b"hi").unwrap();
+        source.deny_capability("shell_exec").unwrap();
+
+        let blob = source.export_snapshot("passphrase").unwrap();
+
+        let target = create_engine(test_config()).unwrap();
+        assert!(target.import_snapshot(&blob, "wrong").is_err());
+        assert!(target.get_identity().is_err());
+
+        target.import_snapshot(&blob, "passphrase").unwrap();
+        assert_eq!(target.get_identity().unwrap().device_id, id.device_id);
+        assert_eq!(target.peers_with_tag("office").len(), 1);
+        // Sessions come back without keys until re-keyed
+        assert!(matches!(
+            target.decrypt_message(&session.session_id, &ct),
+            Err(EdgeClawError::SessionExpired)
+        ));
+        // while live sessions are never replaced by their snapshot copies
+        source.import_snapshot(&blob, "passphrase").unwrap();
+        assert_eq!(
+            source.decrypt_message(&session.session_id, &ct).unwrap(),
+            b"hi"
+        );
+        assert!(
+            !target
+                .evaluate_capability("shell_exec", "owner")
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_peer_challenge_response() {
+        let verifier = create_engine(test_config()).unwrap();
+        let prover = create_engine(test_config()).unwrap();
+        let impostor = create_engine(test_config()).unwrap();
+        let prover_id = prover.generate_identity().unwrap();
+        impostor.generate_identity().unwrap();
+
+        verifier
+            .add_peer("prover", "Phone", "smartphone", "10.0.0.3", vec![])
+            .unwrap();
+        verifier
+            .set_peer_public_key("prover", &prover_id.public_key_hex)
+            .unwrap();
+
+        let challenge = verifier.create_auth_challenge("prover").unwrap();
+        let sig = prover.sign_auth_challenge(&challenge.nonce_frame).unwrap();
+        verifier
+            .verify_auth_response(&challenge.challenge_id, &sig)
+            .unwrap();
+
+        let challenge = verifier.create_auth_challenge("prover").unwrap();
+        let forged = impostor
+            .sign_auth_challenge(&challenge.nonce_frame)
+            .unwrap();
+        assert!(matches!(
+            verifier.verify_auth_response(&challenge.challenge_id, &forged),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        // A failed key lookup still burns the challenge
+        let challenge = verifier.create_auth_challenge("prover").unwrap();
+        let sig = prover.sign_auth_challenge(&challenge.nonce_frame).unwrap();
+        verifier.remove_peer("prover").unwrap();
+        assert!(verifier
+            .verify_auth_response(&challenge.challenge_id, &sig)
+            .is_err());
+        verifier
+            .add_peer("prover", "Phone", "smartphone", "10.0.0.3", vec![])
+            .unwrap();
+        verifier
+            .set_peer_public_key("prover", &prover_id.public_key_hex)
+            .unwrap();
+        assert!(matches!(
+            verifier.verify_auth_response(&challenge.challenge_id, &sig),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn test_peer_attestation_introduction() {
+        // A trusts B and introduces B to C
+        let a = create_engine(test_config()).unwrap();
+        let b = create_engine(test_config()).unwrap();
+        let c = create_engine(test_config()).unwrap();
+        let a_id = a.generate_identity().unwrap();
+        let b_id = b.generate_identity().unwrap();
+
+        a.add_peer("bob", "Laptop", "pc", "10.0.0.4", vec![])
+            .unwrap();
+        a.set_peer_public_key("bob", &b_id.public_key_hex).unwrap();
+
+        let json = a.attest_peer("bob").unwrap();
+        let attestation = c.verify_attestation(&json, &a_id.public_key_hex).unwrap();
+        assert_eq!(attestation.attester_id, a_id.device_id);
+        assert_eq!(attestation.subject_id, "bob");
+        assert_eq!(attestation.subject_pubkey, b_id.public_key_hex);
+
+        let tampered = json.replace(&b_id.public_key_hex, &"00".repeat(32));
+        assert!(matches!(
+            c.verify_attestation(&tampered, &a_id.public_key_hex),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_attestation_follows_time_source() {
+        let a = create_engine(test_config()).unwrap();
+        let c = create_engine(test_config()).unwrap();
+        let start = chrono::Utc::now() - chrono::Duration::days(30);
+        let clock = Arc::new(time::MockTimeSource::new(start));
+        a.set_time_source(clock.clone()).unwrap();
+        c.set_time_source(clock.clone()).unwrap();
+        let a_id = a.generate_identity().unwrap();
+        a.add_peer("bob", "Laptop", "pc", "10.0.0.4", vec![])
+            .unwrap();
+        let bob_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]).verifying_key();
+        a.set_peer_public_key("bob", &hex::encode(bob_key.to_bytes()))
+            .unwrap();
+
+        let json = a.attest_peer("bob").unwrap();
+        let attestation = c.verify_attestation(&json, &a_id.public_key_hex).unwrap();
+        assert_eq!(attestation.timestamp, start.to_rfc3339());
+
+        clock.advance(chrono::Duration::seconds(
+            auth::DEFAULT_ATTESTATION_TTL_SECS,
+        ));
+        assert!(matches!(
+            c.verify_attestation(&json, &a_id.public_key_hex),
+            Err(EdgeClawError::TimeoutError)
+        ));
+    }
+
+    #[test]
+    fn test_attestation_revocation() {
+        let a = create_engine(test_config()).unwrap();
+        let b = create_engine(test_config()).unwrap();
+        let a_id = a.generate_identity().unwrap();
+        let b_id = b.generate_identity().unwrap();
+        a.add_peer("bob", "Laptop", "pc", "10.0.0.4", vec![])
+            .unwrap();
+        a.set_peer_public_key("bob", &b_id.public_key_hex).unwrap();
+
+        let json = a.attest_peer("bob").unwrap();
+        let attestation = a.verify_attestation(&json, &a_id.public_key_hex).unwrap();
+        let issued = a.list_issued_attestations();
+        assert_eq!(issued.len(), 1);
+        assert_eq!(issued[0].id, attestation.id());
+        assert_eq!(issued[0].subject_id, "bob");
+        assert!(!issued[0].revoked);
+
+        a.revoke_attestation(&attestation.id()).unwrap();
+        assert!(a.list_issued_attestations()[0].revoked);
+        assert!(matches!(
+            a.verify_attestation(&json, &a_id.public_key_hex),
+            Err(EdgeClawError::Revoked)
+        ));
+        assert!(matches!(
+            a.revoke_attestation(""),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+
+        // Revocations survive a snapshot round trip
+        let blob = a.export_snapshot("pass").unwrap();
+        let restored = create_engine(test_config()).unwrap();
+        assert!(restored
+            .verify_attestation(&json, &a_id.public_key_hex)
+            .is_ok());
+        restored.import_snapshot(&blob, "pass").unwrap();
+        assert!(matches!(
+            restored.verify_attestation(&json, &a_id.public_key_hex),
+            Err(EdgeClawError::Revoked)
+        ));
+    }
+
+    #[test]
+    fn test_active_identity_drives_ecm() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+        let work = engine.generate_identity_for("work").unwrap();
+        assert_eq!(engine.list_identities(), vec!["default", "work"]);
 
-        // Create a temporary SyncClient for the connection attempt
-        // (the actual connect only needs the config)
-        let temp_config = SyncClientConfig {
-            desktop_address: addr,
-            ..Default::default()
-        };
-        let temp_client = SyncClient::new(temp_config);
-        temp_client.connect().await
+        engine.set_active_identity("work").unwrap();
+        assert_eq!(engine.get_identity().unwrap().device_id, work.device_id);
+        assert!(engine.create_ecm().unwrap().contains(&work.device_id));
     }
 
-    /// Send a remote execution request to the desktop agent
-    pub fn sync_remote_exec(
-        &self,
-        command: &str,
-        args: Vec<String>,
-    ) -> Result<Vec<u8>, EdgeClawError> {
-        let guard = self
-            .sync_client
-            .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
-        client.create_remote_exec(command, args)
-    }
+    #[test]
+    fn test_session_handshake_flow() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let alice_id = alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap();
 
-    /// Process an incoming sync frame from the desktop agent
-    pub fn sync_process_incoming(&self, frame: &[u8]) -> Result<SyncMessage, EdgeClawError> {
-        let guard = self
-            .sync_client
-            .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        let client = guard.as_ref().ok_or(EdgeClawError::InvalidParameter)?;
-        client.process_incoming(frame)
+        let init = alice
+            .initiate_session_handshake(
+                &bob_id.device_id,
+                SessionOptions {
+                    duration_secs: Some(600),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let accepted = bob.handle_session_init(&init).unwrap();
+        assert_eq!(accepted.session.peer_id, alice_id.device_id);
+        assert_eq!(accepted.session.role, "responder");
+
+        let session = alice.handle_session_accept(&accepted.accept_frame).unwrap();
+        assert_eq!(session.role, "initiator");
+        // The accept is single-use
+        assert!(alice.handle_session_accept(&accepted.accept_frame).is_err());
+
+        let ct = alice
+            .encrypt_message(&session.session_id, b"hi bob")
+            .unwrap();
+        assert_eq!(
+            bob.decrypt_message(&accepted.session.session_id, &ct)
+                .unwrap(),
+            b"hi bob"
+        );
+        let reply = bob
+            .encrypt_message(&accepted.session.session_id, b"hi alice")
+            .unwrap();
+        assert_eq!(
+            alice.decrypt_message(&session.session_id, &reply).unwrap(),
+            b"hi alice"
+        );
     }
 
-    /// Shutdown the sync client
-    pub fn sync_shutdown(&self) -> Result<(), EdgeClawError> {
-        let guard = self
-            .sync_client
-            .lock()
-            .map_err(|_| EdgeClawError::InternalError)?;
-        if let Some(client) = guard.as_ref() {
-            client.shutdown();
+    #[test]
+    fn test_session_handshake_uses_key_exchange() {
+        /// Toy scheme with 64-byte keys (public == secret, shared = XOR)
+        struct WideExchange;
+        impl kex::KeyExchange for WideExchange {
+            fn algorithm(&self) -> &'static str {
+                "wide"
+            }
+            fn generate_keypair(&self) -> KeyExchangeKeypair {
+                let mut secret = vec![0u8; 64];
+                rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut secret);
+                KeyExchangeKeypair {
+                    public: secret.clone(),
+                    secret,
+                }
+            }
+            fn shared_secret(
+                &self,
+                local_secret: &[u8],
+                remote_public: &[u8],
+            ) -> Result<Vec<u8>, EdgeClawError> {
+                if local_secret.len() != 64 || remote_public.len() != 64 {
+                    return Err(EdgeClawError::InvalidParameter);
+                }
+                Ok(local_secret
+                    .iter()
+                    .zip(remote_public)
+                    .map(|(a, b)| a ^ b)
+                    .collect())
+            }
         }
-        Ok(())
+
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap();
+        for engine in [&alice, &bob] {
+            engine
+                .session_manager
+                .write()
+                .unwrap()
+                .set_key_exchange(Arc::new(WideExchange));
+        }
+
+        let init = alice
+            .initiate_session_handshake(&bob_id.device_id, SessionOptions::default())
+            .unwrap();
+        match SessionNegotiation::decode(&init).unwrap() {
+            SessionNegotiation::SessionInit(init) => {
+                assert_eq!(init.ephemeral_public_key.len(), 128)
+            }
+            _ => panic!("expected a SessionInit"),
+        }
+        let accepted = bob.handle_session_init(&init).unwrap();
+        let session = alice.handle_session_accept(&accepted.accept_frame).unwrap();
+
+        let ct = alice.encrypt_message(&session.session_id, b"wide").unwrap();
+        assert_eq!(
+            bob.decrypt_message(&accepted.session.session_id, &ct)
+                .unwrap(),
+            b"wide"
+        );
     }
 
-    /// Check if sync client is connected
-    pub fn sync_is_connected(&self) -> bool {
-        self.sync_client
-            .lock()
-            .ok()
-            .and_then(|g| g.as_ref().map(|c| c.is_connected()))
-            .unwrap_or(false)
+    /// Serve `engine` as a peer: answer session inits and probes on every
+    /// accepted connection until it closes
+    async fn serve_peer(engine: EdgeClawEngine) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = Arc::new(engine);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let engine = Arc::clone(&engine);
+                tokio::spawn(async move {
+                    let mut session_id = String::new();
+                    while let Ok(Some(msg)) = ecnp::read_frame_or_eof(&mut socket).await {
+                        if msg.msg_type == MessageType::Handshake as u8 {
+                            let init =
+                                EcnpCodec::encode(MessageType::Handshake, &msg.payload).unwrap();
+                            let accepted = engine.handle_session_init(&init).unwrap();
+                            session_id = accepted.session.session_id;
+                            ecnp::write_encoded_frame(&mut socket, &accepted.accept_frame)
+                                .await
+                                .unwrap();
+                        } else {
+                            let echo = engine.echo_session_probe(&session_id, &msg.payload);
+                            ecnp::write_frame(&mut socket, MessageType::Data, &echo.unwrap())
+                                .await
+                                .unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        addr
     }
 
-    // ─── Logging ───
+    #[tokio::test]
+    async fn test_establish_and_probe_session_over_pooled_connection() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap().device_id;
+        let addr = serve_peer(bob).await;
+        alice
+            .add_peer(&bob_id, "Laptop", "pc", &addr.to_string(), vec![])
+            .unwrap();
 
-    /// Log an event through the tracing subsystem
-    pub fn log_event(&self, level: &str, message: &str) {
-        match level {
-            "error" => tracing::error!(%message),
-            "warn" => tracing::warn!(%message),
-            "info" => tracing::info!(%message),
-            "debug" => tracing::debug!(%message),
-            _ => tracing::trace!(%message),
-        }
+        let session = alice
+            .establish_session(&bob_id, SessionOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(session.peer_id, bob_id);
+        alice.probe_session(&session.session_id).await.unwrap();
+
+        // The probe reused the handshake's connection
+        let stats = alice.connection_pool_stats();
+        assert_eq!((stats.connects, stats.reuses), (1, 1));
+        assert_eq!((stats.idle, stats.checked_out), (1, 0));
+
+        alice
+            .add_peer("nobody", "Phone", "smartphone", "not-an-ip", vec![])
+            .unwrap();
+        assert!(matches!(
+            alice
+                .establish_session("nobody", SessionOptions::default())
+                .await,
+            Err(EdgeClawError::InvalidParameter)
+        ));
     }
-}
 
-// ─── Tests ───
+    #[tokio::test]
+    async fn test_establish_session_accepts_flagged_reply() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap().device_id;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // Bob answers with a compressed v1.2 frame
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let msg = ecnp::read_frame(&mut socket).await.unwrap();
+            let init = EcnpCodec::encode(MessageType::Handshake, &msg.payload).unwrap();
+            let accepted = bob.handle_session_init(&init).unwrap();
+            let payload = EcnpCodec::decode(&accepted.accept_frame).unwrap().payload;
+            let reply = EcnpCodec::encode_compressed(MessageType::Handshake, &payload).unwrap();
+            assert_ne!(EcnpCodec::decode(&reply).unwrap().flags, 0);
+            ecnp::write_encoded_frame(&mut socket, &reply)
+                .await
+                .unwrap();
+            let _ = ecnp::read_frame(&mut socket).await;
+        });
+        alice
+            .add_peer(&bob_id, "Laptop", "pc", &addr.to_string(), vec![])
+            .unwrap();
 
-    fn test_config() -> EngineConfig {
-        EngineConfig {
-            device_name: "test-device".to_string(),
-            device_type: "smartphone".to_string(),
-            listen_port: 8443,
-            max_connections: 10,
-            quic_enabled: false,
-            log_level: "warn".to_string(),
-        }
+        let session = alice
+            .establish_session(&bob_id, SessionOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(session.peer_id, bob_id);
     }
 
     #[test]
-    fn test_create_engine() {
-        let engine = create_engine(test_config()).unwrap();
-        assert!(engine.get_peers().is_empty());
+    fn test_session_handshake_binds_parameters_and_expires() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        alice.set_time_source(clock.clone()).unwrap();
+        alice.generate_identity().unwrap();
+        bob.generate_identity().unwrap();
+
+        // The signature covers the proposed parameters
+        let init = alice
+            .initiate_session_handshake("bob", SessionOptions::default())
+            .unwrap();
+        let SessionNegotiation::SessionInit(mut tampered) =
+            SessionNegotiation::decode(&init).unwrap()
+        else {
+            panic!("expected SessionInit");
+        };
+        tampered.duration_secs = 1;
+        let tampered = SessionNegotiation::SessionInit(tampered).encode().unwrap();
+        assert!(matches!(
+            bob.handle_session_init(&tampered),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        // An accept arriving after the pending init expired is refused
+        let accepted = bob.handle_session_init(&init).unwrap();
+        clock.advance(chrono::Duration::seconds(PENDING_SESSION_INIT_TTL_SECS));
+        assert!(matches!(
+            alice.handle_session_accept(&accepted.accept_frame),
+            Err(EdgeClawError::TimeoutError)
+        ));
+
+        // Unanswered inits are capped until they expire
+        for _ in 0..MAX_PENDING_SESSION_INITS {
+            alice
+                .initiate_session_handshake("bob", SessionOptions::default())
+                .unwrap();
+        }
+        assert!(matches!(
+            alice.initiate_session_handshake("bob", SessionOptions::default()),
+            Err(EdgeClawError::CapacityExceeded { .. })
+        ));
+        clock.advance(chrono::Duration::seconds(PENDING_SESSION_INIT_TTL_SECS));
+        assert!(alice
+            .initiate_session_handshake("bob", SessionOptions::default())
+            .is_ok());
     }
 
     #[test]
-    fn test_identity_lifecycle() {
+    fn test_engine_time_source() {
         let engine = create_engine(test_config()).unwrap();
-
-        // Before generation, get_identity should fail
-        assert!(engine.get_identity().is_err());
+        let start = chrono::Utc::now();
+        let clock = Arc::new(time::MockTimeSource::new(start));
+        engine.set_time_source(clock.clone()).unwrap();
 
         let identity = engine.generate_identity().unwrap();
-        assert!(!identity.device_id.is_empty());
-        assert_eq!(identity.public_key_hex.len(), 64);
-        assert_eq!(identity.fingerprint.len(), 16);
+        assert_eq!(identity.created_at, start.to_rfc3339());
 
-        let retrieved = engine.get_identity().unwrap();
-        assert_eq!(identity.device_id, retrieved.device_id);
+        engine
+            .add_peer("peer-1", "Laptop", "pc", "10.0.0.2", vec![])
+            .unwrap();
+        let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::random_from_rng(
+            rand::rngs::OsRng,
+        ));
+        let session = engine.create_session("peer-1", public.as_bytes()).unwrap();
+        assert_eq!(
+            engine.peer_liveness("peer-1").unwrap(),
+            PeerLiveness::RecentlySeen
+        );
+
+        // Past the default one-hour lifetime and the recently-seen window
+        clock.advance(chrono::Duration::hours(2));
+        assert!(matches!(
+            engine.encrypt_message(&session.session_id, b"late"),
+            Err(EdgeClawError::SessionExpired)
+        ));
+        assert_eq!(engine.peer_liveness("peer-1").unwrap(), PeerLiveness::Stale);
+
+        // Managers rebuilt by a snapshot import keep the injected clock
+        let blob = engine.export_snapshot("pass").unwrap();
+        engine.import_snapshot(&blob, "pass").unwrap();
+        let session = engine.create_session("peer-1", public.as_bytes()).unwrap();
+        clock.advance(chrono::Duration::hours(2));
+        assert!(matches!(
+            engine.encrypt_message(&session.session_id, b"late"),
+            Err(EdgeClawError::SessionExpired)
+        ));
     }
 
     #[test]
-    fn test_peer_management() {
-        let engine = create_engine(test_config()).unwrap();
+    fn test_rekey_all_sessions() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap().device_id;
+        assert!(alice.rekey_all_sessions().unwrap().is_empty());
 
-        engine
-            .add_peer(
-                "peer-001",
-                "test-pc",
-                "pc",
-                "192.168.1.10",
-                vec!["gpu".into()],
-            )
-            .unwrap();
-        assert_eq!(engine.get_peers().len(), 1);
+        let custom = SessionOptions {
+            duration_secs: Some(600),
+            nonce_mode: NonceMode::Random,
+            max_idle_secs: Some(120),
+        };
+        for peer in [bob_id.as_str(), "carol"] {
+            let public = x25519_dalek::PublicKey::from(
+                &x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng),
+            );
+            let secret = alice.identity_manager.lock().unwrap().get_secret_key();
+            alice
+                .session_manager
+                .write()
+                .unwrap()
+                .create_session_with(peer, &secret.unwrap(), public.as_bytes(), &custom)
+                .unwrap();
+        }
+        alice.begin_session("dave").unwrap();
 
-        engine.remove_peer("peer-001").unwrap();
-        assert!(engine.get_peers().is_empty());
+        // Pending sessions are not active and are left alone
+        let rekeys = alice.rekey_all_sessions().unwrap();
+        assert_eq!(rekeys.len(), alice.health().active_sessions as usize);
+        assert_eq!(rekeys.len(), 2);
+
+        let rekey = rekeys.iter().find(|r| r.peer_id == bob_id).unwrap();
+        let accepted = bob.handle_session_init(&rekey.init_frame).unwrap();
+        let fresh = alice.handle_session_accept(&accepted.accept_frame).unwrap();
+        assert_ne!(fresh.session_id, rekey.session_id);
+        assert_eq!(fresh.peer_id, bob_id);
+
+        // The replacement keeps the old options and retires the old session
+        let mgr = alice.session_manager.read().unwrap();
+        let options = mgr.session_options(&fresh.session_id).unwrap();
+        assert_eq!(options.duration_secs, Some(600));
+        assert_eq!(options.nonce_mode, NonceMode::Random);
+        assert_eq!(options.max_idle_secs, Some(120));
+        assert!(matches!(
+            mgr.get_session(&rekey.session_id),
+            Err(EdgeClawError::SessionNotFound)
+        ));
     }
 
     #[test]
-    fn test_session_and_encryption() {
-        let engine = create_engine(test_config()).unwrap();
-        engine.generate_identity().unwrap();
+    fn test_rekey_failure_leaves_nothing_pending() {
+        let alice = create_engine(EngineConfig {
+            max_active_sessions: 0,
+            ..test_config()
+        })
+        .unwrap();
+        alice.generate_identity().unwrap();
+        let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::random_from_rng(
+            rand::rngs::OsRng,
+        ));
+        for i in 0..=MAX_PENDING_SESSION_INITS {
+            alice
+                .create_session(&format!("peer-{i}"), public.as_bytes())
+                .unwrap();
+        }
 
-        // Simulate a valid X25519 peer public key
-        let peer_key: [u8; 32] = [
-            9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 64,
-        ];
+        assert!(matches!(
+            alice.rekey_all_sessions(),
+            Err(EdgeClawError::CapacityExceeded { .. })
+        ));
+        assert!(alice.pending_session_inits.lock().unwrap().is_empty());
+    }
 
-        let session = engine.create_session("peer-001", &peer_key).unwrap();
-        assert_eq!(session.peer_id, "peer-001");
-        assert_eq!(session.state, "established");
+    #[test]
+    fn test_forged_session_accept_keeps_handshake_pending() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let carol = create_engine(test_config()).unwrap();
+        alice.generate_identity().unwrap();
+        let bob_id = bob.generate_identity().unwrap();
+        let carol_id = carol.generate_identity().unwrap();
+        for id in [&bob_id, &carol_id] {
+            alice
+                .add_peer(&id.device_id, "Laptop", "pc", "10.0.0.2", vec![])
+                .unwrap();
+            alice
+                .set_peer_public_key(&id.device_id, &id.public_key_hex)
+                .unwrap();
+        }
 
-        let plaintext = b"EdgeClaw test message";
-        let ciphertext = engine
-            .encrypt_message(&session.session_id, plaintext)
+        let init = alice
+            .initiate_session_handshake(&bob_id.device_id, SessionOptions::default())
             .unwrap();
-        assert_ne!(ciphertext, plaintext.to_vec());
 
-        let decrypted = engine
-            .decrypt_message(&session.session_id, &ciphertext)
+        // Another pinned peer answering Bob's init
+        let carols = carol.handle_session_init(&init).unwrap();
+        assert!(matches!(
+            alice.handle_session_accept(&carols.accept_frame),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        // Carol's accept relabelled as Bob's
+        let SessionNegotiation::SessionAccept(mut forged) =
+            SessionNegotiation::decode(&carols.accept_frame).unwrap()
+        else {
+            panic!("expected SessionAccept");
+        };
+        forged.device_id = bob_id.device_id.clone();
+        let forged = SessionNegotiation::SessionAccept(forged).encode().unwrap();
+        assert!(matches!(
+            alice.handle_session_accept(&forged),
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        // Bob's real accept still completes the handshake
+        let accepted = bob.handle_session_init(&init).unwrap();
+        let session = alice.handle_session_accept(&accepted.accept_frame).unwrap();
+        assert_eq!(session.peer_id, bob_id.device_id);
+    }
+
+    #[test]
+    fn test_session_init_rejects_unpinned_key() {
+        let alice = create_engine(test_config()).unwrap();
+        let bob = create_engine(test_config()).unwrap();
+        let alice_id = alice.generate_identity().unwrap();
+        bob.generate_identity().unwrap();
+
+        // Bob has a different key pinned for Alice's device
+        bob.add_peer(
+            &alice_id.device_id,
+            "Alice",
+            "smartphone",
+            "10.0.0.5",
+            vec![],
+        )
+        .unwrap();
+        bob.set_peer_public_key(&alice_id.device_id, &"11".repeat(32))
             .unwrap();
-        assert_eq!(decrypted, plaintext);
+
+        let init = alice
+            .initiate_session_handshake("bob", SessionOptions::default())
+            .unwrap();
+        assert!(matches!(
+            bob.handle_session_init(&init),
+            Err(EdgeClawError::CryptoError)
+        ));
     }
 
     #[test]
@@ -514,6 +3910,133 @@ mod tests {
         assert!(!engine.sync_is_connected());
     }
 
+    #[test]
+    fn test_sync_follows_desktop_address() {
+        let engine = create_engine(test_config()).unwrap();
+        engine
+            .init_sync(SyncClientConfig {
+                desktop_address: "10.0.0.5:8443".into(),
+                desktop_peer_id: Some("desktop".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        let address = || {
+            engine
+                .sync_client
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .desktop_address()
+        };
+
+        let propose = |addr: &str| {
+            engine
+                .sync_client
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .propose_address(addr)
+        };
+
+        // Without a pinned desktop key announcements are not followed
+        engine
+            .add_peer("desktop", "Desk", "desktop", "10.0.0.9:8443", vec![])
+            .unwrap();
+        assert_eq!(address(), "10.0.0.5:8443");
+        assert!(propose("10.0.0.9:8443").is_err());
+
+        // With one, the address becomes a candidate for the next connect,
+        // leaving the current target (and any open connection) alone
+        let desktop_key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        engine
+            .set_peer_public_key(
+                "desktop",
+                &hex::encode(desktop_key.verifying_key().to_bytes()),
+            )
+            .unwrap();
+        engine
+            .add_peer("desktop", "Desk", "desktop", "10.0.0.9:8443", vec![])
+            .unwrap();
+        assert_eq!(address(), "10.0.0.5:8443");
+        assert!(!propose("10.0.0.9:8443").unwrap());
+
+        // Other peers and unusable addresses are ignored
+        engine
+            .add_peer("phone", "Phone", "smartphone", "10.0.0.20:8443", vec![])
+            .unwrap();
+        engine
+            .add_peer("desktop", "Desk", "desktop", "ble:AA:BB", vec![])
+            .unwrap();
+        assert!(!propose("10.0.0.9:8443").unwrap());
+
+        assert!(engine.sync_update_address("10.0.0.10:8443").unwrap());
+        assert!(engine.sync_update_address("nowhere").is_err());
+        assert_eq!(address(), "10.0.0.10:8443");
+    }
+
+    /// Run the sync handshake against an in-memory desktop that agrees to
+    /// `capabilities`
+    async fn handshake_sync(engine: &EdgeClawEngine, capabilities: &[&str]) {
+        let (mut local, mut desktop) = tokio::io::duplex(4096);
+        let ack = serde_json::json!({ "compression": ["none"], "capabilities": capabilities });
+        let desktop = tokio::spawn(async move {
+            ecnp::read_frame(&mut desktop).await.unwrap();
+            let payload = serde_json::to_vec(&ack).unwrap();
+            ecnp::write_frame(&mut desktop, MessageType::Ack, &payload)
+                .await
+                .unwrap();
+            desktop
+        });
+        engine.sync_connect_over(&mut local).await.unwrap();
+        desktop.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_can_remote_exec() {
+        let engine = create_engine(test_config()).unwrap();
+        let ls = vec!["-la".to_string()];
+
+        let gate = engine.can_remote_exec("ls", &ls, "owner");
+        assert!(!gate.allowed);
+        assert_eq!(gate.code, "capability_not_negotiated");
+
+        // Configured capabilities are not enough before a handshake
+        engine.init_sync(SyncClientConfig::default()).unwrap();
+        let gate = engine.can_remote_exec("ls", &ls, "owner");
+        assert_eq!(gate.code, "capability_not_negotiated");
+        assert!(gate.reason.contains("handshake"));
+
+        handshake_sync(&engine, &[sync::CAP_STATUS_PUSH]).await;
+        assert_eq!(
+            engine.can_remote_exec("ls", &ls, "owner").code,
+            "capability_not_negotiated"
+        );
+
+        handshake_sync(&engine, &[sync::CAP_REMOTE_EXEC]).await;
+        let gate = engine.can_remote_exec("ls", &ls, "admin");
+        assert_eq!(gate.code, "policy_denied");
+        assert_eq!(
+            engine.can_remote_exec("ls", &ls, "root").code,
+            "policy_denied"
+        );
+
+        let gate = engine.can_remote_exec("rm", &["-rf".into(), "/".into()], "owner");
+        assert!(!gate.allowed);
+        assert_eq!(gate.code, "command_too_risky");
+        assert_eq!(gate.command_risk, CommandRisk::High);
+
+        let gate = engine.can_remote_exec("ls", &ls, "owner");
+        assert!(gate.allowed);
+        assert_eq!(gate.code, "allowed");
+        assert_eq!(gate.command_risk, CommandRisk::Low);
+
+        // Asking left no trace in the policy audit trail
+        assert!(engine.policy_audit_log().is_empty());
+        assert_eq!(engine.stats().policy_denials, 0);
+    }
+
     #[test]
     fn test_sync_remote_exec_without_init() {
         let engine = create_engine(test_config()).unwrap();
@@ -521,20 +4044,79 @@ mod tests {
         assert!(engine.sync_remote_exec("ls", vec![]).is_err());
     }
 
-    #[test]
-    fn test_sync_remote_exec_with_init() {
+    /// Handshake and upgrade the engine's sync client against an in-memory
+    /// desktop, moving the upgrade frames ourselves if `caller_driven`;
+    /// returns a decoder for frames the engine sends on the channel
+    async fn upgrade_sync(
+        engine: &EdgeClawEngine,
+        caller_driven: bool,
+    ) -> impl Fn(&[u8]) -> SyncMessage {
+        let (mut local, mut desktop) = tokio::io::duplex(64 * 1024);
+        let desktop = tokio::spawn(async move {
+            ecnp::read_frame(&mut desktop).await.unwrap();
+            ecnp::write_frame(&mut desktop, MessageType::Ack, b"{}")
+                .await
+                .unwrap();
+            let upgrade = ecnp::read_frame(&mut desktop).await.unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&upgrade.payload).unwrap();
+            let client_public =
+                hex::decode(request["ephemeral_public_key"].as_str().unwrap()).unwrap();
+            let local = kex::x25519().generate_keypair();
+            let reply = serde_json::json!({
+                "upgrade": sync::SYNC_UPGRADE_SCHEME,
+                "ephemeral_public_key": hex::encode(&local.public),
+            });
+            let payload = serde_json::to_vec(&reply).unwrap();
+            ecnp::write_frame(&mut desktop, MessageType::Control, &payload)
+                .await
+                .unwrap();
+            let mut sessions = SessionManager::new();
+            let session = sessions
+                .accept_session("mobile", &local.secret, &client_public)
+                .unwrap();
+            (sessions, session.session_id)
+        });
+        engine.sync_connect_over(&mut local).await.unwrap();
+        if caller_driven {
+            let request = engine.sync_begin_upgrade().unwrap();
+            ecnp::write_encoded_frame(&mut local, &request)
+                .await
+                .unwrap();
+            let reply = ecnp::read_frame(&mut local).await.unwrap();
+            let reply = EcnpCodec::encode(MessageType::Control, &reply.payload).unwrap();
+            engine.sync_finish_upgrade(&reply).unwrap();
+        } else {
+            engine.sync_upgrade(&mut local).await.unwrap();
+        }
+        let (sessions, session_id) = desktop.await.unwrap();
+        move |frame: &[u8]| {
+            let sealed = EcnpCodec::decode(frame).unwrap();
+            let inner = sessions.decrypt(&session_id, &sealed.payload).unwrap();
+            SyncMessage::decode_ecnp(&inner).unwrap().1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_remote_exec_with_init() {
         let engine = create_engine(test_config()).unwrap();
         engine.init_sync(SyncClientConfig::default()).unwrap();
 
+        // Not in plaintext
+        assert!(matches!(
+            engine.sync_remote_exec("hostname", vec!["-f".into()]),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+
+        let open = upgrade_sync(&engine, false).await;
         let frame = engine
             .sync_remote_exec("hostname", vec!["-f".into()])
             .unwrap();
-        assert!(!frame.is_empty());
+        assert!(SyncMessage::decode_ecnp(&frame).is_err());
 
-        // Should be decodable as a SyncMessage
-        let (_code, msg) = SyncMessage::decode_ecnp(&frame).unwrap();
+        // The desktop decrypts it to the request
+        let msg = open(&frame);
         match msg {
-            SyncMessage::RemoteExec { command, args } => {
+            SyncMessage::RemoteExec { command, args, .. } => {
                 assert_eq!(command, "hostname");
                 assert_eq!(args, vec!["-f"]);
             }
@@ -542,6 +4124,73 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_sync_cancel_exec() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.init_sync(SyncClientConfig::default()).unwrap();
+        let open = upgrade_sync(&engine, true).await;
+
+        let req = engine
+            .sync_remote_exec_tracked("sleep", vec!["600".into()])
+            .unwrap();
+        let frame = engine.sync_cancel_exec(&req.request_id).unwrap();
+        match open(&frame) {
+            SyncMessage::RemoteExecCancel { request_id } => assert_eq!(request_id, req.request_id),
+            _ => panic!("Expected RemoteExecCancel"),
+        }
+
+        assert!(matches!(
+            engine.sync_cancel_exec("no-such-request"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sync_connect_requires_pinned_desktop_key() {
+        let engine = create_engine(test_config()).unwrap();
+        engine
+            .add_peer("desktop", "Desk", "pc", "127.0.0.1", vec![])
+            .unwrap();
+        engine
+            .init_sync(SyncClientConfig {
+                desktop_peer_id: Some("desktop".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(matches!(
+            engine.sync_connect().await,
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(!engine.sync_is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_sync_connect_notifies_listener() {
+        let engine = create_engine(test_config()).unwrap();
+        engine
+            .init_sync(SyncClientConfig {
+                desktop_address: "not-an-address".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        engine
+            .sync_set_connection_listener(Box::new(move |state| log.lock().unwrap().push(state)))
+            .unwrap();
+
+        assert!(engine.sync_connect().await.is_err());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                sync::SyncConnectionState::Connecting,
+                sync::SyncConnectionState::Error
+            ]
+        );
+    }
+
     #[test]
     fn test_sync_process_incoming() {
         let engine = create_engine(test_config()).unwrap();
@@ -566,6 +4215,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encrypted_sync_roundtrip() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+        let peer = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::random_from_rng(
+            rand::rngs::OsRng,
+        ));
+        let session = engine.create_session("peer-001", peer.as_bytes()).unwrap();
+
+        let status = SyncMessage::StatusPush {
+            cpu_usage: 12.5,
+            memory_usage: 40.0,
+            disk_usage: 70.0,
+            uptime_secs: 120,
+            active_sessions: 1,
+            ai_status: "ready".to_string(),
+        };
+        let encrypted = engine
+            .encrypt_sync_message(&session.session_id, &status)
+            .unwrap();
+        assert_ne!(encrypted, status.encode_ecnp().unwrap());
+
+        let decrypted = engine
+            .decrypt_sync_frame(&session.session_id, &encrypted)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&decrypted).unwrap(),
+            serde_json::to_value(&status).unwrap()
+        );
+
+        let mut tampered = encrypted.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        assert!(engine
+            .decrypt_sync_frame(&session.session_id, &tampered)
+            .is_err());
+        assert!(matches!(
+            engine.decrypt_sync_frame("no-such-session", &encrypted),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
     #[test]
     fn test_sync_shutdown() {
         let engine = create_engine(test_config()).unwrap();
@@ -573,4 +4264,37 @@ mod tests {
         engine.sync_shutdown().unwrap();
         assert!(!engine.sync_is_connected());
     }
+
+    #[test]
+    fn test_recent_logs_bounded() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let engine = create_engine(EngineConfig {
+            log_ring_capacity: 3,
+            ..test_config()
+        })
+        .unwrap();
+
+        // Route events through this engine's ring regardless of which
+        // test installed the global subscriber first
+        let ring = engine.log_ring.clone().unwrap();
+        let subscriber = tracing_subscriber::registry().with(ring);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..5 {
+                engine.log_event("warn", &format!("diagnostic {i}"));
+            }
+        });
+
+        let logs = engine.recent_logs();
+        assert_eq!(logs.len(), 3);
+        assert!(logs[0].contains("diagnostic 2"));
+        assert!(logs[2].contains("diagnostic 4"));
+
+        engine.clear_logs();
+        assert!(engine.recent_logs().is_empty());
+
+        // Disabled by default
+        let quiet = create_engine(test_config()).unwrap();
+        assert!(quiet.recent_logs().is_empty());
+    }
 }
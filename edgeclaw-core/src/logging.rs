@@ -0,0 +1,299 @@
+//! In-memory log capture.
+//!
+//! Mobile hosts cannot easily redirect the JSON tracing output to a file,
+//! so `LogRing` keeps the most recent formatted log lines in memory where a
+//! "share diagnostics" action can export them.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, Weak};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Output format of the tracing subscriber the engine installs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log pipelines
+    #[default]
+    Json,
+    /// Single-line human-readable output
+    Compact,
+    /// Multi-line human-readable output, for terminals
+    Pretty,
+}
+
+/// Formatting layer writing `format` lines to `writer`
+pub(crate) fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Json => layer.json().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+    }
+}
+
+/// Bounded ring buffer of recent log lines, usable as a tracing `Layer`.
+///
+/// Clones share the same buffer, so the engine can hand one clone to the
+/// subscriber and keep another for reading. A ring can also be fed without
+/// being in any subscriber: see `register_global`.
+#[derive(Clone)]
+pub struct LogRing {
+    inner: Arc<RingBuffer>,
+}
+
+struct RingBuffer {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+/// Rings fed by `RingFanout`; dropped rings are pruned as events arrive
+static GLOBAL_RINGS: Mutex<Vec<Weak<RingBuffer>>> = Mutex::new(Vec::new());
+
+impl LogRing {
+    /// Create a ring holding at most `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(RingBuffer {
+                lines: Mutex::new(VecDeque::with_capacity(capacity)),
+                capacity,
+            }),
+        }
+    }
+
+    /// Maximum number of retained lines
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Snapshot of the retained lines, oldest first
+    pub fn lines(&self) -> Vec<String> {
+        self.inner
+            .lines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Drop all retained lines
+    pub fn clear(&self) {
+        self.inner
+            .lines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Also receive every event seen by a subscriber that includes
+    /// `RingFanout`, such as the global one the first engine installs.
+    /// Lets later engines capture logs without installing a subscriber.
+    pub fn register_global(&self) {
+        GLOBAL_RINGS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::downgrade(&self.inner));
+    }
+
+    fn push(&self, line: String) {
+        self.inner.push(line);
+    }
+}
+
+impl RingBuffer {
+    fn push(&self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        while lines.len() >= self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Layer feeding every ring registered with `LogRing::register_global`
+pub struct RingFanout;
+
+impl<S: Subscriber> Layer<S> for RingFanout {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut rings = GLOBAL_RINGS.lock().unwrap_or_else(|e| e.into_inner());
+        rings.retain(|ring| ring.strong_count() > 0);
+        if rings.is_empty() {
+            return;
+        }
+        let line = format_line(event);
+        for ring in rings.iter().filter_map(Weak::upgrade) {
+            ring.push(line.clone());
+        }
+    }
+}
+
+/// `<time> <LEVEL> <target>: <message> key=value...`
+fn format_line(event: &Event<'_>) -> String {
+    let meta = event.metadata();
+    let mut visitor = LineVisitor {
+        message: String::new(),
+        fields: String::new(),
+    };
+    event.record(&mut visitor);
+    format!(
+        "{} {} {}: {}{}",
+        chrono::Utc::now().to_rfc3339(),
+        meta.level(),
+        meta.target(),
+        visitor.message,
+        visitor.fields
+    )
+}
+
+/// Collects an event's message and fields into a single line
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogRing {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.push(format_line(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_ring_is_bounded() {
+        let ring = LogRing::new(3);
+        let subscriber = tracing_subscriber::registry().with(ring.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..5 {
+                tracing::info!(seq = i, "event {}", i);
+            }
+        });
+
+        let lines = ring.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("event 2"));
+        assert!(lines[2].contains("event 4"));
+        assert!(lines[2].contains("INFO"));
+        assert!(lines[2].contains("seq=4"));
+
+        ring.clear();
+        assert!(ring.lines().is_empty());
+    }
+
+    #[test]
+    fn test_registered_rings_share_one_subscriber() {
+        let first = LogRing::new(8);
+        let second = LogRing::new(8);
+        let unregistered = LogRing::new(8);
+        first.register_global();
+        second.register_global();
+
+        let subscriber = tracing_subscriber::registry().with(RingFanout);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("fanout check");
+        });
+
+        for ring in [&first, &second] {
+            assert!(ring.lines().iter().any(|l| l.contains("fanout check")));
+        }
+        assert!(unregistered.lines().is_empty());
+    }
+
+    /// Collects formatter output for inspection
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn render(format: LogFormat) -> String {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(format, capture.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(peer = "p1", "hello");
+        });
+        let out = capture.0.lock().unwrap().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_log_formats() {
+        let json = render(LogFormat::Json);
+        let line: serde_json::Value = serde_json::from_str(json.trim()).unwrap();
+        assert_eq!(line["fields"]["message"], "hello");
+
+        let compact = render(LogFormat::Compact);
+        assert!(serde_json::from_str::<serde_json::Value>(compact.trim()).is_err());
+        assert!(compact.contains("hello"));
+        assert_eq!(compact.trim_end().lines().count(), 1);
+
+        let pretty = render(LogFormat::Pretty);
+        assert!(pretty.contains("hello"));
+        assert!(pretty.trim_end().lines().count() > 1);
+
+        assert_eq!(LogFormat::default(), LogFormat::Json);
+        assert_eq!(
+            serde_json::to_string(&LogFormat::Pretty).unwrap(),
+            r#""pretty""#
+        );
+    }
+
+    #[test]
+    fn test_zero_capacity_captures_nothing() {
+        let ring = LogRing::new(0);
+        let subscriber = tracing_subscriber::registry().with(ring.clone());
+        tracing::subscriber::with_default(subscriber, || tracing::warn!("dropped"));
+        assert!(ring.lines().is_empty());
+    }
+}
@@ -1,4 +1,69 @@
+use std::sync::Arc;
+
 use crate::error::EdgeClawError;
+use crate::policy::Role;
+use crate::protocol::{DeviceType, EcmPayload};
+use crate::time::{self, TimeSource};
+
+const MANIFEST_CONTEXT: &[u8] = b"edgeclaw-peer-manifest-v1:";
+
+/// Window over which `discovery_rate_limit` is counted
+const DISCOVERY_WINDOW_SECS: i64 = 60;
+/// Discovery sources tracked at once; announcements from further sources
+/// are dropped until older windows expire
+const MAX_DISCOVERY_SOURCES: usize = 4096;
+
+/// One peer in a `PeerManifest`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PeerManifestEntry {
+    pub peer_id: String,
+    pub device_name: String,
+    pub device_type: String,
+    pub address: String,
+    /// Hex-encoded Ed25519 identity key, if pinned
+    pub public_key_hex: Option<String>,
+}
+
+/// Signed, shareable list of known peers for bootstrapping a new device.
+///
+/// Unlike an engine snapshot it holds no secrets or local state (tags,
+/// sessions), only what another device needs to reach and authenticate
+/// the peers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PeerManifest {
+    /// Device ID of the signer
+    pub signer_id: String,
+    /// RFC 3339 creation time
+    pub created_at: String,
+    pub peers: Vec<PeerManifestEntry>,
+    /// Hex-encoded Ed25519 signature over `signing_bytes()`
+    pub signature: String,
+}
+
+impl PeerManifest {
+    /// Bytes the signer signs: every field except the signature
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let mut bytes = MANIFEST_CONTEXT.to_vec();
+        serde_json::to_writer(
+            &mut bytes,
+            &(&self.signer_id, &self.created_at, &self.peers),
+        )?;
+        Ok(bytes)
+    }
+
+    /// Check the signature against the expected signer's key (`CryptoError`
+    /// if it does not match)
+    pub fn verify(&self, signer_key: &ed25519_dalek::VerifyingKey) -> Result<(), EdgeClawError> {
+        use ed25519_dalek::Verifier;
+
+        let signature = hex::decode(&self.signature).map_err(|_| EdgeClawError::CryptoError)?;
+        let signature = ed25519_dalek::Signature::from_slice(&signature)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        signer_key
+            .verify(&self.signing_bytes()?, &signature)
+            .map_err(|_| EdgeClawError::CryptoError)
+    }
+}
 
 /// Peer information exposed via UniFFI
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -10,6 +75,79 @@ pub struct PeerInfo {
     pub capabilities: Vec<String>,
     pub last_seen: String,
     pub is_connected: bool,
+    /// Operator-assigned group tags ("home", "office", ...)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Hex-encoded Ed25519 public key on file, used for challenge-response auth
+    #[serde(default)]
+    pub public_key_hex: Option<String>,
+    /// Operating system reported in the peer's ECM
+    #[serde(default)]
+    pub os: Option<String>,
+    /// EdgeClaw version reported in the peer's ECM
+    #[serde(default)]
+    pub agent_version: Option<String>,
+    /// Policy role trusted for the peer's signed control messages
+    /// (`None` = none are applied)
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// Ordering for `PeerManager::list_peers_sorted`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum PeerSortKey {
+    /// Most recently seen first, then by name
+    #[default]
+    LastSeen,
+    /// Device name, case-insensitive
+    Name,
+    /// Device type, then name
+    DeviceType,
+}
+
+/// How recently a peer was heard from, for UI status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PeerLiveness {
+    Connected,
+    /// Not connected, but seen within `recently_seen_secs`
+    RecentlySeen,
+    /// Not seen for a while, but within `unknown_after_secs`
+    Stale,
+    /// Not seen for `unknown_after_secs`, or no usable `last_seen`
+    Unknown,
+}
+
+/// Thresholds for `PeerManager::liveness`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LivenessThresholds {
+    pub recently_seen_secs: u64,
+    pub unknown_after_secs: u64,
+}
+
+impl Default for LivenessThresholds {
+    fn default() -> Self {
+        Self {
+            recently_seen_secs: 300,
+            unknown_after_secs: 86_400,
+        }
+    }
+}
+
+fn last_seen_of(info: &PeerInfo) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(&info.last_seen).ok()
+}
+
+/// Rate-limit key for a discovery address: the IP of a socket address,
+/// otherwise the address itself
+fn discovery_source(address: &str) -> String {
+    address
+        .parse::<std::net::SocketAddr>()
+        .map(|a| a.ip().to_string())
+        .unwrap_or_else(|_| address.to_string())
+}
+
+fn name_key(info: &PeerInfo) -> String {
+    info.device_name.to_lowercase()
 }
 
 /// Internal peer entry
@@ -21,6 +159,16 @@ struct PeerEntry {
 /// Manages discovered and connected peers
 pub struct PeerManager {
     peers: std::collections::HashMap<String, PeerEntry>,
+    liveness_thresholds: LivenessThresholds,
+    /// New peers admitted per discovery source per minute (0 = unlimited)
+    discovery_rate_limit: u32,
+    /// New peers admitted per minute across all sources (0 = unlimited)
+    discovery_global_rate_limit: u32,
+    /// Discovery source → (window start, new peers admitted in it)
+    discovery_windows: std::collections::HashMap<String, (chrono::DateTime<chrono::Utc>, u32)>,
+    /// (window start, new peers admitted in it) across all sources
+    discovery_global_window: Option<(chrono::DateTime<chrono::Utc>, u32)>,
+    clock: Arc<dyn TimeSource>,
 }
 
 impl Default for PeerManager {
@@ -33,7 +181,112 @@ impl PeerManager {
     pub fn new() -> Self {
         Self {
             peers: std::collections::HashMap::new(),
+            liveness_thresholds: LivenessThresholds::default(),
+            discovery_rate_limit: 0,
+            discovery_global_rate_limit: 0,
+            discovery_windows: std::collections::HashMap::new(),
+            discovery_global_window: None,
+            clock: time::system(),
+        }
+    }
+
+    pub fn discovery_rate_limit(&self) -> u32 {
+        self.discovery_rate_limit
+    }
+
+    /// Cap how many previously unknown peers one source may announce per
+    /// minute (0 = unlimited)
+    pub fn set_discovery_rate_limit(&mut self, per_minute: u32) {
+        self.discovery_rate_limit = per_minute;
+        self.discovery_windows.clear();
+    }
+
+    pub fn discovery_global_rate_limit(&self) -> u32 {
+        self.discovery_global_rate_limit
+    }
+
+    /// Cap how many previously unknown peers all sources together may
+    /// announce per minute (0 = unlimited)
+    pub fn set_discovery_global_rate_limit(&mut self, per_minute: u32) {
+        self.discovery_global_rate_limit = per_minute;
+        self.discovery_global_window = None;
+    }
+
+    /// Check a discovery announcement against the rate limits before it is
+    /// recorded.
+    ///
+    /// Only announcements of unknown peer IDs count, so known peers can
+    /// always refresh. Every new peer counts against the global limit.
+    /// `source` is where the announcement actually came from (its
+    /// transport peer, not the address it claims); with one, the
+    /// per-source limit also applies, keyed on the IP of a socket address
+    /// or the whole address otherwise (e.g. BLE). Over a limit the
+    /// announcement is dropped with `RateLimited`.
+    pub fn admit_discovery(
+        &mut self,
+        peer_id: &str,
+        source: Option<&str>,
+    ) -> Result<(), EdgeClawError> {
+        if self.peers.contains_key(peer_id) {
+            return Ok(());
         }
+        let now = self.clock.now();
+        let window = chrono::Duration::seconds(DISCOVERY_WINDOW_SECS);
+        self.discovery_windows
+            .retain(|_, (start, _)| now - *start < window);
+        if self
+            .discovery_global_window
+            .is_some_and(|(start, _)| now - start >= window)
+        {
+            self.discovery_global_window = None;
+        }
+
+        let global = self.discovery_global_window.get_or_insert((now, 0));
+        if self.discovery_global_rate_limit > 0 && global.1 >= self.discovery_global_rate_limit {
+            tracing::warn!(
+                peer_id = %peer_id,
+                limit = self.discovery_global_rate_limit,
+                "Global discovery rate limit hit; announcement dropped"
+            );
+            return Err(EdgeClawError::RateLimited);
+        }
+
+        if let (Some(source), true) = (source, self.discovery_rate_limit > 0) {
+            let source = discovery_source(source);
+            if !self.discovery_windows.contains_key(&source)
+                && self.discovery_windows.len() >= MAX_DISCOVERY_SOURCES
+            {
+                tracing::warn!(
+                    source = %source,
+                    peer_id = %peer_id,
+                    "Too many discovery sources; announcement dropped"
+                );
+                return Err(EdgeClawError::RateLimited);
+            }
+            let (_, admitted) = self
+                .discovery_windows
+                .entry(source.clone())
+                .or_insert((now, 0));
+            if *admitted >= self.discovery_rate_limit {
+                tracing::warn!(
+                    source = %source,
+                    peer_id = %peer_id,
+                    limit = self.discovery_rate_limit,
+                    "Discovery rate limit hit; announcement dropped"
+                );
+                return Err(EdgeClawError::RateLimited);
+            }
+            *admitted += 1;
+        }
+        if let Some((_, admitted)) = &mut self.discovery_global_window {
+            *admitted += 1;
+        }
+        Ok(())
+    }
+
+    /// Stamp `last_seen` and judge staleness using `clock`
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.clock = clock;
     }
 
     /// Add or update a discovered peer
@@ -45,15 +298,35 @@ impl PeerManager {
         address: &str,
         capabilities: Vec<String>,
     ) -> PeerInfo {
-        let now = chrono::Utc::now();
+        let now = self.clock.now();
+        // Tags, pinned keys and roles are local state, and discovery does
+        // not carry ECM details, so rediscovery must not wipe any of them
+        let (tags, public_key_hex, os, agent_version, role) = self
+            .peers
+            .get(peer_id)
+            .map(|e| {
+                (
+                    e.info.tags.clone(),
+                    e.info.public_key_hex.clone(),
+                    e.info.os.clone(),
+                    e.info.agent_version.clone(),
+                    e.info.role.clone(),
+                )
+            })
+            .unwrap_or_default();
         let info = PeerInfo {
             peer_id: peer_id.to_string(),
             device_name: device_name.to_string(),
-            device_type: device_type.to_string(),
+            device_type: DeviceType::normalize(device_type),
             address: address.to_string(),
             capabilities,
             last_seen: now.to_rfc3339(),
             is_connected: false,
+            tags,
+            public_key_hex,
+            os,
+            agent_version,
+            role,
         };
 
         self.peers.insert(
@@ -68,6 +341,34 @@ impl PeerManager {
         info
     }
 
+    /// Add or update a peer from its ECM announcement.
+    ///
+    /// The ECM's `device_id` is the peer ID. A known peer keeps its name;
+    /// a new one is named after its device ID until discovery supplies one.
+    /// Empty `os`/`version` (older peers) are stored as `None`.
+    pub fn ingest_ecm(&mut self, ecm: &EcmPayload, address: &str) -> PeerInfo {
+        let device_name = self
+            .peers
+            .get(&ecm.device_id)
+            .map(|e| e.info.device_name.clone())
+            .unwrap_or_else(|| ecm.device_id.clone());
+        let mut info = self.add_peer(
+            &ecm.device_id,
+            &device_name,
+            &ecm.device_type,
+            address,
+            ecm.capabilities.clone(),
+        );
+
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        info.os = non_empty(&ecm.os);
+        info.agent_version = non_empty(&ecm.version);
+        if let Some(entry) = self.peers.get_mut(&ecm.device_id) {
+            entry.info = info.clone();
+        }
+        info
+    }
+
     /// Mark a peer as connected
     pub fn set_connected(&mut self, peer_id: &str, connected: bool) -> Result<(), EdgeClawError> {
         let entry = self
@@ -75,10 +376,123 @@ impl PeerManager {
             .get_mut(peer_id)
             .ok_or(EdgeClawError::InvalidParameter)?;
         entry.info.is_connected = connected;
-        entry.info.last_seen = chrono::Utc::now().to_rfc3339();
+        entry.info.last_seen = self.clock.now().to_rfc3339();
+        Ok(())
+    }
+
+    /// Mark a known peer as freshly seen (e.g. on heartbeat).
+    ///
+    /// Only `last_seen` and the internal discovery time used by
+    /// `cleanup_stale` move; capabilities, address and tags are untouched.
+    pub fn touch(&mut self, peer_id: &str) -> Result<(), EdgeClawError> {
+        let entry = self
+            .peers
+            .get_mut(peer_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        let now = self.clock.now();
+        entry.info.last_seen = now.to_rfc3339();
+        entry.discovered_at = now;
+        Ok(())
+    }
+
+    /// Record the peer's Ed25519 public key (hex).
+    ///
+    /// A key already held by another peer ID is still recorded, but logged:
+    /// one device announcing under several IDs is a bug or an impersonation
+    /// attempt. `find_by_public_key` lists the IDs involved.
+    pub fn set_public_key(
+        &mut self,
+        peer_id: &str,
+        public_key_hex: &str,
+    ) -> Result<(), EdgeClawError> {
+        crate::identity::fingerprint_for_key(public_key_hex)?;
+        if !self.peers.contains_key(peer_id) {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let others: Vec<String> = self
+            .find_by_public_key(public_key_hex)
+            .into_iter()
+            .map(|p| p.peer_id)
+            .filter(|id| id != peer_id)
+            .collect();
+        if !others.is_empty() {
+            tracing::warn!(
+                peer_id = %peer_id,
+                other_peer_ids = ?others,
+                "Public key already belongs to another peer ID"
+            );
+        }
+        if let Some(entry) = self.peers.get_mut(peer_id) {
+            entry.info.public_key_hex = Some(public_key_hex.to_lowercase());
+        }
+        Ok(())
+    }
+
+    /// Trust the peer's signed control messages at `role` (a policy role
+    /// name), or not at all with `None`
+    pub fn set_role(&mut self, peer_id: &str, role: Option<&str>) -> Result<(), EdgeClawError> {
+        let role = role
+            .map(|r| Role::parse_role(r).map(|role| role.as_str().to_string()))
+            .transpose()?;
+        let entry = self
+            .peers
+            .get_mut(peer_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        entry.info.role = role;
         Ok(())
     }
 
+    /// Peers pinned to the given public key (hex, any case), sorted by ID
+    pub fn find_by_public_key(&self, public_key_hex: &str) -> Vec<PeerInfo> {
+        let mut peers: Vec<PeerInfo> = self
+            .peers
+            .values()
+            .filter(|e| {
+                e.info
+                    .public_key_hex
+                    .as_deref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(public_key_hex))
+            })
+            .map(|e| e.info.clone())
+            .collect();
+        peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        peers
+    }
+
+    /// Add a tag to a peer (no-op if already present)
+    pub fn tag_peer(&mut self, peer_id: &str, tag: &str) -> Result<(), EdgeClawError> {
+        if tag.is_empty() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let entry = self
+            .peers
+            .get_mut(peer_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        if !entry.info.tags.iter().any(|t| t == tag) {
+            entry.info.tags.push(tag.to_string());
+        }
+        Ok(())
+    }
+
+    /// Remove a tag from a peer
+    pub fn untag_peer(&mut self, peer_id: &str, tag: &str) -> Result<(), EdgeClawError> {
+        let entry = self
+            .peers
+            .get_mut(peer_id)
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        entry.info.tags.retain(|t| t != tag);
+        Ok(())
+    }
+
+    /// List peers carrying the given tag
+    pub fn peers_with_tag(&self, tag: &str) -> Vec<PeerInfo> {
+        self.peers
+            .values()
+            .filter(|e| e.info.tags.iter().any(|t| t == tag))
+            .map(|e| e.info.clone())
+            .collect()
+    }
+
     /// Remove a peer
     pub fn remove_peer(&mut self, peer_id: &str) -> Result<(), EdgeClawError> {
         self.peers
@@ -100,6 +514,65 @@ impl PeerManager {
         self.peers.values().map(|e| e.info.clone()).collect()
     }
 
+    /// List all known peers in a stable order; ties fall back to peer ID
+    pub fn list_peers_sorted(&self, by: PeerSortKey) -> Vec<PeerInfo> {
+        let mut peers = self.list_peers();
+        peers.sort_by(|a, b| {
+            let primary = match by {
+                PeerSortKey::LastSeen => last_seen_of(b)
+                    .cmp(&last_seen_of(a))
+                    .then_with(|| name_key(a).cmp(&name_key(b))),
+                PeerSortKey::Name => name_key(a).cmp(&name_key(b)),
+                PeerSortKey::DeviceType => a
+                    .device_type
+                    .cmp(&b.device_type)
+                    .then_with(|| name_key(a).cmp(&name_key(b))),
+            };
+            primary.then_with(|| a.peer_id.cmp(&b.peer_id))
+        });
+        peers
+    }
+
+    pub fn set_liveness_thresholds(&mut self, thresholds: LivenessThresholds) {
+        self.liveness_thresholds = thresholds;
+    }
+
+    /// Liveness of a peer as of now
+    pub fn liveness(&self, peer_id: &str) -> Result<PeerLiveness, EdgeClawError> {
+        self.liveness_at(peer_id, self.clock.now())
+    }
+
+    /// Liveness of a peer as of `now`. A connected peer is `Connected`
+    /// regardless of `last_seen`; otherwise the state follows its age.
+    pub fn liveness_at(
+        &self,
+        peer_id: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<PeerLiveness, EdgeClawError> {
+        let info = &self
+            .peers
+            .get(peer_id)
+            .ok_or(EdgeClawError::InvalidParameter)?
+            .info;
+        if info.is_connected {
+            return Ok(PeerLiveness::Connected);
+        }
+        let Some(last_seen) = last_seen_of(info) else {
+            return Ok(PeerLiveness::Unknown);
+        };
+
+        // Clock skew can put last_seen slightly in the future
+        let age = now.signed_duration_since(last_seen).num_seconds().max(0) as u64;
+        let thresholds = &self.liveness_thresholds;
+        Ok(if age < thresholds.recently_seen_secs {
+            PeerLiveness::RecentlySeen
+        } else if age < thresholds.unknown_after_secs {
+            PeerLiveness::Stale
+        } else {
+            PeerLiveness::Unknown
+        })
+    }
+
     /// List only connected peers
     pub fn connected_peers(&self) -> Vec<PeerInfo> {
         self.peers
@@ -109,14 +582,98 @@ impl PeerManager {
             .collect()
     }
 
+    /// Export all peers (for backup)
+    pub fn export_peers(&self) -> Vec<PeerInfo> {
+        self.list_peers()
+    }
+
+    /// Replace the peer table with previously exported peers
+    pub fn import_peers(&mut self, peers: Vec<PeerInfo>) {
+        let now = self.clock.now();
+        self.peers = peers
+            .into_iter()
+            .map(|info| {
+                (
+                    info.peer_id.clone(),
+                    PeerEntry {
+                        info,
+                        discovered_at: now,
+                    },
+                )
+            })
+            .collect();
+    }
+
     /// Remove peers not seen within the given timeout (seconds)
     pub fn cleanup_stale(&mut self, timeout_secs: i64) -> u32 {
-        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(timeout_secs);
+        let cutoff = self.clock.now() - chrono::Duration::seconds(timeout_secs);
         let initial = self.peers.len();
         self.peers.retain(|_, e| e.discovered_at >= cutoff);
         (initial - self.peers.len()) as u32
     }
 
+    /// Unsigned manifest of all peers, sorted by ID
+    pub fn manifest_entries(&self) -> Vec<PeerManifestEntry> {
+        let mut entries: Vec<PeerManifestEntry> = self
+            .peers
+            .values()
+            .map(|e| PeerManifestEntry {
+                peer_id: e.info.peer_id.clone(),
+                device_name: e.info.device_name.clone(),
+                device_type: e.info.device_type.clone(),
+                address: e.info.address.clone(),
+                public_key_hex: e.info.public_key_hex.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        entries
+    }
+
+    /// Add peers from a verified manifest, returning how many entries
+    /// changed the table.
+    ///
+    /// Peers already known keep their local record and only gain a key if
+    /// none was pinned; a pinned key is never replaced. Fails without
+    /// changes if any entry has an invalid key.
+    pub fn import_manifest(&mut self, entries: &[PeerManifestEntry]) -> Result<u32, EdgeClawError> {
+        for entry in entries {
+            if entry.peer_id.is_empty() {
+                return Err(EdgeClawError::InvalidParameter);
+            }
+            if let Some(key) = &entry.public_key_hex {
+                crate::identity::fingerprint_for_key(key)?;
+            }
+        }
+
+        let mut imported = 0;
+        for entry in entries {
+            match self.peers.get(&entry.peer_id) {
+                None => {
+                    self.add_peer(
+                        &entry.peer_id,
+                        &entry.device_name,
+                        &entry.device_type,
+                        &entry.address,
+                        Vec::new(),
+                    );
+                }
+                Some(existing) => match (&existing.info.public_key_hex, &entry.public_key_hex) {
+                    (None, Some(_)) => {}
+                    (Some(pinned), Some(key)) if !pinned.eq_ignore_ascii_case(key) => {
+                        tracing::warn!(peer_id = %entry.peer_id, "Manifest key differs from pinned key; skipped");
+                        continue;
+                    }
+                    _ => continue,
+                },
+            }
+            if let Some(key) = &entry.public_key_hex {
+                self.set_public_key(&entry.peer_id, key)?;
+            }
+            imported += 1;
+        }
+        Ok(imported)
+    }
+
     /// Total peer count
     pub fn count(&self) -> usize {
         self.peers.len()
@@ -144,6 +701,89 @@ mod tests {
         assert_eq!(fetched.device_name, "TestDevice");
     }
 
+    #[test]
+    fn test_discovery_flood_throttled_per_source() {
+        let clock = Arc::new(crate::time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = PeerManager::new();
+        mgr.set_time_source(clock.clone());
+        mgr.set_discovery_rate_limit(3);
+
+        let mut admitted = 0;
+        for i in 0..100 {
+            let id = format!("flood-{i}");
+            let source = format!("10.0.0.66:{}", 5000 + i);
+            if mgr.admit_discovery(&id, Some(&source)).is_ok() {
+                mgr.add_peer(&id, "Spam", "pc", &source, vec![]);
+                admitted += 1;
+            }
+        }
+        assert_eq!(admitted, 3);
+        assert!(matches!(
+            mgr.admit_discovery("flood-new", Some("10.0.0.66:9")),
+            Err(EdgeClawError::RateLimited)
+        ));
+
+        // Known peers can refresh and other sources are unaffected
+        mgr.admit_discovery("flood-0", Some("10.0.0.66:5000"))
+            .unwrap();
+        mgr.admit_discovery("laptop", Some("10.0.0.7:8443"))
+            .unwrap();
+
+        clock.advance(chrono::Duration::seconds(60));
+        mgr.admit_discovery("flood-new", Some("10.0.0.66:9"))
+            .unwrap();
+
+        mgr.set_discovery_rate_limit(0);
+        for i in 0..10 {
+            mgr.admit_discovery(&format!("free-{i}"), Some("10.0.0.66:9"))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_discovery_global_limit() {
+        let clock = Arc::new(crate::time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = PeerManager::new();
+        mgr.set_time_source(clock.clone());
+        mgr.set_discovery_rate_limit(2);
+        mgr.set_discovery_global_rate_limit(5);
+
+        // Spread over many sources, and with no source at all, the flood
+        // still stops at the global cap
+        let admitted = (0..50)
+            .filter(|i| {
+                let source = format!("10.1.0.{i}:9");
+                mgr.admit_discovery(&format!("spread-{i}"), Some(&source))
+                    .is_ok()
+            })
+            .count();
+        assert_eq!(admitted, 5);
+        assert!(matches!(
+            mgr.admit_discovery("manual", None),
+            Err(EdgeClawError::RateLimited)
+        ));
+        assert_eq!(mgr.discovery_windows.len(), 5);
+
+        clock.advance(chrono::Duration::seconds(60));
+        mgr.admit_discovery("manual", None).unwrap();
+        assert!(mgr.discovery_windows.is_empty());
+    }
+
+    #[test]
+    fn test_add_peer_normalizes_device_type() {
+        let mut mgr = PeerManager::new();
+        assert_eq!(
+            mgr.add_peer("p1", "D1", " Tablet", "1.1.1.1", vec![])
+                .device_type,
+            "tablet"
+        );
+        assert_eq!(
+            mgr.add_peer("p2", "D2", "phne", "2.2.2.2", vec![])
+                .device_type,
+            "phne"
+        );
+    }
+
     #[test]
     fn test_set_connected() {
         let mut mgr = PeerManager::new();
@@ -156,6 +796,93 @@ mod tests {
         assert_eq!(mgr.connected_peers().len(), 1);
     }
 
+    #[test]
+    fn test_touch_refreshes_last_seen_only() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(
+            "peer-1",
+            "Dev",
+            "pc",
+            "10.0.0.1",
+            vec!["status".into(), "file_read".into()],
+        );
+        mgr.tag_peer("peer-1", "lab").unwrap();
+        let old = chrono::Utc::now() - chrono::Duration::seconds(600);
+        {
+            let entry = mgr.peers.get_mut("peer-1").unwrap();
+            entry.info.last_seen = old.to_rfc3339();
+            entry.discovered_at = old;
+        }
+
+        mgr.touch("peer-1").unwrap();
+        let p = mgr.get_peer("peer-1").unwrap();
+        assert_ne!(p.last_seen, old.to_rfc3339());
+        assert_eq!(p.capabilities, vec!["status", "file_read"]);
+        assert_eq!(p.address, "10.0.0.1");
+        assert_eq!(p.tags, vec!["lab"]);
+        assert!(!p.is_connected);
+        // Freshly seen peers survive stale cleanup
+        assert_eq!(mgr.cleanup_stale(300), 0);
+
+        assert!(matches!(
+            mgr.touch("nope"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn test_liveness_transitions() {
+        let mut mgr = PeerManager::new();
+        mgr.set_liveness_thresholds(LivenessThresholds {
+            recently_seen_secs: 60,
+            unknown_after_secs: 3600,
+        });
+        mgr.add_peer("peer-1", "Dev", "pc", "10.0.0.1", vec![]);
+        mgr.set_connected("peer-1", true).unwrap();
+
+        let start = chrono::Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(0)).unwrap(),
+            PeerLiveness::Connected
+        );
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(7200)).unwrap(),
+            PeerLiveness::Connected
+        );
+
+        mgr.set_connected("peer-1", false).unwrap();
+        let start = chrono::Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(0)).unwrap(),
+            PeerLiveness::RecentlySeen
+        );
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(59)).unwrap(),
+            PeerLiveness::RecentlySeen
+        );
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(61)).unwrap(),
+            PeerLiveness::Stale
+        );
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(3599)).unwrap(),
+            PeerLiveness::Stale
+        );
+        assert_eq!(
+            mgr.liveness_at("peer-1", at(3601)).unwrap(),
+            PeerLiveness::Unknown
+        );
+        // Compatibility flag is untouched
+        assert!(!mgr.get_peer("peer-1").unwrap().is_connected);
+
+        // Rediscovery refreshes last_seen
+        mgr.add_peer("peer-1", "Dev", "pc", "10.0.0.1", vec![]);
+        assert_eq!(mgr.liveness("peer-1").unwrap(), PeerLiveness::RecentlySeen);
+        assert!(mgr.liveness("nope").is_err());
+    }
+
     #[test]
     fn test_remove_peer() {
         let mut mgr = PeerManager::new();
@@ -194,4 +921,210 @@ mod tests {
         assert_eq!(p.device_name, "NewName");
         assert_eq!(p.address, "2.2.2.2");
     }
+
+    #[test]
+    fn test_peer_role() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("peer-1", "Hub", "pc", "1.1.1.1", vec![]);
+        assert_eq!(mgr.get_peer("peer-1").unwrap().role, None);
+
+        mgr.set_role("peer-1", Some("Admin")).unwrap();
+        assert_eq!(
+            mgr.get_peer("peer-1").unwrap().role.as_deref(),
+            Some("admin")
+        );
+        // Rediscovery keeps the locally assigned role
+        mgr.add_peer("peer-1", "Hub", "pc", "2.2.2.2", vec![]);
+        assert_eq!(
+            mgr.get_peer("peer-1").unwrap().role.as_deref(),
+            Some("admin")
+        );
+
+        assert!(mgr.set_role("peer-1", Some("root")).is_err());
+        assert!(mgr.set_role("nobody", Some("admin")).is_err());
+        mgr.set_role("peer-1", None).unwrap();
+        assert_eq!(mgr.get_peer("peer-1").unwrap().role, None);
+    }
+
+    #[test]
+    fn test_peer_tags() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("p1", "D1", "phone", "1.1.1.1", vec![]);
+        mgr.add_peer("p2", "D2", "pc", "2.2.2.2", vec![]);
+        mgr.add_peer("p3", "D3", "pc", "3.3.3.3", vec![]);
+
+        mgr.tag_peer("p1", "home").unwrap();
+        mgr.tag_peer("p2", "home").unwrap();
+        mgr.tag_peer("p2", "home").unwrap();
+        mgr.tag_peer("p3", "lab").unwrap();
+        assert!(mgr.tag_peer("nobody", "home").is_err());
+
+        assert_eq!(mgr.peers_with_tag("home").len(), 2);
+        assert_eq!(mgr.get_peer("p2").unwrap().tags, vec!["home"]);
+
+        mgr.untag_peer("p1", "home").unwrap();
+        let home: Vec<_> = mgr
+            .peers_with_tag("home")
+            .into_iter()
+            .map(|p| p.peer_id)
+            .collect();
+        assert_eq!(home, vec!["p2"]);
+
+        // Rediscovery keeps tags
+        mgr.add_peer("p3", "D3", "pc", "3.3.3.4", vec![]);
+        assert_eq!(mgr.peers_with_tag("lab").len(), 1);
+    }
+
+    fn ids(peers: Vec<PeerInfo>) -> Vec<String> {
+        peers.into_iter().map(|p| p.peer_id).collect()
+    }
+
+    fn sorting_fixture() -> PeerManager {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("p1", "kitchen", "pc", "1.1.1.1", vec![]);
+        mgr.add_peer("p2", "Attic", "phone", "2.2.2.2", vec![]);
+        mgr.add_peer("p3", "bedroom", "tablet", "3.3.3.3", vec![]);
+        mgr.add_peer("p4", "attic", "pc", "4.4.4.4", vec![]);
+        for (id, seen) in [
+            ("p1", "2026-01-01T12:00:00Z"),
+            ("p2", "2026-01-01T10:00:00Z"),
+            ("p3", "2026-01-01T11:00:00Z"),
+            ("p4", "2026-01-01T12:00:00Z"),
+        ] {
+            mgr.peers.get_mut(id).unwrap().info.last_seen = seen.to_string();
+        }
+        mgr
+    }
+
+    #[test]
+    fn test_list_peers_sorted_by_last_seen() {
+        let mgr = sorting_fixture();
+        // Newest first; same timestamp falls back to name
+        assert_eq!(
+            ids(mgr.list_peers_sorted(PeerSortKey::LastSeen)),
+            vec!["p4", "p1", "p3", "p2"]
+        );
+    }
+
+    #[test]
+    fn test_list_peers_sorted_by_name() {
+        let mgr = sorting_fixture();
+        // Case-insensitive; "Attic"/"attic" tie falls back to peer ID
+        assert_eq!(
+            ids(mgr.list_peers_sorted(PeerSortKey::Name)),
+            vec!["p2", "p4", "p3", "p1"]
+        );
+    }
+
+    #[test]
+    fn test_list_peers_sorted_by_device_type() {
+        let mgr = sorting_fixture();
+        assert_eq!(
+            ids(mgr.list_peers_sorted(PeerSortKey::DeviceType)),
+            vec!["p4", "p1", "p2", "p3"]
+        );
+    }
+
+    #[test]
+    fn test_ingest_ecm_records_os_and_version() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("desk-1", "Office PC", "pc", "10.0.0.5", vec![]);
+
+        let ecm = crate::protocol::parse_ecm(
+            r#"{"device_id":"desk-1","device_type":"pc","capabilities":["shell_exec"],"os":"linux","version":"0.3.1"}"#,
+        )
+        .unwrap();
+        let info = mgr.ingest_ecm(&ecm, "10.0.0.6");
+        assert_eq!(info.device_name, "Office PC");
+        assert_eq!(info.address, "10.0.0.6");
+        assert_eq!(info.capabilities, vec!["shell_exec"]);
+        assert_eq!(info.os.as_deref(), Some("linux"));
+        assert_eq!(info.agent_version.as_deref(), Some("0.3.1"));
+
+        // Plain rediscovery keeps the ECM details
+        mgr.add_peer("desk-1", "Office PC", "pc", "10.0.0.6", vec![]);
+        assert_eq!(mgr.get_peer("desk-1").unwrap().os.as_deref(), Some("linux"));
+    }
+
+    #[test]
+    fn test_find_by_public_key_reports_shared_keys() {
+        let key = hex::encode(
+            ed25519_dalek::SigningKey::from_bytes(&[9; 32])
+                .verifying_key()
+                .to_bytes(),
+        );
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("p1", "Phone", "phone", "10.0.0.1", vec![]);
+        mgr.add_peer("p2", "Phone again", "phone", "10.0.0.2", vec![]);
+        mgr.add_peer("p3", "Other", "pc", "10.0.0.3", vec![]);
+        assert!(mgr.find_by_public_key(&key).is_empty());
+
+        mgr.set_public_key("p2", &key).unwrap();
+        // Recorded despite the clash; lookup is case-insensitive
+        mgr.set_public_key("p1", &key.to_uppercase()).unwrap();
+
+        let ids: Vec<String> = mgr
+            .find_by_public_key(&key.to_uppercase())
+            .into_iter()
+            .map(|p| p.peer_id)
+            .collect();
+        assert_eq!(ids, vec!["p1", "p2"]);
+        assert!(mgr.set_public_key("nobody", &key).is_err());
+    }
+
+    #[test]
+    fn test_import_manifest_keeps_pinned_keys() {
+        let key = |b: u8| {
+            hex::encode(
+                ed25519_dalek::SigningKey::from_bytes(&[b; 32])
+                    .verifying_key()
+                    .to_bytes(),
+            )
+        };
+        let mut mgr = PeerManager::new();
+        mgr.add_peer("p1", "Mine", "pc", "10.0.0.1", vec![]);
+        mgr.set_public_key("p1", &key(1)).unwrap();
+        mgr.add_peer("p2", "Unpinned", "pc", "10.0.0.2", vec![]);
+
+        let entry = |id: &str, k: Option<String>| PeerManifestEntry {
+            peer_id: id.into(),
+            device_name: "From manifest".into(),
+            device_type: "tablet".into(),
+            address: "10.9.9.9".into(),
+            public_key_hex: k,
+        };
+        let imported = mgr
+            .import_manifest(&[
+                entry("p1", Some(key(2))),
+                entry("p2", Some(key(3))),
+                entry("p3", None),
+            ])
+            .unwrap();
+        assert_eq!(imported, 2);
+
+        let p1 = mgr.get_peer("p1").unwrap();
+        assert_eq!(p1.public_key_hex, Some(key(1)));
+        assert_eq!(p1.device_name, "Mine");
+        assert_eq!(mgr.get_peer("p2").unwrap().public_key_hex, Some(key(3)));
+        assert_eq!(mgr.get_peer("p3").unwrap().device_type, "tablet");
+
+        // A bad key rejects the whole manifest
+        assert!(mgr
+            .import_manifest(&[entry("p4", None), entry("p5", Some("zz".into()))])
+            .is_err());
+        assert!(mgr.get_peer("p4").is_err());
+    }
+
+    #[test]
+    fn test_ingest_legacy_ecm() {
+        let mut mgr = PeerManager::new();
+        let ecm = crate::protocol::parse_ecm(
+            r#"{"device_id":"old-1","device_type":"phone","capabilities":[]}"#,
+        )
+        .unwrap();
+        let info = mgr.ingest_ecm(&ecm, "10.0.0.9");
+        assert_eq!(info.device_name, "old-1");
+        assert!(info.os.is_none());
+        assert!(info.agent_version.is_none());
+    }
 }
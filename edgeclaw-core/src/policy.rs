@@ -1,4 +1,9 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
 use crate::error::EdgeClawError;
+use crate::time::{self, TimeSource};
 
 /// Capability risk levels (0-3)
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -13,6 +18,20 @@ pub enum RiskLevel {
     High = 3,
 }
 
+impl TryFrom<u8> for RiskLevel {
+    type Error = EdgeClawError;
+
+    fn try_from(v: u8) -> Result<Self, EdgeClawError> {
+        match v {
+            0 => Ok(RiskLevel::None),
+            1 => Ok(RiskLevel::Low),
+            2 => Ok(RiskLevel::Medium),
+            3 => Ok(RiskLevel::High),
+            _ => Err(EdgeClawError::InvalidParameter),
+        }
+    }
+}
+
 /// Role-Based Access Control roles
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Role {
@@ -23,6 +42,18 @@ pub enum Role {
 }
 
 impl Role {
+    /// Every role, least privileged first
+    pub const ALL: [Role; 4] = [Role::Viewer, Role::Operator, Role::Admin, Role::Owner];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
     pub fn max_allowed_risk(&self) -> RiskLevel {
         match self {
             Role::Viewer => RiskLevel::None,
@@ -49,6 +80,125 @@ pub struct PolicyDecision {
     pub allowed: bool,
     pub reason: String,
     pub risk_level: u8,
+    /// Report-only mode: the request was allowed but policy would deny it
+    #[serde(default)]
+    pub would_have_denied: bool,
+}
+
+/// A request's verdict under the current and a candidate policy, from
+/// `PolicyEngine::simulate`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PolicySimDiff {
+    pub capability: String,
+    pub role: String,
+    pub old: PolicyDecision,
+    pub new: PolicyDecision,
+    /// `allowed` differs between `old` and `new`
+    pub changed: bool,
+}
+
+impl PolicySimDiff {
+    /// Allowed today, denied under the candidate policy
+    pub fn is_regression(&self) -> bool {
+        self.old.allowed && !self.new.allowed
+    }
+}
+
+/// Role × capability verdicts, from `PolicyEngine::permission_matrix`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PermissionMatrix {
+    /// Row labels, least privileged first
+    pub roles: Vec<String>,
+    /// Column labels, in registration order
+    pub capabilities: Vec<String>,
+    /// `allowed[r][c]` is the verdict for `roles[r]` × `capabilities[c]`
+    pub allowed: Vec<Vec<bool>>,
+}
+
+impl PermissionMatrix {
+    /// Verdict for one cell; `None` if the role or capability isn't listed
+    pub fn is_allowed(&self, role: &str, capability: &str) -> Option<bool> {
+        let r = self
+            .roles
+            .iter()
+            .position(|x| x.eq_ignore_ascii_case(role))?;
+        let c = self.capabilities.iter().position(|x| x == capability)?;
+        Some(self.allowed[r][c])
+    }
+
+    /// CSV with a `role` header column and `allow`/`deny` cells
+    pub fn to_csv(&self) -> String {
+        let field = |s: &str| {
+            if s.contains([',', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
+        };
+        let mut out = String::from("role");
+        for capability in &self.capabilities {
+            out.push(',');
+            out.push_str(&field(capability));
+        }
+        out.push('\n');
+        for (role, row) in self.roles.iter().zip(&self.allowed) {
+            out.push_str(&field(role));
+            for allowed in row {
+                out.push_str(if *allowed { ",allow" } else { ",deny" });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Number of denials kept in the policy audit log
+pub const AUDIT_LOG_CAPACITY: usize = 256;
+
+/// A denied (or, in report-only mode, would-be denied) capability request
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PolicyAuditEntry {
+    pub timestamp: String,
+    pub capability: String,
+    pub role: String,
+    pub reason: String,
+    /// Machine-readable cause: `capability_disabled`,
+    /// `capability_revoked`, `risk_exceeds_role` or `unknown_capability`
+    #[serde(default)]
+    pub reason_code: String,
+    /// False when the denial was only reported
+    pub enforced: bool,
+}
+
+/// One NDJSON line of the exported audit log
+#[derive(serde::Serialize)]
+struct AuditRecord<'a> {
+    timestamp: &'a str,
+    capability: &'a str,
+    role: &'a str,
+    /// `denied`, or `would_deny` in report-only mode
+    outcome: &'static str,
+    reason_code: &'a str,
+    reason: &'a str,
+}
+
+impl PolicyAuditEntry {
+    /// This entry as a single JSON line (no trailing newline)
+    fn to_ndjson_line(&self) -> Result<String, EdgeClawError> {
+        let record = AuditRecord {
+            timestamp: &self.timestamp,
+            capability: &self.capability,
+            role: &self.role,
+            outcome: if self.enforced {
+                "denied"
+            } else {
+                "would_deny"
+            },
+            reason_code: &self.reason_code,
+            reason: &self.reason,
+        };
+        serde_json::to_string(&record).map_err(EdgeClawError::from)
+    }
 }
 
 /// Capability entry
@@ -56,13 +206,36 @@ pub struct PolicyDecision {
 pub struct Capability {
     pub name: String,
     pub risk_level: RiskLevel,
+    /// English description, used when no translation is available
     pub description: String,
+    /// Stable key for looking up a translated description (e.g. `cap.shell_exec.desc`)
+    pub localization_key: Option<String>,
+}
+
+/// Structured capability listing entry
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CapabilityInfo {
+    pub name: String,
+    pub risk_level: u8,
+    pub description: String,
+    pub localization_key: Option<String>,
 }
 
 /// Policy Engine — evaluates capability requests against role-based policies
 pub struct PolicyEngine {
     capabilities: Vec<Capability>,
     default_deny: bool,
+    /// Capabilities disabled for every role (incident kill-switch)
+    denied_capabilities: HashSet<String>,
+    /// Capabilities revoked for specific roles only (capability → roles)
+    revoked_capabilities: HashMap<String, HashSet<String>>,
+    /// Report denials without enforcing them
+    report_only: bool,
+    /// Bounded denial log; behind a mutex so `evaluate` stays `&self`
+    audit_log: Mutex<VecDeque<PolicyAuditEntry>>,
+    /// Denials recorded since creation, including ones evicted from the log
+    denial_count: AtomicU64,
+    clock: Arc<dyn TimeSource>,
 }
 
 impl Default for PolicyEngine {
@@ -73,14 +246,38 @@ impl Default for PolicyEngine {
 
 impl PolicyEngine {
     pub fn new() -> Self {
-        let mut engine = Self {
-            capabilities: Vec::new(),
-            default_deny: true,
-        };
+        let mut engine = Self::without_defaults();
         engine.register_default_capabilities();
         engine
     }
 
+    /// An engine with no capabilities registered, so every request hits
+    /// default deny until a custom policy is loaded
+    pub fn without_defaults() -> Self {
+        Self {
+            capabilities: Vec::new(),
+            default_deny: true,
+            denied_capabilities: HashSet::new(),
+            revoked_capabilities: HashMap::new(),
+            report_only: false,
+            audit_log: Mutex::new(VecDeque::new()),
+            denial_count: AtomicU64::new(0),
+            clock: time::system(),
+        }
+    }
+
+    /// Unregister every capability, including the built-in set. Deny and
+    /// revocation lists are left alone.
+    pub fn clear_capabilities(&mut self) {
+        self.capabilities.clear();
+        tracing::info!("Policy capabilities cleared");
+    }
+
+    /// Timestamp audit entries using `clock`
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.clock = clock;
+    }
+
     /// Register the built-in capability set
     fn register_default_capabilities(&mut self) {
         let defaults = vec![
@@ -106,21 +303,136 @@ impl PolicyEngine {
                 name: name.to_string(),
                 risk_level: risk,
                 description: desc.to_string(),
+                localization_key: Some(format!("cap.{}.desc", name)),
             });
         }
     }
 
-    /// Evaluate a capability request against a role
-    pub fn evaluate(
+    /// Globally disable a capability, overriding every role (including Owner)
+    pub fn deny_capability(&mut self, name: &str) {
+        self.denied_capabilities.insert(name.to_string());
+        tracing::warn!(capability = %name, "Capability globally disabled");
+    }
+
+    /// Lift a global deny previously set with `deny_capability`, along with
+    /// any per-role revocations of the capability
+    pub fn allow_capability(&mut self, name: &str) {
+        let revoked = self.revoked_capabilities.remove(name).is_some();
+        if self.denied_capabilities.remove(name) || revoked {
+            tracing::info!(capability = %name, "Capability re-enabled");
+        }
+    }
+
+    /// Current global deny list, sorted
+    pub fn denied_capabilities(&self) -> Vec<String> {
+        let mut denied: Vec<String> = self.denied_capabilities.iter().cloned().collect();
+        denied.sort();
+        denied
+    }
+
+    /// Replace the global deny list (used when restoring a snapshot)
+    pub fn set_denied_capabilities(&mut self, names: Vec<String>) {
+        self.denied_capabilities = names.into_iter().collect();
+    }
+
+    /// Check whether a capability is on the global deny list
+    pub fn is_globally_denied(&self, name: &str) -> bool {
+        self.denied_capabilities.contains(name)
+    }
+
+    /// Revoke a capability for the given roles; an empty list revokes it
+    /// for every role via the global deny list
+    pub fn revoke_capability(&mut self, name: &str, roles: &[String]) -> Result<(), EdgeClawError> {
+        if roles.is_empty() {
+            self.deny_capability(name);
+            return Ok(());
+        }
+        let roles = roles
+            .iter()
+            .map(|r| Role::parse_role(r).map(|_| r.to_lowercase()))
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::warn!(capability = %name, roles = ?roles, "Capability revoked for roles");
+        self.revoked_capabilities
+            .entry(name.to_string())
+            .or_default()
+            .extend(roles);
+        Ok(())
+    }
+
+    /// Every per-role revocation, capability to sorted roles
+    pub fn revoked_capabilities(&self) -> std::collections::BTreeMap<String, Vec<String>> {
+        self.revoked_capabilities
+            .keys()
+            .map(|name| (name.clone(), self.revoked_roles(name)))
+            .collect()
+    }
+
+    /// Replace the per-role revocations (used when restoring a snapshot)
+    pub fn set_revoked_capabilities(
+        &mut self,
+        revoked: std::collections::BTreeMap<String, Vec<String>>,
+    ) {
+        self.revoked_capabilities = revoked
+            .into_iter()
+            .map(|(name, roles)| (name, roles.into_iter().collect()))
+            .collect();
+    }
+
+    /// Roles a capability has been revoked for, sorted
+    pub fn revoked_roles(&self, name: &str) -> Vec<String> {
+        let mut roles: Vec<String> = self
+            .revoked_capabilities
+            .get(name)
+            .map(|r| r.iter().cloned().collect())
+            .unwrap_or_default();
+        roles.sort();
+        roles
+    }
+
+    /// Policy verdict before report-only mode is applied, with its reason code
+    fn decide(
         &self,
         capability_name: &str,
         role_str: &str,
-    ) -> Result<PolicyDecision, EdgeClawError> {
+    ) -> Result<(PolicyDecision, &'static str), EdgeClawError> {
         let role = Role::parse_role(role_str)?;
 
         // Find the capability
         let cap = self.capabilities.iter().find(|c| c.name == capability_name);
 
+        // Global deny list is checked before any role evaluation
+        if self.denied_capabilities.contains(capability_name) {
+            return Ok((
+                PolicyDecision {
+                    allowed: false,
+                    reason: format!("Capability '{}' globally disabled", capability_name),
+                    risk_level: cap.map(|c| c.risk_level as u8).unwrap_or(3),
+                    would_have_denied: false,
+                },
+                "capability_disabled",
+            ));
+        }
+
+        let role_key = role_str.to_lowercase();
+        if self
+            .revoked_capabilities
+            .get(capability_name)
+            .is_some_and(|roles| roles.contains(&role_key))
+        {
+            return Ok((
+                PolicyDecision {
+                    allowed: false,
+                    reason: format!(
+                        "Capability '{}' revoked for role '{}'",
+                        capability_name, role_str
+                    ),
+                    risk_level: cap.map(|c| c.risk_level as u8).unwrap_or(3),
+                    would_have_denied: false,
+                },
+                "capability_revoked",
+            ));
+        }
+
         match cap {
             Some(capability) => {
                 let max_risk = role.max_allowed_risk();
@@ -139,33 +451,200 @@ impl PolicyEngine {
                     )
                 };
 
-                Ok(PolicyDecision {
-                    allowed,
-                    reason,
-                    risk_level: risk_u8,
-                })
+                let code = if allowed {
+                    "allowed"
+                } else {
+                    "risk_exceeds_role"
+                };
+                Ok((
+                    PolicyDecision {
+                        allowed,
+                        reason,
+                        risk_level: risk_u8,
+                        would_have_denied: false,
+                    },
+                    code,
+                ))
             }
             None => {
-                if self.default_deny {
-                    Ok(PolicyDecision {
+                let decision = if self.default_deny {
+                    PolicyDecision {
                         allowed: false,
                         reason: format!("Unknown capability '{}' — default deny", capability_name),
                         risk_level: 3,
-                    })
+                        would_have_denied: false,
+                    }
                 } else {
-                    Ok(PolicyDecision {
+                    PolicyDecision {
                         allowed: true,
                         reason: format!(
                             "Unknown capability '{}' — default allow (not recommended)",
                             capability_name
                         ),
                         risk_level: 0,
-                    })
-                }
+                        would_have_denied: false,
+                    }
+                };
+                Ok((decision, "unknown_capability"))
             }
         }
     }
 
+    /// Evaluate a capability request against a role.
+    ///
+    /// Denials are recorded in the audit log. In report-only mode a denied
+    /// request is returned as allowed, with `would_have_denied` set and the
+    /// real denial reason kept.
+    pub fn evaluate(
+        &self,
+        capability_name: &str,
+        role_str: &str,
+    ) -> Result<PolicyDecision, EdgeClawError> {
+        let (mut decision, reason_code) = self.decide(capability_name, role_str)?;
+        if decision.allowed {
+            return Ok(decision);
+        }
+
+        self.record_denial(capability_name, role_str, &decision.reason, reason_code);
+        if self.report_only {
+            tracing::warn!(
+                capability = %capability_name,
+                role = %role_str,
+                reason = %decision.reason,
+                "Policy would deny (report-only)"
+            );
+            decision.allowed = true;
+            decision.would_have_denied = true;
+        }
+        Ok(decision)
+    }
+
+    /// Evaluate many `(capability, role)` requests, preserving order.
+    ///
+    /// A request that cannot be evaluated (e.g. an unknown role) yields a
+    /// denied decision explaining why, instead of failing the batch.
+    pub fn evaluate_batch(&self, requests: &[(String, String)]) -> Vec<PolicyDecision> {
+        requests
+            .iter()
+            .map(|(capability, role)| {
+                self.evaluate(capability, role)
+                    .unwrap_or_else(|e| unevaluable(capability, role, &e))
+            })
+            .collect()
+    }
+
+    /// Compare this policy's verdicts with `new_policy`'s for each
+    /// `(capability, role)` request, e.g. to spot an Operator losing
+    /// `file_read` before rolling a policy out.
+    ///
+    /// Both sides are judged as enforced (report-only mode is ignored) and
+    /// nothing is written to either audit log.
+    pub fn simulate(
+        &self,
+        new_policy: &PolicyEngine,
+        sample_requests: &[(String, String)],
+    ) -> Vec<PolicySimDiff> {
+        sample_requests
+            .iter()
+            .map(|(capability, role)| {
+                let old = self.verdict(capability, role);
+                let new = new_policy.verdict(capability, role);
+                PolicySimDiff {
+                    capability: capability.clone(),
+                    role: role.clone(),
+                    changed: old.allowed != new.allowed,
+                    old,
+                    new,
+                }
+            })
+            .collect()
+    }
+
+    /// Enforced verdict without side effects: report-only mode is ignored
+    /// and nothing is audited or counted, so callers can ask ahead of an
+    /// action. A request that cannot be evaluated is denied.
+    pub fn verdict(&self, capability: &str, role: &str) -> PolicyDecision {
+        self.decide(capability, role)
+            .map(|(decision, _)| decision)
+            .unwrap_or_else(|e| unevaluable(capability, role, &e))
+    }
+
+    fn record_denial(&self, capability: &str, role: &str, reason: &str, reason_code: &str) {
+        self.denial_count.fetch_add(1, Ordering::Relaxed);
+        let mut log = self.audit_log.lock().unwrap_or_else(|e| e.into_inner());
+        if log.len() == AUDIT_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(PolicyAuditEntry {
+            timestamp: self.clock.now().to_rfc3339(),
+            capability: capability.to_string(),
+            role: role.to_string(),
+            reason: reason.to_string(),
+            reason_code: reason_code.to_string(),
+            enforced: !self.report_only,
+        });
+    }
+
+    /// Denials (or report-only would-denies) since creation
+    pub fn denial_count(&self) -> u64 {
+        self.denial_count.load(Ordering::Relaxed)
+    }
+
+    /// Recorded denials, oldest first (at most `AUDIT_LOG_CAPACITY`)
+    pub fn audit_log(&self) -> Vec<PolicyAuditEntry> {
+        self.audit_log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// The audit log as newline-delimited JSON, one entry per line with
+    /// `timestamp`, `capability`, `role`, `outcome`, `reason_code` and
+    /// `reason`. The log is left intact.
+    pub fn export_audit_ndjson(&self) -> String {
+        let log = self.audit_log.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        for line in log.iter().filter_map(|e| e.to_ndjson_line().ok()) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Write the audit log to `writer` as NDJSON, removing each entry once
+    /// written. On a write error the unwritten entries stay queued.
+    /// Returns the number of entries written.
+    pub fn drain_audit_ndjson<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, EdgeClawError> {
+        let mut log = self.audit_log.lock().unwrap_or_else(|e| e.into_inner());
+        let mut written = 0;
+        while let Some(entry) = log.front() {
+            let mut line = entry.to_ndjson_line()?;
+            line.push('\n');
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|_| EdgeClawError::ConnectionError)?;
+            log.pop_front();
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Log denials without enforcing them, to measure a policy's impact
+    pub fn set_report_only(&mut self, enabled: bool) {
+        self.report_only = enabled;
+        tracing::info!(report_only = enabled, "Policy enforcement mode changed");
+    }
+
+    /// Whether denials are currently only reported
+    pub fn is_report_only(&self) -> bool {
+        self.report_only
+    }
+
     /// Get all registered capabilities as strings
     pub fn list_capabilities(&self) -> Vec<String> {
         self.capabilities
@@ -178,6 +657,74 @@ impl PolicyEngine {
             })
             .collect()
     }
+
+    /// Verdict for every role × registered capability, for compliance
+    /// reports.
+    ///
+    /// Cells are judged as enforced (report-only mode is ignored), with the
+    /// global deny list and revocations applied, and nothing is written to
+    /// the audit log.
+    pub fn permission_matrix(&self) -> PermissionMatrix {
+        let capabilities: Vec<String> = self.capabilities.iter().map(|c| c.name.clone()).collect();
+        let allowed = Role::ALL
+            .iter()
+            .map(|role| {
+                capabilities
+                    .iter()
+                    .map(|cap| {
+                        self.decide(cap, role.as_str())
+                            .is_ok_and(|(decision, _)| decision.allowed)
+                    })
+                    .collect()
+            })
+            .collect();
+        PermissionMatrix {
+            roles: Role::ALL.iter().map(|r| r.as_str().to_string()).collect(),
+            capabilities,
+            allowed,
+        }
+    }
+
+    /// Get all registered capabilities as structured entries
+    pub fn capability_infos(&self) -> Vec<CapabilityInfo> {
+        self.infos_where(|_| true)
+    }
+
+    /// Capabilities registered at exactly `level`
+    pub fn capabilities_at_risk(&self, level: RiskLevel) -> Vec<CapabilityInfo> {
+        self.infos_where(|risk| risk == level)
+    }
+
+    /// Capabilities registered at `level` or below
+    pub fn capabilities_up_to(&self, level: RiskLevel) -> Vec<CapabilityInfo> {
+        self.infos_where(|risk| risk <= level)
+    }
+
+    fn infos_where(&self, keep: impl Fn(RiskLevel) -> bool) -> Vec<CapabilityInfo> {
+        self.capabilities
+            .iter()
+            .filter(|c| keep(c.risk_level))
+            .map(|c| CapabilityInfo {
+                name: c.name.clone(),
+                risk_level: c.risk_level as u8,
+                description: c.description.clone(),
+                localization_key: c.localization_key.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Denied decision for a request that could not be evaluated
+fn unevaluable(capability: &str, role: &str, e: &EdgeClawError) -> PolicyDecision {
+    PolicyDecision {
+        allowed: false,
+        reason: format!(
+            "Cannot evaluate '{}' for role '{}': {}",
+            capability, role, e
+        ),
+        risk_level: 3,
+        would_have_denied: false,
+    }
 }
 
 #[cfg(test)]
@@ -252,16 +799,295 @@ mod tests {
         assert!(!decision.allowed);
     }
 
+    #[test]
+    fn test_without_defaults_denies_everything() {
+        let engine = PolicyEngine::without_defaults();
+        assert!(engine.list_capabilities().is_empty());
+        let decision = engine.evaluate("status_query", "owner").unwrap();
+        assert!(!decision.allowed);
+        assert!(decision.reason.contains("default deny"));
+
+        let mut engine = PolicyEngine::new();
+        engine.clear_capabilities();
+        assert!(engine.list_capabilities().is_empty());
+        assert!(!engine.evaluate("heartbeat", "viewer").unwrap().allowed);
+    }
+
     #[test]
     fn test_invalid_role() {
         let engine = PolicyEngine::new();
         assert!(engine.evaluate("status_query", "hacker").is_err());
     }
 
+    #[test]
+    fn test_global_deny_overrides_owner() {
+        let mut engine = PolicyEngine::new();
+        engine.deny_capability("firmware_update");
+        assert!(engine.is_globally_denied("firmware_update"));
+
+        let decision = engine.evaluate("firmware_update", "owner").unwrap();
+        assert!(!decision.allowed);
+        assert!(decision.reason.contains("globally disabled"));
+        assert_eq!(decision.risk_level, 3);
+
+        // Other capabilities are unaffected
+        assert!(engine.evaluate("shell_exec", "owner").unwrap().allowed);
+
+        engine.allow_capability("firmware_update");
+        assert!(!engine.is_globally_denied("firmware_update"));
+        assert!(engine.evaluate("firmware_update", "owner").unwrap().allowed);
+    }
+
+    #[test]
+    fn test_revoke_capability_for_roles() {
+        let mut engine = PolicyEngine::new();
+        engine
+            .revoke_capability("file_read", &["Operator".into()])
+            .unwrap();
+        assert_eq!(engine.revoked_roles("file_read"), vec!["operator"]);
+
+        let decision = engine.evaluate("file_read", "operator").unwrap();
+        assert!(!decision.allowed);
+        assert!(decision.reason.contains("revoked"));
+        // Other roles keep the capability
+        assert!(engine.evaluate("file_read", "admin").unwrap().allowed);
+        assert!(!engine.is_globally_denied("file_read"));
+
+        engine.allow_capability("file_read");
+        assert!(engine.evaluate("file_read", "operator").unwrap().allowed);
+
+        // No roles means every role
+        engine.revoke_capability("shell_exec", &[]).unwrap();
+        assert!(engine.is_globally_denied("shell_exec"));
+
+        assert!(engine
+            .revoke_capability("file_write", &["hacker".into()])
+            .is_err());
+        assert!(engine.revoked_roles("file_write").is_empty());
+    }
+
     #[test]
     fn test_list_capabilities() {
         let engine = PolicyEngine::new();
         let caps = engine.list_capabilities();
         assert!(caps.len() >= 11);
     }
+
+    #[test]
+    fn test_capabilities_by_risk() {
+        let engine = PolicyEngine::new();
+        let mut high: Vec<String> = engine
+            .capabilities_at_risk(RiskLevel::High)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        high.sort();
+        assert_eq!(high, ["firmware_update", "shell_exec", "system_reboot"]);
+
+        let passive = engine.capabilities_up_to(RiskLevel::None);
+        assert_eq!(passive.len(), 2);
+        assert!(passive.iter().all(|c| c.risk_level == 0));
+        assert_eq!(
+            engine.capabilities_up_to(RiskLevel::High).len(),
+            engine.capability_infos().len()
+        );
+
+        assert_eq!(RiskLevel::try_from(2).unwrap(), RiskLevel::Medium);
+        assert!(RiskLevel::try_from(4).is_err());
+    }
+
+    #[test]
+    fn test_builtin_localization_keys() {
+        let engine = PolicyEngine::new();
+        let infos = engine.capability_infos();
+        assert_eq!(infos.len(), 11);
+        for info in &infos {
+            assert_eq!(
+                info.localization_key.as_deref(),
+                Some(format!("cap.{}.desc", info.name).as_str())
+            );
+            assert!(!info.description.is_empty());
+        }
+        let shell = infos.iter().find(|i| i.name == "shell_exec").unwrap();
+        assert_eq!(
+            shell.localization_key.as_deref(),
+            Some("cap.shell_exec.desc")
+        );
+    }
+
+    #[test]
+    fn test_evaluate_batch_mixed() {
+        let engine = PolicyEngine::new();
+        let requests: Vec<(String, String)> = [
+            ("status_query", "viewer"),
+            ("shell_exec", "viewer"),
+            ("file_read", "superuser"),
+            ("shell_exec", "owner"),
+        ]
+        .iter()
+        .map(|(c, r)| (c.to_string(), r.to_string()))
+        .collect();
+
+        let decisions = engine.evaluate_batch(&requests);
+        let allowed: Vec<bool> = decisions.iter().map(|d| d.allowed).collect();
+        assert_eq!(allowed, vec![true, false, false, true]);
+        assert!(decisions[2].reason.contains("superuser"));
+        assert!(engine.evaluate_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_permission_matrix() {
+        let mut engine = PolicyEngine::new();
+        engine.set_report_only(true);
+        engine.deny_capability("file_read");
+        let matrix = engine.permission_matrix();
+
+        assert_eq!(matrix.roles, vec!["viewer", "operator", "admin", "owner"]);
+        assert_eq!(matrix.capabilities.len(), engine.capability_infos().len());
+        assert_eq!(matrix.allowed.len(), 4);
+        assert!(matrix
+            .allowed
+            .iter()
+            .all(|row| row.len() == matrix.capabilities.len()));
+
+        assert_eq!(matrix.is_allowed("viewer", "shell_exec"), Some(false));
+        assert_eq!(matrix.is_allowed("Owner", "shell_exec"), Some(true));
+        assert_eq!(matrix.is_allowed("viewer", "status_query"), Some(true));
+        // Global denials apply; report-only mode does not
+        assert_eq!(matrix.is_allowed("owner", "file_read"), Some(false));
+        assert_eq!(matrix.is_allowed("intern", "shell_exec"), None);
+        assert_eq!(matrix.is_allowed("owner", "teleport"), None);
+        assert_eq!(engine.denial_count(), 0);
+
+        let csv = matrix.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("role,"));
+        assert_eq!(lines[1].split(',').count(), matrix.capabilities.len() + 1);
+        assert!(lines[1].starts_with("viewer,"));
+    }
+
+    #[test]
+    fn test_simulate_flags_tightened_capability() {
+        let current = PolicyEngine::new();
+        let mut candidate = PolicyEngine::new();
+        candidate.deny_capability("file_read");
+
+        let requests: Vec<(String, String)> = [
+            ("file_read", "operator"),
+            ("file_read", "owner"),
+            ("status_query", "viewer"),
+            ("shell_exec", "operator"),
+            ("file_read", "intern"),
+        ]
+        .iter()
+        .map(|(c, r)| (c.to_string(), r.to_string()))
+        .collect();
+
+        let diffs = current.simulate(&candidate, &requests);
+        assert_eq!(diffs.len(), requests.len());
+        let regressions: Vec<_> = diffs
+            .iter()
+            .filter(|d| d.is_regression())
+            .map(|d| (d.capability.as_str(), d.role.as_str()))
+            .collect();
+        assert_eq!(
+            regressions,
+            vec![("file_read", "operator"), ("file_read", "owner")]
+        );
+
+        // Already denied, or unevaluable, under both: not a change
+        assert!(!diffs[3].changed);
+        assert!(!diffs[4].changed);
+        assert!(diffs[4].new.reason.contains("Cannot evaluate"));
+
+        // Simulation never touches the audit logs
+        assert!(current.audit_log().is_empty());
+        assert!(candidate.audit_log().is_empty());
+    }
+
+    #[test]
+    fn test_report_only_allows_with_flag() {
+        let mut engine = PolicyEngine::new();
+        engine.set_report_only(true);
+
+        let decision = engine.evaluate("shell_exec", "viewer").unwrap();
+        assert!(decision.allowed);
+        assert!(decision.would_have_denied);
+        assert!(decision.reason.contains("denied"));
+
+        // Requests policy allows are not flagged
+        let ok = engine.evaluate("status_query", "viewer").unwrap();
+        assert!(ok.allowed);
+        assert!(!ok.would_have_denied);
+
+        let log = engine.audit_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].capability, "shell_exec");
+        assert!(!log[0].enforced);
+    }
+
+    #[test]
+    fn test_enforced_denials_audited() {
+        let mut engine = PolicyEngine::new();
+        engine.deny_capability("file_read");
+
+        let decision = engine.evaluate("file_read", "owner").unwrap();
+        assert!(!decision.allowed);
+        assert!(!decision.would_have_denied);
+        let log = engine.audit_log();
+        assert_eq!(log.len(), 1);
+        assert!(log[0].enforced);
+
+        engine.set_report_only(true);
+        assert!(engine.evaluate("file_read", "owner").unwrap().allowed);
+        engine.set_report_only(false);
+        assert!(!engine.evaluate("file_read", "owner").unwrap().allowed);
+        assert_eq!(engine.audit_log().len(), 3);
+    }
+
+    #[test]
+    fn test_audit_ndjson_export_and_drain() {
+        let mut engine = PolicyEngine::new();
+        engine.deny_capability("file_read");
+        engine.evaluate("file_read", "owner").unwrap();
+        engine.evaluate("shell_exec", "viewer").unwrap();
+        engine.evaluate("status_query", "viewer").unwrap(); // allowed, not audited
+        engine.evaluate("teleport", "owner").unwrap();
+        engine.set_report_only(true);
+        engine.evaluate("shell_exec", "viewer").unwrap();
+
+        let ndjson = engine.export_audit_ndjson();
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert!(ndjson.ends_with('\n'));
+        let codes: Vec<&str> = lines
+            .iter()
+            .map(|l| l["reason_code"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                "capability_disabled",
+                "risk_exceeds_role",
+                "unknown_capability",
+                "risk_exceeds_role"
+            ]
+        );
+        assert_eq!(lines[0]["outcome"], "denied");
+        assert_eq!(lines[3]["outcome"], "would_deny");
+        assert_eq!(lines[1]["role"], "viewer");
+        assert!(lines[0]["timestamp"].is_string());
+
+        // Exporting keeps the log; draining empties it
+        assert_eq!(engine.audit_log().len(), 4);
+        let mut sink = Vec::new();
+        assert_eq!(engine.drain_audit_ndjson(&mut sink).unwrap(), 4);
+        assert_eq!(String::from_utf8(sink).unwrap(), ndjson);
+        assert!(engine.audit_log().is_empty());
+        assert!(engine.export_audit_ndjson().is_empty());
+    }
 }
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use crate::error::EdgeClawError;
+
+/// Limits for `ConnectionPool`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PoolConfig {
+    /// Idle connections kept per peer address
+    pub max_idle_per_peer: usize,
+    /// Open connections (idle + checked out) across all peers
+    pub max_total: usize,
+    /// Idle connections older than this are closed instead of reused
+    pub idle_ttl_secs: u64,
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_peer: 2,
+            max_total: 32,
+            idle_ttl_secs: 60,
+            connect_timeout_secs: 10,
+        }
+    }
+}
+
+/// Pool counters, for diagnostics
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PoolStats {
+    pub idle: usize,
+    pub checked_out: usize,
+    /// Connections opened since the pool was created
+    pub connects: u64,
+    /// Acquires served from an idle connection
+    pub reuses: u64,
+}
+
+struct IdleConnection {
+    stream: TcpStream,
+    idle_since: Instant,
+}
+
+#[derive(Default)]
+struct PoolState {
+    /// `PoolConfig::max_total`, adjustable through `set_max_total`
+    max_total: usize,
+    idle: HashMap<String, Vec<IdleConnection>>,
+    checked_out: usize,
+    connects: u64,
+    reuses: u64,
+}
+
+impl PoolState {
+    fn idle_count(&self) -> usize {
+        self.idle.values().map(Vec::len).sum()
+    }
+
+    /// Drop idle connections past the TTL
+    fn evict_expired(&mut self, ttl: Duration, now: Instant) {
+        for conns in self.idle.values_mut() {
+            conns.retain(|c| now.duration_since(c.idle_since) < ttl);
+        }
+        self.idle.retain(|_, conns| !conns.is_empty());
+    }
+
+    /// Close the longest-idle connection to make room; false if none
+    fn evict_oldest(&mut self) -> bool {
+        let oldest = self
+            .idle
+            .iter()
+            .flat_map(|(addr, conns)| conns.iter().enumerate().map(move |(i, c)| (addr, i, c)))
+            .min_by_key(|(_, _, c)| c.idle_since)
+            .map(|(addr, i, _)| (addr.clone(), i));
+        let Some((addr, index)) = oldest else {
+            return false;
+        };
+        if let Some(conns) = self.idle.get_mut(&addr) {
+            conns.remove(index);
+            if conns.is_empty() {
+                self.idle.remove(&addr);
+            }
+        }
+        true
+    }
+}
+
+/// Outbound TCP connections keyed by peer address.
+///
+/// `acquire` hands out an idle connection younger than `idle_ttl_secs` if
+/// there is one, otherwise opens a new one. Hand it back with `release` to
+/// make it reusable; dropping it (e.g. after an I/O error) closes it.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    config: PoolConfig,
+    state: Arc<Mutex<PoolState>>,
+}
+
+/// A connection checked out of a `ConnectionPool`
+pub struct PooledConnection {
+    peer_addr: String,
+    stream: Option<TcpStream>,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl PooledConnection {
+    /// Address this connection was acquired for
+    pub fn peer_addr(&self) -> &str {
+        &self.peer_addr
+    }
+
+    pub fn stream(&mut self) -> &mut TcpStream {
+        self.stream.as_mut().expect("stream present until released")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        // Still holding the stream means it was never released
+        if self.stream.is_some() {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.checked_out = state.checked_out.saturating_sub(1);
+        }
+    }
+}
+
+/// A `checked_out` slot held while a new connection is opened. Dropping it
+/// (a failed connect, or the `acquire` future being dropped) frees the slot.
+struct Reservation {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl Reservation {
+    /// Turn the slot into a checked-out connection
+    fn fill(self, peer_addr: &str, stream: TcpStream) -> PooledConnection {
+        let state = Arc::clone(&self.state);
+        state.lock().unwrap_or_else(|e| e.into_inner()).connects += 1;
+        std::mem::forget(self);
+        PooledConnection {
+            peer_addr: peer_addr.to_string(),
+            stream: Some(stream),
+            state,
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.checked_out = state.checked_out.saturating_sub(1);
+    }
+}
+
+impl ConnectionPool {
+    pub fn new(config: PoolConfig) -> Self {
+        let state = PoolState {
+            max_total: config.max_total,
+            ..Default::default()
+        };
+        Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Change the cap on open connections; connections already open are
+    /// kept, only new ones are refused while over it
+    pub fn set_max_total(&self, max_total: usize) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .max_total = max_total;
+    }
+
+    /// Check out a connection to `peer_addr` ("ip:port"), reusing an idle
+    /// one within the TTL. Fails with `ConnectionError` if `max_total`
+    /// connections are checked out.
+    pub async fn acquire(&self, peer_addr: &str) -> Result<PooledConnection, EdgeClawError> {
+        let addr = peer_addr
+            .parse::<std::net::SocketAddr>()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+
+        let reservation = {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| EdgeClawError::InternalError)?;
+            state.evict_expired(self.ttl(), Instant::now());
+            if let Some(idle) = state.idle.get_mut(peer_addr).and_then(Vec::pop) {
+                state.checked_out += 1;
+                state.reuses += 1;
+                return Ok(self.checked_out(peer_addr, idle.stream));
+            }
+            if state.checked_out + state.idle_count() >= state.max_total && !state.evict_oldest() {
+                return Err(EdgeClawError::ConnectionError);
+            }
+            // Reserve the slot while connecting without the lock
+            state.checked_out += 1;
+            Reservation {
+                state: Arc::clone(&self.state),
+            }
+        };
+
+        let timeout = Duration::from_secs(self.config.connect_timeout_secs);
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| EdgeClawError::TimeoutError)?
+            .map_err(|_| EdgeClawError::ConnectionError)?;
+        Ok(reservation.fill(peer_addr, stream))
+    }
+
+    /// Return a connection for reuse. It is closed instead if its peer
+    /// already has `max_idle_per_peer` idle connections.
+    pub fn release(&self, mut conn: PooledConnection) {
+        let Some(stream) = conn.stream.take() else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.checked_out = state.checked_out.saturating_sub(1);
+
+        let idle = state.idle.entry(conn.peer_addr.clone()).or_default();
+        if idle.len() < self.config.max_idle_per_peer {
+            idle.push(IdleConnection {
+                stream,
+                idle_since: Instant::now(),
+            });
+        } else if idle.is_empty() {
+            state.idle.remove(&conn.peer_addr);
+        }
+    }
+
+    /// Close every idle connection
+    pub fn clear_idle(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.idle.clear();
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.evict_expired(self.ttl(), Instant::now());
+        PoolStats {
+            idle: state.idle_count(),
+            checked_out: state.checked_out,
+            connects: state.connects,
+            reuses: state.reuses,
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.idle_ttl_secs)
+    }
+
+    fn checked_out(&self, peer_addr: &str, stream: TcpStream) -> PooledConnection {
+        PooledConnection {
+            peer_addr: peer_addr.to_string(),
+            stream: Some(stream),
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Listener that accepts and holds connections open
+    async fn spawn_listener() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_released_connection_reused_within_ttl() {
+        let addr = spawn_listener().await;
+        let pool = ConnectionPool::new(PoolConfig::default());
+
+        let conn = pool.acquire(&addr).await.unwrap();
+        let local = conn.stream.as_ref().unwrap().local_addr().unwrap();
+        pool.release(conn);
+        assert_eq!(pool.stats().idle, 1);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let conn = pool.acquire(&addr).await.unwrap();
+        assert_eq!(conn.stream.as_ref().unwrap().local_addr().unwrap(), local);
+        assert_eq!(pool.stats().reuses, 1);
+        assert_eq!(pool.stats().connects, 1);
+        pool.release(conn);
+
+        // Past the TTL the idle connection is closed and a new one opened
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert_eq!(pool.stats().idle, 0);
+        let conn = pool.acquire(&addr).await.unwrap();
+        assert_ne!(conn.stream.as_ref().unwrap().local_addr().unwrap(), local);
+        assert_eq!(pool.stats().connects, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_limits() {
+        let addr = spawn_listener().await;
+        let pool = ConnectionPool::new(PoolConfig {
+            max_idle_per_peer: 1,
+            max_total: 2,
+            ..Default::default()
+        });
+
+        let a = pool.acquire(&addr).await.unwrap();
+        let b = pool.acquire(&addr).await.unwrap();
+        assert!(matches!(
+            pool.acquire(&addr).await,
+            Err(EdgeClawError::ConnectionError)
+        ));
+
+        // Only one idle connection is kept per peer
+        pool.release(a);
+        pool.release(b);
+        assert_eq!(pool.stats().idle, 1);
+        assert_eq!(pool.stats().checked_out, 0);
+
+        // Dropping instead of releasing closes the connection
+        drop(pool.acquire(&addr).await.unwrap());
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                idle: 0,
+                checked_out: 0,
+                connects: 2,
+                reuses: 1,
+            }
+        );
+        assert!(matches!(
+            pool.acquire("not-an-address").await,
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_acquire_frees_its_slot() {
+        let addr = spawn_listener().await;
+        let pool = ConnectionPool::new(PoolConfig {
+            max_total: 1,
+            ..Default::default()
+        });
+
+        // Poll the connect once, then abandon it
+        let mut pending = Box::pin(pool.acquire(&addr));
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let _ = std::future::Future::poll(pending.as_mut(), &mut cx);
+        drop(pending);
+        assert_eq!(pool.stats().checked_out, 0);
+
+        // A refused connect frees it too
+        let refused = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        assert!(pool.acquire(&refused).await.is_err());
+        let conn = pool.acquire(&addr).await.unwrap();
+        assert_eq!(pool.stats().checked_out, 1);
+        pool.release(conn);
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::ecnp::{EcnpCodec, EcnpMessage};
 use crate::error::EdgeClawError;
 
 // ─── ECNP v1.1 Message Types ───
@@ -16,6 +17,18 @@ pub enum MessageType {
     Error = 0x06,
 }
 
+impl MessageType {
+    /// Every message type this build understands
+    pub const ALL: [MessageType; 6] = [
+        MessageType::Handshake,
+        MessageType::Data,
+        MessageType::Control,
+        MessageType::Heartbeat,
+        MessageType::Ack,
+        MessageType::Error,
+    ];
+}
+
 impl TryFrom<u8> for MessageType {
     type Error = EdgeClawError;
 
@@ -32,6 +45,86 @@ impl TryFrom<u8> for MessageType {
     }
 }
 
+// ─── Device types ───
+
+/// Kind of device an agent runs on.
+///
+/// Parsing is case-insensitive and never fails: strings outside the known
+/// set are kept verbatim (trimmed) as `Other`, so newer peers still
+/// interoperate but callers can tell a typo like `"phne"` from a real type.
+/// Serialized as its string form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceType {
+    Smartphone,
+    Tablet,
+    Pc,
+    Server,
+    Iot,
+    Other(String),
+}
+
+impl DeviceType {
+    /// Every device type with a canonical name
+    pub const KNOWN: [DeviceType; 5] = [
+        DeviceType::Smartphone,
+        DeviceType::Tablet,
+        DeviceType::Pc,
+        DeviceType::Server,
+        DeviceType::Iot,
+    ];
+
+    pub fn parse(s: &str) -> Self {
+        let s = s.trim();
+        Self::KNOWN
+            .into_iter()
+            .find(|known| known.as_str().eq_ignore_ascii_case(s))
+            .unwrap_or_else(|| DeviceType::Other(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            DeviceType::Smartphone => "smartphone",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Pc => "pc",
+            DeviceType::Server => "server",
+            DeviceType::Iot => "iot",
+            DeviceType::Other(s) => s,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, DeviceType::Other(_))
+    }
+
+    /// Canonical string for a free-form device type; unknown types are
+    /// logged and passed through trimmed
+    pub fn normalize(s: &str) -> String {
+        let device_type = Self::parse(s);
+        if !device_type.is_known() {
+            tracing::warn!(device_type = %s, "Unknown device type");
+        }
+        device_type.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for DeviceType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|s| DeviceType::parse(&s))
+    }
+}
+
 // ─── ECM (Edge Capability Manifest) ───
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,7 +132,11 @@ pub struct EcmPayload {
     pub device_id: String,
     pub device_type: String,
     pub capabilities: Vec<String>,
+    /// Empty when sent by peers that predate the field
+    #[serde(default)]
     pub os: String,
+    /// Empty when sent by peers that predate the field
+    #[serde(default)]
     pub version: String,
 }
 
@@ -70,21 +167,246 @@ pub struct HeartbeatPayload {
     pub active_sessions: u32,
 }
 
+// ─── Session negotiation ───
+
+/// Only cipher suite currently offered for sessions
+pub const SESSION_CIPHER_AES256GCM: &str = "aes-256-gcm";
+
+/// Initiator → responder: ephemeral X25519 key plus proposed parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInit {
+    pub init_id: String,
+    pub device_id: String,
+    /// Hex-encoded ephemeral X25519 public key
+    pub ephemeral_public_key: String,
+    /// Hex-encoded Ed25519 identity key that signed the ephemeral key
+    pub identity_public_key: String,
+    pub cipher: String,
+    pub duration_secs: u64,
+    /// Hex-encoded Ed25519 signature (see `session_key_signing_bytes`)
+    pub signature: String,
+}
+
+/// Responder → initiator: ephemeral key plus the accepted parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionAccept {
+    pub init_id: String,
+    pub device_id: String,
+    pub ephemeral_public_key: String,
+    pub identity_public_key: String,
+    pub cipher: String,
+    pub duration_secs: u64,
+    pub signature: String,
+}
+
+/// Session negotiation messages, carried in ECNP Handshake frames
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionNegotiation {
+    SessionInit(SessionInit),
+    SessionAccept(SessionAccept),
+}
+
+impl SessionNegotiation {
+    /// Encode as an ECNP Handshake frame
+    pub fn encode(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let json = serde_json::to_vec(self)?;
+        EcnpCodec::encode(MessageType::Handshake, &json)
+    }
+
+    /// Decode from an ECNP Handshake frame
+    pub fn decode(frame: &[u8]) -> Result<Self, EdgeClawError> {
+        Self::from_message(&EcnpCodec::decode(frame)?)
+    }
+
+    /// Decode from an already decoded ECNP Handshake message
+    pub fn from_message(msg: &EcnpMessage) -> Result<Self, EdgeClawError> {
+        if msg.msg_type != MessageType::Handshake as u8 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        serde_json::from_slice(&msg.payload).map_err(EdgeClawError::from)
+    }
+}
+
+// ─── Control messages ───
+
+const CONTROL_CONTEXT: &[u8] = b"edgeclaw-control-v1:";
+
+/// Policy updates pushed to peers, carried in ECNP Control frames inside a
+/// `SignedControlMessage`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    /// `capability` is no longer granted to `affected_roles`; an empty
+    /// list means it is disabled for every role
+    CapabilityRevoked {
+        capability: String,
+        affected_roles: Vec<String>,
+    },
+}
+
+/// A `ControlMessage` signed by the device that issued it.
+///
+/// Receivers only apply control messages whose signer is a known peer with
+/// a pinned identity key, and only fresh ones: `issued_at` must be recent
+/// and `sequence` above the last one seen from that signer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedControlMessage {
+    /// Device ID of the signer
+    pub signer_id: String,
+    /// RFC 3339 issue time
+    pub issued_at: String,
+    /// Strictly increasing per signer, across restarts too
+    pub sequence: u64,
+    pub message: ControlMessage,
+    /// Hex-encoded Ed25519 signature over `signing_bytes()`
+    pub signature: String,
+}
+
+impl SignedControlMessage {
+    /// Bytes the signer signs: every field except the signature
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let mut bytes = CONTROL_CONTEXT.to_vec();
+        serde_json::to_writer(
+            &mut bytes,
+            &(
+                &self.signer_id,
+                &self.issued_at,
+                self.sequence,
+                &self.message,
+            ),
+        )?;
+        Ok(bytes)
+    }
+
+    /// Check the signature against the signer's key (`CryptoError` if it
+    /// does not match)
+    pub fn verify(&self, signer_key: &ed25519_dalek::VerifyingKey) -> Result<(), EdgeClawError> {
+        use ed25519_dalek::Verifier;
+
+        let signature = hex::decode(&self.signature).map_err(|_| EdgeClawError::CryptoError)?;
+        let signature = ed25519_dalek::Signature::from_slice(&signature)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        signer_key
+            .verify(&self.signing_bytes()?, &signature)
+            .map_err(|_| EdgeClawError::CryptoError)
+    }
+
+    /// Encode as an ECNP Control frame
+    pub fn encode(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let json = serde_json::to_vec(self)?;
+        EcnpCodec::encode(MessageType::Control, &json)
+    }
+
+    /// Decode from an ECNP Control frame
+    pub fn decode(frame: &[u8]) -> Result<Self, EdgeClawError> {
+        let msg = EcnpCodec::decode(frame)?;
+        if msg.msg_type != MessageType::Control as u8 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        serde_json::from_slice(&msg.payload).map_err(EdgeClawError::from)
+    }
+}
+
+impl ControlMessage {
+    /// Encode as an ECNP Control frame
+    pub fn encode(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let json = serde_json::to_vec(self)?;
+        EcnpCodec::encode(MessageType::Control, &json)
+    }
+
+    /// Decode from an ECNP Control frame
+    pub fn decode(frame: &[u8]) -> Result<Self, EdgeClawError> {
+        let msg = EcnpCodec::decode(frame)?;
+        if msg.msg_type != MessageType::Control as u8 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        serde_json::from_slice(&msg.payload).map_err(EdgeClawError::from)
+    }
+}
+
+/// Bytes each side signs: a direction tag, the init id, the signer's
+/// device ID and ephemeral key, and the session parameters. Variable-length
+/// fields are length-prefixed so no two inputs share an encoding.
+pub fn session_key_signing_bytes(
+    tag: &str,
+    init_id: &str,
+    device_id: &str,
+    ephemeral_public_key: &[u8],
+    cipher: &str,
+    duration_secs: u64,
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for field in [
+        tag.as_bytes(),
+        init_id.as_bytes(),
+        device_id.as_bytes(),
+        ephemeral_public_key,
+        cipher.as_bytes(),
+    ] {
+        bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(field);
+    }
+    bytes.extend_from_slice(&duration_secs.to_be_bytes());
+    bytes
+}
+
+/// Verify a hex signature against a hex Ed25519 identity key
+pub fn verify_negotiation_signature(
+    identity_public_key: &str,
+    signed_bytes: &[u8],
+    signature: &str,
+) -> Result<(), EdgeClawError> {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let key: [u8; 32] = hex::decode(identity_public_key)
+        .map_err(|_| EdgeClawError::InvalidParameter)?
+        .try_into()
+        .map_err(|_| EdgeClawError::InvalidParameter)?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| EdgeClawError::CryptoError)?;
+    let signature = hex::decode(signature).map_err(|_| EdgeClawError::InvalidParameter)?;
+    let signature = Signature::from_slice(&signature).map_err(|_| EdgeClawError::CryptoError)?;
+    key.verify(signed_bytes, &signature)
+        .map_err(|_| EdgeClawError::CryptoError)
+}
+
+/// Decode a hex-encoded key exchange public key. Its length depends on
+/// the scheme, so only emptiness is checked here.
+pub fn decode_kex_key_hex(key_hex: &str) -> Result<Vec<u8>, EdgeClawError> {
+    match hex::decode(key_hex) {
+        Ok(key) if !key.is_empty() => Ok(key),
+        _ => Err(EdgeClawError::InvalidParameter),
+    }
+}
+
+/// Decode a hex-encoded 32-byte key
+pub fn decode_key_hex(key_hex: &str) -> Result<[u8; 32], EdgeClawError> {
+    hex::decode(key_hex)
+        .map_err(|_| EdgeClawError::InvalidParameter)?
+        .try_into()
+        .map_err(|_| EdgeClawError::InvalidParameter)
+}
+
 // ─── Protocol message constructors ───
 
+/// Build an ECM announcement for this device
+pub fn ecm_payload(device_id: &str, device_type: &str, capabilities: Vec<String>) -> EcmPayload {
+    EcmPayload {
+        device_id: device_id.to_string(),
+        device_type: DeviceType::normalize(device_type),
+        capabilities,
+        os: std::env::consts::OS.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
 /// Create an ECM announcement JSON string
 pub fn create_ecm(
     device_id: &str,
     device_type: &str,
     capabilities: Vec<String>,
 ) -> Result<String, EdgeClawError> {
-    let ecm = EcmPayload {
-        device_id: device_id.to_string(),
-        device_type: device_type.to_string(),
-        capabilities,
-        os: std::env::consts::OS.to_string(),
-        version: env!("CARGO_PKG_VERSION").to_string(),
-    };
+    let ecm = ecm_payload(device_id, device_type, capabilities);
     serde_json::to_string(&ecm).map_err(EdgeClawError::from)
 }
 
@@ -132,6 +454,50 @@ pub fn parse_ecm(json: &str) -> Result<EcmPayload, EdgeClawError> {
     serde_json::from_str(json).map_err(EdgeClawError::from)
 }
 
+/// Layout version leading every binary ECM
+const ECM_BINARY_VERSION: u8 = 1;
+
+/// Binary ECM on the wire: a CBOR array of the layout version and the
+/// fields in declaration order
+type EcmBinary = (u8, String, String, Vec<String>, String, String);
+
+/// Encode an ECM compactly for constrained transports (BLE advertisements).
+///
+/// Field names are dropped and strings are length-prefixed rather than
+/// quoted, so a typical announcement is about 40% smaller than the JSON
+/// form (93 vs 162 bytes for a UUID device ID and three capabilities).
+/// TCP peers keep using JSON.
+pub fn encode_ecm_binary(ecm: &EcmPayload) -> Vec<u8> {
+    let wire: EcmBinary = (
+        ECM_BINARY_VERSION,
+        ecm.device_id.clone(),
+        ecm.device_type.clone(),
+        ecm.capabilities.clone(),
+        ecm.os.clone(),
+        ecm.version.clone(),
+    );
+    let mut out = Vec::new();
+    // Writing to a Vec cannot fail
+    let _ = ciborium::into_writer(&wire, &mut out);
+    out
+}
+
+/// Decode an ECM produced by `encode_ecm_binary`
+pub fn decode_ecm_binary(data: &[u8]) -> Result<EcmPayload, EdgeClawError> {
+    let (layout, device_id, device_type, capabilities, os, version): EcmBinary =
+        ciborium::from_reader(data).map_err(|_| EdgeClawError::SerializationError)?;
+    if layout != ECM_BINARY_VERSION {
+        return Err(EdgeClawError::SerializationError);
+    }
+    Ok(EcmPayload {
+        device_id,
+        device_type,
+        capabilities,
+        os,
+        version,
+    })
+}
+
 /// Parse an EAP from JSON
 pub fn parse_eap(json: &str) -> Result<EapPayload, EdgeClawError> {
     serde_json::from_str(json).map_err(EdgeClawError::from)
@@ -160,6 +526,30 @@ mod tests {
         assert_eq!(parsed.capabilities.len(), 2);
     }
 
+    #[test]
+    fn test_ecm_binary_roundtrip_is_smaller() {
+        let ecm = EcmPayload {
+            device_id: "3f2b8c1e-9a4d-4e7f-b6c5-0d1e2f3a4b5c".into(),
+            device_type: "smartphone".into(),
+            capabilities: vec!["status".into(), "file_read".into(), "heartbeat".into()],
+            os: "android".into(),
+            version: "1.4.0".into(),
+        };
+        let json = serde_json::to_vec(&ecm).unwrap();
+        let binary = encode_ecm_binary(&ecm);
+        assert!(binary.len() * 10 < json.len() * 7);
+
+        let decoded = decode_ecm_binary(&binary).unwrap();
+        assert_eq!(decoded.device_id, ecm.device_id);
+        assert_eq!(decoded.device_type, ecm.device_type);
+        assert_eq!(decoded.capabilities, ecm.capabilities);
+        assert_eq!(decoded.os, ecm.os);
+        assert_eq!(decoded.version, ecm.version);
+
+        assert!(decode_ecm_binary(&json).is_err());
+        assert!(decode_ecm_binary(&binary[..binary.len() - 1]).is_err());
+    }
+
     #[test]
     fn test_eap_roundtrip() {
         let actions = vec![(
@@ -186,6 +576,67 @@ mod tests {
         assert_eq!(parsed.active_sessions, 3);
     }
 
+    #[test]
+    fn test_session_negotiation_frame_roundtrip() {
+        let init = SessionNegotiation::SessionInit(SessionInit {
+            init_id: "init-1".into(),
+            device_id: "dev-a".into(),
+            ephemeral_public_key: "aa".repeat(32),
+            identity_public_key: "bb".repeat(32),
+            cipher: SESSION_CIPHER_AES256GCM.into(),
+            duration_secs: 600,
+            signature: "cc".repeat(64),
+        });
+        let frame = init.encode().unwrap();
+        assert_eq!(frame[1], MessageType::Handshake as u8);
+
+        match SessionNegotiation::decode(&frame).unwrap() {
+            SessionNegotiation::SessionInit(i) => assert_eq!(i.duration_secs, 600),
+            _ => panic!("Expected SessionInit"),
+        }
+
+        let data = EcnpCodec::encode(MessageType::Data, b"{}").unwrap();
+        assert!(SessionNegotiation::decode(&data).is_err());
+    }
+
+    #[test]
+    fn test_control_message_frame_roundtrip() {
+        let msg = ControlMessage::CapabilityRevoked {
+            capability: "shell_exec".into(),
+            affected_roles: vec!["admin".into()],
+        };
+        let frame = msg.encode().unwrap();
+        assert_eq!(frame[1], MessageType::Control as u8);
+        assert_eq!(ControlMessage::decode(&frame).unwrap(), msg);
+
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "capability_revoked");
+
+        let handshake = EcnpCodec::encode(MessageType::Handshake, b"{}").unwrap();
+        assert!(ControlMessage::decode(&handshake).is_err());
+    }
+
+    #[test]
+    fn test_device_type_parse() {
+        for known in DeviceType::KNOWN {
+            assert_eq!(DeviceType::parse(known.as_str()), known);
+        }
+        assert_eq!(DeviceType::parse(" PC "), DeviceType::Pc);
+        assert_eq!(DeviceType::parse("SmartPhone"), DeviceType::Smartphone);
+
+        let typo = DeviceType::parse("phne");
+        assert_eq!(typo, DeviceType::Other("phne".into()));
+        assert!(!typo.is_known());
+        assert_eq!(DeviceType::normalize(" Tablet"), "tablet");
+        assert_eq!(DeviceType::normalize("fridge "), "fridge");
+
+        // Serialized as a plain string either way
+        assert_eq!(serde_json::to_string(&DeviceType::Iot).unwrap(), r#""iot""#);
+        assert_eq!(serde_json::to_string(&typo).unwrap(), r#""phne""#);
+        let parsed: DeviceType = serde_json::from_str(r#""Server""#).unwrap();
+        assert_eq!(parsed, DeviceType::Server);
+    }
+
     #[test]
     fn test_message_type_conversion() {
         assert_eq!(MessageType::try_from(0x01).unwrap(), MessageType::Handshake);
@@ -1,12 +1,34 @@
+use crate::error::EdgeClawError;
+use crate::kex::{self, KeyExchange, KeyExchangeKeypair};
+use crate::keystore::KeyProvider;
+use crate::time::{self, TimeSource};
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
 use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
 use sha2::Sha256;
-use x25519_dalek::{PublicKey, StaticSecret};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use subtle::ConstantTimeEq;
 
-use crate::error::EdgeClawError;
+/// HMAC label for `SessionManager::session_confirmation`
+const SESSION_CONFIRMATION_LABEL: &[u8] = b"edgeclaw-key-confirm-v1";
+
+/// Plaintexts of the encrypted key-agreement probe and its echo. They
+/// differ so a probe reflected back unchanged is not taken as an echo.
+const SESSION_PROBE: &[u8] = b"edgeclaw-session-probe-v1";
+const SESSION_PROBE_ECHO: &[u8] = b"edgeclaw-session-echo-v1";
+
+/// Chunked stream framing: `stream_id (16) | index (u32 BE) | final (u8) |
+/// nonce (12) | ciphertext + tag`. The first 21 bytes are bound into each
+/// chunk's AAD, so chunks can't be reordered, spliced across streams or
+/// have the final flag moved.
+const STREAM_LABEL: &[u8] = b"edgeclaw-stream-v1";
+const STREAM_AAD_SIZE: usize = 16 + 4 + 1;
+const STREAM_HEADER_SIZE: usize = STREAM_AAD_SIZE + 12;
 
 /// Session information exposed via UniFFI
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -18,6 +40,57 @@ pub struct SessionInfo {
     pub expires_at: String,
     pub messages_sent: u64,
     pub messages_received: u64,
+    /// "initiator" or "responder"
+    pub role: String,
+}
+
+impl SessionInfo {
+    /// One-line description for diagnostics, as of `clock`'s current time
+    /// (pass the manager's clock so ages follow an injected time source)
+    pub fn summary(&self, clock: &dyn TimeSource) -> String {
+        self.summary_at(clock.now())
+    }
+
+    /// One-line description as of `now`, e.g.
+    /// `3f2a9c1e peer=laptop role=initiator state=established age=5m12s
+    /// expires_in=54m48s sent=3 received=2 cipher=aes-256-gcm`
+    pub fn summary_at(&self, now: chrono::DateTime<chrono::Utc>) -> String {
+        let since = |ts: &str| {
+            chrono::DateTime::parse_from_rfc3339(ts)
+                .map(|t| now.signed_duration_since(t).num_seconds())
+                .ok()
+        };
+        let age = since(&self.created_at).map_or_else(|| "?".into(), format_secs);
+        let expires_in = match since(&self.expires_at) {
+            Some(elapsed) if elapsed >= 0 => "expired".to_string(),
+            Some(elapsed) => format_secs(-elapsed),
+            None => "?".into(),
+        };
+        let short_id: String = self.session_id.chars().take(8).collect();
+        format!(
+            "{} peer={} role={} state={} age={} expires_in={} sent={} received={} cipher={}",
+            short_id,
+            self.peer_id,
+            self.role,
+            self.state,
+            age,
+            expires_in,
+            self.messages_sent,
+            self.messages_received,
+            crate::protocol::SESSION_CIPHER_AES256GCM,
+        )
+    }
+}
+
+/// Compact duration: `45s`, `5m12s`, `2h03m`, `3d04h`
+fn format_secs(secs: i64) -> String {
+    let secs = secs.max(0);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        3600..=86_399 => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{:02}h", secs / 86_400, secs % 86_400 / 3600),
+    }
 }
 
 /// Internal session state
@@ -38,20 +111,211 @@ impl std::fmt::Display for SessionState {
     }
 }
 
+/// Which side of the key exchange this device played
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionRole {
+    Initiator,
+    Responder,
+}
+
+impl SessionRole {
+    /// Nonce prefix for this side, so both directions never share a nonce
+    /// under the same key
+    pub(crate) fn nonce_prefix(self) -> [u8; 4] {
+        match self {
+            SessionRole::Initiator => *b"INIT",
+            SessionRole::Responder => *b"RESP",
+        }
+    }
+}
+
+impl std::fmt::Display for SessionRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionRole::Initiator => write!(f, "initiator"),
+            SessionRole::Responder => write!(f, "responder"),
+        }
+    }
+}
+
+/// How AES-GCM nonces are generated for outgoing messages.
+///
+/// Either way the nonce is prepended to the ciphertext, so the receiver
+/// does not need to know which mode the sender uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceMode {
+    /// Role prefix + 64-bit message counter. Never repeats within a session
+    /// and costs no randomness, but reveals the message count and could
+    /// repeat if a session key were reused after a restart that lost the
+    /// counter.
+    #[default]
+    Counter,
+    /// 12 random bytes per message. Immune to lost counters; the birthday
+    /// bound makes a collision negligible below ~2^32 messages per key.
+    Random,
+}
+
+/// Per-session parameters chosen at creation
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SessionOptions {
+    /// Lifetime in seconds (None = the manager's default)
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// Nonce generation for messages we encrypt
+    #[serde(default)]
+    pub nonce_mode: NonceMode,
+    /// Expire the session after this many seconds without an encrypt or
+    /// decrypt (None = the manager's default)
+    #[serde(default)]
+    pub max_idle_secs: Option<u64>,
+}
+
+/// Exportable session metadata.
+///
+/// Keys and nonce counters are never exported: a restored copy could reuse
+/// nonces under a key still live elsewhere. Imported sessions come back
+/// expired and must be re-keyed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionSnapshot {
+    pub session_id: String,
+    pub peer_id: String,
+    pub role: SessionRole,
+    #[serde(default)]
+    pub nonce_mode: NonceMode,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// Absent in older snapshots; `created_at` is used instead
+    #[serde(default)]
+    pub last_activity: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub max_idle_secs: Option<u64>,
+}
+
+/// Which copy survived a session ID collision during import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// The session already held, or listed earlier in the same import
+    /// and expiring no earlier, was kept
+    KeptExisting,
+    /// A later duplicate within the same import expires later and
+    /// replaced the earlier entry
+    Replaced,
+}
+
+/// A session ID that appeared more than once during import
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionConflict {
+    pub session_id: String,
+    pub resolution: ConflictResolution,
+}
+
+/// Outcome of `import_sessions` / `merge_sessions`
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionImportReport {
+    /// Sessions held afterwards that came from the import
+    pub imported: u32,
+    pub conflicts: Vec<SessionConflict>,
+}
+
 /// Secure session with X25519 ECDH + AES-256-GCM
 struct Session {
     session_id: String,
     peer_id: String,
     state: SessionState,
+    role: SessionRole,
     session_key: [u8; 32],
+    /// Our side of the exchange while `Initiating`; cleared once the key
+    /// is derived
+    pending_key: Option<PendingKey>,
     nonce_counter: u64,
+    nonce_mode: NonceMode,
     created_at: chrono::DateTime<chrono::Utc>,
     expires_at: chrono::DateTime<chrono::Utc>,
+    /// Last successful encrypt or decrypt (creation until then)
+    last_activity: chrono::DateTime<chrono::Utc>,
+    max_idle: Option<chrono::Duration>,
     messages_sent: u64,
     messages_received: u64,
+    /// Most recent message metadata, oldest first
+    #[cfg(feature = "session-transcript")]
+    transcript: std::collections::VecDeque<TranscriptEntry>,
+}
+
+/// Messages kept per session transcript
+#[cfg(feature = "session-transcript")]
+const TRANSCRIPT_CAPACITY: usize = 256;
+
+/// Direction of a transcript entry
+#[cfg(feature = "session-transcript")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptDirection {
+    Sent,
+    Received,
+}
+
+/// Metadata for one message through a session, for debugging interop
+/// problems. Never holds plaintext or key material; the nonce is sent in
+/// the clear anyway.
+#[cfg(feature = "session-transcript")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TranscriptEntry {
+    pub direction: TranscriptDirection,
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub nonce_hex: String,
+    /// Counter part of the nonce when the sender used `NonceMode::Counter`
+    /// (for received messages: when the nonce carries the peer's role
+    /// prefix, since the peer's nonce mode is not known here)
+    pub nonce_counter: Option<u64>,
+    /// Size on the wire, including nonce and tag
+    pub ciphertext_len: usize,
+    /// False for a received message that failed to authenticate
+    pub ok: bool,
 }
 
 impl Session {
+    #[cfg(feature = "session-transcript")]
+    fn record(
+        &mut self,
+        direction: TranscriptDirection,
+        at: chrono::DateTime<chrono::Utc>,
+        frame: &[u8],
+        ok: bool,
+    ) {
+        let nonce = &frame[..12];
+        let counter_nonce = match direction {
+            TranscriptDirection::Sent => self.nonce_mode == NonceMode::Counter,
+            TranscriptDirection::Received => {
+                let peer = match self.role {
+                    SessionRole::Initiator => SessionRole::Responder,
+                    SessionRole::Responder => SessionRole::Initiator,
+                };
+                nonce[..4] == peer.nonce_prefix()
+            }
+        };
+        let nonce_counter = counter_nonce.then(|| {
+            let mut counter = [0u8; 8];
+            counter.copy_from_slice(&nonce[4..12]);
+            u64::from_be_bytes(counter)
+        });
+        if self.transcript.len() == TRANSCRIPT_CAPACITY {
+            self.transcript.pop_front();
+        }
+        self.transcript.push_back(TranscriptEntry {
+            direction,
+            at,
+            nonce_hex: hex::encode(nonce),
+            nonce_counter,
+            ciphertext_len: frame.len(),
+            ok,
+        });
+    }
+
     fn to_info(&self) -> SessionInfo {
         SessionInfo {
             session_id: self.session_id.clone(),
@@ -61,18 +325,190 @@ impl Session {
             expires_at: self.expires_at.to_rfc3339(),
             messages_sent: self.messages_sent,
             messages_received: self.messages_received,
+            role: self.role.to_string(),
+        }
+    }
+
+    /// Past its absolute lifetime, or idle for longer than its idle limit
+    fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.state == SessionState::Expired
+            || now >= self.expires_at
+            || self
+                .max_idle
+                .is_some_and(|max_idle| now - self.last_activity > max_idle)
+    }
+
+    /// Nonce for the next outgoing message; advances the counter
+    fn next_nonce(&mut self) -> [u8; 12] {
+        let mut nonce_bytes = [0u8; 12];
+        match self.nonce_mode {
+            // Role prefix + counter (12 bytes)
+            NonceMode::Counter => {
+                nonce_bytes[..4].copy_from_slice(&self.role.nonce_prefix());
+                nonce_bytes[4..12].copy_from_slice(&self.nonce_counter.to_be_bytes());
+            }
+            NonceMode::Random => rand::thread_rng().fill_bytes(&mut nonce_bytes),
+        }
+        self.nonce_counter += 1;
+        nonce_bytes
+    }
+
+    /// Fail unless the session key exists and the session is still valid
+    fn ensure_usable(&mut self, now: chrono::DateTime<chrono::Utc>) -> Result<(), EdgeClawError> {
+        if self.state == SessionState::Initiating {
+            return Err(EdgeClawError::SessionNotEstablished);
         }
+        if self.is_expired(now) {
+            self.state = SessionState::Expired;
+            return Err(EdgeClawError::SessionExpired);
+        }
+        Ok(())
+    }
+}
+
+/// Our key material for a session still waiting on the peer's public key
+enum PendingKey {
+    Secret(Vec<u8>),
+    Provider(Arc<dyn KeyProvider>),
+}
+
+impl Drop for PendingKey {
+    fn drop(&mut self) {
+        if let PendingKey::Secret(secret) = self {
+            kex::zeroize(secret);
+        }
+    }
+}
+
+/// Derive the symmetric session key through `kex`
+fn derive_session_key(
+    kex: &dyn KeyExchange,
+    local_secret: &[u8],
+    remote_public: &[u8],
+) -> Result<[u8; 32], EdgeClawError> {
+    session_key_from_shared(&kex.shared_secret(local_secret, remote_public)?)
+}
+
+/// Derive the session key with the ECDH done by a `KeyProvider`
+fn derive_session_key_via(
+    provider: &dyn KeyProvider,
+    remote_public: &[u8],
+) -> Result<[u8; 32], EdgeClawError> {
+    let remote_public: &[u8; 32] = remote_public
+        .try_into()
+        .map_err(|_| EdgeClawError::InvalidParameter)?;
+    session_key_from_shared(&provider.diffie_hellman(remote_public)?)
+}
+
+fn session_key_from_shared(shared_secret: &[u8]) -> Result<[u8; 32], EdgeClawError> {
+    // A low-order remote point yields an all-zero secret regardless of our
+    // key; refuse it instead of deriving a key an attacker can predict.
+    // Folded without early exit so the check doesn't leak timing.
+    if shared_secret.iter().fold(0u8, |acc, b| acc | b) == 0 {
+        tracing::warn!("Rejected non-contributory key exchange (low-order public key)");
+        return Err(EdgeClawError::CryptoError);
+    }
+    // Derive session key via HKDF-SHA256 (role-independent, so both
+    // sides arrive at the same key)
+    let hk = Hkdf::<Sha256>::new(None, shared_secret);
+    let mut session_key = [0u8; 32];
+    hk.expand(b"edgeclaw-session-v1", &mut session_key)
+        .map_err(|_| EdgeClawError::CryptoError)?;
+    Ok(session_key)
+}
+
+/// Restore `snapshots` into `sessions` as expired, keyless sessions,
+/// resolving duplicate IDs by later expiry (ties keep the earlier entry).
+/// Fails without touching `sessions` if any snapshot is invalid.
+fn merge_into(
+    sessions: &mut std::collections::HashMap<String, Mutex<Session>>,
+    snapshots: Vec<SessionSnapshot>,
+) -> Result<SessionImportReport, EdgeClawError> {
+    let mut restored = Vec::with_capacity(snapshots.len());
+    for snap in snapshots {
+        let max_idle = snap.max_idle_secs.map(idle_duration).transpose()?;
+        restored.push(Session {
+            session_id: snap.session_id,
+            peer_id: snap.peer_id,
+            state: SessionState::Expired,
+            role: snap.role,
+            session_key: [0u8; 32],
+            pending_key: None,
+            nonce_counter: 0,
+            nonce_mode: snap.nonce_mode,
+            created_at: snap.created_at,
+            expires_at: snap.expires_at,
+            last_activity: snap.last_activity.unwrap_or(snap.created_at),
+            max_idle,
+            messages_sent: snap.messages_sent,
+            messages_received: snap.messages_received,
+            #[cfg(feature = "session-transcript")]
+            transcript: std::collections::VecDeque::new(),
+        });
+    }
+
+    let mut report = SessionImportReport::default();
+    for session in restored {
+        if let Some(existing) = sessions.get(&session.session_id) {
+            let resolution = resolve_conflict(&lock_session(existing), &session);
+            tracing::warn!(session_id = %session.session_id, ?resolution, "Duplicate session ID in import");
+            report.conflicts.push(SessionConflict {
+                session_id: session.session_id.clone(),
+                resolution,
+            });
+            if resolution == ConflictResolution::KeptExisting {
+                continue;
+            }
+        }
+        sessions.insert(session.session_id.clone(), Mutex::new(session));
     }
+    report.imported = sessions.len() as u32;
+    Ok(report)
+}
 
-    fn is_expired(&self) -> bool {
-        chrono::Utc::now() >= self.expires_at
+/// The later expiry wins; a tie keeps `existing`
+fn resolve_conflict(existing: &Session, incoming: &Session) -> ConflictResolution {
+    if existing.expires_at >= incoming.expires_at {
+        ConflictResolution::KeptExisting
+    } else {
+        ConflictResolution::Replaced
     }
 }
 
-/// Session manager: handles key exchange, session creation, encrypt/decrypt
+/// Validate an idle limit in seconds
+fn idle_duration(secs: u64) -> Result<chrono::Duration, EdgeClawError> {
+    i64::try_from(secs)
+        .ok()
+        .filter(|s| *s > 0)
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or(EdgeClawError::InvalidParameter)
+}
+
+/// Lock a session, recovering the state if a holder panicked
+fn lock_session(session: &Mutex<Session>) -> MutexGuard<'_, Session> {
+    session.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Session manager: handles key exchange, session creation, encrypt/decrypt.
+///
+/// Each session sits behind its own mutex, so `encrypt`/`decrypt` take
+/// `&self`: a shared (read) borrow of the manager lets different sessions
+/// run crypto in parallel, while one session's nonce counter is still
+/// advanced by a single caller at a time. Adding or removing sessions needs
+/// `&mut self`.
 pub struct SessionManager {
-    sessions: std::collections::HashMap<String, Session>,
+    sessions: std::collections::HashMap<String, Mutex<Session>>,
     session_duration_secs: i64,
+    /// Idle limit for sessions that don't set their own (None = no limit)
+    max_idle: Option<chrono::Duration>,
+    /// Cap on held sessions, pending ones included (0 = unlimited)
+    max_active_sessions: u32,
+    /// Plaintext bytes through `encrypt` / out of `decrypt`
+    bytes_encrypted: AtomicU64,
+    bytes_decrypted: AtomicU64,
+    /// Key agreement for sessions created from raw secrets
+    key_exchange: Arc<dyn KeyExchange>,
+    clock: Arc<dyn TimeSource>,
 }
 
 impl Default for SessionManager {
@@ -86,72 +522,438 @@ impl SessionManager {
         Self {
             sessions: std::collections::HashMap::new(),
             session_duration_secs: 3600, // 1 hour default
+            max_idle: None,
+            max_active_sessions: 0,
+            bytes_encrypted: AtomicU64::new(0),
+            bytes_decrypted: AtomicU64::new(0),
+            key_exchange: kex::x25519(),
+            clock: time::system(),
         }
     }
 
-    /// Create a new session via X25519 ECDH key exchange
+    /// Read timestamps and check expiry against `clock`
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.clock = clock;
+    }
+
+    /// Use `kex` for sessions created from raw secrets from now on.
+    /// `KeyProvider` sessions keep using the provider's own exchange.
+    pub fn set_key_exchange(&mut self, kex: Arc<dyn KeyExchange>) {
+        self.key_exchange = kex;
+    }
+
+    /// Fresh ephemeral key pair from the key exchange in use
+    pub fn generate_keypair(&self) -> KeyExchangeKeypair {
+        self.key_exchange.generate_keypair()
+    }
+
+    /// Name of the key exchange in use
+    pub fn key_exchange_algorithm(&self) -> &'static str {
+        self.key_exchange.algorithm()
+    }
+
+    /// Create a new session via the configured key exchange (as initiator)
     pub fn create_session(
         &mut self,
         peer_id: &str,
-        local_secret: &[u8; 32],
-        remote_public: &[u8; 32],
+        local_secret: &[u8],
+        remote_public: &[u8],
     ) -> Result<SessionInfo, EdgeClawError> {
-        // Perform X25519 ECDH
-        let secret = StaticSecret::from(*local_secret);
-        let remote_pk = PublicKey::from(*remote_public);
-        let shared_secret = secret.diffie_hellman(&remote_pk);
-
-        // Derive session key via HKDF-SHA256
-        let hk = Hkdf::<Sha256>::new(None, shared_secret.as_bytes());
-        let mut session_key = [0u8; 32];
-        hk.expand(b"edgeclaw-session-v1", &mut session_key)
-            .map_err(|_| EdgeClawError::CryptoError)?;
+        self.create_session_with(
+            peer_id,
+            local_secret,
+            remote_public,
+            &SessionOptions::default(),
+        )
+    }
+
+    /// Create a session as initiator with explicit options
+    pub fn create_session_with(
+        &mut self,
+        peer_id: &str,
+        local_secret: &[u8],
+        remote_public: &[u8],
+        options: &SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key =
+            derive_session_key(self.key_exchange.as_ref(), local_secret, remote_public)?;
+        self.establish(peer_id, session_key, SessionRole::Initiator, options)
+    }
+
+    /// Create a session as initiator, with the ECDH done by `provider`
+    pub fn create_session_via(
+        &mut self,
+        peer_id: &str,
+        provider: &dyn KeyProvider,
+        remote_public: &[u8],
+        options: &SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key = derive_session_key_via(provider, remote_public)?;
+        self.establish(peer_id, session_key, SessionRole::Initiator, options)
+    }
+
+    /// Return the peer's current session, creating one (as initiator) only
+    /// if needed, so there is at most one active session per peer.
+    ///
+    /// Reuse policy: an established, unexpired session with `peer_id` is
+    /// returned as-is if it was derived from the same key pair. If the peer's
+    /// key changed, that session is closed and replaced. Expired or pending
+    /// sessions are never reused. Callers sharing a manager must hold its
+    /// lock across this call (the engine does).
+    pub fn get_or_create_session(
+        &mut self,
+        peer_id: &str,
+        local_secret: &[u8],
+        remote_public: &[u8],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key =
+            derive_session_key(self.key_exchange.as_ref(), local_secret, remote_public)?;
+        self.get_or_establish(peer_id, session_key)
+    }
+
+    /// `get_or_create_session` with the ECDH done by `provider`
+    pub fn get_or_create_session_via(
+        &mut self,
+        peer_id: &str,
+        provider: &dyn KeyProvider,
+        remote_public: &[u8],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key = derive_session_key_via(provider, remote_public)?;
+        self.get_or_establish(peer_id, session_key)
+    }
+
+    fn get_or_establish(
+        &mut self,
+        peer_id: &str,
+        session_key: [u8; 32],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let now = self.clock.now();
+        let mut stale = Vec::new();
+        for session in self.sessions.values() {
+            let session = lock_session(session);
+            if session.peer_id != peer_id
+                || session.state != SessionState::Established
+                || session.is_expired(now)
+            {
+                continue;
+            }
+            // Constant time, so the comparison leaks nothing about the key
+            if bool::from(session.session_key.ct_eq(&session_key)) {
+                return Ok(session.to_info());
+            }
+            stale.push(session.session_id.clone());
+        }
+
+        for session_id in stale {
+            self.sessions.remove(&session_id);
+            tracing::info!(peer_id = %peer_id, "Replacing session after peer key change");
+        }
+        self.establish(
+            peer_id,
+            session_key,
+            SessionRole::Initiator,
+            &SessionOptions::default(),
+        )
+    }
+
+    /// Accept a session initiated by a peer (as responder)
+    pub fn accept_session(
+        &mut self,
+        peer_id: &str,
+        local_secret: &[u8],
+        remote_public: &[u8],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        self.accept_session_with(
+            peer_id,
+            local_secret,
+            remote_public,
+            &SessionOptions::default(),
+        )
+    }
+
+    /// Accept a session as responder with explicit options
+    pub fn accept_session_with(
+        &mut self,
+        peer_id: &str,
+        local_secret: &[u8],
+        remote_public: &[u8],
+        options: &SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key =
+            derive_session_key(self.key_exchange.as_ref(), local_secret, remote_public)?;
+        self.establish(peer_id, session_key, SessionRole::Responder, options)
+    }
+
+    /// Accept a session as responder, with the ECDH done by `provider`
+    pub fn accept_session_via(
+        &mut self,
+        peer_id: &str,
+        provider: &dyn KeyProvider,
+        remote_public: &[u8],
+        options: &SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let session_key = derive_session_key_via(provider, remote_public)?;
+        self.establish(peer_id, session_key, SessionRole::Responder, options)
+    }
+
+    /// Default session lifetime in seconds
+    pub fn default_duration_secs(&self) -> u64 {
+        self.session_duration_secs as u64
+    }
+
+    /// Change the lifetime given to sessions created from now on
+    pub fn set_default_duration_secs(&mut self, secs: u64) -> Result<(), EdgeClawError> {
+        self.session_duration_secs = i64::try_from(secs)
+            .ok()
+            .filter(|s| *s > 0 && chrono::Duration::try_seconds(*s).is_some())
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        Ok(())
+    }
+
+    /// Default idle limit in seconds, if any
+    pub fn default_max_idle_secs(&self) -> Option<u64> {
+        self.max_idle.map(|d| d.num_seconds() as u64)
+    }
+
+    /// Change the idle limit given to sessions created from now on
+    /// (None = sessions only expire at the end of their lifetime)
+    pub fn set_default_max_idle_secs(&mut self, secs: Option<u64>) -> Result<(), EdgeClawError> {
+        self.max_idle = secs.map(idle_duration).transpose()?;
+        Ok(())
+    }
+
+    /// Total plaintext bytes encrypted across all sessions
+    pub fn bytes_encrypted(&self) -> u64 {
+        self.bytes_encrypted.load(Ordering::Relaxed)
+    }
+
+    /// Total plaintext bytes recovered by decryption across all sessions
+    pub fn bytes_decrypted(&self) -> u64 {
+        self.bytes_decrypted.load(Ordering::Relaxed)
+    }
+
+    /// Session cap (0 = unlimited)
+    pub fn max_active_sessions(&self) -> u32 {
+        self.max_active_sessions
+    }
 
-        let now = chrono::Utc::now();
+    /// Cap the number of sessions held. Sessions already over the cap are
+    /// kept; imports are not limited.
+    pub fn set_max_active_sessions(&mut self, max: u32) {
+        self.max_active_sessions = max;
+    }
+
+    /// At the cap, evict the oldest expired session to make room for a new
+    /// one, or fail with `CapacityExceeded` if every session is still valid
+    fn make_room(&mut self, now: chrono::DateTime<chrono::Utc>) -> Result<(), EdgeClawError> {
+        let max = self.max_active_sessions;
+        if max == 0 || self.sessions.len() < max as usize {
+            return Ok(());
+        }
+        let oldest_expired = self
+            .sessions
+            .values()
+            .map(|s| lock_session(s))
+            .filter(|s| s.is_expired(now))
+            .min_by_key(|s| s.created_at)
+            .map(|s| s.session_id.clone());
+        match oldest_expired {
+            Some(session_id) => {
+                self.sessions.remove(&session_id);
+                tracing::debug!(session_id = %session_id, "Evicted expired session at capacity");
+                Ok(())
+            }
+            None => {
+                tracing::warn!(max, "Session capacity reached");
+                Err(EdgeClawError::CapacityExceeded { max })
+            }
+        }
+    }
+
+    /// Idle limit for a session created with `options`
+    fn max_idle_for(
+        &self,
+        options: &SessionOptions,
+    ) -> Result<Option<chrono::Duration>, EdgeClawError> {
+        match options.max_idle_secs {
+            Some(secs) => idle_duration(secs).map(Some),
+            None => Ok(self.max_idle),
+        }
+    }
+
+    /// Expiry for a session created now with `options`
+    fn expiry_for(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+        options: &SessionOptions,
+    ) -> Result<chrono::DateTime<chrono::Utc>, EdgeClawError> {
+        let lifetime = match options.duration_secs {
+            Some(0) => None,
+            Some(secs) => i64::try_from(secs)
+                .ok()
+                .and_then(chrono::Duration::try_seconds),
+            None => chrono::Duration::try_seconds(self.session_duration_secs),
+        }
+        .ok_or(EdgeClawError::InvalidParameter)?;
+
+        now.checked_add_signed(lifetime)
+            .ok_or(EdgeClawError::InvalidParameter)
+    }
+
+    fn establish(
+        &mut self,
+        peer_id: &str,
+        session_key: [u8; 32],
+        role: SessionRole,
+        options: &SessionOptions,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let now = self.clock.now();
+        let expires_at = self.expiry_for(now, options)?;
+        let max_idle = self.max_idle_for(options)?;
+        self.make_room(now)?;
         let session_id = uuid::Uuid::new_v4().to_string();
 
         let session = Session {
             session_id: session_id.clone(),
             peer_id: peer_id.to_string(),
             state: SessionState::Established,
+            role,
             session_key,
+            pending_key: None,
             nonce_counter: 0,
+            nonce_mode: options.nonce_mode,
             created_at: now,
-            expires_at: now + chrono::Duration::seconds(self.session_duration_secs),
+            expires_at,
+            last_activity: now,
+            max_idle,
             messages_sent: 0,
             messages_received: 0,
+            #[cfg(feature = "session-transcript")]
+            transcript: std::collections::VecDeque::new(),
         };
 
         let info = session.to_info();
-        self.sessions.insert(session_id, session);
+        self.sessions.insert(session_id, Mutex::new(session));
 
-        tracing::info!(peer_id = %peer_id, "Session established");
+        tracing::info!(peer_id = %peer_id, role = %role, "Session established");
         Ok(info)
     }
 
-    /// Encrypt data using session's AES-256-GCM key
-    pub fn encrypt(
+    /// Start an initiator session while our key is in flight to the peer.
+    ///
+    /// The session stays `Initiating`, with no key, until `complete_session`
+    /// receives the peer's public key; until then encrypt/decrypt fail with
+    /// `SessionNotEstablished`. Returns the pending session ID.
+    pub fn begin_session(
         &mut self,
-        session_id: &str,
-        plaintext: &[u8],
-    ) -> Result<Vec<u8>, EdgeClawError> {
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or(EdgeClawError::InvalidParameter)?;
+        peer_id: &str,
+        local_secret: &[u8],
+    ) -> Result<String, EdgeClawError> {
+        self.begin_pending(peer_id, PendingKey::Secret(local_secret.to_vec()))
+    }
+
+    /// `begin_session` with the ECDH done by `provider` on completion
+    pub fn begin_session_via(
+        &mut self,
+        peer_id: &str,
+        provider: Arc<dyn KeyProvider>,
+    ) -> Result<String, EdgeClawError> {
+        self.begin_pending(peer_id, PendingKey::Provider(provider))
+    }
+
+    fn begin_pending(
+        &mut self,
+        peer_id: &str,
+        pending_key: PendingKey,
+    ) -> Result<String, EdgeClawError> {
+        let now = self.clock.now();
+        let expires_at = self.expiry_for(now, &SessionOptions::default())?;
+        self.make_room(now)?;
+        let session_id = uuid::Uuid::new_v4().to_string();
+
+        self.sessions.insert(
+            session_id.clone(),
+            Mutex::new(Session {
+                session_id: session_id.clone(),
+                peer_id: peer_id.to_string(),
+                state: SessionState::Initiating,
+                role: SessionRole::Initiator,
+                session_key: [0u8; 32],
+                pending_key: Some(pending_key),
+                nonce_counter: 0,
+                nonce_mode: NonceMode::Counter,
+                created_at: now,
+                expires_at,
+                last_activity: now,
+                max_idle: self.max_idle,
+                messages_sent: 0,
+                messages_received: 0,
+                #[cfg(feature = "session-transcript")]
+                transcript: std::collections::VecDeque::new(),
+            }),
+        );
+
+        tracing::info!(peer_id = %peer_id, "Session initiating");
+        Ok(session_id)
+    }
 
-        if session.is_expired() {
+    /// Finish a session started with `begin_session` by deriving the key
+    /// from the peer's public key. The lifetime starts now.
+    ///
+    /// A pending session that outlived its lifetime fails with
+    /// `SessionExpired` and can no longer be completed.
+    pub fn complete_session(
+        &mut self,
+        pending_id: &str,
+        remote_public: &[u8],
+    ) -> Result<SessionInfo, EdgeClawError> {
+        let now = self.clock.now();
+        let expires_at = self.expiry_for(now, &SessionOptions::default())?;
+        let mut session = lock_session(
+            self.sessions
+                .get(pending_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        if session.state == SessionState::Initiating && session.is_expired(now) {
             session.state = SessionState::Expired;
+            session.pending_key = None;
             return Err(EdgeClawError::SessionExpired);
         }
+        session.session_key = match (&session.state, &session.pending_key) {
+            (SessionState::Initiating, Some(PendingKey::Secret(secret))) => {
+                derive_session_key(self.key_exchange.as_ref(), secret, remote_public)?
+            }
+            (SessionState::Initiating, Some(PendingKey::Provider(provider))) => {
+                derive_session_key_via(provider.as_ref(), remote_public)?
+            }
+            _ => return Err(EdgeClawError::InvalidParameter),
+        };
+        session.pending_key = None;
+        session.state = SessionState::Established;
+        session.created_at = now;
+        session.expires_at = expires_at;
+        session.last_activity = now;
+
+        tracing::info!(peer_id = %session.peer_id, "Session established");
+        Ok(session.to_info())
+    }
+
+    /// Encrypt data using session's AES-256-GCM key.
+    ///
+    /// Empty plaintext is allowed and yields a 28-byte frame (nonce + tag)
+    /// usable as an authenticated keepalive; `decrypt` returns an empty `Vec`.
+    pub fn encrypt(&self, session_id: &str, plaintext: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+        let mut session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        let now = self.clock.now();
+        session.ensure_usable(now)?;
 
         let cipher = Aes256Gcm::new_from_slice(&session.session_key)
             .map_err(|_| EdgeClawError::CryptoError)?;
-
-        // Build nonce from counter (12 bytes)
-        let mut nonce_bytes = [0u8; 12];
-        nonce_bytes[4..12].copy_from_slice(&session.nonce_counter.to_be_bytes());
-        session.nonce_counter += 1;
+        let nonce_bytes = session.next_nonce();
 
         let nonce = Nonce::from_slice(&nonce_bytes);
         let ciphertext = cipher
@@ -162,81 +964,496 @@ impl SessionManager {
         let mut result = Vec::with_capacity(12 + ciphertext.len());
         result.extend_from_slice(&nonce_bytes);
         result.extend_from_slice(&ciphertext);
+        #[cfg(feature = "session-transcript")]
+        session.record(TranscriptDirection::Sent, now, &result, true);
 
         session.messages_sent += 1;
+        session.last_activity = now;
+        self.bytes_encrypted
+            .fetch_add(plaintext.len() as u64, Ordering::Relaxed);
         Ok(result)
     }
 
     /// Decrypt data using session's AES-256-GCM key
-    pub fn decrypt(
-        &mut self,
-        session_id: &str,
-        ciphertext: &[u8],
-    ) -> Result<Vec<u8>, EdgeClawError> {
+    pub fn decrypt(&self, session_id: &str, ciphertext: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+        let mut session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+
+        // Too short to carry a nonce: the message is malformed, the session is fine
         if ciphertext.len() < 12 {
             return Err(EdgeClawError::InvalidParameter);
         }
+        let now = self.clock.now();
+        session.ensure_usable(now)?;
 
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or(EdgeClawError::InvalidParameter)?;
+        let cipher = Aes256Gcm::new_from_slice(&session.session_key)
+            .map_err(|_| EdgeClawError::CryptoError)?;
 
-        if session.is_expired() {
-            session.state = SessionState::Expired;
-            return Err(EdgeClawError::SessionExpired);
+        let nonce = Nonce::from_slice(&ciphertext[..12]);
+        let decrypted = cipher.decrypt(nonce, &ciphertext[12..]);
+        #[cfg(feature = "session-transcript")]
+        session.record(
+            TranscriptDirection::Received,
+            now,
+            ciphertext,
+            decrypted.is_ok(),
+        );
+        let plaintext = decrypted.map_err(|_| EdgeClawError::CryptoError)?;
+
+        session.messages_received += 1;
+        session.last_activity = now;
+        self.bytes_decrypted
+            .fetch_add(plaintext.len() as u64, Ordering::Relaxed);
+        Ok(plaintext)
+    }
+
+    /// Encrypt `plaintext` as a stream of independently authenticated
+    /// chunks of at most `chunk_size` plaintext bytes, for `decrypt_stream`.
+    ///
+    /// Empty plaintext yields a single empty final chunk. Each chunk uses
+    /// one session nonce.
+    pub fn encrypt_stream(
+        &self,
+        session_id: &str,
+        plaintext: &[u8],
+        chunk_size: usize,
+    ) -> Result<Vec<Vec<u8>>, EdgeClawError> {
+        if chunk_size == 0 || plaintext.len().div_ceil(chunk_size) > u32::MAX as usize {
+            return Err(EdgeClawError::InvalidParameter);
         }
+        let mut session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        let now = self.clock.now();
+        session.ensure_usable(now)?;
 
         let cipher = Aes256Gcm::new_from_slice(&session.session_key)
             .map_err(|_| EdgeClawError::CryptoError)?;
+        let mut stream_id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut stream_id);
 
-        let nonce = Nonce::from_slice(&ciphertext[..12]);
-        let plaintext = cipher
-            .decrypt(nonce, &ciphertext[12..])
+        let mut parts: Vec<&[u8]> = plaintext.chunks(chunk_size).collect();
+        if parts.is_empty() {
+            parts.push(&[]);
+        }
+        let last_index = parts.len() - 1;
+        let mut chunks = Vec::with_capacity(parts.len());
+        for (index, part) in parts.into_iter().enumerate() {
+            let mut chunk = Vec::with_capacity(STREAM_HEADER_SIZE + part.len() + 16);
+            chunk.extend_from_slice(&stream_id);
+            chunk.extend_from_slice(&(index as u32).to_be_bytes());
+            chunk.push(u8::from(index == last_index));
+            let nonce_bytes = session.next_nonce();
+            chunk.extend_from_slice(&nonce_bytes);
+
+            let aad = [STREAM_LABEL, &chunk[..STREAM_AAD_SIZE]].concat();
+            let ciphertext = cipher
+                .encrypt(
+                    Nonce::from_slice(&nonce_bytes),
+                    Payload {
+                        msg: part,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| EdgeClawError::CryptoError)?;
+            chunk.extend_from_slice(&ciphertext);
+            #[cfg(feature = "session-transcript")]
+            session.record(
+                TranscriptDirection::Sent,
+                now,
+                &chunk[STREAM_AAD_SIZE..],
+                true,
+            );
+            session.messages_sent += 1;
+            chunks.push(chunk);
+        }
+
+        session.last_activity = now;
+        self.bytes_encrypted
+            .fetch_add(plaintext.len() as u64, Ordering::Relaxed);
+        Ok(chunks)
+    }
+
+    /// Decrypt a chunk stream from `encrypt_stream`, yielding each chunk's
+    /// plaintext as soon as it authenticates.
+    ///
+    /// The first bad chunk (tampered, out of order, from another stream)
+    /// yields an error and ends the iteration without reading further
+    /// chunks. Input that ends before the final chunk, or continues past
+    /// it, also ends with `CryptoError`, so a truncated stream is never
+    /// mistaken for a complete one.
+    pub fn decrypt_stream<I>(&self, session_id: &str, chunks: I) -> DecryptStream<'_, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        DecryptStream {
+            manager: self,
+            session_id: session_id.to_string(),
+            chunks: chunks.into_iter(),
+            stream_id: None,
+            next_index: 0,
+            finished: false,
+            done: false,
+        }
+    }
+
+    /// Authenticate and decrypt one stream chunk. Returns the plaintext
+    /// and the chunk's final flag.
+    fn open_stream_chunk(
+        &self,
+        session_id: &str,
+        chunk: &[u8],
+        expected_stream: Option<[u8; 16]>,
+        expected_index: u32,
+    ) -> Result<(Vec<u8>, bool), EdgeClawError> {
+        let mut session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        if chunk.len() < STREAM_HEADER_SIZE + 16 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let stream_id: [u8; 16] = chunk[..16].try_into().expect("16-byte slice");
+        let index = u32::from_be_bytes(chunk[16..20].try_into().expect("4-byte slice"));
+        let is_final = match chunk[20] {
+            0 => false,
+            1 => true,
+            _ => return Err(EdgeClawError::InvalidParameter),
+        };
+        if expected_stream.is_some_and(|id| id != stream_id) || index != expected_index {
+            return Err(EdgeClawError::CryptoError);
+        }
+        let now = self.clock.now();
+        session.ensure_usable(now)?;
+
+        let cipher = Aes256Gcm::new_from_slice(&session.session_key)
             .map_err(|_| EdgeClawError::CryptoError)?;
+        let aad = [STREAM_LABEL, &chunk[..STREAM_AAD_SIZE]].concat();
+        let decrypted = cipher.decrypt(
+            Nonce::from_slice(&chunk[STREAM_AAD_SIZE..STREAM_HEADER_SIZE]),
+            Payload {
+                msg: &chunk[STREAM_HEADER_SIZE..],
+                aad: &aad,
+            },
+        );
+        #[cfg(feature = "session-transcript")]
+        session.record(
+            TranscriptDirection::Received,
+            now,
+            &chunk[STREAM_AAD_SIZE..],
+            decrypted.is_ok(),
+        );
+        let plaintext = decrypted.map_err(|_| EdgeClawError::CryptoError)?;
 
         session.messages_received += 1;
-        Ok(plaintext)
+        session.last_activity = now;
+        self.bytes_decrypted
+            .fetch_add(plaintext.len() as u64, Ordering::Relaxed);
+        Ok((plaintext, is_final))
     }
 
     /// Get session info
     pub fn get_session(&self, session_id: &str) -> Result<SessionInfo, EdgeClawError> {
         self.sessions
             .get(session_id)
-            .map(|s| s.to_info())
-            .ok_or(EdgeClawError::InvalidParameter)
+            .map(|s| lock_session(s).to_info())
+            .ok_or(EdgeClawError::SessionNotFound)
     }
 
-    /// Get all active sessions
-    pub fn active_sessions(&self) -> Vec<SessionInfo> {
-        self.sessions
-            .values()
-            .filter(|s| s.state == SessionState::Established && !s.is_expired())
-            .map(|s| s.to_info())
-            .collect()
+    /// Options that recreate a session's lifetime, nonce mode and idle limit,
+    /// e.g. for a rekeyed replacement
+    pub fn session_options(&self, session_id: &str) -> Result<SessionOptions, EdgeClawError> {
+        let session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        Ok(SessionOptions {
+            duration_secs: Some((session.expires_at - session.created_at).num_seconds() as u64),
+            nonce_mode: session.nonce_mode,
+            max_idle_secs: session.max_idle.map(|d| d.num_seconds() as u64),
+        })
     }
 
-    /// Close a session
-    pub fn close_session(&mut self, session_id: &str) -> Result<(), EdgeClawError> {
-        self.sessions
-            .remove(session_id)
-            .map(|_| ())
-            .ok_or(EdgeClawError::InvalidParameter)
+    /// Key confirmation tag for out-of-band MITM detection.
+    ///
+    /// HMAC-SHA256 over a fixed label keyed with the session key, truncated to
+    /// 16 bytes. Both ends of an uncompromised handshake derive the same value;
+    /// exchanging it reveals nothing about the key itself.
+    pub fn session_confirmation(&self, session_id: &str) -> Result<[u8; 16], EdgeClawError> {
+        let session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        if session.state == SessionState::Initiating {
+            return Err(EdgeClawError::SessionNotEstablished);
+        }
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&session.session_key)
+            .map_err(|_| EdgeClawError::CryptoError)?;
+        mac.update(SESSION_CONFIRMATION_LABEL);
+        let digest = mac.finalize().into_bytes();
+
+        let mut confirmation = [0u8; 16];
+        confirmation.copy_from_slice(&digest[..16]);
+        Ok(confirmation)
+    }
+
+    /// Encrypted probe for in-band key confirmation. The peer answers with
+    /// `echo_session_probe`; check the reply with `confirm_session_echo`.
+    pub fn session_probe(&self, session_id: &str) -> Result<Vec<u8>, EdgeClawError> {
+        self.encrypt(session_id, SESSION_PROBE)
+    }
+
+    /// Answer a peer's probe with an encrypted echo. `CryptoError` if the
+    /// probe does not decrypt to the expected plaintext (key disagreement).
+    pub fn echo_session_probe(
+        &self,
+        session_id: &str,
+        probe: &[u8],
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        if self.decrypt(session_id, probe)? != SESSION_PROBE {
+            tracing::warn!(session_id = %session_id, "Session probe has unexpected content");
+            return Err(EdgeClawError::CryptoError);
+        }
+        self.encrypt(session_id, SESSION_PROBE_ECHO)
+    }
+
+    /// Validate the peer's echo of our probe; `CryptoError` means the two
+    /// sides did not derive the same key
+    pub fn confirm_session_echo(&self, session_id: &str, echo: &[u8]) -> Result<(), EdgeClawError> {
+        let confirmed = self
+            .decrypt(session_id, echo)
+            .is_ok_and(|plaintext| plaintext == SESSION_PROBE_ECHO);
+        if !confirmed {
+            tracing::warn!(session_id = %session_id, "Session key agreement not confirmed");
+            return Err(EdgeClawError::CryptoError);
+        }
+        Ok(())
+    }
+
+    /// Per-message metadata recorded for a session (most recent
+    /// `TRANSCRIPT_CAPACITY` messages). Only built with the
+    /// `session-transcript` feature.
+    #[cfg(feature = "session-transcript")]
+    pub fn transcript(&self, session_id: &str) -> Result<Vec<TranscriptEntry>, EdgeClawError> {
+        let session = lock_session(
+            self.sessions
+                .get(session_id)
+                .ok_or(EdgeClawError::SessionNotFound)?,
+        );
+        Ok(session.transcript.iter().cloned().collect())
+    }
+
+    /// Get all active sessions
+    pub fn active_sessions(&self) -> Vec<SessionInfo> {
+        let now = self.clock.now();
+        self.sessions
+            .values()
+            .map(|s| lock_session(s))
+            .filter(|s| s.state == SessionState::Established && !s.is_expired(now))
+            .map(|s| s.to_info())
+            .collect()
+    }
+
+    /// Active sessions with a peer, newest first
+    pub fn sessions_for_peer(&self, peer_id: &str) -> Vec<SessionInfo> {
+        let now = self.clock.now();
+        let mut sessions: Vec<_> = self
+            .sessions
+            .values()
+            .map(|s| lock_session(s))
+            .filter(|s| {
+                s.peer_id == peer_id && s.state == SessionState::Established && !s.is_expired(now)
+            })
+            .map(|s| (s.created_at, s.to_info()))
+            .collect();
+        sessions.sort_by_key(|(created_at, _)| std::cmp::Reverse(*created_at));
+        sessions.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// Get every session, including expired ones not yet cleaned up
+    pub fn all_sessions(&self) -> Vec<SessionInfo> {
+        let now = self.clock.now();
+        self.sessions
+            .values()
+            .map(|s| {
+                let s = lock_session(s);
+                let mut info = s.to_info();
+                if s.is_expired(now) {
+                    info.state = SessionState::Expired.to_string();
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// `SessionInfo::summary` of every session, oldest first
+    pub fn session_summaries(&self) -> Vec<String> {
+        let now = self.clock.now();
+        let mut sessions = self.all_sessions();
+        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        sessions.iter().map(|s| s.summary_at(now)).collect()
+    }
+
+    /// Close a session
+    pub fn close_session(&mut self, session_id: &str) -> Result<(), EdgeClawError> {
+        self.sessions
+            .remove(session_id)
+            .map(|_| ())
+            .ok_or(EdgeClawError::SessionNotFound)
+    }
+
+    /// Export metadata of all established, unexpired sessions
+    pub fn export_sessions(&self) -> Vec<SessionSnapshot> {
+        let now = self.clock.now();
+        self.sessions
+            .values()
+            .map(|s| lock_session(s))
+            .filter(|s| s.state == SessionState::Established && !s.is_expired(now))
+            .map(|s| SessionSnapshot {
+                session_id: s.session_id.clone(),
+                peer_id: s.peer_id.clone(),
+                role: s.role,
+                nonce_mode: s.nonce_mode,
+                created_at: s.created_at,
+                expires_at: s.expires_at,
+                messages_sent: s.messages_sent,
+                messages_received: s.messages_received,
+                last_activity: Some(s.last_activity),
+                max_idle_secs: s.max_idle.map(|d| d.num_seconds() as u64),
+            })
+            .collect()
+    }
+
+    /// Replace all sessions with previously exported ones, restored as
+    /// expired until re-keyed.
+    ///
+    /// Every snapshot is validated before any state changes, so a bad entry
+    /// leaves the manager untouched. Duplicate IDs within `snapshots` are
+    /// resolved as in `merge_sessions` and reported.
+    pub fn import_sessions(
+        &mut self,
+        snapshots: Vec<SessionSnapshot>,
+    ) -> Result<SessionImportReport, EdgeClawError> {
+        let mut sessions = std::collections::HashMap::with_capacity(snapshots.len());
+        let report = merge_into(&mut sessions, snapshots)?;
+        self.sessions = sessions;
+        Ok(report)
+    }
+
+    /// Add previously exported sessions to the ones already held.
+    ///
+    /// A session already held always wins an ID collision, so a live key
+    /// and nonce counter are never replaced by a keyless imported copy.
+    /// Duplicates within `snapshots` resolve as in `import_sessions`. Each
+    /// collision is listed in the report. Nothing changes if any snapshot
+    /// is invalid.
+    pub fn merge_sessions(
+        &mut self,
+        snapshots: Vec<SessionSnapshot>,
+    ) -> Result<SessionImportReport, EdgeClawError> {
+        let mut incoming = std::collections::HashMap::with_capacity(snapshots.len());
+        let mut report = merge_into(&mut incoming, snapshots)?;
+        let mut imported = 0;
+        for (session_id, session) in incoming {
+            if self.sessions.contains_key(&session_id) {
+                let resolution = ConflictResolution::KeptExisting;
+                tracing::warn!(session_id = %session_id, ?resolution, "Session ID collision on import");
+                report.conflicts.push(SessionConflict {
+                    session_id,
+                    resolution,
+                });
+                continue;
+            }
+            self.sessions.insert(session_id, session);
+            imported += 1;
+        }
+        report.imported = imported;
+        Ok(report)
     }
 
     /// Clean up expired sessions
     pub fn cleanup_expired(&mut self) -> u32 {
+        let now = self.clock.now();
         let initial = self.sessions.len();
-        self.sessions.retain(|_, s| !s.is_expired());
+        self.sessions
+            .retain(|_, s| !lock_session(s).is_expired(now));
         (initial - self.sessions.len()) as u32
     }
 }
 
+/// Iterator returned by `SessionManager::decrypt_stream`
+pub struct DecryptStream<'a, I> {
+    manager: &'a SessionManager,
+    session_id: String,
+    chunks: I,
+    stream_id: Option<[u8; 16]>,
+    next_index: u32,
+    /// The final chunk has been yielded
+    finished: bool,
+    done: bool,
+}
+
+impl<I> Iterator for DecryptStream<'_, I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    type Item = Result<Vec<u8>, EdgeClawError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.finished {
+            self.done = true;
+            // Anything after the final chunk was appended by someone else
+            return self.chunks.next().map(|_| Err(EdgeClawError::CryptoError));
+        }
+        let Some(chunk) = self.chunks.next() else {
+            self.done = true;
+            return Some(Err(EdgeClawError::CryptoError));
+        };
+
+        let chunk = chunk.as_ref();
+        match self.manager.open_stream_chunk(
+            &self.session_id,
+            chunk,
+            self.stream_id,
+            self.next_index,
+        ) {
+            Ok((plaintext, is_final)) => {
+                self.stream_id.get_or_insert_with(|| {
+                    chunk[..16]
+                        .try_into()
+                        .expect("checked by open_stream_chunk")
+                });
+                self.next_index = self.next_index.wrapping_add(1);
+                self.finished = is_final;
+                Some(Ok(plaintext))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::rngs::OsRng;
-    use x25519_dalek::StaticSecret;
+    use x25519_dalek::{PublicKey, StaticSecret};
 
     fn create_keypair() -> ([u8; 32], [u8; 32]) {
         let secret = StaticSecret::random_from_rng(OsRng);
@@ -255,6 +1472,212 @@ mod tests {
         assert_eq!(info.state, "established");
     }
 
+    #[test]
+    fn test_key_exchange_trait_matches_direct_x25519() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+
+        // Key derivation as it was before the exchange was pluggable
+        let shared = StaticSecret::from(secret_a).diffie_hellman(&PublicKey::from(pub_b));
+        let mut expected = [0u8; 32];
+        Hkdf::<Sha256>::new(None, shared.as_bytes())
+            .expand(b"edgeclaw-session-v1", &mut expected)
+            .unwrap();
+
+        let mut mgr_a = SessionManager::new();
+        assert_eq!(mgr_a.key_exchange_algorithm(), "x25519");
+        let info_a = mgr_a.create_session("peer-b", &secret_a, &pub_b).unwrap();
+        let mut mgr_b = SessionManager::new();
+        mgr_b.set_key_exchange(Arc::new(kex::X25519KeyExchange));
+        let info_b = mgr_b.accept_session("peer-a", &secret_b, &pub_a).unwrap();
+
+        for (mgr, id) in [(&mgr_a, &info_a.session_id), (&mgr_b, &info_b.session_id)] {
+            assert_eq!(lock_session(&mgr.sessions[id]).session_key, expected);
+        }
+        let ciphertext = mgr_a.encrypt(&info_a.session_id, b"hello").unwrap();
+        assert_eq!(
+            mgr_b.decrypt(&info_b.session_id, &ciphertext).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_custom_key_exchange_is_used() {
+        struct FixedExchange;
+        impl KeyExchange for FixedExchange {
+            fn algorithm(&self) -> &'static str {
+                "fixed"
+            }
+            fn generate_keypair(&self) -> kex::KeyExchangeKeypair {
+                kex::KeyExchangeKeypair {
+                    secret: vec![1; 32],
+                    public: vec![2; 32],
+                }
+            }
+            fn shared_secret(&self, _: &[u8], _: &[u8]) -> Result<Vec<u8>, EdgeClawError> {
+                Ok(vec![7; 64])
+            }
+        }
+
+        let mut mgr_a = SessionManager::new();
+        mgr_a.set_key_exchange(Arc::new(FixedExchange));
+        let mut mgr_b = SessionManager::new();
+        mgr_b.set_key_exchange(Arc::new(FixedExchange));
+        assert_eq!(mgr_a.key_exchange_algorithm(), "fixed");
+
+        // Unrelated keys still agree, so the exchange really was replaced
+        let (secret_a, _) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+        let (_, pub_c) = create_keypair();
+        let a = mgr_a.create_session("b", &secret_a, &pub_b).unwrap();
+        let b = mgr_b.accept_session("a", &secret_b, &pub_c).unwrap();
+        let ciphertext = mgr_a.encrypt(&a.session_id, b"pqc-ready").unwrap();
+        assert_eq!(
+            mgr_b.decrypt(&b.session_id, &ciphertext).unwrap(),
+            b"pqc-ready"
+        );
+    }
+
+    #[test]
+    fn test_key_provider_sessions_delegate_exchange() {
+        use crate::keystore::mock::MockKeyProvider;
+
+        let provider = Arc::new(MockKeyProvider::new());
+        let (secret_b, pub_b) = create_keypair();
+
+        let mut mgr_a = SessionManager::new();
+        let info_a = mgr_a
+            .create_session_via(
+                "peer-b",
+                provider.as_ref(),
+                &pub_b,
+                &SessionOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(provider.exchanges(), 1);
+
+        let mut mgr_b = SessionManager::new();
+        let info_b = mgr_b
+            .accept_session("peer-a", &secret_b, &provider.x25519_public_key())
+            .unwrap();
+        let ciphertext = mgr_a.encrypt(&info_a.session_id, b"hello").unwrap();
+        assert_eq!(
+            mgr_b.decrypt(&info_b.session_id, &ciphertext).unwrap(),
+            b"hello"
+        );
+
+        // Deferred sessions run the exchange only once the peer key arrives
+        let pending = mgr_a.begin_session_via("peer-b", provider.clone()).unwrap();
+        assert_eq!(provider.exchanges(), 1);
+        let completed = mgr_a.complete_session(&pending, &pub_b).unwrap();
+        assert_eq!(provider.exchanges(), 2);
+        let ciphertext = mgr_a.encrypt(&completed.session_id, b"again").unwrap();
+        assert_eq!(
+            mgr_b.decrypt(&info_b.session_id, &ciphertext).unwrap(),
+            b"again"
+        );
+
+        mgr_a
+            .get_or_create_session_via("peer-b", provider.as_ref(), &pub_b)
+            .unwrap();
+        assert_eq!(provider.exchanges(), 3);
+    }
+
+    fn stream_pair() -> (SessionManager, String, SessionManager, String) {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+        let mut mgr_a = SessionManager::new();
+        let a = mgr_a.create_session("peer-b", &secret_a, &pub_b).unwrap();
+        let mut mgr_b = SessionManager::new();
+        let b = mgr_b.accept_session("peer-a", &secret_b, &pub_a).unwrap();
+        (mgr_a, a.session_id, mgr_b, b.session_id)
+    }
+
+    #[test]
+    fn test_stream_roundtrip() {
+        let (mgr_a, a, mgr_b, b) = stream_pair();
+        let chunks = mgr_a.encrypt_stream(&a, b"0123456789", 4).unwrap();
+        assert_eq!(chunks.len(), 3);
+
+        let parts: Vec<Vec<u8>> = mgr_b
+            .decrypt_stream(&b, &chunks)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            parts,
+            vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]
+        );
+        assert_eq!(mgr_b.get_session(&b).unwrap().messages_received, 3);
+
+        let empty = mgr_a.encrypt_stream(&a, b"", 4).unwrap();
+        assert_eq!(empty.len(), 1);
+        let parts: Vec<_> = mgr_b.decrypt_stream(&b, &empty).collect();
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].as_ref().unwrap().is_empty());
+
+        assert!(matches!(
+            mgr_a.encrypt_stream(&a, b"x", 0),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(matches!(
+            mgr_a.encrypt_stream("nope", b"x", 4),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_stream_tampered_first_chunk_fails_fast() {
+        let (mgr_a, a, mgr_b, b) = stream_pair();
+        let mut chunks = mgr_a.encrypt_stream(&a, &[7u8; 64], 16).unwrap();
+        assert_eq!(chunks.len(), 4);
+        *chunks[0].last_mut().unwrap() ^= 1;
+
+        let mut pulled = 0;
+        let mut stream = mgr_b.decrypt_stream(&b, chunks.iter().inspect(|_| pulled += 1));
+        assert!(matches!(
+            stream.next(),
+            Some(Err(EdgeClawError::CryptoError))
+        ));
+        assert!(stream.next().is_none());
+        drop(stream);
+        assert_eq!(pulled, 1);
+    }
+
+    #[test]
+    fn test_stream_rejects_reorder_truncation_and_splicing() {
+        let (mgr_a, a, mgr_b, b) = stream_pair();
+        let chunks = mgr_a.encrypt_stream(&a, b"abcdefgh", 4).unwrap();
+        let other = mgr_a.encrypt_stream(&a, b"ABCDEFGH", 4).unwrap();
+
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, [&chunks[1], &chunks[0]]).collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+
+        // Dropping the final chunk is detected once the input runs out
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, &chunks[..1]).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), b"abcd");
+        assert!(matches!(results[1], Err(EdgeClawError::CryptoError)));
+
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, [&chunks[0], &other[1]]).collect();
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(EdgeClawError::CryptoError)));
+
+        // Moving the final flag breaks authentication
+        let mut early_end = chunks[0].clone();
+        early_end[20] = 1;
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, [&early_end]).collect();
+        assert!(matches!(results[0], Err(EdgeClawError::CryptoError)));
+
+        let trailing = [&chunks[0], &chunks[1], &other[0]];
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, trailing).collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[2], Err(EdgeClawError::CryptoError)));
+
+        let results: Vec<_> = mgr_b.decrypt_stream(&b, [&chunks[0][..10]]).collect();
+        assert!(matches!(results[0], Err(EdgeClawError::InvalidParameter)));
+    }
+
     #[test]
     fn test_encrypt_decrypt_roundtrip() {
         let mut mgr = SessionManager::new();
@@ -302,6 +1725,722 @@ mod tests {
         assert!(mgr.get_session(&info.session_id).is_err());
     }
 
+    #[test]
+    fn test_session_role() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+
+        let initiated = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+        assert_eq!(initiated.role, "initiator");
+
+        let accepted = mgr.accept_session("peer-2", &secret_a, &pub_b).unwrap();
+        assert_eq!(accepted.role, "responder");
+        assert_eq!(
+            mgr.get_session(&accepted.session_id).unwrap().role,
+            "responder"
+        );
+
+        let json = serde_json::to_value(&accepted).unwrap();
+        assert_eq!(json["role"], "responder");
+    }
+
+    #[test]
+    fn test_create_and_accept_exchange() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+
+        let to_bob = alice.encrypt(&sa.session_id, b"hello bob").unwrap();
+        assert_eq!(bob.decrypt(&sb.session_id, &to_bob).unwrap(), b"hello bob");
+
+        let to_alice = bob.encrypt(&sb.session_id, b"hello alice").unwrap();
+        assert_eq!(
+            alice.decrypt(&sa.session_id, &to_alice).unwrap(),
+            b"hello alice"
+        );
+
+        // First message in each direction must not reuse a nonce
+        assert_ne!(&to_bob[..12], &to_alice[..12]);
+    }
+
+    #[test]
+    fn test_begin_complete_session() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+
+        let mut alice = SessionManager::new();
+        let pending = alice.begin_session("bob", &secret_a).unwrap();
+        assert_eq!(alice.get_session(&pending).unwrap().state, "initiating");
+        assert!(alice.active_sessions().is_empty());
+
+        let info = alice.complete_session(&pending, &pub_b).unwrap();
+        assert_eq!(info.session_id, pending);
+        assert_eq!(info.state, "established");
+        assert_eq!(info.role, "initiator");
+
+        let mut bob = SessionManager::new();
+        let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+        let frame = alice.encrypt(&pending, b"after handshake").unwrap();
+        assert_eq!(
+            bob.decrypt(&sb.session_id, &frame).unwrap(),
+            b"after handshake"
+        );
+
+        // A session can only be completed once
+        assert!(matches!(
+            alice.complete_session(&pending, &pub_b),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn test_complete_expired_pending_session_fails() {
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = SessionManager::new();
+        mgr.set_time_source(clock.clone());
+        mgr.set_default_duration_secs(60).unwrap();
+
+        let pending = mgr.begin_session("bob", &secret_a).unwrap();
+        clock.advance(chrono::Duration::seconds(60));
+        assert!(matches!(
+            mgr.complete_session(&pending, &pub_b),
+            Err(EdgeClawError::SessionExpired)
+        ));
+        assert_eq!(mgr.get_session(&pending).unwrap().state, "expired");
+        assert_eq!(mgr.cleanup_expired(), 1);
+    }
+
+    #[test]
+    fn test_encrypt_before_completion_fails() {
+        let (secret_a, _) = create_keypair();
+        let mut mgr = SessionManager::new();
+        let pending = mgr.begin_session("bob", &secret_a).unwrap();
+
+        assert!(matches!(
+            mgr.encrypt(&pending, b"too early"),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+        assert!(matches!(
+            mgr.decrypt(&pending, &[0u8; 28]),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+        assert!(matches!(
+            mgr.session_confirmation(&pending),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+        assert!(mgr.export_sessions().is_empty());
+    }
+
+    #[test]
+    fn test_sessions_for_peer_newest_first() {
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let (_, pub_c) = create_keypair();
+        let mut mgr = SessionManager::new();
+
+        let older = mgr.create_session("bob", &secret_a, &pub_b).unwrap();
+        let newer = mgr.create_session("bob", &secret_a, &pub_b).unwrap();
+        mgr.create_session("carol", &secret_a, &pub_c).unwrap();
+        mgr.sessions
+            .get_mut(&older.session_id)
+            .unwrap()
+            .get_mut()
+            .unwrap()
+            .created_at -= chrono::Duration::seconds(10);
+
+        let ids: Vec<String> = mgr
+            .sessions_for_peer("bob")
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect();
+        assert_eq!(ids, vec![newer.session_id, older.session_id]);
+        assert!(mgr.sessions_for_peer("dave").is_empty());
+    }
+
+    #[test]
+    fn test_get_or_create_session_reuse_policy() {
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let (_, pub_b_rotated) = create_keypair();
+        let mut mgr = SessionManager::new();
+
+        let first = mgr.get_or_create_session("bob", &secret_a, &pub_b).unwrap();
+        let again = mgr.get_or_create_session("bob", &secret_a, &pub_b).unwrap();
+        assert_eq!(first.session_id, again.session_id);
+
+        // New peer key: the old session is replaced, not duplicated
+        let rotated = mgr
+            .get_or_create_session("bob", &secret_a, &pub_b_rotated)
+            .unwrap();
+        assert_ne!(rotated.session_id, first.session_id);
+        assert_eq!(mgr.active_sessions().len(), 1);
+        assert!(mgr.get_session(&first.session_id).is_err());
+
+        // Expired sessions are not reused
+        mgr.sessions
+            .get_mut(&rotated.session_id)
+            .unwrap()
+            .get_mut()
+            .unwrap()
+            .expires_at = chrono::Utc::now();
+        let fresh = mgr
+            .get_or_create_session("bob", &secret_a, &pub_b_rotated)
+            .unwrap();
+        assert_ne!(fresh.session_id, rotated.session_id);
+    }
+
+    #[test]
+    fn test_session_confirmation_matches() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+
+        assert_eq!(
+            alice.session_confirmation(&sa.session_id).unwrap(),
+            bob.session_confirmation(&sb.session_id).unwrap()
+        );
+        assert!(matches!(
+            alice.session_confirmation("missing"),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_session_confirmation_detects_divergent_keys() {
+        let (secret_a, _) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+        let (_, pub_mitm) = create_keypair();
+
+        // Bob unknowingly completed the exchange with an attacker's key
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_mitm).unwrap();
+
+        assert_ne!(
+            alice.session_confirmation(&sa.session_id).unwrap(),
+            bob.session_confirmation(&sb.session_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_session_probe_echo() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+
+        let probe = alice.session_probe(&sa.session_id).unwrap();
+        let echo = bob.echo_session_probe(&sb.session_id, &probe).unwrap();
+        alice.confirm_session_echo(&sa.session_id, &echo).unwrap();
+
+        // A reflected probe is not an echo
+        let probe = alice.session_probe(&sa.session_id).unwrap();
+        assert!(matches!(
+            alice.confirm_session_echo(&sa.session_id, &probe),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_session_probe_detects_divergent_keys() {
+        let (secret_a, _) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+        let (_, pub_mitm) = create_keypair();
+
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_mitm).unwrap();
+
+        let probe = alice.session_probe(&sa.session_id).unwrap();
+        assert!(matches!(
+            bob.echo_session_probe(&sb.session_id, &probe),
+            Err(EdgeClawError::CryptoError)
+        ));
+        let forged = bob.encrypt(&sb.session_id, SESSION_PROBE_ECHO).unwrap();
+        assert!(matches!(
+            alice.confirm_session_echo(&sa.session_id, &forged),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[cfg(feature = "session-transcript")]
+    #[test]
+    fn test_transcript_records_metadata() {
+        let (secret_a, pub_a) = create_keypair();
+        let (secret_b, pub_b) = create_keypair();
+        let mut alice = SessionManager::new();
+        let mut bob = SessionManager::new();
+        let sa = alice.create_session("bob", &secret_a, &pub_b).unwrap();
+        let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+
+        let mut frames = Vec::new();
+        for msg in [&b"one"[..], b"two", b"three"] {
+            frames.push(alice.encrypt(&sa.session_id, msg).unwrap());
+        }
+        for frame in &frames {
+            bob.decrypt(&sb.session_id, frame).unwrap();
+        }
+        let mut tampered = frames[0].clone();
+        tampered[20] ^= 1;
+        assert!(bob.decrypt(&sb.session_id, &tampered).is_err());
+
+        let sent = alice.transcript(&sa.session_id).unwrap();
+        assert_eq!(sent.len(), 3);
+        assert!(sent
+            .iter()
+            .all(|e| e.direction == TranscriptDirection::Sent && e.ok));
+        let counters: Vec<_> = sent.iter().map(|e| e.nonce_counter.unwrap()).collect();
+        assert_eq!(counters, [0, 1, 2]);
+        assert_eq!(sent[2].ciphertext_len, frames[2].len());
+
+        let received = bob.transcript(&sb.session_id).unwrap();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[1].nonce_hex, sent[1].nonce_hex);
+        assert_eq!(received[1].nonce_counter, Some(1));
+        assert!(!received[3].ok);
+
+        // Received counters follow the peer's nonces, not our own mode
+        let (secret_c, pub_c) = create_keypair();
+        let mut carol = SessionManager::new();
+        let sc = carol
+            .accept_session_with(
+                "alice",
+                &secret_c,
+                &pub_a,
+                &SessionOptions {
+                    nonce_mode: NonceMode::Random,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let sa2 = alice.create_session("carol", &secret_a, &pub_c).unwrap();
+        let frame = alice.encrypt(&sa2.session_id, b"hi").unwrap();
+        carol.decrypt(&sc.session_id, &frame).unwrap();
+        assert_eq!(
+            carol.transcript(&sc.session_id).unwrap()[0].nonce_counter,
+            Some(0)
+        );
+        let reply = carol.encrypt(&sc.session_id, b"hey").unwrap();
+        alice.decrypt(&sa2.session_id, &reply).unwrap();
+        assert_eq!(
+            alice.transcript(&sa2.session_id).unwrap()[1].nonce_counter,
+            None
+        );
+
+        // Nothing derived from the plaintext or key ends up in the record
+        let json = serde_json::to_string(&received).unwrap();
+        assert!(!json.contains(&hex::encode(b"three")));
+    }
+
+    #[test]
+    fn test_unknown_session_vs_malformed_ciphertext() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+
+        assert!(matches!(
+            mgr.encrypt("no-such-session", b"data"),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+        assert!(matches!(
+            mgr.decrypt("no-such-session", &[0u8; 32]),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+        assert!(matches!(
+            mgr.decrypt(&info.session_id, &[0u8; 5]),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(matches!(
+            mgr.close_session("no-such-session"),
+            Err(EdgeClawError::SessionNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_export_import_sessions() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+        let ct = mgr.encrypt(&info.session_id, b"before export").unwrap();
+
+        let exported = mgr.export_sessions();
+        assert_eq!(exported.len(), 1);
+        let json = serde_json::to_string(&exported).unwrap();
+        assert!(!json.contains("session_key") && !json.contains("nonce_counter"));
+
+        // Restored sessions hold no key and must be re-keyed
+        let mut restored = SessionManager::new();
+        restored.import_sessions(exported.clone()).unwrap();
+        let all = restored.all_sessions();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].peer_id, "peer-1");
+        assert_eq!(all[0].state, "expired");
+        assert!(restored.active_sessions().is_empty());
+        assert!(matches!(
+            restored.decrypt(&info.session_id, &ct),
+            Err(EdgeClawError::SessionExpired)
+        ));
+        assert!(matches!(
+            restored.encrypt(&info.session_id, b"x"),
+            Err(EdgeClawError::SessionExpired)
+        ));
+
+        let mut bad = exported;
+        bad[0].max_idle_secs = Some(0);
+        assert!(restored.import_sessions(bad).is_err());
+        assert_eq!(restored.all_sessions().len(), 1);
+    }
+
+    #[test]
+    fn test_import_session_id_collision() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let live = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+        let ct = mgr.encrypt(&live.session_id, b"live").unwrap();
+
+        // A stale copy of the same ID (earlier expiry)
+        let mut stale = mgr.export_sessions().remove(0);
+        stale.expires_at -= chrono::Duration::minutes(30);
+
+        let report = mgr.merge_sessions(vec![stale.clone()]).unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(
+            report.conflicts,
+            vec![SessionConflict {
+                session_id: live.session_id.clone(),
+                resolution: ConflictResolution::KeptExisting,
+            }]
+        );
+        assert_eq!(mgr.decrypt(&live.session_id, &ct).unwrap(), b"live");
+
+        // Even a copy that outlives the live one never replaces it
+        let mut fresher = stale.clone();
+        fresher.expires_at += chrono::Duration::hours(2);
+        let report = mgr.merge_sessions(vec![fresher.clone()]).unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(
+            report.conflicts[0].resolution,
+            ConflictResolution::KeptExisting
+        );
+        assert_eq!(mgr.decrypt(&live.session_id, &ct).unwrap(), b"live");
+
+        // Duplicates within one import keep the later expiry
+        let report = mgr
+            .import_sessions(vec![stale.clone(), fresher.clone()])
+            .unwrap();
+        assert_eq!(report.conflicts[0].resolution, ConflictResolution::Replaced);
+        assert_eq!(
+            mgr.all_sessions()[0].expires_at,
+            fresher.expires_at.to_rfc3339()
+        );
+        let report = mgr.import_sessions(vec![fresher, stale]).unwrap();
+        assert_eq!(report.imported, 1);
+        assert_eq!(
+            report.conflicts[0].resolution,
+            ConflictResolution::KeptExisting
+        );
+        assert_eq!(mgr.all_sessions().len(), 1);
+    }
+
+    #[test]
+    fn test_expiry_follows_time_source() {
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = SessionManager::new();
+        mgr.set_time_source(clock.clone());
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr
+            .create_session_with(
+                "peer-1",
+                &secret_a,
+                &pub_b,
+                &SessionOptions {
+                    duration_secs: Some(60),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(info.created_at, clock.now().to_rfc3339());
+
+        clock.advance(chrono::Duration::seconds(59));
+        assert!(mgr.encrypt(&info.session_id, b"still ok").is_ok());
+        assert_eq!(mgr.active_sessions().len(), 1);
+
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(matches!(
+            mgr.encrypt(&info.session_id, b"too late"),
+            Err(EdgeClawError::SessionExpired)
+        ));
+        assert!(mgr.active_sessions().is_empty());
+        assert_eq!(mgr.cleanup_expired(), 1);
+    }
+
+    #[test]
+    fn test_idle_session_expires() {
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = SessionManager::new();
+        mgr.set_time_source(clock.clone());
+        mgr.set_default_max_idle_secs(Some(300)).unwrap();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+
+        // Activity resets the idle timer
+        clock.advance(chrono::Duration::seconds(250));
+        mgr.encrypt(&info.session_id, b"ping").unwrap();
+        clock.advance(chrono::Duration::seconds(300));
+        assert_eq!(mgr.active_sessions().len(), 1);
+
+        // Well inside the one-hour lifetime, but idle past the limit
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(mgr.active_sessions().is_empty());
+        assert!(matches!(
+            mgr.encrypt(&info.session_id, b"too late"),
+            Err(EdgeClawError::SessionExpired)
+        ));
+
+        // A per-session limit overrides the default
+        let info = mgr
+            .create_session_with(
+                "peer-2",
+                &secret_a,
+                &pub_b,
+                &SessionOptions {
+                    max_idle_secs: Some(30),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let snap = mgr.export_sessions();
+        assert_eq!(snap[0].max_idle_secs, Some(30));
+        clock.advance(chrono::Duration::seconds(31));
+        assert!(matches!(
+            mgr.encrypt(&info.session_id, b"idle"),
+            Err(EdgeClawError::SessionExpired)
+        ));
+
+        assert!(mgr.set_default_max_idle_secs(Some(0)).is_err());
+        mgr.set_default_max_idle_secs(None).unwrap();
+        assert_eq!(mgr.default_max_idle_secs(), None);
+    }
+
+    #[test]
+    fn test_low_order_public_key_rejected() {
+        let (secret_a, _) = create_keypair();
+        let mut mgr = SessionManager::new();
+        // The identity point and a point of order 8 on Curve25519
+        let mut order_eight = [0u8; 32];
+        order_eight.copy_from_slice(
+            &hex::decode("e0eb7a7c3b41b8ae1656e3faf19fc46ada098deb9c32b1fd866205165f49b800")
+                .unwrap(),
+        );
+        for low_order in [[0u8; 32], order_eight] {
+            assert!(matches!(
+                mgr.create_session("peer-1", &secret_a, &low_order),
+                Err(EdgeClawError::CryptoError)
+            ));
+            assert!(matches!(
+                mgr.accept_session("peer-1", &secret_a, &low_order),
+                Err(EdgeClawError::CryptoError)
+            ));
+        }
+        assert!(mgr.active_sessions().is_empty());
+
+        // Completing a pending session with a low-order key fails too
+        let pending = mgr.begin_session("peer-1", &secret_a).unwrap();
+        assert!(matches!(
+            mgr.complete_session(&pending, &[0u8; 32]),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[test]
+    fn test_session_capacity() {
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = SessionManager::new();
+        mgr.set_time_source(clock.clone());
+        mgr.set_max_active_sessions(2);
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let short = SessionOptions {
+            duration_secs: Some(10),
+            ..Default::default()
+        };
+
+        let first = mgr
+            .create_session_with("peer-1", &secret_a, &pub_b, &short)
+            .unwrap();
+        clock.advance(chrono::Duration::seconds(1));
+        let second = mgr
+            .create_session_with("peer-2", &secret_a, &pub_b, &short)
+            .unwrap();
+
+        // Full, and nothing has expired yet
+        assert!(matches!(
+            mgr.create_session("peer-3", &secret_a, &pub_b),
+            Err(EdgeClawError::CapacityExceeded { max: 2 })
+        ));
+        assert!(matches!(
+            mgr.begin_session("peer-3", &secret_a),
+            Err(EdgeClawError::CapacityExceeded { .. })
+        ));
+
+        // Once both have expired, the oldest makes way
+        clock.advance(chrono::Duration::seconds(10));
+        let third = mgr.create_session("peer-3", &secret_a, &pub_b).unwrap();
+        assert!(mgr.get_session(&first.session_id).is_err());
+        assert!(mgr.get_session(&second.session_id).is_ok());
+        assert!(mgr.get_session(&third.session_id).is_ok());
+
+        mgr.set_max_active_sessions(0);
+        mgr.create_session("peer-4", &secret_a, &pub_b).unwrap();
+        mgr.create_session("peer-5", &secret_a, &pub_b).unwrap();
+    }
+
+    #[test]
+    fn test_session_summary() {
+        let clock = Arc::new(time::MockTimeSource::new(chrono::Utc::now()));
+        let mut mgr = SessionManager::new();
+        mgr.set_time_source(clock.clone());
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr.create_session("laptop-7", &secret_a, &pub_b).unwrap();
+        mgr.encrypt(&info.session_id, b"one").unwrap();
+        mgr.encrypt(&info.session_id, b"two").unwrap();
+
+        clock.advance(chrono::Duration::seconds(312));
+        let summaries = mgr.session_summaries();
+        assert_eq!(summaries.len(), 1);
+        let line = &summaries[0];
+        assert!(line.starts_with(&info.session_id[..8]));
+        for part in [
+            "peer=laptop-7",
+            "role=initiator",
+            "state=established",
+            "age=5m12s",
+            "expires_in=54m48s",
+            "sent=2",
+            "received=0",
+            "cipher=aes-256-gcm",
+        ] {
+            assert!(line.contains(part), "{line} lacks {part}");
+        }
+
+        let info = mgr.all_sessions().remove(0);
+        assert_eq!(info.summary(clock.as_ref()), *line);
+        clock.advance(chrono::Duration::hours(2));
+        assert!(info.summary(clock.as_ref()).contains("expires_in=expired"));
+    }
+
+    #[test]
+    fn test_all_sessions_includes_expired() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let live = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+        let dead = mgr.create_session("peer-2", &secret_a, &pub_b).unwrap();
+
+        mgr.sessions
+            .get_mut(&dead.session_id)
+            .unwrap()
+            .get_mut()
+            .unwrap()
+            .expires_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+
+        let active = mgr.active_sessions();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].session_id, live.session_id);
+
+        let all = mgr.all_sessions();
+        assert_eq!(all.len(), 2);
+        let expired = all
+            .iter()
+            .find(|s| s.session_id == dead.session_id)
+            .unwrap();
+        assert_eq!(expired.state, "expired");
+    }
+
+    #[test]
+    fn test_nonce_modes_roundtrip() {
+        for mode in [NonceMode::Counter, NonceMode::Random] {
+            let (secret_a, pub_a) = create_keypair();
+            let (secret_b, pub_b) = create_keypair();
+            let options = SessionOptions {
+                nonce_mode: mode,
+                ..Default::default()
+            };
+
+            let mut alice = SessionManager::new();
+            let mut bob = SessionManager::new();
+            let sa = alice
+                .create_session_with("bob", &secret_a, &pub_b, &options)
+                .unwrap();
+            let sb = bob.accept_session("alice", &secret_b, &pub_a).unwrap();
+
+            let frame = alice.encrypt(&sa.session_id, b"mode test").unwrap();
+            assert_eq!(bob.decrypt(&sb.session_id, &frame).unwrap(), b"mode test");
+        }
+    }
+
+    #[test]
+    fn test_random_nonces_distinct() {
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let mut mgr = SessionManager::new();
+        let info = mgr
+            .create_session_with(
+                "bob",
+                &secret_a,
+                &pub_b,
+                &SessionOptions {
+                    nonce_mode: NonceMode::Random,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let nonces: std::collections::HashSet<Vec<u8>> = (0..64)
+            .map(|_| mgr.encrypt(&info.session_id, b"x").unwrap()[..12].to_vec())
+            .collect();
+        assert_eq!(nonces.len(), 64);
+        // No counter layout: the role prefix is absent
+        assert!(nonces.iter().all(|n| &n[..4] != b"INIT"));
+    }
+
+    #[test]
+    fn test_empty_plaintext_keepalive() {
+        let mut mgr = SessionManager::new();
+        let (secret_a, _) = create_keypair();
+        let (_, pub_b) = create_keypair();
+        let info = mgr.create_session("peer-1", &secret_a, &pub_b).unwrap();
+
+        let keepalive = mgr.encrypt(&info.session_id, b"").unwrap();
+        assert_eq!(keepalive.len(), 12 + 16);
+        assert!(mgr
+            .decrypt(&info.session_id, &keepalive)
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn test_decrypt_invalid_data() {
         let mut mgr = SessionManager::new();
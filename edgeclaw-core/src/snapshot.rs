@@ -0,0 +1,191 @@
+//! Encrypted engine snapshots for backup and migration.
+//!
+//! Container layout:
+//! ┌──────────┬─────────┬──────────┬──────────┬──────────────────────┐
+//! │  Magic   │ Version │   Salt   │  Nonce   │ AES-256-GCM(JSON)    │
+//! │ 4 bytes  │ 1 byte  │ 16 bytes │ 12 bytes │   N bytes            │
+//! └──────────┴─────────┴──────────┴──────────┴──────────────────────┘
+//!
+//! The key is derived from the passphrase with PBKDF2-HMAC-SHA256.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::error::EdgeClawError;
+use crate::identity::{IdentitySnapshot, DEFAULT_IDENTITY_LABEL};
+use crate::peer::PeerInfo;
+use crate::session::SessionSnapshot;
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"ECSS";
+const SNAPSHOT_VERSION: u8 = 0x01;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const HEADER_SIZE: usize = 4 + 1 + SALT_SIZE + NONCE_SIZE;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Full engine state captured by `EdgeClawEngine::export_snapshot`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EngineSnapshot {
+    /// Older snapshots held a single optional `identity`
+    #[serde(default, alias = "identity", deserialize_with = "identity_list")]
+    pub identities: Vec<IdentitySnapshot>,
+    /// Label of the identity that was active at export
+    #[serde(default = "default_active_identity")]
+    pub active_identity: String,
+    pub peers: Vec<PeerInfo>,
+    pub sessions: Vec<SessionSnapshot>,
+    pub denied_capabilities: Vec<String>,
+    /// IDs of revoked attestations (absent in older snapshots)
+    #[serde(default)]
+    pub revoked_attestations: Vec<String>,
+    /// Capabilities revoked for specific roles, with those roles (absent
+    /// in older snapshots)
+    #[serde(default)]
+    pub revoked_capabilities: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+/// `identities` as written now, or the single `identity` of older snapshots
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum IdentityList {
+    Many(Vec<IdentitySnapshot>),
+    One(Option<IdentitySnapshot>),
+}
+
+fn identity_list<'de, D>(deserializer: D) -> Result<Vec<IdentitySnapshot>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        match <IdentityList as serde::Deserialize>::deserialize(deserializer)? {
+            IdentityList::Many(list) => list,
+            IdentityList::One(one) => one.into_iter().collect(),
+        },
+    )
+}
+
+fn default_active_identity() -> String {
+    DEFAULT_IDENTITY_LABEL.to_string()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Serialize and encrypt a snapshot under `passphrase`
+pub fn seal(snapshot: &EngineSnapshot, passphrase: &str) -> Result<Vec<u8>, EdgeClawError> {
+    if passphrase.is_empty() {
+        return Err(EdgeClawError::InvalidParameter);
+    }
+
+    let mut salt = [0u8; SALT_SIZE];
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+    let plaintext = serde_json::to_vec(snapshot)?;
+    let cipher = Aes256Gcm::new_from_slice(&derive_key(passphrase, &salt))
+        .map_err(|_| EdgeClawError::CryptoError)?;
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_slice())?;
+
+    let mut blob = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+    blob.extend_from_slice(SNAPSHOT_MAGIC);
+    blob.push(SNAPSHOT_VERSION);
+    blob.extend_from_slice(&salt);
+    blob.extend_from_slice(&nonce_bytes);
+    blob.extend_from_slice(&ciphertext);
+    Ok(blob)
+}
+
+/// Decrypt and parse a snapshot.
+///
+/// A wrong passphrase or any tampering fails authentication (`CryptoError`);
+/// an unrecognized container is `InvalidParameter`.
+pub fn open(blob: &[u8], passphrase: &str) -> Result<EngineSnapshot, EdgeClawError> {
+    if blob.len() < HEADER_SIZE || &blob[..4] != SNAPSHOT_MAGIC {
+        return Err(EdgeClawError::InvalidParameter);
+    }
+    if blob[4] != SNAPSHOT_VERSION {
+        return Err(EdgeClawError::InvalidParameter);
+    }
+
+    let salt = &blob[5..5 + SALT_SIZE];
+    let nonce = &blob[5 + SALT_SIZE..HEADER_SIZE];
+    let cipher = Aes256Gcm::new_from_slice(&derive_key(passphrase, salt))
+        .map_err(|_| EdgeClawError::CryptoError)?;
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), &blob[HEADER_SIZE..])?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> EngineSnapshot {
+        EngineSnapshot {
+            identities: vec![],
+            active_identity: "default".into(),
+            peers: vec![],
+            sessions: vec![],
+            denied_capabilities: vec!["shell_exec".into()],
+            revoked_attestations: vec![],
+            revoked_capabilities: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let blob = seal(&sample(), "correct horse").unwrap();
+        assert_eq!(&blob[..4], SNAPSHOT_MAGIC);
+
+        let restored = open(&blob, "correct horse").unwrap();
+        assert_eq!(restored.denied_capabilities, vec!["shell_exec"]);
+    }
+
+    #[test]
+    fn test_open_rejects_bad_input() {
+        let mut blob = seal(&sample(), "pw").unwrap();
+        assert!(matches!(
+            open(&blob, "wrong"),
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert!(matches!(
+            open(&blob[..10], "pw"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+
+        let last = blob.len() - 1;
+        blob[last] ^= 0xFF;
+        assert!(matches!(open(&blob, "pw"), Err(EdgeClawError::CryptoError)));
+    }
+
+    #[test]
+    fn test_reads_single_identity_snapshots() {
+        let mut mgr = crate::identity::IdentityManager::new();
+        let id = mgr.generate_identity().unwrap();
+        let mut legacy = serde_json::to_value(sample()).unwrap();
+        let fields = legacy.as_object_mut().unwrap();
+        fields.remove("identities");
+        fields.remove("active_identity");
+        fields.insert(
+            "identity".into(),
+            serde_json::to_value(&mgr.export_identities()[0]).unwrap(),
+        );
+
+        let restored: EngineSnapshot = serde_json::from_value(legacy.clone()).unwrap();
+        assert_eq!(restored.identities.len(), 1);
+        assert_eq!(restored.identities[0].identity.device_id, id.device_id);
+        assert_eq!(restored.active_identity, DEFAULT_IDENTITY_LABEL);
+
+        legacy["identity"] = serde_json::Value::Null;
+        let restored: EngineSnapshot = serde_json::from_value(legacy).unwrap();
+        assert!(restored.identities.is_empty());
+    }
+}
@@ -3,13 +3,22 @@
 //! Provides `SyncClient` for TCP-based synchronization with an EdgeClaw
 //! Desktop agent, supporting config sync, status push, and remote execution.
 
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
-use crate::ecnp::{EcnpCodec, EcnpMessage};
+use crate::ecnp::{
+    self, BoundedFrame, Compression, EcnpCodec, EcnpMessage, DEFAULT_COMPRESSION_MIN_BYTES,
+    FLAG_CBOR,
+};
 use crate::error::EdgeClawError;
+use crate::kex::{KeyExchange, KeyExchangeKeypair, X25519KeyExchange};
 use crate::protocol::MessageType;
+use crate::session::{SessionManager, SessionOptions, SessionRole};
 
 // ─── Sync message type codes (0x10–0x1F reserved) ───
 
@@ -18,10 +27,80 @@ pub const SYNC_CONFIG: u8 = 0x10;
 pub const SYNC_REMOTE_EXEC: u8 = 0x11;
 pub const SYNC_STATUS_PUSH: u8 = 0x12;
 pub const SYNC_REMOTE_EXEC_RESULT: u8 = 0x13;
+pub const SYNC_REMOTE_EXEC_CANCEL: u8 = 0x14;
+
+/// Every sync sub-type this build can decode
+pub const SYNC_TYPES: [u8; 5] = [
+    SYNC_CONFIG,
+    SYNC_REMOTE_EXEC,
+    SYNC_STATUS_PUSH,
+    SYNC_REMOTE_EXEC_RESULT,
+    SYNC_REMOTE_EXEC_CANCEL,
+];
+
+/// Capability names advertised in the sync handshake
+pub const CAP_CONFIG_SYNC: &str = "config_sync";
+pub const CAP_REMOTE_EXEC: &str = "remote_exec";
+pub const CAP_STATUS_PUSH: &str = "status_push";
+
+/// Every sync capability this build supports
+pub const SYNC_CAPABILITIES: [&str; 3] = [CAP_CONFIG_SYNC, CAP_REMOTE_EXEC, CAP_STATUS_PUSH];
+
+/// Version of the sync message body layout, sent as the `v` field.
+/// Bodies without `v` predate versioning and are read as version 1.
+pub const SYNC_WIRE_VERSION: u8 = 1;
+
+/// Domain separator for the desktop's handshake challenge signature
+const SYNC_AUTH_CONTEXT: &[u8] = b"edgeclaw-sync-auth-v1:";
+const SYNC_CHALLENGE_SIZE: usize = 32;
+
+/// Bytes the desktop signs to answer the handshake `challenge`
+pub fn sync_challenge_message(challenge: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(SYNC_AUTH_CONTEXT.len() + challenge.len());
+    msg.extend_from_slice(SYNC_AUTH_CONTEXT);
+    msg.extend_from_slice(challenge);
+    msg
+}
+
+/// Scheme named in the channel upgrade Control frames
+pub const SYNC_UPGRADE_SCHEME: &str = "x25519-aes256gcm";
+/// Domain separator for the desktop's signature over the upgrade keys
+const SYNC_UPGRADE_CONTEXT: &[u8] = b"edgeclaw-sync-upgrade-v1:";
+/// Lifetime of an upgraded channel's key; older connections must reconnect
+const SYNC_CHANNEL_DURATION_SECS: u64 = 24 * 3600;
+
+/// Bytes the desktop signs in its upgrade reply when it is pinned: both
+/// ephemeral public keys, so neither can be swapped in transit
+pub fn sync_upgrade_message(client_public: &[u8], desktop_public: &[u8]) -> Vec<u8> {
+    [SYNC_UPGRADE_CONTEXT, client_public, desktop_public].concat()
+}
+
+/// Algorithm prefix of a canonical `ConfigSync.config_hash`
+const CONFIG_HASH_PREFIX: &str = "sha256:";
+
+/// Canonical `ConfigSync.config_hash`: `sha256:` followed by the lowercase
+/// hex SHA-256 of `config_data`'s UTF-8 bytes, exactly as sent (no
+/// whitespace or key-order normalisation). Desktop and mobile must both
+/// use this.
+pub fn compute_config_hash(config_data: &str) -> String {
+    let digest = Sha256::digest(config_data.as_bytes());
+    format!("{CONFIG_HASH_PREFIX}{}", hex::encode(digest))
+}
 
 // ─── Sync message payloads ───
 
-/// Synchronization message — wraps the three sync sub-types.
+/// Body encoding for sync messages; CBOR frames carry `FLAG_CBOR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerializationFormat {
+    /// Human-readable, the default
+    #[default]
+    Json,
+    /// Compact binary, for metered links
+    Cbor,
+}
+
+/// Synchronization message — wraps the sync sub-types.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum SyncMessage {
@@ -34,7 +113,13 @@ pub enum SyncMessage {
 
     /// Mobile → Desktop: request remote command execution
     #[serde(rename = "remote_exec")]
-    RemoteExec { command: String, args: Vec<String> },
+    RemoteExec {
+        command: String,
+        args: Vec<String>,
+        /// Correlates results and cancels; empty from senders that predate it
+        #[serde(default)]
+        request_id: String,
+    },
 
     /// Desktop → Mobile: system status push
     #[serde(rename = "status_push")]
@@ -54,20 +139,306 @@ pub enum SyncMessage {
         exit_code: i32,
         stdout: String,
         stderr: String,
+        /// Set when stdout or stderr was cut to the output size limit
+        #[serde(default)]
+        truncated: bool,
+        /// How `stdout`/`stderr` are encoded; binary output travels as base64
+        #[serde(default)]
+        encoding: OutputEncoding,
+        /// `request_id` of the `RemoteExec` this answers, if the desktop echoes it
+        #[serde(default)]
+        request_id: String,
     },
+
+    /// Either direction: abort an in-flight remote execution
+    #[serde(rename = "remote_exec_cancel")]
+    RemoteExecCancel { request_id: String },
+}
+
+/// Lifecycle of a remote exec request sent by this client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecRequestState {
+    /// Sent, no result yet
+    Pending,
+    /// Cancelled locally or by the desktop; a late result is still delivered
+    Cancelled,
+}
+
+/// Serialized form of a message: the body plus its wire version
+#[derive(Serialize)]
+struct VersionedMessage<'a> {
+    v: u8,
+    #[serde(flatten)]
+    msg: &'a SyncMessage,
+}
+
+/// Just the version of an incoming body, read before the body itself
+#[derive(Deserialize)]
+struct WireVersion {
+    #[serde(default = "legacy_wire_version")]
+    v: u8,
+}
+
+fn legacy_wire_version() -> u8 {
+    1
+}
+
+impl WireVersion {
+    fn check(self) -> Result<(), EdgeClawError> {
+        if self.v != SYNC_WIRE_VERSION {
+            tracing::warn!(version = self.v, "Rejecting unsupported sync wire version");
+            return Err(EdgeClawError::UnsupportedSyncVersion { version: self.v });
+        }
+        Ok(())
+    }
+}
+
+/// Encoding of remote exec output streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputEncoding {
+    /// Plain UTF-8 text
+    #[default]
+    Utf8,
+    /// Standard base64 of raw bytes (output was not valid UTF-8)
+    Base64,
+}
+
+/// Default per-stream cap on remote exec output (256 KiB), keeping a result
+/// with both streams full well under the 1 MB ECNP payload limit
+pub const DEFAULT_MAX_EXEC_OUTPUT_BYTES: usize = 256 * 1024;
+
+/// Cut `text` to at most `max_bytes`, on a UTF-8 character boundary
+fn truncate_utf8(text: &mut String, max_bytes: usize) -> bool {
+    if text.len() <= max_bytes {
+        return false;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
 }
 
 impl SyncMessage {
-    /// Serialize to JSON bytes
+    /// Build a config sync carrying its canonical `compute_config_hash`
+    pub fn config_sync(config_data: &str) -> Self {
+        SyncMessage::ConfigSync {
+            config_hash: compute_config_hash(config_data),
+            config_data: config_data.to_string(),
+        }
+    }
+
+    /// Check a `ConfigSync` hash against its data; `CryptoError` on a
+    /// mismatch. Other messages always pass.
+    pub fn verify_config_hash(&self) -> Result<(), EdgeClawError> {
+        match self {
+            SyncMessage::ConfigSync {
+                config_hash,
+                config_data,
+            } if *config_hash != compute_config_hash(config_data) => {
+                tracing::warn!(config_hash = %config_hash, "Config sync hash mismatch");
+                Err(EdgeClawError::CryptoError)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Build a remote exec result, truncating each stream to `max_output_bytes`
+    pub fn remote_exec_result(
+        command: &str,
+        exit_code: i32,
+        stdout: String,
+        stderr: String,
+        max_output_bytes: usize,
+    ) -> Self {
+        let mut msg = SyncMessage::RemoteExecResult {
+            command: command.to_string(),
+            exit_code,
+            stdout,
+            stderr,
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: String::new(),
+        };
+        msg.truncate_output(max_output_bytes);
+        msg
+    }
+
+    /// Build a remote exec result from raw output bytes.
+    ///
+    /// Valid UTF-8 output is sent as text; otherwise both streams are
+    /// base64-encoded. Either way each encoded stream is at most
+    /// `max_output_bytes` long.
+    pub fn remote_exec_result_bytes(
+        command: &str,
+        exit_code: i32,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        max_output_bytes: usize,
+    ) -> Self {
+        use base64::Engine as _;
+
+        match (String::from_utf8(stdout), String::from_utf8(stderr)) {
+            (Ok(stdout), Ok(stderr)) => {
+                Self::remote_exec_result(command, exit_code, stdout, stderr, max_output_bytes)
+            }
+            (stdout, stderr) => {
+                let mut stdout = stdout
+                    .map(String::into_bytes)
+                    .unwrap_or_else(|e| e.into_bytes());
+                let mut stderr = stderr
+                    .map(String::into_bytes)
+                    .unwrap_or_else(|e| e.into_bytes());
+                // Cut raw bytes so the base64 text stays within the limit
+                let raw_limit = max_output_bytes / 4 * 3;
+                let truncated = stdout.len() > raw_limit || stderr.len() > raw_limit;
+                stdout.truncate(raw_limit);
+                stderr.truncate(raw_limit);
+
+                let b64 = base64::engine::general_purpose::STANDARD;
+                SyncMessage::RemoteExecResult {
+                    command: command.to_string(),
+                    exit_code,
+                    stdout: b64.encode(stdout),
+                    stderr: b64.encode(stderr),
+                    truncated,
+                    encoding: OutputEncoding::Base64,
+                    request_id: String::new(),
+                }
+            }
+        }
+    }
+
+    /// Tag a remote exec result with the `request_id` it answers
+    pub fn with_request_id(mut self, id: &str) -> Self {
+        if let SyncMessage::RemoteExecResult { request_id, .. } = &mut self {
+            *request_id = id.to_string();
+        }
+        self
+    }
+
+    /// Raw stdout and stderr bytes of a remote exec result, decoding base64
+    /// output. Other messages return `InvalidParameter`.
+    pub fn exec_output_bytes(&self) -> Result<(Vec<u8>, Vec<u8>), EdgeClawError> {
+        use base64::Engine as _;
+
+        match self {
+            SyncMessage::RemoteExecResult {
+                stdout,
+                stderr,
+                encoding: OutputEncoding::Utf8,
+                ..
+            } => Ok((stdout.clone().into_bytes(), stderr.clone().into_bytes())),
+            SyncMessage::RemoteExecResult {
+                stdout,
+                stderr,
+                encoding: OutputEncoding::Base64,
+                ..
+            } => {
+                let b64 = base64::engine::general_purpose::STANDARD;
+                let decode = |s: &str| b64.decode(s).map_err(|_| EdgeClawError::SerializationError);
+                Ok((decode(stdout)?, decode(stderr)?))
+            }
+            _ => Err(EdgeClawError::InvalidParameter),
+        }
+    }
+
+    /// Cap the stdout/stderr of a remote exec result at `max_bytes` each,
+    /// setting `truncated` if anything was cut. Other messages are untouched.
+    /// Returns whether this call truncated anything.
+    pub fn truncate_output(&mut self, max_bytes: usize) -> bool {
+        match self {
+            SyncMessage::RemoteExecResult {
+                stdout,
+                stderr,
+                truncated,
+                encoding,
+                ..
+            } => {
+                // Base64 is cut on a 4-character group so it still decodes
+                let max_bytes = match encoding {
+                    OutputEncoding::Utf8 => max_bytes,
+                    OutputEncoding::Base64 => max_bytes / 4 * 4,
+                };
+                let cut_stdout = truncate_utf8(stdout, max_bytes);
+                let cut_stderr = truncate_utf8(stderr, max_bytes);
+                *truncated |= cut_stdout || cut_stderr;
+                cut_stdout || cut_stderr
+            }
+            _ => false,
+        }
+    }
+
+    fn versioned(&self) -> VersionedMessage<'_> {
+        VersionedMessage {
+            v: SYNC_WIRE_VERSION,
+            msg: self,
+        }
+    }
+
+    /// Serialize to JSON bytes, tagged with `SYNC_WIRE_VERSION`
     pub fn to_bytes(&self) -> Result<Vec<u8>, EdgeClawError> {
-        serde_json::to_vec(self).map_err(EdgeClawError::from)
+        serde_json::to_vec(&self.versioned()).map_err(EdgeClawError::from)
     }
 
-    /// Deserialize from JSON bytes
+    /// Deserialize from JSON bytes. A version other than
+    /// `SYNC_WIRE_VERSION` fails with `UnsupportedSyncVersion`.
     pub fn from_bytes(data: &[u8]) -> Result<Self, EdgeClawError> {
+        serde_json::from_slice::<WireVersion>(data)?.check()?;
         serde_json::from_slice(data).map_err(EdgeClawError::from)
     }
 
+    /// Serialize in the given format
+    pub fn to_bytes_as(&self, format: SerializationFormat) -> Result<Vec<u8>, EdgeClawError> {
+        match format {
+            SerializationFormat::Json => self.to_bytes(),
+            SerializationFormat::Cbor => {
+                let mut out = Vec::new();
+                ciborium::into_writer(&self.versioned(), &mut out)
+                    .map_err(|_| EdgeClawError::SerializationError)?;
+                Ok(out)
+            }
+        }
+    }
+
+    /// Deserialize from the given format
+    pub fn from_bytes_as(data: &[u8], format: SerializationFormat) -> Result<Self, EdgeClawError> {
+        match format {
+            SerializationFormat::Json => Self::from_bytes(data),
+            SerializationFormat::Cbor => {
+                ciborium::from_reader::<WireVersion, _>(data)
+                    .map_err(|_| EdgeClawError::SerializationError)?
+                    .check()?;
+                ciborium::from_reader(data).map_err(|_| EdgeClawError::SerializationError)
+            }
+        }
+    }
+
+    /// Carries configuration or commands/output, so it should only travel
+    /// over an encrypted channel
+    pub fn is_sensitive(&self) -> bool {
+        matches!(
+            self,
+            SyncMessage::ConfigSync { .. }
+                | SyncMessage::RemoteExec { .. }
+                | SyncMessage::RemoteExecResult { .. }
+        )
+    }
+
+    /// Handshake capability this message belongs to
+    pub fn capability(&self) -> &'static str {
+        match self {
+            SyncMessage::ConfigSync { .. } => CAP_CONFIG_SYNC,
+            SyncMessage::StatusPush { .. } => CAP_STATUS_PUSH,
+            SyncMessage::RemoteExec { .. }
+            | SyncMessage::RemoteExecResult { .. }
+            | SyncMessage::RemoteExecCancel { .. } => CAP_REMOTE_EXEC,
+        }
+    }
+
     /// Get the sync sub-type code for ECNP framing
     pub fn sync_type_code(&self) -> u8 {
         match self {
@@ -75,31 +446,159 @@ impl SyncMessage {
             SyncMessage::RemoteExec { .. } => SYNC_REMOTE_EXEC,
             SyncMessage::StatusPush { .. } => SYNC_STATUS_PUSH,
             SyncMessage::RemoteExecResult { .. } => SYNC_REMOTE_EXEC_RESULT,
+            SyncMessage::RemoteExecCancel { .. } => SYNC_REMOTE_EXEC_CANCEL,
         }
     }
 
+    /// Uncompressed wire size of `encode_ecnp()`, computed without building the frame
+    pub fn estimated_frame_size(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        // Serializing these plain structs cannot fail
+        let _ = serde_json::to_writer(&mut counter, &self.versioned());
+        EcnpCodec::encoded_size(1 + counter.0)
+    }
+
     /// Encode into an ECNP Data frame (with sync sub-type prefix)
     pub fn encode_ecnp(&self) -> Result<Vec<u8>, EdgeClawError> {
-        let json_bytes = self.to_bytes()?;
-        // Prefix the payload with the sync sub-type byte
-        let mut payload = Vec::with_capacity(1 + json_bytes.len());
-        payload.push(self.sync_type_code());
-        payload.extend_from_slice(&json_bytes);
-        EcnpCodec::encode(MessageType::Data, &payload)
+        self.encode_ecnp_with(Compression::None)
+    }
+
+    /// Encode into an ECNP Data frame, compressing the payload if requested
+    pub fn encode_ecnp_with(&self, compression: Compression) -> Result<Vec<u8>, EdgeClawError> {
+        self.encode_ecnp_as(SerializationFormat::Json, compression)
+    }
+
+    /// Encode into an ECNP Data frame with the given body format and compression
+    pub fn encode_ecnp_as(
+        &self,
+        format: SerializationFormat,
+        compression: Compression,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.encode_ecnp_with_threshold(format, compression, DEFAULT_COMPRESSION_MIN_BYTES)
+    }
+
+    /// `encode_ecnp_as`, compressing only payloads of at least
+    /// `compression_min_bytes`
+    pub fn encode_ecnp_with_threshold(
+        &self,
+        format: SerializationFormat,
+        compression: Compression,
+        compression_min_bytes: usize,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        SyncFrame {
+            sync_type: self.sync_type_code(),
+            format,
+            body: self.to_bytes_as(format)?,
+        }
+        .encode_with_threshold(compression, compression_min_bytes)
     }
 
     /// Decode from an ECNP Data frame
     pub fn decode_ecnp(frame: &[u8]) -> Result<(u8, Self), EdgeClawError> {
-        let msg: EcnpMessage = EcnpCodec::decode(frame)?;
-        if msg.msg_type != MessageType::Data as u8 {
-            return Err(EdgeClawError::InvalidParameter);
+        Self::from_ecnp_message(&EcnpCodec::decode(frame)?)
+    }
+
+    /// Parse an already-decoded ECNP Data message.
+    ///
+    /// The sub-type byte is checked against `SYNC_TYPES` before the body is
+    /// parsed; anything else fails with `UnknownSyncType`.
+    pub fn from_ecnp_message(msg: &EcnpMessage) -> Result<(u8, Self), EdgeClawError> {
+        let frame = SyncFrame::from_ecnp_message(msg)?;
+        if !SYNC_TYPES.contains(&frame.sync_type) {
+            tracing::warn!(
+                code = frame.sync_type,
+                "Rejecting unknown sync message type"
+            );
+            return Err(EdgeClawError::UnknownSyncType {
+                code: frame.sync_type,
+            });
         }
-        if msg.payload.is_empty() {
+        let sync_msg = Self::from_bytes_as(&frame.body, frame.format)?;
+        Ok((frame.sync_type, sync_msg))
+    }
+}
+
+/// A sync sub-type byte and its serialized body: the one place that knows
+/// how sync payloads sit inside ECNP Data frames.
+///
+/// Layout: `[sync_type][body]` as the Data payload, with `FLAG_CBOR` set
+/// for CBOR bodies. The sub-type is not checked here, so framing works for
+/// types this build cannot parse yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncFrame {
+    pub sync_type: u8,
+    pub format: SerializationFormat,
+    pub body: Vec<u8>,
+}
+
+impl SyncFrame {
+    /// Encode as an uncompressed ECNP Data frame
+    pub fn encode(&self) -> Result<Vec<u8>, EdgeClawError> {
+        self.encode_with_threshold(Compression::None, DEFAULT_COMPRESSION_MIN_BYTES)
+    }
+
+    /// Encode as an ECNP Data frame, compressing payloads of at least
+    /// `compression_min_bytes`
+    pub fn encode_with_threshold(
+        &self,
+        compression: Compression,
+        compression_min_bytes: usize,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let mut payload = Vec::with_capacity(1 + self.body.len());
+        payload.push(self.sync_type);
+        payload.extend_from_slice(&self.body);
+        let flags = match self.format {
+            SerializationFormat::Json => 0,
+            SerializationFormat::Cbor => FLAG_CBOR,
+        };
+        EcnpCodec::encode_flagged_with_threshold(
+            MessageType::Data,
+            &payload,
+            compression,
+            flags,
+            compression_min_bytes,
+        )
+    }
+
+    /// Decode from an ECNP Data frame
+    pub fn decode(frame: &[u8]) -> Result<Self, EdgeClawError> {
+        Self::from_ecnp_message(&EcnpCodec::decode(frame)?)
+    }
+
+    /// Split an already-decoded ECNP Data message; anything else, or an
+    /// empty payload, is `InvalidParameter`
+    pub fn from_ecnp_message(msg: &EcnpMessage) -> Result<Self, EdgeClawError> {
+        if msg.msg_type != MessageType::Data as u8 {
             return Err(EdgeClawError::InvalidParameter);
         }
-        let sync_type = msg.payload[0];
-        let sync_msg = Self::from_bytes(&msg.payload[1..])?;
-        Ok((sync_type, sync_msg))
+        let (&sync_type, body) = msg
+            .payload
+            .split_first()
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        let format = if msg.flags & FLAG_CBOR != 0 {
+            SerializationFormat::Cbor
+        } else {
+            SerializationFormat::Json
+        };
+        Ok(Self {
+            sync_type,
+            format,
+            body: body.to_vec(),
+        })
+    }
+}
+
+/// `io::Write` sink that only counts bytes
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
@@ -114,6 +613,10 @@ pub enum SyncConnectionState {
     Connected,
     Syncing,
     Error,
+    /// Too many consecutive connect failures; attempts paused for a cooldown
+    CircuitOpen,
+    /// Suspended by `pause` (e.g. app backgrounded) until `resume`
+    Paused,
 }
 
 impl std::fmt::Display for SyncConnectionState {
@@ -125,12 +628,26 @@ impl std::fmt::Display for SyncConnectionState {
             Self::Connected => write!(f, "connected"),
             Self::Syncing => write!(f, "syncing"),
             Self::Error => write!(f, "error"),
+            Self::CircuitOpen => write!(f, "circuit_open"),
+            Self::Paused => write!(f, "paused"),
         }
     }
 }
 
 // ─── Sync client configuration ───
 
+/// What `next_message` does with a frame it cannot accept (oversized,
+/// undecodable, not negotiated, replayed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadMessagePolicy {
+    /// Log it, count it in `messages_skipped` and read the next frame
+    #[default]
+    Skip,
+    /// Return the error so the caller can drop the connection
+    Disconnect,
+}
+
 /// Configuration for the sync client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncClientConfig {
@@ -142,10 +659,120 @@ pub struct SyncClientConfig {
     pub status_interval_secs: u64,
     /// Connection timeout (seconds)
     pub connect_timeout_secs: u64,
+    /// Maximum wait for an incoming frame, including the handshake ack (seconds)
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout_secs: u64,
+    /// Maximum time to write and flush one outgoing frame (seconds)
+    #[serde(default = "default_write_timeout")]
+    pub write_timeout_secs: u64,
+    /// Maximum wait for a remote exec result (seconds)
+    #[serde(default = "default_exec_result_timeout")]
+    pub exec_result_timeout_secs: u64,
     /// Auto-reconnect on disconnect
     pub auto_reconnect: bool,
     /// Maximum reconnect attempts (0 = unlimited)
     pub max_reconnect_attempts: u32,
+    /// Compression algorithms offered in the handshake, in preference order
+    #[serde(default = "default_compression")]
+    pub compression: Vec<Compression>,
+    /// Outgoing payloads shorter than this are never compressed
+    #[serde(default = "default_compression_min_bytes")]
+    pub compression_min_bytes: usize,
+    /// Anomaly thresholds for incoming status pushes (None = no analysis)
+    #[serde(default)]
+    pub status_thresholds: Option<StatusThresholds>,
+    /// Body encoding for outgoing sync messages
+    #[serde(default)]
+    pub format: SerializationFormat,
+    /// Per-stream cap on remote exec stdout/stderr, enforced on incoming
+    /// results (default `DEFAULT_MAX_EXEC_OUTPUT_BYTES`)
+    #[serde(default = "default_max_exec_output")]
+    pub max_exec_output_bytes: usize,
+    /// Peer ID of the paired desktop. When set, the desktop must sign the
+    /// handshake challenge with the Ed25519 key pinned for this peer, and
+    /// connecting fails until that key is pinned. Leave it unset only on
+    /// trusted networks: without it or `set_desktop_key`, the desktop is
+    /// not authenticated at all.
+    #[serde(default)]
+    pub desktop_peer_id: Option<String>,
+    /// Consecutive connect failures that open the circuit breaker (0 = never)
+    #[serde(default = "default_breaker_threshold")]
+    pub breaker_failure_threshold: u32,
+    /// How long an open breaker refuses connects before allowing a trial (seconds)
+    #[serde(default = "default_breaker_cooldown")]
+    pub breaker_cooldown_secs: u64,
+    /// Capabilities advertised in the handshake (see `SYNC_CAPABILITIES`);
+    /// messages outside the negotiated set are refused
+    #[serde(default = "default_capabilities")]
+    pub capabilities: Vec<String>,
+    /// Frame digests remembered to reject exact duplicates, whether frames
+    /// come through `process_incoming` or are read from the connection
+    /// (0 = replay cache disabled)
+    #[serde(default)]
+    pub replay_cache_size: usize,
+    /// How long a seen frame counts as a replay (seconds)
+    #[serde(default = "default_replay_window")]
+    pub replay_window_secs: u64,
+    /// Largest incoming sync payload accepted; bigger frames are discarded
+    /// (capped by the 1 MB ECNP maximum)
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// Handling of bad frames in `next_message`
+    #[serde(default)]
+    pub bad_message_policy: BadMessagePolicy,
+    /// Refuse to build sensitive frames (config, remote exec) until
+    /// `upgrade` has set up the encrypted channel (default on; turn off
+    /// only for desktops that cannot upgrade)
+    #[serde(default = "default_require_encrypted_channel")]
+    pub require_encrypted_channel: bool,
+}
+
+fn default_require_encrypted_channel() -> bool {
+    true
+}
+
+fn default_max_message_bytes() -> usize {
+    ecnp::MAX_PAYLOAD_SIZE
+}
+
+fn default_replay_window() -> u64 {
+    300
+}
+
+fn default_capabilities() -> Vec<String> {
+    SYNC_CAPABILITIES.iter().map(|c| c.to_string()).collect()
+}
+
+fn default_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown() -> u64 {
+    60
+}
+
+fn default_compression() -> Vec<Compression> {
+    vec![Compression::Zstd, Compression::None]
+}
+
+fn default_compression_min_bytes() -> usize {
+    DEFAULT_COMPRESSION_MIN_BYTES
+}
+
+fn default_max_exec_output() -> usize {
+    DEFAULT_MAX_EXEC_OUTPUT_BYTES
+}
+
+fn default_read_timeout() -> u64 {
+    30
+}
+
+fn default_write_timeout() -> u64 {
+    10
+}
+
+fn default_exec_result_timeout() -> u64 {
+    60
 }
 
 impl Default for SyncClientConfig {
@@ -155,636 +782,3578 @@ impl Default for SyncClientConfig {
             heartbeat_interval_secs: 30,
             status_interval_secs: 30,
             connect_timeout_secs: 10,
+            read_timeout_secs: default_read_timeout(),
+            write_timeout_secs: default_write_timeout(),
+            exec_result_timeout_secs: default_exec_result_timeout(),
             auto_reconnect: true,
             max_reconnect_attempts: 0,
+            compression: default_compression(),
+            compression_min_bytes: default_compression_min_bytes(),
+            status_thresholds: None,
+            format: SerializationFormat::Json,
+            max_exec_output_bytes: default_max_exec_output(),
+            desktop_peer_id: None,
+            breaker_failure_threshold: default_breaker_threshold(),
+            breaker_cooldown_secs: default_breaker_cooldown(),
+            capabilities: default_capabilities(),
+            replay_cache_size: 0,
+            replay_window_secs: default_replay_window(),
+            max_message_bytes: default_max_message_bytes(),
+            bad_message_policy: BadMessagePolicy::Skip,
+            require_encrypted_channel: default_require_encrypted_channel(),
         }
     }
 }
 
-// ─── Sync client stats ───
+/// Pick the best compression algorithm both sides support.
+///
+/// `local` is in preference order; the first entry also offered by the
+/// remote wins. With no overlap the session falls back to `None`.
+pub fn negotiate_compression(local: &[Compression], remote: &[Compression]) -> Compression {
+    local
+        .iter()
+        .copied()
+        .find(|c| remote.contains(c))
+        .unwrap_or(Compression::None)
+}
 
-/// Runtime statistics for the sync client
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SyncStats {
-    pub messages_sent: u64,
-    pub messages_received: u64,
-    pub reconnect_count: u32,
-    pub last_config_hash: Option<String>,
-    pub last_status_push: Option<String>,
+/// Check the desktop's `challenge_signature` in a handshake ack
+fn verify_ack_signature(
+    payload: &[u8],
+    desktop_key: &VerifyingKey,
+    challenge: &[u8],
+) -> Result<(), EdgeClawError> {
+    let signature = serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| v.get("challenge_signature")?.as_str().map(hex::decode))
+        .and_then(Result::ok)
+        .ok_or(EdgeClawError::CryptoError)?;
+    let signature = Signature::from_slice(&signature).map_err(|_| EdgeClawError::CryptoError)?;
+    desktop_key
+        .verify(&sync_challenge_message(challenge), &signature)
+        .map_err(|_| EdgeClawError::CryptoError)
 }
 
-// ─── Sync Client ───
+/// Desktop's ephemeral key from an upgrade reply; `None` unless it is a
+/// Control frame accepting `SYNC_UPGRADE_SCHEME`
+fn upgrade_reply_key(reply: &EcnpMessage) -> Option<[u8; 32]> {
+    if reply.msg_type != MessageType::Control as u8 {
+        return None;
+    }
+    let body: serde_json::Value = serde_json::from_slice(&reply.payload).ok()?;
+    if body.get("upgrade")?.as_str()? != SYNC_UPGRADE_SCHEME {
+        return None;
+    }
+    let key = hex::decode(body.get("ephemeral_public_key")?.as_str()?).ok()?;
+    key.try_into().ok()
+}
 
-/// TCP-based synchronization client for Desktop-Mobile communication.
-///
-/// Handles the mobile side of the Desktop-Mobile sync protocol:
-/// - Config synchronization (receive from Desktop)
-/// - Status push (receive from Desktop)
-/// - Remote execution (send to Desktop, receive result)
+fn verify_upgrade_signature(
+    payload: &[u8],
+    desktop_key: &VerifyingKey,
+    signed: &[u8],
+) -> Result<(), EdgeClawError> {
+    let signature = serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| v.get("signature")?.as_str().map(hex::decode))
+        .and_then(Result::ok)
+        .ok_or(EdgeClawError::CryptoError)?;
+    let signature = Signature::from_slice(&signature).map_err(|_| EdgeClawError::CryptoError)?;
+    desktop_key
+        .verify(signed, &signature)
+        .map_err(|_| EdgeClawError::CryptoError)
+}
+
+/// Device ID the desktop reported in its handshake ack, if any
+fn ack_device_id(payload: &[u8]) -> Option<String> {
+    let ack: serde_json::Value = serde_json::from_slice(payload).ok()?;
+    ack.get("device_id")?.as_str().map(str::to_string)
+}
+
+/// Capabilities listed in a handshake ack; `None` if the desktop sent no
+/// list (older agents), in which case ours stand
+fn advertised_capabilities(payload: &[u8]) -> Option<Vec<String>> {
+    let ack: serde_json::Value = serde_json::from_slice(payload).ok()?;
+    let list = ack.get("capabilities")?.as_array()?;
+    Some(
+        list.iter()
+            .filter_map(|c| c.as_str())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Extract the compression list a peer advertised in its handshake payload.
 ///
-/// # Usage
-/// ```rust,ignore
-/// let config = SyncClientConfig {
-///     desktop_address: "192.168.1.100:8443".into(),
-///     ..Default::default()
-/// };
-/// let client = SyncClient::new(config);
-/// // In async context: client.connect().await
-/// ```
-pub struct SyncClient {
-    config: SyncClientConfig,
-    state: Arc<std::sync::Mutex<SyncConnectionState>>,
-    connected: Arc<AtomicBool>,
-    messages_sent: Arc<AtomicU64>,
-    messages_received: Arc<AtomicU64>,
-    reconnect_count: Arc<std::sync::atomic::AtomicU32>,
-    last_config_hash: Arc<std::sync::Mutex<Option<String>>>,
-    last_status: Arc<std::sync::Mutex<Option<SyncMessage>>>,
-    shutdown: Arc<AtomicBool>,
+/// Peers that predate negotiation send no list and are treated as `["none"]`;
+/// unrecognised algorithm names are ignored.
+fn advertised_compression(payload: &[u8]) -> Vec<Compression> {
+    serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| {
+            v.get("compression").and_then(|c| c.as_array()).map(|list| {
+                list.iter()
+                    .filter_map(|name| name.as_str())
+                    .filter_map(|name| Compression::parse(name).ok())
+                    .collect()
+            })
+        })
+        .unwrap_or_else(|| vec![Compression::None])
 }
 
-impl SyncClient {
-    /// Create a new sync client
-    pub fn new(config: SyncClientConfig) -> Self {
+// ─── Status anomaly detection ───
+
+/// Limits that turn status pushes into alerts (percentages, 0–100)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusThresholds {
+    pub cpu_usage: f64,
+    pub memory_usage: f64,
+    pub disk_usage: f64,
+    /// Largest allowed jump of any metric between two consecutive pushes
+    pub max_delta: f64,
+}
+
+impl Default for StatusThresholds {
+    fn default() -> Self {
         Self {
-            config,
-            state: Arc::new(std::sync::Mutex::new(SyncConnectionState::Disconnected)),
-            connected: Arc::new(AtomicBool::new(false)),
-            messages_sent: Arc::new(AtomicU64::new(0)),
-            messages_received: Arc::new(AtomicU64::new(0)),
-            reconnect_count: Arc::new(std::sync::atomic::AtomicU32::new(0)),
-            last_config_hash: Arc::new(std::sync::Mutex::new(None)),
-            last_status: Arc::new(std::sync::Mutex::new(None)),
-            shutdown: Arc::new(AtomicBool::new(false)),
+            cpu_usage: 90.0,
+            memory_usage: 90.0,
+            disk_usage: 95.0,
+            max_delta: 50.0,
         }
     }
+}
 
-    /// Get current connection state
-    pub fn state(&self) -> SyncConnectionState {
-        *self.state.lock().unwrap_or_else(|e| e.into_inner())
-    }
-
-    /// Check if connected
-    pub fn is_connected(&self) -> bool {
-        self.connected.load(Ordering::Relaxed)
-    }
+/// Anomaly flags for one status push
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusAnalysis {
+    pub cpu_high: bool,
+    pub memory_high: bool,
+    pub disk_high: bool,
+    pub cpu_spike: bool,
+    pub memory_spike: bool,
+    pub disk_spike: bool,
+}
 
-    /// Get the desktop address
-    pub fn desktop_address(&self) -> &str {
-        &self.config.desktop_address
+impl StatusAnalysis {
+    /// True if any flag is set
+    pub fn is_anomalous(&self) -> bool {
+        self.cpu_high
+            || self.memory_high
+            || self.disk_high
+            || self.cpu_spike
+            || self.memory_spike
+            || self.disk_spike
     }
+}
 
-    /// Get runtime statistics
-    pub fn stats(&self) -> SyncStats {
-        SyncStats {
-            messages_sent: self.messages_sent.load(Ordering::Relaxed),
-            messages_received: self.messages_received.load(Ordering::Relaxed),
-            reconnect_count: self.reconnect_count.load(Ordering::Relaxed),
-            last_config_hash: self
-                .last_config_hash
-                .lock()
-                .unwrap_or_else(|e| e.into_inner())
-                .clone(),
-            last_status_push: self
-                .last_status
-                .lock()
-                .unwrap_or_else(|e| e.into_inner())
-                .as_ref()
-                .map(|s| serde_json::to_string(s).unwrap_or_default()),
+/// Compare a status push against thresholds and the previous push.
+///
+/// Returns `None` if `current` is not a `StatusPush`. Spike flags need a
+/// previous `StatusPush` to compare against.
+pub fn analyze_status(
+    thresholds: &StatusThresholds,
+    previous: Option<&SyncMessage>,
+    current: &SyncMessage,
+) -> Option<StatusAnalysis> {
+    fn metrics(msg: &SyncMessage) -> Option<[f64; 3]> {
+        match msg {
+            SyncMessage::StatusPush {
+                cpu_usage,
+                memory_usage,
+                disk_usage,
+                ..
+            } => Some([*cpu_usage, *memory_usage, *disk_usage]),
+            _ => None,
         }
     }
 
-    /// Initiate TCP connection to the desktop agent.
-    ///
-    /// Performs:
-    /// 1. TCP connect with timeout
-    /// 2. ECNP handshake (send Handshake frame, expect Ack)
-    /// 3. Transition to Connected state
-    pub async fn connect(&self) -> Result<(), EdgeClawError> {
-        self.set_state(SyncConnectionState::Connecting);
+    let [cpu, mem, disk] = metrics(current)?;
+    let spike = |now: f64, idx: usize| {
+        previous
+            .and_then(metrics)
+            .is_some_and(|prev| (now - prev[idx]).abs() > thresholds.max_delta)
+    };
 
-        let addr = self
-            .config
-            .desktop_address
-            .parse::<std::net::SocketAddr>()
-            .map_err(|_| EdgeClawError::InvalidParameter)?;
+    Some(StatusAnalysis {
+        cpu_high: cpu > thresholds.cpu_usage,
+        memory_high: mem > thresholds.memory_usage,
+        disk_high: disk > thresholds.disk_usage,
+        cpu_spike: spike(cpu, 0),
+        memory_spike: spike(mem, 1),
+        disk_spike: spike(disk, 2),
+    })
+}
 
-        let timeout = std::time::Duration::from_secs(self.config.connect_timeout_secs);
+// ─── Remote exec risk ───
 
-        let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
-            .await
-            .map_err(|_| EdgeClawError::TimeoutError)?
-            .map_err(|_| EdgeClawError::ConnectionError)?;
+/// How dangerous a `RemoteExec` looks, for warning the user before sending
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandRisk {
+    Low,
+    /// Modifies files, processes or permissions, or escalates privileges
+    Medium,
+    /// Destructive, or relies on shell interpretation (metacharacters,
+    /// `sh -c`) that hides what will actually run
+    High,
+}
 
-        // Send ECNP handshake
-        self.set_state(SyncConnectionState::Handshaking);
+/// Characters that only mean something to a shell
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '`', '$', '>', '<', '\n', '(', ')'];
 
-        let handshake_payload = serde_json::json!({
-            "protocol": "ecnp",
-            "version": "1.1",
-            "client_type": "mobile",
-            "capabilities": ["config_sync", "remote_exec", "status_push"]
-        });
-        let handshake_data = serde_json::to_vec(&handshake_payload)
-            .map_err(|_| EdgeClawError::SerializationError)?;
-        let frame = EcnpCodec::encode(MessageType::Handshake, &handshake_data)?;
+const DESTRUCTIVE_COMMANDS: &[&str] = &[
+    "mkfs", "dd", "shred", "wipefs", "fdisk", "parted", "format", "diskpart", "shutdown", "reboot",
+    "halt", "poweroff", "init",
+];
 
-        use tokio::io::AsyncWriteExt;
-        let mut stream = stream;
-        stream
-            .write_all(&frame)
-            .await
-            .map_err(|_| EdgeClawError::ConnectionError)?;
-        stream
-            .flush()
-            .await
-            .map_err(|_| EdgeClawError::ConnectionError)?;
+const SHELLS: &[&str] = &[
+    "sh",
+    "bash",
+    "zsh",
+    "dash",
+    "ksh",
+    "fish",
+    "cmd",
+    "powershell",
+    "pwsh",
+];
 
-        // Read handshake ack
-        use tokio::io::AsyncReadExt;
-        let mut header_buf = [0u8; 6]; // ECNP header
-        tokio::time::timeout(timeout, stream.read_exact(&mut header_buf))
-            .await
-            .map_err(|_| EdgeClawError::TimeoutError)?
-            .map_err(|_| EdgeClawError::ConnectionError)?;
-
-        let payload_len =
-            u32::from_be_bytes([header_buf[2], header_buf[3], header_buf[4], header_buf[5]])
-                as usize;
-        let mut payload_buf = vec![0u8; payload_len];
-        if payload_len > 0 {
-            tokio::time::timeout(timeout, stream.read_exact(&mut payload_buf))
-                .await
-                .map_err(|_| EdgeClawError::TimeoutError)?
-                .map_err(|_| EdgeClawError::ConnectionError)?;
-        }
+/// Change or remove files; high risk when recursive or aimed at `/`
+const FILE_COMMANDS: &[&str] = &[
+    "rm", "rmdir", "del", "mv", "chmod", "chown", "chgrp", "truncate",
+];
 
-        // Validate ack
-        if header_buf[1] != MessageType::Ack as u8 {
-            self.set_state(SyncConnectionState::Error);
-            return Err(EdgeClawError::ConnectionError);
-        }
+const PROCESS_COMMANDS: &[&str] = &[
+    "kill",
+    "killall",
+    "pkill",
+    "systemctl",
+    "service",
+    "crontab",
+];
 
-        self.connected.store(true, Ordering::Relaxed);
-        self.set_state(SyncConnectionState::Connected);
-        tracing::info!(addr = %self.config.desktop_address, "Sync client connected");
-        Ok(())
+const ELEVATION_COMMANDS: &[&str] = &["sudo", "su", "doas", "runas"];
+
+/// Flag dangerous patterns in a `RemoteExec` command line.
+///
+/// Defense in depth for the UI only: the desktop must still enforce its
+/// own policy. A command string containing spaces is split into words, so
+/// `"rm -rf /"` is judged the same as `"rm"` with `["-rf", "/"]`.
+pub fn classify_command(command: &str, args: &[String]) -> CommandRisk {
+    let words: Vec<&str> = command
+        .split_whitespace()
+        .chain(args.iter().map(String::as_str))
+        .collect();
+    if words.iter().any(|w| w.contains(SHELL_METACHARACTERS)) {
+        return CommandRisk::High;
     }
 
-    /// Create a RemoteExec sync message
-    pub fn create_remote_exec(
-        &self,
-        command: &str,
-        args: Vec<String>,
-    ) -> Result<Vec<u8>, EdgeClawError> {
-        let msg = SyncMessage::RemoteExec {
-            command: command.to_string(),
-            args,
-        };
-        let frame = msg.encode_ecnp()?;
-        self.messages_sent.fetch_add(1, Ordering::Relaxed);
-        Ok(frame)
+    let mut risk = CommandRisk::Low;
+    let mut rest = &words[..];
+    // Look through privilege escalation at the command being run
+    while let Some((first, tail)) = rest.split_first() {
+        let name = command_name(first);
+        if !ELEVATION_COMMANDS.contains(&name.as_str()) {
+            break;
+        }
+        risk = CommandRisk::Medium;
+        rest = tail;
     }
+    let Some((first, tail)) = rest.split_first() else {
+        return risk;
+    };
 
-    /// Process a received sync message
-    pub fn process_incoming(&self, frame: &[u8]) -> Result<SyncMessage, EdgeClawError> {
-        let (_sync_type, msg) = SyncMessage::decode_ecnp(frame)?;
-        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    let name = command_name(first);
+    if SHELLS.contains(&name.as_str()) || DESTRUCTIVE_COMMANDS.contains(&name.as_str()) {
+        return CommandRisk::High;
+    }
+    if PROCESS_COMMANDS.contains(&name.as_str()) {
+        return CommandRisk::Medium;
+    }
+    if FILE_COMMANDS.contains(&name.as_str()) {
+        let recursive = tail.iter().any(|a| {
+            let a = a.to_ascii_lowercase();
+            a == "--recursive"
+                || a == "/s"
+                || (a.starts_with('-') && !a.starts_with("--") && a.contains('r'))
+        });
+        let targets_root = tail.iter().any(|a| matches!(*a, "/" | "/*" | "~" | "*"));
+        if recursive || targets_root {
+            return CommandRisk::High;
+        }
+        return CommandRisk::Medium;
+    }
+    risk
+}
 
-        match &msg {
-            SyncMessage::ConfigSync { config_hash, .. } => {
-                if let Ok(mut hash) = self.last_config_hash.lock() {
-                    *hash = Some(config_hash.clone());
-                }
-                tracing::info!(config_hash = %config_hash, "Config sync received");
-            }
-            SyncMessage::StatusPush { .. } => {
-                if let Ok(mut status) = self.last_status.lock() {
-                    *status = Some(msg.clone());
-                }
-                tracing::info!("Status push received");
-            }
-            SyncMessage::RemoteExecResult {
-                command, exit_code, ..
-            } => {
-                tracing::info!(command = %command, exit_code = %exit_code, "Remote exec result received");
-            }
-            _ => {}
+/// Lowercased base name of a command, without directory or extension
+/// (`/sbin/mkfs.ext4` → `mkfs`, `SHUTDOWN.EXE` → `shutdown`)
+fn command_name(word: &str) -> String {
+    let base = word.rsplit(['/', '\\']).next().unwrap_or(word);
+    base.split('.').next().unwrap_or(base).to_ascii_lowercase()
+}
+
+// ─── Priority send queue ───
+
+/// Outbound frame priority; higher levels are always drained first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendPriority {
+    /// Handshake, ack and close frames
+    Control,
+    /// Heartbeats and remote exec requests
+    Interactive,
+    /// Large transfers such as file chunks
+    Bulk,
+}
+
+impl SendPriority {
+    const LEVELS: usize = 3;
+
+    fn index(self) -> usize {
+        match self {
+            SendPriority::Control => 0,
+            SendPriority::Interactive => 1,
+            SendPriority::Bulk => 2,
         }
+    }
+}
 
-        Ok(msg)
+/// FIFO-per-level queue of encoded frames awaiting transmission
+#[derive(Debug, Default)]
+pub struct SendQueue {
+    levels: [VecDeque<Vec<u8>>; SendPriority::LEVELS],
+}
+
+impl SendQueue {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Request shutdown
-    pub fn shutdown(&self) {
-        self.shutdown.store(true, Ordering::Relaxed);
-        self.connected.store(false, Ordering::Relaxed);
-        self.set_state(SyncConnectionState::Disconnected);
-        tracing::info!("Sync client shutdown requested");
+    /// Append a frame behind others of the same priority
+    pub fn push(&mut self, frame: Vec<u8>, priority: SendPriority) {
+        self.levels[priority.index()].push_back(frame);
     }
 
-    /// Check if shutdown was requested
-    pub fn is_shutdown(&self) -> bool {
-        self.shutdown.load(Ordering::Relaxed)
+    /// Return a frame to the head of its level (e.g. after a failed write)
+    pub fn push_front(&mut self, frame: Vec<u8>, priority: SendPriority) {
+        self.levels[priority.index()].push_front(frame);
     }
 
-    fn set_state(&self, new_state: SyncConnectionState) {
-        if let Ok(mut state) = self.state.lock() {
-            *state = new_state;
-        }
+    /// Take the oldest frame of the highest non-empty priority
+    pub fn pop(&mut self) -> Option<(Vec<u8>, SendPriority)> {
+        [
+            SendPriority::Control,
+            SendPriority::Interactive,
+            SendPriority::Bulk,
+        ]
+        .into_iter()
+        .find_map(|p| self.levels[p.index()].pop_front().map(|f| (f, p)))
+    }
+
+    /// Number of queued frames across all priorities
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(VecDeque::is_empty)
     }
 }
 
-// ─── Transport switch helper ───
+// ─── Circuit breaker ───
 
-/// Transport preference for Desktop connection
+/// Circuit breaker guarding `SyncClient::connect`
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
-pub enum TransportPreference {
-    /// BLE for proximity, TCP for data
-    BleFirst,
-    /// TCP/WiFi LAN direct
-    TcpLan,
-    /// Auto-detect: BLE discovery → TCP switch
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Connects are attempted normally
     #[default]
-    Auto,
+    Closed,
+    /// Connects fail immediately until the cooldown ends
+    Open,
+    /// Cooldown over; the next connect is a trial that closes or re-opens it
+    HalfOpen,
 }
 
-/// Connection strategy result
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConnectionStrategy {
-    pub transport: TransportPreference,
-    pub desktop_address: Option<String>,
-    pub ble_device_id: Option<String>,
-    pub should_use_tcp: bool,
+/// Consecutive-failure circuit breaker. Time is passed in so transitions
+/// can be tested without waiting.
+#[derive(Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: std::time::Duration,
+    consecutive_failures: u32,
+    opened_at: Option<std::time::Instant>,
 }
 
-/// Determines the best connection strategy based on available transports.
-///
-/// In Auto mode:
-/// - If BLE device is nearby (discovered), use BLE for initial handshake
-/// - Then switch to TCP/LAN for data transfer
-/// - Fall back to TCP if BLE not available
-pub fn determine_connection_strategy(
-    preference: TransportPreference,
-    ble_device_available: bool,
-    lan_address: Option<&str>,
-) -> ConnectionStrategy {
-    match preference {
-        TransportPreference::BleFirst => ConnectionStrategy {
-            transport: TransportPreference::BleFirst,
-            desktop_address: lan_address.map(|s| s.to_string()),
-            ble_device_id: None,
-            should_use_tcp: !ble_device_available,
-        },
-        TransportPreference::TcpLan => ConnectionStrategy {
-            transport: TransportPreference::TcpLan,
-            desktop_address: lan_address.map(|s| s.to_string()),
-            ble_device_id: None,
-            should_use_tcp: true,
-        },
-        TransportPreference::Auto => {
-            if ble_device_available && lan_address.is_some() {
-                // BLE for discovery, TCP for data
-                ConnectionStrategy {
-                    transport: TransportPreference::Auto,
-                    desktop_address: lan_address.map(|s| s.to_string()),
-                    ble_device_id: None,
-                    should_use_tcp: true, // switch to TCP after BLE discovery
-                }
-            } else if lan_address.is_some() {
-                // TCP only
-                ConnectionStrategy {
-                    transport: TransportPreference::TcpLan,
-                    desktop_address: lan_address.map(|s| s.to_string()),
-                    ble_device_id: None,
-                    should_use_tcp: true,
-                }
-            } else {
-                // BLE only or nothing
-                ConnectionStrategy {
-                    transport: TransportPreference::BleFirst,
-                    desktop_address: None,
-                    ble_device_id: None,
-                    should_use_tcp: false,
-                }
-            }
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown_secs: u64) -> Self {
+        Self {
+            threshold,
+            cooldown: std::time::Duration::from_secs(cooldown_secs),
+            consecutive_failures: 0,
+            opened_at: None,
+        }
+    }
+
+    fn state(&self, now: std::time::Instant) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(opened) if now.duration_since(opened) < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether an attempt may be made now
+    fn allows(&self, now: std::time::Instant) -> bool {
+        self.state(now) != BreakerState::Open
+    }
+
+    /// Record a failed attempt; returns true if this opened the breaker
+    fn record_failure(&mut self, now: std::time::Instant) -> bool {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let trial_failed = self.opened_at.is_some();
+        let tripped = self.threshold > 0 && self.consecutive_failures >= self.threshold;
+        if trial_failed || tripped {
+            self.opened_at = Some(now);
+            return true;
         }
+        false
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.opened_at = None;
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Bounded LRU of recently seen frame digests. Time is passed in like
+/// `CircuitBreaker`.
+#[derive(Debug)]
+struct ReplayCache {
+    capacity: usize,
+    window: std::time::Duration,
+    seen: HashMap<[u8; 32], std::time::Instant>,
+    /// Digests oldest-first, for eviction
+    order: VecDeque<[u8; 32]>,
+}
 
-    // ─── SyncMessage tests ───
+impl ReplayCache {
+    fn new(capacity: usize, window_secs: u64) -> Self {
+        Self {
+            capacity,
+            window: std::time::Duration::from_secs(window_secs),
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
 
-    #[test]
-    fn test_config_sync_roundtrip() {
-        let msg = SyncMessage::ConfigSync {
-            config_hash: "abc123".to_string(),
-            config_data: r#"{"agent":{"name":"test"}}"#.to_string(),
-        };
+    /// Record `frame`; false if the same bytes were seen within the window
+    fn check(&mut self, frame: &[u8], now: std::time::Instant) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        let digest: [u8; 32] = Sha256::digest(frame).into();
+        if let Some(at) = self.seen.get(&digest) {
+            if now.duration_since(*at) < self.window {
+                return false;
+            }
+        }
 
-        let bytes = msg.to_bytes().unwrap();
-        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
-        match decoded {
-            SyncMessage::ConfigSync {
-                config_hash,
-                config_data,
-            } => {
-                assert_eq!(config_hash, "abc123");
-                assert_eq!(config_data, r#"{"agent":{"name":"test"}}"#);
+        self.order.retain(|d| *d != digest);
+        self.order.push_back(digest);
+        self.seen.insert(digest, now);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
             }
-            _ => panic!("Expected ConfigSync"),
         }
+        true
     }
+}
 
-    #[test]
-    fn test_remote_exec_roundtrip() {
-        let msg = SyncMessage::RemoteExec {
-            command: "systemctl".to_string(),
-            args: vec!["status".into(), "nginx".into()],
-        };
+/// Remote execs tracked at most this long without a result
+const EXEC_REQUEST_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// Cap on tracked remote execs; the oldest is dropped beyond it
+const MAX_TRACKED_EXEC_REQUESTS: usize = 256;
 
-        let bytes = msg.to_bytes().unwrap();
-        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
-        match decoded {
-            SyncMessage::RemoteExec { command, args } => {
-                assert_eq!(command, "systemctl");
-                assert_eq!(args, vec!["status", "nginx"]);
+/// Remote execs awaiting a result, bounded in age and count since older
+/// desktops never echo the request ID. Time is passed in like
+/// `ReplayCache`.
+#[derive(Debug, Default)]
+struct ExecRequests {
+    entries: HashMap<String, (ExecRequestState, std::time::Instant)>,
+}
+
+impl ExecRequests {
+    /// Track `request_id` as pending, first dropping expired entries and,
+    /// at the cap, the oldest one
+    fn insert(&mut self, request_id: String, now: std::time::Instant) {
+        self.entries
+            .retain(|_, (_, sent)| now.duration_since(*sent) < EXEC_REQUEST_TTL);
+        if self.entries.len() >= MAX_TRACKED_EXEC_REQUESTS {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, sent))| *sent)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                tracing::debug!(request_id = %oldest, "Dropped oldest tracked remote exec");
+                self.entries.remove(&oldest);
             }
-            _ => panic!("Expected RemoteExec"),
         }
+        self.entries
+            .insert(request_id, (ExecRequestState::Pending, now));
     }
 
+    fn get(&self, request_id: &str) -> Option<ExecRequestState> {
+        self.entries.get(request_id).map(|(state, _)| *state)
+    }
+
+    /// Mark a tracked request cancelled; false if it is not tracked
+    fn cancel(&mut self, request_id: &str) -> bool {
+        match self.entries.get_mut(request_id) {
+            Some((state, _)) => {
+                *state = ExecRequestState::Cancelled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, request_id: &str) -> Option<ExecRequestState> {
+        self.entries.remove(request_id).map(|(state, _)| state)
+    }
+}
+
+// ─── Sync client stats ───
+
+/// Runtime statistics for the sync client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub reconnect_count: u32,
+    pub last_config_hash: Option<String>,
+    pub last_status_push: Option<String>,
+    /// Messages processed per sync sub-type code (types never seen are omitted)
+    #[serde(default)]
+    pub received_by_type: HashMap<u8, u64>,
+    /// Connect circuit breaker state
+    #[serde(default)]
+    pub breaker: BreakerState,
+    /// Connect failures since the last successful connect
+    #[serde(default)]
+    pub consecutive_connect_failures: u32,
+    /// Bad frames dropped by `next_message` under `BadMessagePolicy::Skip`
+    #[serde(default)]
+    pub messages_skipped: u64,
+}
+
+// ─── Sync Client ───
+
+/// TCP-based synchronization client for Desktop-Mobile communication.
+///
+/// Handles the mobile side of the Desktop-Mobile sync protocol:
+/// - Config synchronization (receive from Desktop)
+/// - Status push (receive from Desktop)
+/// - Remote execution (send to Desktop, receive result)
+///
+/// # Usage
+/// ```rust,ignore
+/// let config = SyncClientConfig {
+///     desktop_address: "192.168.1.100:8443".into(),
+///     ..Default::default()
+/// };
+/// let client = SyncClient::new(config);
+/// // In async context: client.connect().await
+/// ```
+///
+/// Clones share all runtime state, so a clone can drive `connect()` while
+/// the original is polled for state and stats.
+#[derive(Clone)]
+pub struct SyncClient {
+    config: SyncClientConfig,
+    state: Arc<std::sync::Mutex<SyncConnectionState>>,
+    connected: Arc<AtomicBool>,
+    messages_sent: Arc<AtomicU64>,
+    messages_received: Arc<AtomicU64>,
+    messages_skipped: Arc<AtomicU64>,
+    /// Per-type counters, indexed like `SYNC_TYPES`
+    received_by_type: Arc<[AtomicU64; SYNC_TYPES.len()]>,
+    reconnect_count: Arc<std::sync::atomic::AtomicU32>,
+    last_config_hash: Arc<std::sync::Mutex<Option<String>>>,
+    last_status: Arc<std::sync::Mutex<Option<SyncMessage>>>,
+    compression: Arc<std::sync::Mutex<Compression>>,
+    listener: Arc<std::sync::Mutex<Option<ConnectionListener>>>,
+    send_queue: Arc<std::sync::Mutex<SendQueue>>,
+    desktop_key: Arc<std::sync::Mutex<Option<VerifyingKey>>>,
+    /// Capabilities agreed in the last handshake (None = not yet connected)
+    negotiated_capabilities: Arc<std::sync::Mutex<Option<Vec<String>>>>,
+    /// Current target; starts as `config.desktop_address`
+    desktop_address: Arc<std::sync::Mutex<String>>,
+    /// Discovered address tried on the next connect, adopted only once the
+    /// desktop there proves the pinned key
+    candidate_address: Arc<std::sync::Mutex<Option<String>>>,
+    /// Our device ID, sent in the handshake to detect self-connection
+    local_device_id: Arc<std::sync::Mutex<Option<String>>>,
+    exec_requests: Arc<std::sync::Mutex<ExecRequests>>,
+    breaker: Arc<std::sync::Mutex<CircuitBreaker>>,
+    replay_cache: Arc<std::sync::Mutex<ReplayCache>>,
+    /// Session-encrypted channel once `upgrade` completes on this connection
+    channel: Arc<std::sync::Mutex<Option<SyncChannel>>>,
+    /// Our ephemeral key between `begin_upgrade` and `finish_upgrade`
+    pending_upgrade: Arc<std::sync::Mutex<Option<KeyExchangeKeypair>>>,
+    /// While paused: the state to report again on `resume`, kept up to date
+    /// by transitions that happen in the meantime
+    paused: Arc<std::sync::Mutex<Option<SyncConnectionState>>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+/// Session state of an upgraded connection
+struct SyncChannel {
+    sessions: SessionManager,
+    session_id: String,
+    /// Nonce counter of the desktop's last accepted frame
+    last_peer_counter: Option<u64>,
+}
+
+/// Callback invoked on every connection state transition
+pub type ConnectionListener = Arc<dyn Fn(SyncConnectionState) + Send + Sync>;
+
+impl SyncClient {
+    /// Create a new sync client
+    pub fn new(config: SyncClientConfig) -> Self {
+        let breaker = CircuitBreaker::new(
+            config.breaker_failure_threshold,
+            config.breaker_cooldown_secs,
+        );
+        let replay_cache = ReplayCache::new(config.replay_cache_size, config.replay_window_secs);
+        let desktop_address = config.desktop_address.clone();
+        Self {
+            config,
+            state: Arc::new(std::sync::Mutex::new(SyncConnectionState::Disconnected)),
+            connected: Arc::new(AtomicBool::new(false)),
+            messages_sent: Arc::new(AtomicU64::new(0)),
+            messages_received: Arc::new(AtomicU64::new(0)),
+            messages_skipped: Arc::new(AtomicU64::new(0)),
+            received_by_type: Arc::new(std::array::from_fn(|_| AtomicU64::new(0))),
+            reconnect_count: Arc::new(std::sync::atomic::AtomicU32::new(0)),
+            last_config_hash: Arc::new(std::sync::Mutex::new(None)),
+            last_status: Arc::new(std::sync::Mutex::new(None)),
+            compression: Arc::new(std::sync::Mutex::new(Compression::None)),
+            listener: Arc::new(std::sync::Mutex::new(None)),
+            send_queue: Arc::new(std::sync::Mutex::new(SendQueue::new())),
+            desktop_key: Arc::new(std::sync::Mutex::new(None)),
+            negotiated_capabilities: Arc::new(std::sync::Mutex::new(None)),
+            desktop_address: Arc::new(std::sync::Mutex::new(desktop_address)),
+            candidate_address: Arc::new(std::sync::Mutex::new(None)),
+            local_device_id: Arc::new(std::sync::Mutex::new(None)),
+            exec_requests: Arc::new(std::sync::Mutex::new(ExecRequests::default())),
+            breaker: Arc::new(std::sync::Mutex::new(breaker)),
+            replay_cache: Arc::new(std::sync::Mutex::new(replay_cache)),
+            channel: Arc::new(std::sync::Mutex::new(None)),
+            pending_upgrade: Arc::new(std::sync::Mutex::new(None)),
+            paused: Arc::new(std::sync::Mutex::new(None)),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Get current connection state
+    pub fn state(&self) -> SyncConnectionState {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a callback for connection state transitions, replacing any
+    /// previous one
+    pub fn set_connection_listener(
+        &self,
+        listener: Box<dyn Fn(SyncConnectionState) + Send + Sync>,
+    ) {
+        let mut slot = self.listener.lock().unwrap_or_else(|e| e.into_inner());
+        *slot = Some(Arc::from(listener));
+    }
+
+    /// Set the paired desktop's Ed25519 key; the handshake then requires the
+    /// desktop to sign a fresh challenge with it
+    pub fn set_desktop_key(&self, key: VerifyingKey) {
+        *self.desktop_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(key);
+    }
+
+    /// Set our device ID. It is sent in the handshake, and an ack that
+    /// carries the same ID (we dialed our own listener) fails with
+    /// `SelfConnection`.
+    pub fn set_local_device_id(&self, device_id: &str) {
+        *self
+            .local_device_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(device_id.to_string());
+    }
+
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Client configuration
+    pub fn config(&self) -> &SyncClientConfig {
+        &self.config
+    }
+
+    /// Address the next connect will target: `desktop_address` from the
+    /// config unless replaced with `update_address`
+    pub fn desktop_address(&self) -> String {
+        self.desktop_address
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Point the client at a new desktop address (`host:port` socket
+    /// address), e.g. after a DHCP change. Returns whether it changed.
+    ///
+    /// An open connection is dropped (state `Disconnected`) so the caller's
+    /// reconnect goes to the new address, and the circuit breaker is reset
+    /// since its failures were against the old one.
+    pub fn update_address(&self, new_addr: &str) -> Result<bool, EdgeClawError> {
+        new_addr
+            .parse::<std::net::SocketAddr>()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        {
+            let mut addr = self
+                .desktop_address
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if *addr == new_addr {
+                return Ok(false);
+            }
+            tracing::info!(old = %addr, new = %new_addr, "Sync desktop address changed");
+            *addr = new_addr.to_string();
+        }
+
+        self.breaker
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record_success();
+        if self.connected.swap(false, Ordering::Relaxed) {
+            self.drop_channel();
+            self.set_state(SyncConnectionState::Disconnected);
+        }
+        Ok(true)
+    }
+
+    /// Offer an address learned from discovery, which is unauthenticated.
+    /// Returns whether it was recorded as a new candidate.
+    ///
+    /// Unlike `update_address` nothing is dropped or replaced now: the next
+    /// connect tries the candidate first and adopts it only if the desktop
+    /// there passes the pinned-key handshake, falling back to the current
+    /// address otherwise. `InvalidParameter` if `addr` is not a socket
+    /// address or no desktop key is pinned.
+    pub fn propose_address(&self, addr: &str) -> Result<bool, EdgeClawError> {
+        addr.parse::<std::net::SocketAddr>()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+        if self.handshake_key()?.is_none() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        if self.desktop_address() == addr {
+            return Ok(false);
+        }
+        let mut candidate = self
+            .candidate_address
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if candidate.as_deref() == Some(addr) {
+            return Ok(false);
+        }
+        *candidate = Some(addr.to_string());
+        Ok(true)
+    }
+
+    /// `update_address`, then reconnect if the client was connected
+    pub async fn reconnect_to(&self, new_addr: &str) -> Result<(), EdgeClawError> {
+        let was_connected = self.is_connected();
+        if self.update_address(new_addr)? && was_connected {
+            self.reconnect_count.fetch_add(1, Ordering::Relaxed);
+            self.connect().await?;
+        }
+        Ok(())
+    }
+
+    /// Compression agreed with the desktop during the handshake
+    pub fn negotiated_compression(&self) -> Compression {
+        *self.compression.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Capabilities agreed in the last handshake with the desktop, or
+    /// `None` if there has not been one
+    pub fn agreed_capabilities(&self) -> Option<Vec<String>> {
+        self.negotiated_capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Capabilities in effect: those agreed with the desktop once
+    /// connected, otherwise the configured ones
+    pub fn negotiated_capabilities(&self) -> Vec<String> {
+        self.negotiated_capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| self.config.capabilities.clone())
+    }
+
+    /// Fail with `InvalidCapability` unless `capability` is in effect
+    fn require_capability(&self, capability: &str) -> Result<(), EdgeClawError> {
+        if self
+            .negotiated_capabilities()
+            .iter()
+            .any(|c| c == capability)
+        {
+            Ok(())
+        } else {
+            tracing::warn!(capability, "Sync capability not negotiated");
+            Err(EdgeClawError::InvalidCapability)
+        }
+    }
+
+    /// Get runtime statistics
+    pub fn stats(&self) -> SyncStats {
+        let (breaker, consecutive_connect_failures) = {
+            let b = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+            (b.state(std::time::Instant::now()), b.consecutive_failures)
+        };
+        SyncStats {
+            breaker,
+            consecutive_connect_failures,
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            messages_skipped: self.messages_skipped.load(Ordering::Relaxed),
+            reconnect_count: self.reconnect_count.load(Ordering::Relaxed),
+            last_config_hash: self
+                .last_config_hash
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            last_status_push: self
+                .last_status
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_ref()
+                .map(|s| serde_json::to_string(s).unwrap_or_default()),
+            received_by_type: SYNC_TYPES
+                .iter()
+                .zip(self.received_by_type.iter())
+                .map(|(code, count)| (*code, count.load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+        }
+    }
+
+    /// Initiate TCP connection to the desktop agent.
+    ///
+    /// Performs:
+    /// 1. TCP connect with timeout
+    /// 2. ECNP handshake (send Handshake frame, expect Ack)
+    /// 3. Desktop authentication, if a desktop key is set: the Ack must carry
+    ///    a valid signature over our random challenge (`CryptoError` if not)
+    /// 4. Transition to Connected state
+    ///
+    /// Authentication is opt-in: with neither `desktop_peer_id` nor a
+    /// desktop key, step 3 is skipped and any host at `desktop_address` is
+    /// accepted. With `desktop_peer_id` configured but no desktop key set,
+    /// the connection is refused with `InvalidParameter`.
+    ///
+    /// A pending `propose_address` candidate is tried before
+    /// `desktop_address` and adopted if it authenticates.
+    ///
+    /// After `breaker_failure_threshold` consecutive failures the circuit
+    /// breaker opens: connects fail at once with `ConnectionError` (state
+    /// `CircuitOpen`) until `breaker_cooldown_secs` pass. The next connect
+    /// is then a trial; success closes the breaker, failure re-opens it.
+    pub async fn connect(&self) -> Result<(), EdgeClawError> {
+        if self.is_paused() {
+            tracing::debug!("Sync connect skipped: client paused");
+            return Err(EdgeClawError::ConnectionError);
+        }
+        let now = std::time::Instant::now();
+        if !self
+            .breaker
+            .lock()
+            .map_err(|_| EdgeClawError::InternalError)?
+            .allows(now)
+        {
+            tracing::debug!("Sync connect skipped: circuit breaker open");
+            return Err(EdgeClawError::ConnectionError);
+        }
+
+        let result = self.connect_once().await;
+        let mut breaker = self
+            .breaker
+            .lock()
+            .map_err(|_| EdgeClawError::InternalError)?;
+        match &result {
+            Ok(()) => breaker.record_success(),
+            // Configuration errors say nothing about the desktop's health
+            Err(EdgeClawError::InvalidParameter | EdgeClawError::SelfConnection) => {}
+            Err(_) => {
+                if breaker.record_failure(std::time::Instant::now()) {
+                    tracing::warn!(
+                        addr = %self.desktop_address(),
+                        failures = breaker.consecutive_failures,
+                        cooldown_secs = self.config.breaker_cooldown_secs,
+                        "Sync circuit breaker opened"
+                    );
+                    drop(breaker);
+                    self.set_state(SyncConnectionState::CircuitOpen);
+                }
+            }
+        }
+        result
+    }
+
+    async fn connect_once(&self) -> Result<(), EdgeClawError> {
+        let desktop_key = self.handshake_key()?;
+        self.set_state(SyncConnectionState::Connecting);
+
+        let candidate = self
+            .candidate_address
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let (Some(candidate), Some(key)) = (candidate, desktop_key) {
+            let result = match self.dial(&candidate).await {
+                Ok(mut stream) => self.handshake(&mut stream, Some(key)).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    tracing::info!(new = %candidate, "Sync desktop verified at discovered address");
+                    *self
+                        .desktop_address
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner()) = candidate;
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!(addr = %candidate, error = %e, "Discovered desktop address not verified");
+                }
+            }
+        }
+
+        let result = match self.dial(&self.desktop_address()).await {
+            Ok(mut stream) => self.handshake(&mut stream, desktop_key).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.set_state(SyncConnectionState::Error);
+        }
+        result
+    }
+
+    async fn dial(&self, addr: &str) -> Result<tokio::net::TcpStream, EdgeClawError> {
+        let addr = addr
+            .parse::<std::net::SocketAddr>()
+            .map_err(|_| EdgeClawError::InvalidParameter)?;
+
+        let timeout = std::time::Duration::from_secs(self.config.connect_timeout_secs);
+
+        tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
+            .await
+            .map_err(|_| EdgeClawError::TimeoutError)?
+            .map_err(|_| EdgeClawError::ConnectionError)
+    }
+
+    /// Run the sync handshake over an already open transport (e.g. a BLE
+    /// bridge or an in-memory pipe) instead of dialing `desktop_address`.
+    /// The circuit breaker is not involved.
+    pub async fn connect_over<S>(&self, stream: &mut S) -> Result<(), EdgeClawError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let desktop_key = self.handshake_key()?;
+        let result = self.handshake(stream, desktop_key).await;
+        if result.is_err() {
+            self.set_state(SyncConnectionState::Error);
+        }
+        result
+    }
+
+    /// The pinned desktop key, if any; `InvalidParameter` if a desktop peer
+    /// is configured but its key was never set
+    fn handshake_key(&self) -> Result<Option<VerifyingKey>, EdgeClawError> {
+        let desktop_key = *self.desktop_key.lock().unwrap_or_else(|e| e.into_inner());
+        if self.config.desktop_peer_id.is_some() && desktop_key.is_none() {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        Ok(desktop_key)
+    }
+
+    async fn handshake<S>(
+        &self,
+        stream: &mut S,
+        desktop_key: Option<VerifyingKey>,
+    ) -> Result<(), EdgeClawError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        // A new connection starts in plaintext
+        self.drop_channel();
+
+        // Send ECNP handshake
+        self.set_state(SyncConnectionState::Handshaking);
+
+        let mut handshake_payload = serde_json::json!({
+            "protocol": "ecnp",
+            "version": "1.1",
+            "client_type": "mobile",
+            "capabilities": self.config.capabilities,
+            "compression": self
+                .config
+                .compression
+                .iter()
+                .map(|c| c.as_str())
+                .collect::<Vec<_>>()
+        });
+        let local_device_id = self
+            .local_device_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(device_id) = &local_device_id {
+            handshake_payload["device_id"] = device_id.as_str().into();
+        }
+        let mut challenge = [0u8; SYNC_CHALLENGE_SIZE];
+        if desktop_key.is_some() {
+            rand::thread_rng().fill_bytes(&mut challenge);
+            handshake_payload["challenge"] = hex::encode(challenge).into();
+        }
+        let handshake_data = serde_json::to_vec(&handshake_payload)
+            .map_err(|_| EdgeClawError::SerializationError)?;
+
+        let handshake_frame = EcnpCodec::encode(MessageType::Handshake, &handshake_data)?;
+        self.write_frame_timed(stream, &handshake_frame).await?;
+
+        // Read handshake ack
+        let ack = self.read_frame_timed(stream).await?;
+
+        // Validate ack
+        if ack.msg_type != MessageType::Ack as u8 {
+            return Err(EdgeClawError::ConnectionError);
+        }
+
+        if local_device_id.is_some() && ack_device_id(&ack.payload) == local_device_id {
+            tracing::warn!(
+                addr = %self.desktop_address(),
+                "Sync handshake answered by this device; desktop_address points at ourselves"
+            );
+            return Err(EdgeClawError::SelfConnection);
+        }
+
+        if let Some(key) = &desktop_key {
+            if let Err(e) = verify_ack_signature(&ack.payload, key, &challenge) {
+                tracing::warn!(
+                    addr = %self.desktop_address(),
+                    "Desktop failed handshake authentication"
+                );
+                return Err(e);
+            }
+        }
+
+        let negotiated = match advertised_capabilities(&ack.payload) {
+            Some(remote) => self
+                .config
+                .capabilities
+                .iter()
+                .filter(|c| remote.contains(c))
+                .cloned()
+                .collect(),
+            None => self.config.capabilities.clone(),
+        };
+        *self
+            .negotiated_capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(negotiated);
+
+        let remote_compression = advertised_compression(&ack.payload);
+        let agreed = negotiate_compression(&self.config.compression, &remote_compression);
+        if let Ok(mut compression) = self.compression.lock() {
+            *compression = agreed;
+        }
+
+        self.connected.store(true, Ordering::Relaxed);
+        self.set_state(SyncConnectionState::Connected);
+        tracing::info!(
+            addr = %self.desktop_address(),
+            compression = agreed.as_str(),
+            "Sync client connected"
+        );
+        Ok(())
+    }
+
+    /// Switch a connected stream to the session-encrypted channel.
+    ///
+    /// Sends a Control frame `{"upgrade": SYNC_UPGRADE_SCHEME,
+    /// "ephemeral_public_key": hex}` and expects the same from the desktop,
+    /// plus a `signature` over `sync_upgrade_message` when the desktop key
+    /// is pinned. Both sides then derive an X25519 session (we initiate).
+    /// From then on every frame in either direction is an ECNP Data frame
+    /// holding a session-encrypted sync frame (`encrypt_to_frame`), and
+    /// plaintext frames are rejected.
+    ///
+    /// A desktop that answers anything else fails with `ConnectionError`
+    /// and the connection stays in plaintext.
+    ///
+    /// Without a pinned desktop key (`desktop_peer_id` or
+    /// `set_desktop_key`) the upgrade is unauthenticated: an active
+    /// man-in-the-middle can answer it with its own key and read and alter
+    /// everything sent on the channel. Only skip pinning on trusted
+    /// networks.
+    pub async fn upgrade<S>(&self, stream: &mut S) -> Result<(), EdgeClawError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        if !self.is_connected() {
+            return Err(EdgeClawError::ConnectionError);
+        }
+        let (local, frame) = self.upgrade_request()?;
+        self.write_frame_timed(stream, &frame).await?;
+        let reply = self.read_frame_timed(stream).await?;
+        self.complete_upgrade(local, &reply)
+    }
+
+    /// Start an upgrade on a transport the caller drives (e.g. across the
+    /// FFI), after its own handshake with the desktop: returns the Control
+    /// frame to send. Pass the desktop's reply to `finish_upgrade`.
+    pub fn begin_upgrade(&self) -> Result<Vec<u8>, EdgeClawError> {
+        let (local, frame) = self.upgrade_request()?;
+        *self
+            .pending_upgrade
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(local);
+        Ok(frame)
+    }
+
+    /// Complete an upgrade started by `begin_upgrade` with the desktop's
+    /// reply frame (`InvalidParameter` if none is pending). Fails like
+    /// `upgrade`; either way the pending key is used up.
+    pub fn finish_upgrade(&self, reply: &[u8]) -> Result<(), EdgeClawError> {
+        let local = self
+            .pending_upgrade
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .ok_or(EdgeClawError::InvalidParameter)?;
+        self.complete_upgrade(local, &EcnpCodec::decode(reply)?)
+    }
+
+    /// Fresh ephemeral key pair and the Control frame offering it
+    fn upgrade_request(&self) -> Result<(KeyExchangeKeypair, Vec<u8>), EdgeClawError> {
+        let local = X25519KeyExchange.generate_keypair();
+        let request = serde_json::json!({
+            "upgrade": SYNC_UPGRADE_SCHEME,
+            "ephemeral_public_key": hex::encode(&local.public),
+        });
+        let payload =
+            serde_json::to_vec(&request).map_err(|_| EdgeClawError::SerializationError)?;
+        let frame = EcnpCodec::encode(MessageType::Control, &payload)?;
+        Ok((local, frame))
+    }
+
+    /// Check the desktop's upgrade reply and install the channel
+    fn complete_upgrade(
+        &self,
+        local: KeyExchangeKeypair,
+        reply: &EcnpMessage,
+    ) -> Result<(), EdgeClawError> {
+        let remote_public = upgrade_reply_key(reply).ok_or_else(|| {
+            tracing::warn!(
+                addr = %self.desktop_address(),
+                "Desktop declined sync channel upgrade"
+            );
+            EdgeClawError::ConnectionError
+        })?;
+        let desktop_key = *self.desktop_key.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(key) = &desktop_key {
+            let signed = sync_upgrade_message(&local.public, &remote_public);
+            if verify_upgrade_signature(&reply.payload, key, &signed).is_err() {
+                tracing::warn!(
+                    addr = %self.desktop_address(),
+                    "Desktop failed upgrade authentication"
+                );
+                return Err(EdgeClawError::CryptoError);
+            }
+        }
+
+        let mut sessions = SessionManager::new();
+        let session = sessions.create_session_with(
+            "desktop",
+            &local.secret,
+            &remote_public,
+            &SessionOptions {
+                duration_secs: Some(SYNC_CHANNEL_DURATION_SECS),
+                ..Default::default()
+            },
+        )?;
+        *self.channel.lock().unwrap_or_else(|e| e.into_inner()) = Some(SyncChannel {
+            sessions,
+            session_id: session.session_id,
+            last_peer_counter: None,
+        });
+        tracing::info!(addr = %self.desktop_address(), "Sync channel upgraded");
+        Ok(())
+    }
+
+    /// Whether `upgrade` completed on the current connection
+    pub fn is_upgraded(&self) -> bool {
+        self.channel
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Encode `msg` and encrypt it into an ECNP Data frame for the upgraded
+    /// channel (`SessionNotEstablished` before `upgrade`)
+    pub fn encrypt_to_frame(&self, msg: &SyncMessage) -> Result<Vec<u8>, EdgeClawError> {
+        let inner = msg.encode_ecnp_with_threshold(
+            self.config.format,
+            self.negotiated_compression(),
+            self.config.compression_min_bytes,
+        )?;
+        let channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        let channel = channel
+            .as_ref()
+            .ok_or(EdgeClawError::SessionNotEstablished)?;
+        let sealed = channel.sessions.encrypt(&channel.session_id, &inner)?;
+        EcnpCodec::encode(MessageType::Data, &sealed)
+    }
+
+    /// Frame for an outgoing message: encrypted once upgraded, otherwise
+    /// plaintext unless the message is sensitive and the config demands
+    /// an encrypted channel
+    fn encode_outgoing(&self, msg: &SyncMessage) -> Result<Vec<u8>, EdgeClawError> {
+        if self.is_upgraded() {
+            return self.encrypt_to_frame(msg);
+        }
+        if self.config.require_encrypted_channel && msg.is_sensitive() {
+            tracing::warn!(
+                sync_type = msg.sync_type_code(),
+                "Refusing to send sensitive sync message before channel upgrade"
+            );
+            return Err(EdgeClawError::SessionNotEstablished);
+        }
+        msg.encode_ecnp_with_threshold(
+            self.config.format,
+            self.negotiated_compression(),
+            self.config.compression_min_bytes,
+        )
+    }
+
+    /// Decrypt an incoming frame on the upgraded channel, returning the
+    /// inner sync frame; `None` while the connection is plaintext.
+    ///
+    /// The nonce must carry the desktop's (responder) prefix, so our own
+    /// frames reflected back fail with `CryptoError`, and a counter above
+    /// the last accepted one, so replays fail with `ReplayDetected`.
+    fn open_channel(&self, frame: &EcnpMessage) -> Result<Option<Vec<u8>>, EdgeClawError> {
+        let mut channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(channel) = channel.as_mut() else {
+            return Ok(None);
+        };
+        if frame.msg_type != MessageType::Data as u8 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let nonce = frame.payload.get(..12).ok_or(EdgeClawError::CryptoError)?;
+        let counter = u64::from_be_bytes(nonce[4..].try_into().expect("8-byte counter"));
+        if nonce[..4] != SessionRole::Responder.nonce_prefix() {
+            return Err(EdgeClawError::CryptoError);
+        }
+        if channel
+            .last_peer_counter
+            .is_some_and(|last| counter <= last)
+        {
+            tracing::warn!(
+                addr = %self.desktop_address(),
+                counter,
+                "Rejected replayed sync channel frame"
+            );
+            return Err(EdgeClawError::ReplayDetected);
+        }
+        let inner = channel
+            .sessions
+            .decrypt(&channel.session_id, &frame.payload)?;
+        channel.last_peer_counter = Some(counter);
+        Ok(Some(inner))
+    }
+
+    fn drop_channel(&self) {
+        self.channel
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+
+    /// Create a RemoteExec sync message
+    pub fn create_remote_exec(
+        &self,
+        command: &str,
+        args: Vec<String>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.create_tracked_exec(command, args)
+            .map(|(_, frame)| frame)
+    }
+
+    /// Create a RemoteExec sync message under a fresh request ID, tracked as
+    /// pending until its result or a cancel arrives
+    pub fn create_tracked_exec(
+        &self,
+        command: &str,
+        args: Vec<String>,
+    ) -> Result<(String, Vec<u8>), EdgeClawError> {
+        self.require_capability(CAP_REMOTE_EXEC)?;
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let msg = SyncMessage::RemoteExec {
+            command: command.to_string(),
+            args,
+            request_id: request_id.clone(),
+        };
+        let frame = self.encode_outgoing(&msg)?;
+        self.exec_requests
+            .lock()
+            .map_err(|_| EdgeClawError::InternalError)?
+            .insert(request_id.clone(), std::time::Instant::now());
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        Ok((request_id, frame))
+    }
+
+    /// Mark a pending remote exec cancelled and build the cancel frame for
+    /// the desktop. Unknown or already-finished requests are `InvalidParameter`.
+    pub fn cancel_exec(&self, request_id: &str) -> Result<Vec<u8>, EdgeClawError> {
+        if !self
+            .exec_requests
+            .lock()
+            .map_err(|_| EdgeClawError::InternalError)?
+            .cancel(request_id)
+        {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let msg = SyncMessage::RemoteExecCancel {
+            request_id: request_id.to_string(),
+        };
+        let frame = self.encode_outgoing(&msg)?;
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        tracing::info!(request_id = %request_id, "Remote exec cancel sent");
+        Ok(frame)
+    }
+
+    /// State of a tracked remote exec; `None` once its result has arrived
+    /// or it was dropped for age (an hour) or to stay under the cap of 256
+    pub fn exec_request_state(&self, request_id: &str) -> Option<ExecRequestState> {
+        self.exec_requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(request_id)
+    }
+
+    /// Queue an encoded frame for transmission at the given priority
+    pub fn enqueue(&self, frame: Vec<u8>, priority: SendPriority) -> Result<(), EdgeClawError> {
+        if self.is_shutdown() {
+            return Err(EdgeClawError::ConnectionClosed);
+        }
+        self.send_queue
+            .lock()
+            .map_err(|_| EdgeClawError::InternalError)?
+            .push(frame, priority);
+        Ok(())
+    }
+
+    /// Take the next frame to send, highest priority first
+    pub fn dequeue(&self) -> Option<Vec<u8>> {
+        self.send_queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .map(|(frame, _)| frame)
+    }
+
+    /// Number of frames waiting to be sent
+    pub fn pending_frames(&self) -> usize {
+        self.send_queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Write queued frames to `writer` until the queue is empty.
+    ///
+    /// Frames are popped one at a time, so while a slow socket holds up a
+    /// write, newly enqueued higher-priority frames overtake waiting bulk
+    /// frames. A frame whose write fails or exceeds `write_timeout_secs` is
+    /// put back at the head of its level. While paused nothing is written
+    /// and frames stay queued.
+    pub async fn drain_queue<W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, EdgeClawError> {
+        if self.is_paused() {
+            return Ok(0);
+        }
+        let mut written = 0;
+        loop {
+            let next = self
+                .send_queue
+                .lock()
+                .map_err(|_| EdgeClawError::InternalError)?
+                .pop();
+            let Some((frame, priority)) = next else {
+                return Ok(written);
+            };
+
+            if let Err(e) = self.write_frame_timed(writer, &frame).await {
+                if let Ok(mut queue) = self.send_queue.lock() {
+                    queue.push_front(frame, priority);
+                }
+                return Err(e);
+            }
+            self.messages_sent.fetch_add(1, Ordering::Relaxed);
+            written += 1;
+        }
+    }
+
+    /// Read and process one sync message, failing with `TimeoutError` if
+    /// nothing arrives within `read_timeout_secs` (`IncompleteFrame` if the
+    /// desktop stalls part-way through a frame)
+    pub async fn read_message<R: tokio::io::AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<SyncMessage, EdgeClawError> {
+        self.read_one(reader).await?
+    }
+
+    /// Read and process one frame. The outer error means the stream can no
+    /// longer be trusted; the inner one rejects only this message, with the
+    /// reader already at the next frame boundary.
+    async fn read_one<R: tokio::io::AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Result<SyncMessage, EdgeClawError>, EdgeClawError> {
+        let timeout = std::time::Duration::from_secs(self.config.read_timeout_secs);
+        let limit = self.config.max_message_bytes;
+        let frame = match ecnp::read_frame_bounded(reader, timeout, limit).await? {
+            BoundedFrame::Frame(frame) => frame,
+            BoundedFrame::Skipped { size } => {
+                return Ok(Err(EdgeClawError::PayloadTooLarge { size, max: limit }))
+            }
+        };
+        Ok(self.process_frame(&frame).map(|(msg, _)| msg))
+    }
+
+    /// Open, decode and check one received frame (negotiated capability,
+    /// config hash, replay cache), then record it
+    fn process_frame(
+        &self,
+        frame: &EcnpMessage,
+    ) -> Result<(SyncMessage, Option<StatusAnalysis>), EdgeClawError> {
+        let opened;
+        let (frame, encrypted) = match self.open_channel(frame)? {
+            Some(inner) => {
+                opened = EcnpCodec::decode(&inner)?;
+                (&opened, true)
+            }
+            None => (frame, false),
+        };
+        let (_sync_type, msg) = SyncMessage::from_ecnp_message(frame)?;
+        if !encrypted && self.config.require_encrypted_channel && msg.is_sensitive() {
+            tracing::warn!(
+                sync_type = msg.sync_type_code(),
+                "Rejected sensitive sync message received before channel upgrade"
+            );
+            return Err(EdgeClawError::SessionNotEstablished);
+        }
+        msg.verify_config_hash()?;
+        self.require_capability(msg.capability())?;
+        let mut seen = vec![frame.version, frame.msg_type, frame.flags];
+        seen.extend_from_slice(&frame.payload);
+        let fresh = self
+            .replay_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .check(&seen, std::time::Instant::now());
+        if !fresh {
+            tracing::warn!(
+                sync_type = msg.sync_type_code(),
+                "Replayed sync frame rejected"
+            );
+            return Err(EdgeClawError::ReplayDetected);
+        }
+        Ok(self.record_incoming(msg))
+    }
+
+    /// `read_message` for receive loops: `Ok(None)` once the desktop closes
+    /// the connection between frames, which is a normal disconnect and only
+    /// logged at debug level. A close part-way through a frame is still an
+    /// `IncompleteFrame` error.
+    ///
+    /// A complete frame that cannot be accepted (over `max_message_bytes`,
+    /// undecodable, replayed, ...) is skipped under `BadMessagePolicy::Skip`, so one
+    /// bad message does not cost the connection.
+    pub async fn next_message<R: tokio::io::AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Option<SyncMessage>, EdgeClawError> {
+        loop {
+            match self.read_one(reader).await {
+                Ok(Ok(msg)) => return Ok(Some(msg)),
+                Ok(Err(e)) if self.config.bad_message_policy == BadMessagePolicy::Skip => {
+                    self.messages_skipped.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(error = %e, "Skipping bad sync message");
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(EdgeClawError::ConnectionClosed) => {
+                    tracing::debug!(addr = %self.desktop_address(), "Desktop closed sync connection");
+                    self.set_state(SyncConnectionState::Disconnected);
+                    return Ok(None);
+                }
+                Err(e @ EdgeClawError::IncompleteFrame { .. }) => {
+                    tracing::warn!(
+                        addr = %self.desktop_address(),
+                        error = %e,
+                        "Sync connection closed mid-frame"
+                    );
+                    self.set_state(SyncConnectionState::Error);
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Wait for the `RemoteExecResult` echoing `request_id` (from
+    /// `create_tracked_exec`), processing any other messages that arrive
+    /// first. Results for other requests, even of the same command, are
+    /// not taken; nor are results that don't echo an ID. The whole wait is
+    /// bounded by `exec_result_timeout_secs`.
+    pub async fn await_exec_result<R: tokio::io::AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+        request_id: &str,
+    ) -> Result<SyncMessage, EdgeClawError> {
+        let deadline = std::time::Duration::from_secs(self.config.exec_result_timeout_secs);
+        tokio::time::timeout(deadline, async {
+            loop {
+                let msg = self.read_message(reader).await?;
+                if matches!(&msg, SyncMessage::RemoteExecResult { request_id: id, .. } if id == request_id)
+                {
+                    return Ok(msg);
+                }
+            }
+        })
+        .await
+        .map_err(|_| EdgeClawError::TimeoutError)?
+    }
+
+    async fn read_frame_timed<R: tokio::io::AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<EcnpMessage, EdgeClawError> {
+        let timeout = std::time::Duration::from_secs(self.config.read_timeout_secs);
+        ecnp::read_frame_timeout(reader, timeout).await
+    }
+
+    async fn write_frame_timed<W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        frame: &[u8],
+    ) -> Result<(), EdgeClawError> {
+        let timeout = std::time::Duration::from_secs(self.config.write_timeout_secs);
+        tokio::time::timeout(timeout, ecnp::write_encoded_frame(writer, frame))
+            .await
+            .map_err(|_| EdgeClawError::TimeoutError)?
+    }
+
+    /// Process a received sync message
+    pub fn process_incoming(&self, frame: &[u8]) -> Result<SyncMessage, EdgeClawError> {
+        self.process_incoming_with_analysis(frame)
+            .map(|(msg, _)| msg)
+    }
+
+    /// Process a received sync message, also returning anomaly flags for
+    /// status pushes when `status_thresholds` is configured
+    pub fn process_incoming_with_analysis(
+        &self,
+        frame: &[u8],
+    ) -> Result<(SyncMessage, Option<StatusAnalysis>), EdgeClawError> {
+        self.process_frame(&EcnpCodec::decode(frame)?)
+    }
+
+    /// Update stats and cached state for a decoded incoming message
+    fn record_incoming(&self, mut msg: SyncMessage) -> (SyncMessage, Option<StatusAnalysis>) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        let code = msg.sync_type_code();
+        if let Some(idx) = SYNC_TYPES.iter().position(|t| *t == code) {
+            self.received_by_type[idx].fetch_add(1, Ordering::Relaxed);
+        }
+        if msg.truncate_output(self.config.max_exec_output_bytes) {
+            tracing::warn!(
+                limit = self.config.max_exec_output_bytes,
+                "Remote exec output exceeded limit; truncated"
+            );
+        }
+        let mut analysis = None;
+
+        match &msg {
+            SyncMessage::ConfigSync { config_hash, .. } => {
+                if let Ok(mut hash) = self.last_config_hash.lock() {
+                    *hash = Some(config_hash.clone());
+                }
+                tracing::info!(config_hash = %config_hash, "Config sync received");
+            }
+            SyncMessage::StatusPush { .. } => {
+                if let Ok(mut status) = self.last_status.lock() {
+                    if let Some(thresholds) = &self.config.status_thresholds {
+                        analysis = analyze_status(thresholds, status.as_ref(), &msg);
+                    }
+                    *status = Some(msg.clone());
+                }
+                if analysis.as_ref().is_some_and(|a| a.is_anomalous()) {
+                    tracing::warn!(?analysis, "Status push anomaly");
+                }
+                tracing::info!("Status push received");
+            }
+            SyncMessage::RemoteExecResult {
+                command,
+                exit_code,
+                request_id,
+                ..
+            } => {
+                let state = match self.exec_requests.lock() {
+                    Ok(mut requests) => requests.remove(request_id),
+                    Err(_) => None,
+                };
+                if state == Some(ExecRequestState::Cancelled) {
+                    tracing::info!(request_id = %request_id, "Result for cancelled remote exec");
+                }
+                tracing::info!(command = %command, exit_code = %exit_code, "Remote exec result received");
+            }
+            SyncMessage::RemoteExecCancel { request_id } => {
+                if let Ok(mut requests) = self.exec_requests.lock() {
+                    requests.cancel(request_id);
+                }
+                tracing::info!(request_id = %request_id, "Remote exec cancelled by desktop");
+            }
+            _ => {}
+        }
+
+        (msg, analysis)
+    }
+
+    /// Suspend sync without dropping the connection, e.g. while the app is
+    /// backgrounded: `connect` is refused and `drain_queue` parks outgoing
+    /// frames (heartbeats included) until `resume`. The state reads `Paused`.
+    pub fn pause(&self) {
+        {
+            let mut paused = self.paused.lock().unwrap_or_else(|e| e.into_inner());
+            if paused.is_some() || self.is_shutdown() {
+                return;
+            }
+            *paused = Some(self.state());
+        }
+        self.transition(SyncConnectionState::Paused);
+        tracing::info!("Sync client paused");
+    }
+
+    /// Undo `pause`, restoring the state the client would otherwise be in
+    /// (`Connected` if the connection survived)
+    pub fn resume(&self) {
+        let restored = self.paused.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(state) = restored {
+            self.transition(state);
+            tracing::info!(state = %state, "Sync client resumed");
+        }
+    }
+
+    /// Check if the client is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Request shutdown
+    pub fn shutdown(&self) {
+        *self.paused.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.connected.store(false, Ordering::Relaxed);
+        self.drop_channel();
+        self.set_state(SyncConnectionState::Disconnected);
+        tracing::info!("Sync client shutdown requested");
+    }
+
+    /// Check if shutdown was requested
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+
+    fn set_state(&self, new_state: SyncConnectionState) {
+        if let Some(underlying) = self
+            .paused
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+        {
+            *underlying = new_state;
+            return;
+        }
+        self.transition(new_state);
+    }
+
+    fn transition(&self, new_state: SyncConnectionState) {
+        let changed = match self.state.lock() {
+            Ok(mut state) => std::mem::replace(&mut *state, new_state) != new_state,
+            Err(_) => false,
+        };
+        if !changed {
+            return;
+        }
+
+        // Call the listener with no locks held so it may re-enter the client
+        let listener = self
+            .listener
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(listener) = listener {
+            listener(new_state);
+        }
+    }
+}
+
+// ─── Transport switch helper ───
+
+/// Transport preference for Desktop connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TransportPreference {
+    /// BLE for proximity, TCP for data
+    BleFirst,
+    /// TCP/WiFi LAN direct
+    TcpLan,
+    /// Auto-detect: BLE discovery → TCP switch
+    #[default]
+    Auto,
+}
+
+/// Connection strategy result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionStrategy {
+    pub transport: TransportPreference,
+    pub desktop_address: Option<String>,
+    pub ble_device_id: Option<String>,
+    pub should_use_tcp: bool,
+}
+
+/// Determines the best connection strategy based on available transports.
+///
+/// In Auto mode:
+/// - If BLE device is nearby (discovered), use BLE for initial handshake
+/// - Then switch to TCP/LAN for data transfer
+/// - Fall back to TCP if BLE not available
+pub fn determine_connection_strategy(
+    preference: TransportPreference,
+    ble_device_available: bool,
+    lan_address: Option<&str>,
+) -> ConnectionStrategy {
+    match preference {
+        TransportPreference::BleFirst => ConnectionStrategy {
+            transport: TransportPreference::BleFirst,
+            desktop_address: lan_address.map(|s| s.to_string()),
+            ble_device_id: None,
+            should_use_tcp: !ble_device_available,
+        },
+        TransportPreference::TcpLan => ConnectionStrategy {
+            transport: TransportPreference::TcpLan,
+            desktop_address: lan_address.map(|s| s.to_string()),
+            ble_device_id: None,
+            should_use_tcp: true,
+        },
+        TransportPreference::Auto => {
+            if ble_device_available && lan_address.is_some() {
+                // BLE for discovery, TCP for data
+                ConnectionStrategy {
+                    transport: TransportPreference::Auto,
+                    desktop_address: lan_address.map(|s| s.to_string()),
+                    ble_device_id: None,
+                    should_use_tcp: true, // switch to TCP after BLE discovery
+                }
+            } else if lan_address.is_some() {
+                // TCP only
+                ConnectionStrategy {
+                    transport: TransportPreference::TcpLan,
+                    desktop_address: lan_address.map(|s| s.to_string()),
+                    ble_device_id: None,
+                    should_use_tcp: true,
+                }
+            } else {
+                // BLE only or nothing
+                ConnectionStrategy {
+                    transport: TransportPreference::BleFirst,
+                    desktop_address: None,
+                    ble_device_id: None,
+                    should_use_tcp: false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ─── SyncMessage tests ───
+
+    #[test]
+    fn test_config_sync_roundtrip() {
+        let msg = SyncMessage::ConfigSync {
+            config_hash: "abc123".to_string(),
+            config_data: r#"{"agent":{"name":"test"}}"#.to_string(),
+        };
+
+        let bytes = msg.to_bytes().unwrap();
+        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
+        match decoded {
+            SyncMessage::ConfigSync {
+                config_hash,
+                config_data,
+            } => {
+                assert_eq!(config_hash, "abc123");
+                assert_eq!(config_data, r#"{"agent":{"name":"test"}}"#);
+            }
+            _ => panic!("Expected ConfigSync"),
+        }
+    }
+
+    #[test]
+    fn test_estimated_frame_size() {
+        let messages = [
+            SyncMessage::ConfigSync {
+                config_hash: String::new(),
+                config_data: String::new(),
+            },
+            SyncMessage::RemoteExec {
+                command: "ls".to_string(),
+                args: vec!["-la".to_string(), "/tmp".to_string()],
+                request_id: "req-1".to_string(),
+            },
+            SyncMessage::RemoteExecResult {
+                command: "cat".to_string(),
+                exit_code: 0,
+                stdout: "x".repeat(10_000),
+                stderr: String::new(),
+                truncated: false,
+                encoding: OutputEncoding::Utf8,
+                request_id: String::new(),
+            },
+        ];
+        for msg in &messages {
+            assert_eq!(msg.estimated_frame_size(), msg.encode_ecnp().unwrap().len());
+        }
+    }
+
+    fn all_variants() -> Vec<SyncMessage> {
+        vec![
+            SyncMessage::ConfigSync {
+                config_hash: "abc".to_string(),
+                config_data: "{}".to_string(),
+            },
+            SyncMessage::RemoteExec {
+                command: "ls".to_string(),
+                args: vec!["-la".to_string()],
+                request_id: "req-1".to_string(),
+            },
+            SyncMessage::StatusPush {
+                cpu_usage: 12.5,
+                memory_usage: 40.0,
+                disk_usage: 71.25,
+                uptime_secs: 3600,
+                active_sessions: 2,
+                ai_status: "ollama:running".to_string(),
+            },
+            SyncMessage::RemoteExecResult {
+                command: "ls".to_string(),
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: "denied".to_string(),
+                truncated: false,
+                encoding: OutputEncoding::Utf8,
+                request_id: String::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_cbor_roundtrip_all_variants() {
+        for msg in all_variants() {
+            let frame = msg
+                .encode_ecnp_as(SerializationFormat::Cbor, Compression::None)
+                .unwrap();
+            assert_eq!(EcnpCodec::decode(&frame).unwrap().flags, FLAG_CBOR);
+
+            let (sync_type, decoded) = SyncMessage::decode_ecnp(&frame).unwrap();
+            assert_eq!(sync_type, msg.sync_type_code());
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&msg).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cbor_smaller_than_json() {
+        let status = &all_variants()[2];
+        let json = status.to_bytes_as(SerializationFormat::Json).unwrap();
+        let cbor = status.to_bytes_as(SerializationFormat::Cbor).unwrap();
+        assert!(
+            cbor.len() < json.len(),
+            "cbor {} vs json {}",
+            cbor.len(),
+            json.len()
+        );
+
+        // CBOR combines with compression
+        let frame = status
+            .encode_ecnp_as(SerializationFormat::Cbor, Compression::Zstd)
+            .unwrap();
+        assert!(SyncMessage::decode_ecnp(&frame).is_ok());
+    }
+
+    #[test]
+    fn test_remote_exec_roundtrip() {
+        let msg = SyncMessage::RemoteExec {
+            command: "systemctl".to_string(),
+            args: vec!["status".into(), "nginx".into()],
+            request_id: "req-1".to_string(),
+        };
+
+        let bytes = msg.to_bytes().unwrap();
+        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
+        match decoded {
+            SyncMessage::RemoteExec { command, args, .. } => {
+                assert_eq!(command, "systemctl");
+                assert_eq!(args, vec!["status", "nginx"]);
+            }
+            _ => panic!("Expected RemoteExec"),
+        }
+    }
+
+    #[test]
+    fn test_status_push_roundtrip() {
+        let msg = SyncMessage::StatusPush {
+            cpu_usage: 45.5,
+            memory_usage: 60.0,
+            disk_usage: 72.3,
+            uptime_secs: 86400,
+            active_sessions: 3,
+            ai_status: "ollama:ready".to_string(),
+        };
+
+        let bytes = msg.to_bytes().unwrap();
+        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
+        match decoded {
+            SyncMessage::StatusPush {
+                cpu_usage,
+                uptime_secs,
+                ai_status,
+                ..
+            } => {
+                assert!((cpu_usage - 45.5).abs() < f64::EPSILON);
+                assert_eq!(uptime_secs, 86400);
+                assert_eq!(ai_status, "ollama:ready");
+            }
+            _ => panic!("Expected StatusPush"),
+        }
+    }
+
+    #[test]
+    fn test_remote_exec_result_truncates_oversized_output() {
+        let msg = SyncMessage::remote_exec_result(
+            "dump",
+            0,
+            "x".repeat(DEFAULT_MAX_EXEC_OUTPUT_BYTES + 100),
+            "warn".to_string(),
+            DEFAULT_MAX_EXEC_OUTPUT_BYTES,
+        );
+        let (_, decoded) = SyncMessage::decode_ecnp(&msg.encode_ecnp().unwrap()).unwrap();
+        match decoded {
+            SyncMessage::RemoteExecResult {
+                stdout,
+                stderr,
+                truncated,
+                ..
+            } => {
+                assert_eq!(stdout.len(), DEFAULT_MAX_EXEC_OUTPUT_BYTES);
+                assert_eq!(stderr, "warn");
+                assert!(truncated);
+            }
+            _ => panic!("Expected RemoteExecResult"),
+        }
+    }
+
+    #[test]
+    fn test_wire_version_roundtrip() {
+        let msg = SyncMessage::RemoteExecCancel {
+            request_id: "req-1".into(),
+        };
+        let body: serde_json::Value = serde_json::from_slice(&msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(body["v"], SYNC_WIRE_VERSION);
+
+        for format in [SerializationFormat::Json, SerializationFormat::Cbor] {
+            let frame = msg.encode_ecnp_as(format, Compression::None).unwrap();
+            let (_, decoded) = SyncMessage::decode_ecnp(&frame).unwrap();
+            assert!(
+                matches!(decoded, SyncMessage::RemoteExecCancel { request_id } if request_id == "req-1")
+            );
+        }
+
+        // Bodies from peers that predate versioning are read as version 1
+        let legacy = br#"{"type":"remote_exec_cancel","request_id":"req-1"}"#;
+        assert!(SyncMessage::from_bytes(legacy).is_ok());
+    }
+
+    #[test]
+    fn test_future_wire_version_rejected() {
+        let frame = SyncFrame {
+            sync_type: SYNC_REMOTE_EXEC_CANCEL,
+            format: SerializationFormat::Json,
+            body: br#"{"v":2,"type":"remote_exec_cancel","request_id":"r"}"#.to_vec(),
+        }
+        .encode()
+        .unwrap();
+        assert!(matches!(
+            SyncMessage::decode_ecnp(&frame),
+            Err(EdgeClawError::UnsupportedSyncVersion { version: 2 })
+        ));
+
+        let mut cbor = Vec::new();
+        ciborium::into_writer(
+            &serde_json::json!({ "v": 9, "type": "remote_exec_cancel", "request_id": "r" }),
+            &mut cbor,
+        )
+        .unwrap();
+        assert!(matches!(
+            SyncMessage::from_bytes_as(&cbor, SerializationFormat::Cbor),
+            Err(EdgeClawError::UnsupportedSyncVersion { version: 9 })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_sync_type_rejected() {
+        let mut payload = vec![0x1A];
+        payload.extend_from_slice(br#"{"type":"future_feature"}"#);
+        let frame = EcnpCodec::encode(MessageType::Data, &payload).unwrap();
+
+        assert!(matches!(
+            SyncMessage::decode_ecnp(&frame),
+            Err(EdgeClawError::UnknownSyncType { code: 0x1A })
+        ));
+
+        let client = SyncClient::new(SyncClientConfig::default());
+        assert!(matches!(
+            client.process_incoming(&frame),
+            Err(EdgeClawError::UnknownSyncType { code: 0x1A })
+        ));
+        assert_eq!(client.stats().messages_received, 0);
+    }
+
+    #[test]
+    fn test_binary_stdout_roundtrip() {
+        let png_header = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0xFF, 0x00];
+        assert!(std::str::from_utf8(&png_header).is_err());
+
+        let msg = SyncMessage::remote_exec_result_bytes(
+            "cat image.png",
+            0,
+            png_header.clone(),
+            b"done".to_vec(),
+            DEFAULT_MAX_EXEC_OUTPUT_BYTES,
+        );
+        for format in [SerializationFormat::Json, SerializationFormat::Cbor] {
+            let frame = msg.encode_ecnp_as(format, Compression::None).unwrap();
+            let (_, decoded) = SyncMessage::decode_ecnp(&frame).unwrap();
+            assert!(matches!(
+                decoded,
+                SyncMessage::RemoteExecResult {
+                    encoding: OutputEncoding::Base64,
+                    ..
+                }
+            ));
+            let (stdout, stderr) = decoded.exec_output_bytes().unwrap();
+            assert_eq!(stdout, png_header);
+            assert_eq!(stderr, b"done");
+        }
+    }
+
+    #[test]
+    fn test_text_output_stays_utf8() {
+        let msg = SyncMessage::remote_exec_result_bytes("echo", 0, b"hi\n".to_vec(), vec![], 1024);
+        match &msg {
+            SyncMessage::RemoteExecResult {
+                stdout, encoding, ..
+            } => {
+                assert_eq!(stdout, "hi\n");
+                assert_eq!(*encoding, OutputEncoding::Utf8);
+            }
+            _ => panic!("Expected RemoteExecResult"),
+        }
+        assert_eq!(msg.exec_output_bytes().unwrap().0, b"hi\n");
+    }
+
+    #[test]
+    fn test_binary_output_truncation_still_decodes() {
+        let mut msg = SyncMessage::remote_exec_result_bytes("dd", 0, vec![0xFF; 100], vec![], 30);
+        let (stdout, _) = msg.exec_output_bytes().unwrap();
+        assert_eq!(stdout.len(), 21);
+
+        // Incoming cap on an encoded stream keeps whole base64 groups
+        msg.truncate_output(10);
+        let (stdout, _) = msg.exec_output_bytes().unwrap();
+        assert_eq!(stdout, vec![0xFF; 6]);
+    }
+
+    #[test]
+    fn test_truncate_output_respects_char_boundaries() {
+        let mut msg = SyncMessage::remote_exec_result("x", 0, "é".repeat(10), String::new(), 5);
+        if let SyncMessage::RemoteExecResult { stdout, .. } = &msg {
+            assert_eq!(stdout, "éé");
+        }
+        // Already within the limit: nothing more to cut
+        assert!(!msg.truncate_output(5));
+    }
+
+    #[test]
+    fn test_incoming_exec_output_capped() {
+        let client = SyncClient::new(SyncClientConfig {
+            max_exec_output_bytes: 16,
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        let oversized = SyncMessage::RemoteExecResult {
+            command: "cat".into(),
+            exit_code: 0,
+            stdout: "y".repeat(100),
+            stderr: String::new(),
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: String::new(),
+        };
+        match client
+            .process_incoming(&oversized.encode_ecnp().unwrap())
+            .unwrap()
+        {
+            SyncMessage::RemoteExecResult {
+                stdout, truncated, ..
+            } => {
+                assert_eq!(stdout.len(), 16);
+                assert!(truncated);
+            }
+            _ => panic!("Expected RemoteExecResult"),
+        }
+
+        // Results from older desktops lack the flag
+        let legacy =
+            r#"{"type":"remote_exec_result","command":"ls","exit_code":0,"stdout":"","stderr":""}"#;
+        assert!(SyncMessage::from_bytes(legacy.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_remote_exec_result_roundtrip() {
+        let msg = SyncMessage::RemoteExecResult {
+            command: "hostname".to_string(),
+            exit_code: 0,
+            stdout: "edgeclaw-pc\n".to_string(),
+            stderr: String::new(),
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: String::new(),
+        };
+
+        let bytes = msg.to_bytes().unwrap();
+        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
+        match decoded {
+            SyncMessage::RemoteExecResult {
+                command,
+                exit_code,
+                stdout,
+                stderr,
+                ..
+            } => {
+                assert_eq!(command, "hostname");
+                assert_eq!(exit_code, 0);
+                assert_eq!(stdout, "edgeclaw-pc\n");
+                assert!(stderr.is_empty());
+            }
+            _ => panic!("Expected RemoteExecResult"),
+        }
+    }
+
+    #[test]
+    fn test_sync_type_codes() {
+        let config = SyncMessage::ConfigSync {
+            config_hash: "h".into(),
+            config_data: "d".into(),
+        };
+        assert_eq!(config.sync_type_code(), SYNC_CONFIG);
+
+        let exec = SyncMessage::RemoteExec {
+            command: "ls".into(),
+            args: vec![],
+            request_id: "req-1".to_string(),
+        };
+        assert_eq!(exec.sync_type_code(), SYNC_REMOTE_EXEC);
+
+        let status = SyncMessage::StatusPush {
+            cpu_usage: 0.0,
+            memory_usage: 0.0,
+            disk_usage: 0.0,
+            uptime_secs: 0,
+            active_sessions: 0,
+            ai_status: String::new(),
+        };
+        assert_eq!(status.sync_type_code(), SYNC_STATUS_PUSH);
+
+        let result = SyncMessage::RemoteExecResult {
+            command: String::new(),
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: String::new(),
+        };
+        assert_eq!(result.sync_type_code(), SYNC_REMOTE_EXEC_RESULT);
+    }
+
+    // ─── ECNP encoding tests ───
+
     #[test]
-    fn test_status_push_roundtrip() {
+    fn test_sync_message_ecnp_encode_decode() {
+        let msg = SyncMessage::RemoteExec {
+            command: "uptime".to_string(),
+            args: vec![],
+            request_id: "req-1".to_string(),
+        };
+
+        let frame = msg.encode_ecnp().unwrap();
+        let (sync_type, decoded) = SyncMessage::decode_ecnp(&frame).unwrap();
+
+        assert_eq!(sync_type, SYNC_REMOTE_EXEC);
+        match decoded {
+            SyncMessage::RemoteExec { command, args, .. } => {
+                assert_eq!(command, "uptime");
+                assert!(args.is_empty());
+            }
+            _ => panic!("Expected RemoteExec"),
+        }
+    }
+
+    #[test]
+    fn test_sync_frame_roundtrip() {
+        let frame = SyncFrame {
+            sync_type: 0x1A,
+            format: SerializationFormat::Cbor,
+            body: vec![1, 2, 3],
+        };
+        let encoded = frame.encode().unwrap();
+        assert_eq!(SyncFrame::decode(&encoded).unwrap(), frame);
+
+        let compressed = SyncFrame {
+            body: vec![b'x'; 4096],
+            ..frame.clone()
+        };
+        let encoded = compressed
+            .encode_with_threshold(Compression::Zstd, 0)
+            .unwrap();
+        assert!(encoded.len() < 4096);
+        assert_eq!(SyncFrame::decode(&encoded).unwrap(), compressed);
+
+        let heartbeat = EcnpCodec::encode(MessageType::Heartbeat, b"x").unwrap();
+        assert!(SyncFrame::decode(&heartbeat).is_err());
+        let empty = EcnpCodec::encode(MessageType::Data, b"").unwrap();
+        assert!(SyncFrame::decode(&empty).is_err());
+    }
+
+    #[test]
+    fn test_sync_frame_matches_sync_message_framing() {
+        let msg = SyncMessage::RemoteExecCancel {
+            request_id: "req-9".into(),
+        };
+        for format in [SerializationFormat::Json, SerializationFormat::Cbor] {
+            let encoded = msg.encode_ecnp_as(format, Compression::None).unwrap();
+            let frame = SyncFrame::decode(&encoded).unwrap();
+            assert_eq!(frame.sync_type, SYNC_REMOTE_EXEC_CANCEL);
+            assert_eq!(frame.format, format);
+            assert_eq!(frame.body, msg.to_bytes_as(format).unwrap());
+
+            // A hand-built frame is byte-identical and still decodes
+            assert_eq!(frame.encode().unwrap(), encoded);
+            let (code, decoded) = SyncMessage::decode_ecnp(&frame.encode().unwrap()).unwrap();
+            assert_eq!(code, SYNC_REMOTE_EXEC_CANCEL);
+            assert!(matches!(decoded, SyncMessage::RemoteExecCancel { .. }));
+        }
+    }
+
+    #[test]
+    fn test_ecnp_decode_wrong_type_fails() {
+        // Encode as Heartbeat (not Data) — should fail sync decode
+        let payload = b"dummy";
+        let frame = EcnpCodec::encode(MessageType::Heartbeat, payload).unwrap();
+        assert!(SyncMessage::decode_ecnp(&frame).is_err());
+    }
+
+    // ─── SyncClient tests ───
+
+    #[test]
+    fn test_sync_client_creation() {
+        let config = SyncClientConfig::default();
+        let client = SyncClient::new(config);
+
+        assert_eq!(client.state(), SyncConnectionState::Disconnected);
+        assert!(!client.is_connected());
+        assert_eq!(client.desktop_address(), "127.0.0.1:8443");
+    }
+
+    #[test]
+    fn test_sync_client_stats() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        let stats = client.stats();
+        assert_eq!(stats.messages_sent, 0);
+        assert_eq!(stats.messages_received, 0);
+        assert_eq!(stats.reconnect_count, 0);
+        assert!(stats.last_config_hash.is_none());
+    }
+
+    /// Client allowed to send sensitive messages without `upgrade`
+    fn plaintext_client() -> SyncClient {
+        SyncClient::new(SyncClientConfig {
+            require_encrypted_channel: false,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_sync_client_create_remote_exec() {
+        // Never in plaintext unless explicitly allowed
+        let client = SyncClient::new(SyncClientConfig::default());
+        assert!(matches!(
+            client.create_remote_exec("hostname", vec![]),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+
+        let client = plaintext_client();
+        let frame = client.create_remote_exec("hostname", vec![]).unwrap();
+
+        // Should be a valid ECNP frame
+        let (sync_type, msg) = SyncMessage::decode_ecnp(&frame).unwrap();
+        assert_eq!(sync_type, SYNC_REMOTE_EXEC);
+        match msg {
+            SyncMessage::RemoteExec { command, .. } => assert_eq!(command, "hostname"),
+            _ => panic!("Expected RemoteExec"),
+        }
+
+        // Stats should be updated
+        assert_eq!(client.stats().messages_sent, 1);
+    }
+
+    #[test]
+    fn test_compute_config_hash_known_values() {
+        assert_eq!(
+            compute_config_hash(""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            compute_config_hash(r#"{"agent":{"name":"pc"}}"#),
+            "sha256:b528aa55c5e8bf57467a39291022f0963e503debe162e9cc170e04b93f7e8c10"
+        );
+        // No normalisation: whitespace changes the hash
+        assert_ne!(
+            compute_config_hash(r#"{"agent": {"name": "pc"}}"#),
+            compute_config_hash(r#"{"agent":{"name":"pc"}}"#)
+        );
+    }
+
+    #[test]
+    fn test_sync_client_process_config_sync() {
+        let client = plaintext_client();
+        let msg = SyncMessage::config_sync(r#"{"agent":{"name":"pc"}}"#);
+        let frame = msg.encode_ecnp().unwrap();
+
+        let result = client.process_incoming(&frame).unwrap();
+        match result {
+            SyncMessage::ConfigSync { config_hash, .. } => {
+                assert_eq!(
+                    config_hash,
+                    "sha256:b528aa55c5e8bf57467a39291022f0963e503debe162e9cc170e04b93f7e8c10"
+                );
+            }
+            _ => panic!("Expected ConfigSync"),
+        }
+
+        assert_eq!(client.stats().messages_received, 1);
+        assert_eq!(
+            client.stats().last_config_hash,
+            Some(
+                "sha256:b528aa55c5e8bf57467a39291022f0963e503debe162e9cc170e04b93f7e8c10"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_sync_client_rejects_config_hash_mismatch() {
+        let client = plaintext_client();
+        let msg = SyncMessage::ConfigSync {
+            config_hash: "sha256:abc".to_string(),
+            config_data: r#"{"agent":{"name":"pc"}}"#.to_string(),
+        };
+        assert!(matches!(
+            client.process_incoming(&msg.encode_ecnp().unwrap()),
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert_eq!(client.stats().messages_received, 0);
+        assert!(client.stats().last_config_hash.is_none());
+    }
+
+    #[test]
+    fn test_sync_client_process_status_push() {
+        let client = SyncClient::new(SyncClientConfig::default());
         let msg = SyncMessage::StatusPush {
-            cpu_usage: 45.5,
-            memory_usage: 60.0,
-            disk_usage: 72.3,
-            uptime_secs: 86400,
-            active_sessions: 3,
-            ai_status: "ollama:ready".to_string(),
+            cpu_usage: 10.0,
+            memory_usage: 50.0,
+            disk_usage: 30.0,
+            uptime_secs: 7200,
+            active_sessions: 2,
+            ai_status: "ollama:running".to_string(),
+        };
+        let frame = msg.encode_ecnp().unwrap();
+
+        let result = client.process_incoming(&frame).unwrap();
+        match result {
+            SyncMessage::StatusPush { uptime_secs, .. } => {
+                assert_eq!(uptime_secs, 7200);
+            }
+            _ => panic!("Expected StatusPush"),
+        }
+
+        assert!(client.stats().last_status_push.is_some());
+    }
+
+    #[test]
+    fn test_remote_exec_cancel_roundtrip() {
+        let client = plaintext_client();
+        let (request_id, exec_frame) = client.create_tracked_exec("sleep", vec![]).unwrap();
+        let (_, exec) = SyncMessage::decode_ecnp(&exec_frame).unwrap();
+        assert!(
+            matches!(exec, SyncMessage::RemoteExec { request_id: ref id, .. } if *id == request_id)
+        );
+        assert_eq!(
+            client.exec_request_state(&request_id),
+            Some(ExecRequestState::Pending)
+        );
+
+        let frame = client.cancel_exec(&request_id).unwrap();
+        let (code, msg) = SyncMessage::decode_ecnp(&frame).unwrap();
+        assert_eq!(code, SYNC_REMOTE_EXEC_CANCEL);
+        match msg {
+            SyncMessage::RemoteExecCancel { request_id: id } => assert_eq!(id, request_id),
+            _ => panic!("Expected RemoteExecCancel"),
+        }
+        assert_eq!(
+            client.exec_request_state(&request_id),
+            Some(ExecRequestState::Cancelled)
+        );
+        assert!(matches!(
+            client.cancel_exec("unknown"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn test_incoming_cancel_and_result_update_request_state() {
+        let client = plaintext_client();
+        let (first, _) = client.create_tracked_exec("sleep", vec![]).unwrap();
+        let (second, _) = client.create_tracked_exec("make", vec![]).unwrap();
+
+        // Desktop aborts the first request on its own
+        let cancel = SyncMessage::RemoteExecCancel {
+            request_id: first.clone(),
         };
+        client
+            .process_incoming(&cancel.encode_ecnp().unwrap())
+            .unwrap();
+        assert_eq!(
+            client.exec_request_state(&first),
+            Some(ExecRequestState::Cancelled)
+        );
+
+        // A result echoing the ID finishes tracking for that request
+        let result = SyncMessage::remote_exec_result("make", 0, String::new(), String::new(), 1024)
+            .with_request_id(&second);
+        client
+            .process_incoming(&result.encode_ecnp().unwrap())
+            .unwrap();
+        assert_eq!(client.exec_request_state(&second), None);
+    }
+
+    #[test]
+    fn test_received_by_type_histogram() {
+        let client = plaintext_client();
+        let config = SyncMessage::config_sync("{}");
+        let result = SyncMessage::remote_exec_result("ls", 0, "ok".into(), String::new(), 1024);
+        let mut frames = vec![config.encode_ecnp().unwrap(), result.encode_ecnp().unwrap()];
+        for cpu in [10.0, 20.0, 30.0] {
+            frames.push(status(cpu).encode_ecnp().unwrap());
+        }
+        for frame in &frames {
+            client.process_incoming(frame).unwrap();
+        }
+
+        let stats = client.stats();
+        assert_eq!(stats.messages_received, 5);
+        assert_eq!(stats.received_by_type.get(&SYNC_STATUS_PUSH), Some(&3));
+        assert_eq!(stats.received_by_type.get(&SYNC_CONFIG), Some(&1));
+        assert_eq!(
+            stats.received_by_type.get(&SYNC_REMOTE_EXEC_RESULT),
+            Some(&1)
+        );
+        assert!(!stats.received_by_type.contains_key(&SYNC_REMOTE_EXEC));
+    }
+
+    #[test]
+    fn test_sync_client_shutdown() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        assert!(!client.is_shutdown());
+
+        client.shutdown();
+        assert!(client.is_shutdown());
+        assert!(!client.is_connected());
+        assert_eq!(client.state(), SyncConnectionState::Disconnected);
+    }
+
+    // ─── Send queue tests ───
+
+    #[test]
+    fn test_send_queue_drains_by_priority() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        client
+            .enqueue(b"bulk-1".to_vec(), SendPriority::Bulk)
+            .unwrap();
+        client
+            .enqueue(b"heartbeat".to_vec(), SendPriority::Interactive)
+            .unwrap();
+        client
+            .enqueue(b"bulk-2".to_vec(), SendPriority::Bulk)
+            .unwrap();
+        client
+            .enqueue(b"close".to_vec(), SendPriority::Control)
+            .unwrap();
+        assert_eq!(client.pending_frames(), 4);
+
+        let order: Vec<Vec<u8>> = std::iter::from_fn(|| client.dequeue()).collect();
+        assert_eq!(
+            order,
+            vec![
+                b"close".to_vec(),
+                b"heartbeat".to_vec(),
+                b"bulk-1".to_vec(),
+                b"bulk-2".to_vec(),
+            ]
+        );
+        assert_eq!(client.pending_frames(), 0);
+    }
+
+    #[test]
+    fn test_enqueue_after_shutdown() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        client.shutdown();
+        assert!(matches!(
+            client.enqueue(vec![1], SendPriority::Control),
+            Err(EdgeClawError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_control_frame_overtakes_bulk_under_backpressure() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        for i in 0..3u8 {
+            let frame = EcnpCodec::encode(MessageType::Data, &[i; 40]).unwrap();
+            client.enqueue(frame, SendPriority::Bulk).unwrap();
+        }
+
+        // Small pipe: the drain stalls on the second bulk frame
+        let (mut writer, mut reader) = tokio::io::duplex(64);
+        let drainer = client.clone();
+        let drain = tokio::spawn(async move { drainer.drain_queue(&mut writer).await });
+        tokio::task::yield_now().await;
+
+        let close = EcnpCodec::encode(MessageType::Control, b"close").unwrap();
+        client.enqueue(close, SendPriority::Control).unwrap();
+
+        let mut types = Vec::new();
+        for _ in 0..4 {
+            types.push(ecnp::read_frame(&mut reader).await.unwrap().msg_type);
+        }
+        assert_eq!(drain.await.unwrap().unwrap(), 4);
+
+        let data = MessageType::Data as u8;
+        let control = MessageType::Control as u8;
+        assert_eq!(types, vec![data, data, control, data]);
+    }
+
+    // ─── Connection strategy tests ───
+
+    #[test]
+    fn test_connection_strategy_auto_both() {
+        let strategy = determine_connection_strategy(
+            TransportPreference::Auto,
+            true,
+            Some("192.168.1.10:8443"),
+        );
+        assert!(strategy.should_use_tcp);
+        assert_eq!(strategy.transport, TransportPreference::Auto);
+        assert_eq!(
+            strategy.desktop_address,
+            Some("192.168.1.10:8443".to_string())
+        );
+    }
+
+    #[test]
+    fn test_connection_strategy_auto_tcp_only() {
+        let strategy =
+            determine_connection_strategy(TransportPreference::Auto, false, Some("10.0.0.5:8443"));
+        assert!(strategy.should_use_tcp);
+        assert_eq!(strategy.transport, TransportPreference::TcpLan);
+    }
+
+    #[test]
+    fn test_connection_strategy_auto_ble_only() {
+        let strategy = determine_connection_strategy(TransportPreference::Auto, true, None);
+        assert!(!strategy.should_use_tcp);
+        assert_eq!(strategy.transport, TransportPreference::BleFirst);
+    }
+
+    #[test]
+    fn test_connection_strategy_explicit_tcp() {
+        let strategy = determine_connection_strategy(
+            TransportPreference::TcpLan,
+            true,
+            Some("192.168.1.1:8443"),
+        );
+        assert!(strategy.should_use_tcp);
+        assert_eq!(strategy.transport, TransportPreference::TcpLan);
+    }
+
+    #[test]
+    fn test_connection_strategy_ble_first_no_ble() {
+        let strategy = determine_connection_strategy(
+            TransportPreference::BleFirst,
+            false,
+            Some("192.168.1.1:8443"),
+        );
+        // Falls back to TCP when BLE not available
+        assert!(strategy.should_use_tcp);
+    }
 
-        let bytes = msg.to_bytes().unwrap();
-        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
-        match decoded {
-            SyncMessage::StatusPush {
-                cpu_usage,
-                uptime_secs,
-                ai_status,
-                ..
-            } => {
-                assert!((cpu_usage - 45.5).abs() < f64::EPSILON);
-                assert_eq!(uptime_secs, 86400);
-                assert_eq!(ai_status, "ollama:ready");
-            }
-            _ => panic!("Expected StatusPush"),
+    fn status(cpu_usage: f64) -> SyncMessage {
+        SyncMessage::StatusPush {
+            cpu_usage,
+            memory_usage: 40.0,
+            disk_usage: 30.0,
+            uptime_secs: 100,
+            active_sessions: 1,
+            ai_status: "idle".to_string(),
         }
     }
 
     #[test]
-    fn test_remote_exec_result_roundtrip() {
-        let msg = SyncMessage::RemoteExecResult {
-            command: "hostname".to_string(),
-            exit_code: 0,
-            stdout: "edgeclaw-pc\n".to_string(),
-            stderr: String::new(),
-        };
+    fn test_status_push_cpu_spike_flagged() {
+        let client = SyncClient::new(SyncClientConfig {
+            status_thresholds: Some(StatusThresholds::default()),
+            ..Default::default()
+        });
 
-        let bytes = msg.to_bytes().unwrap();
-        let decoded = SyncMessage::from_bytes(&bytes).unwrap();
-        match decoded {
-            SyncMessage::RemoteExecResult {
-                command,
-                exit_code,
-                stdout,
-                stderr,
-            } => {
-                assert_eq!(command, "hostname");
-                assert_eq!(exit_code, 0);
-                assert_eq!(stdout, "edgeclaw-pc\n");
-                assert!(stderr.is_empty());
-            }
-            _ => panic!("Expected RemoteExecResult"),
-        }
+        let (_, first) = client
+            .process_incoming_with_analysis(&status(20.0).encode_ecnp().unwrap())
+            .unwrap();
+        assert!(!first.unwrap().is_anomalous());
+
+        let (_, second) = client
+            .process_incoming_with_analysis(&status(100.0).encode_ecnp().unwrap())
+            .unwrap();
+        let second = second.unwrap();
+        assert!(second.cpu_high);
+        assert!(second.cpu_spike);
+        assert!(!second.memory_high && !second.memory_spike);
     }
 
     #[test]
-    fn test_sync_type_codes() {
-        let config = SyncMessage::ConfigSync {
-            config_hash: "h".into(),
-            config_data: "d".into(),
-        };
-        assert_eq!(config.sync_type_code(), SYNC_CONFIG);
+    fn test_status_analysis_disabled_by_default() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        let (_, analysis) = client
+            .process_incoming_with_analysis(&status(100.0).encode_ecnp().unwrap())
+            .unwrap();
+        assert!(analysis.is_none());
+    }
+
+    #[test]
+    fn test_classify_command() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            classify_command("ls", &args(&["-la", "/tmp"])),
+            CommandRisk::Low
+        );
+        assert_eq!(classify_command("uptime", &[]), CommandRisk::Low);
+        assert_eq!(
+            classify_command("kill", &args(&["-TERM", "42"])),
+            CommandRisk::Medium
+        );
+        assert_eq!(
+            classify_command("rm", &args(&["notes.txt"])),
+            CommandRisk::Medium
+        );
+        assert_eq!(
+            classify_command("sudo", &args(&["apt", "update"])),
+            CommandRisk::Medium
+        );
+
+        assert_eq!(
+            classify_command("rm", &args(&["-rf", "/"])),
+            CommandRisk::High
+        );
+        assert_eq!(classify_command("rm -rf /", &[]), CommandRisk::High);
+        assert_eq!(
+            classify_command("sudo", &args(&["/sbin/mkfs.ext4", "/dev/sda1"])),
+            CommandRisk::High
+        );
+        assert_eq!(
+            classify_command("/usr/bin/sudo", &args(&["dd", "if=/dev/zero"])),
+            CommandRisk::High
+        );
+        assert_eq!(
+            classify_command("bash", &args(&["-c", "echo hi"])),
+            CommandRisk::High
+        );
+        assert_eq!(
+            classify_command("echo", &args(&["hi", ";", "reboot"])),
+            CommandRisk::High
+        );
+        assert_eq!(
+            classify_command("cat", &args(&["$(whoami)"])),
+            CommandRisk::High
+        );
+        assert_eq!(
+            classify_command("SHUTDOWN.EXE", &args(&["/s"])),
+            CommandRisk::High
+        );
+    }
+
+    #[test]
+    fn test_connection_listener_observes_transitions() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let observer = client.clone();
+        let log = seen.clone();
+        client.set_connection_listener(Box::new(move |state| {
+            // Re-entering the client must not deadlock
+            assert_eq!(observer.state(), state);
+            log.lock().unwrap().push(state);
+        }));
+
+        client.set_state(SyncConnectionState::Connecting);
+        client.set_state(SyncConnectionState::Handshaking);
+        client.set_state(SyncConnectionState::Handshaking);
+        client.set_state(SyncConnectionState::Connected);
+        client.shutdown();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                SyncConnectionState::Connecting,
+                SyncConnectionState::Handshaking,
+                SyncConnectionState::Connected,
+                SyncConnectionState::Disconnected,
+            ]
+        );
+    }
+
+    /// Mock desktop that answers the handshake, signing the challenge with
+    /// `signer` if given
+    async fn spawn_signing_desktop(
+        signer: Option<ed25519_dalek::SigningKey>,
+    ) -> std::net::SocketAddr {
+        use ed25519_dalek::Signer;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handshake = ecnp::read_frame(&mut socket).await.unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&handshake.payload).unwrap();
+
+            let mut ack = serde_json::json!({ "compression": ["none"] });
+            if let (Some(key), Some(challenge)) = (signer, request["challenge"].as_str()) {
+                let challenge = hex::decode(challenge).unwrap();
+                let sig = key.sign(&sync_challenge_message(&challenge));
+                ack["challenge_signature"] = hex::encode(sig.to_bytes()).into();
+            }
+            let payload = serde_json::to_vec(&ack).unwrap();
+            ecnp::write_frame(&mut socket, MessageType::Ack, &payload)
+                .await
+                .unwrap();
+            let _ = ecnp::read_frame(&mut socket).await;
+        });
+        addr
+    }
 
+    /// Desktop that acks with the given capability list and hands back the
+    /// capabilities the client advertised
+    async fn spawn_capability_desktop(
+        capabilities: Option<Vec<&'static str>>,
+    ) -> (
+        std::net::SocketAddr,
+        tokio::sync::oneshot::Receiver<serde_json::Value>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handshake = ecnp::read_frame(&mut socket).await.unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&handshake.payload).unwrap();
+            let _ = tx.send(request["capabilities"].clone());
+
+            let mut ack = serde_json::json!({ "compression": ["none"] });
+            if let Some(caps) = capabilities {
+                ack["capabilities"] = caps.into();
+            }
+            let payload = serde_json::to_vec(&ack).unwrap();
+            ecnp::write_frame(&mut socket, MessageType::Ack, &payload)
+                .await
+                .unwrap();
+            let _ = ecnp::read_frame(&mut socket).await;
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_read_only_client_cannot_exec() {
+        let (addr, advertised) = spawn_capability_desktop(None).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            capabilities: vec![CAP_STATUS_PUSH.into()],
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+
+        assert_eq!(
+            advertised.await.unwrap(),
+            serde_json::json!(["status_push"])
+        );
+        assert_eq!(client.negotiated_capabilities(), vec!["status_push"]);
+        assert!(matches!(
+            client.create_remote_exec("ls", vec![]),
+            Err(EdgeClawError::InvalidCapability)
+        ));
+
+        // An incoming exec request is refused as well
         let exec = SyncMessage::RemoteExec {
+            request_id: "r1".into(),
             command: "ls".into(),
             args: vec![],
-        };
-        assert_eq!(exec.sync_type_code(), SYNC_REMOTE_EXEC);
+        }
+        .encode_ecnp()
+        .unwrap();
+        assert!(matches!(
+            client.process_incoming(&exec),
+            Err(EdgeClawError::InvalidCapability)
+        ));
 
         let status = SyncMessage::StatusPush {
-            cpu_usage: 0.0,
-            memory_usage: 0.0,
-            disk_usage: 0.0,
-            uptime_secs: 0,
+            cpu_usage: 1.0,
+            memory_usage: 2.0,
+            disk_usage: 3.0,
+            uptime_secs: 4,
             active_sessions: 0,
-            ai_status: String::new(),
-        };
-        assert_eq!(status.sync_type_code(), SYNC_STATUS_PUSH);
+            ai_status: "idle".into(),
+        }
+        .encode_ecnp()
+        .unwrap();
+        assert!(client.process_incoming(&status).is_ok());
+    }
 
-        let result = SyncMessage::RemoteExecResult {
-            command: String::new(),
-            exit_code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
-        assert_eq!(result.sync_type_code(), SYNC_REMOTE_EXEC_RESULT);
+    #[tokio::test]
+    async fn test_capabilities_negotiated_with_desktop() {
+        let (addr, _) = spawn_capability_desktop(Some(vec!["config_sync", "status_push"])).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        assert!(client.create_remote_exec("ls", vec![]).is_ok());
+
+        client.connect().await.unwrap();
+        assert_eq!(
+            client.negotiated_capabilities(),
+            vec!["config_sync", "status_push"]
+        );
+        assert!(matches!(
+            client.create_remote_exec("ls", vec![]),
+            Err(EdgeClawError::InvalidCapability)
+        ));
     }
 
-    // ─── ECNP encoding tests ───
+    fn paired_client(addr: std::net::SocketAddr, key: VerifyingKey) -> SyncClient {
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            desktop_peer_id: Some("desktop".into()),
+            ..Default::default()
+        });
+        client.set_desktop_key(key);
+        client
+    }
+
+    /// Listener that acks as the device ID it was handed, or as whoever
+    /// dialed it when `None` (i.e. the client reached its own listener)
+    async fn spawn_identified_desktop(device_id: Option<&'static str>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handshake = ecnp::read_frame(&mut socket).await.unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&handshake.payload).unwrap();
+            let id = device_id.map_or(request["device_id"].clone(), Into::into);
+            let ack = serde_json::json!({ "compression": ["none"], "device_id": id });
+            let payload = serde_json::to_vec(&ack).unwrap();
+            ecnp::write_frame(&mut socket, MessageType::Ack, &payload)
+                .await
+                .unwrap();
+            let _ = ecnp::read_frame(&mut socket).await;
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_self_connection() {
+        let addr = spawn_identified_desktop(None).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            ..Default::default()
+        });
+        client.set_local_device_id("device-self");
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::SelfConnection)
+        ));
+        assert_eq!(client.state(), SyncConnectionState::Error);
+        // A misconfiguration, not a desktop outage
+        assert_eq!(client.stats().consecutive_connect_failures, 0);
+
+        let addr = spawn_identified_desktop(Some("device-desktop")).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            ..Default::default()
+        });
+        client.set_local_device_id("device-self");
+        client.connect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_accepts_paired_desktop() {
+        let desktop = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let addr = spawn_signing_desktop(Some(desktop.clone())).await;
+
+        let client = paired_client(addr, desktop.verifying_key());
+        client.connect().await.unwrap();
+        assert_eq!(client.state(), SyncConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let addr = spawn_signing_desktop(None).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            ..Default::default()
+        });
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        client.set_connection_listener(Box::new(move |state| {
+            log.lock().unwrap().push(state);
+        }));
+        client.connect().await.unwrap();
+
+        client.pause();
+        assert!(client.is_paused());
+        assert_eq!(client.state(), SyncConnectionState::Paused);
+        // The connection itself is kept
+        assert!(client.is_connected());
+
+        // Outgoing frames are parked and reconnects refused
+        let heartbeat = EcnpCodec::encode(MessageType::Heartbeat, b"{}").unwrap();
+        client.enqueue(heartbeat, SendPriority::Control).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(client.drain_queue(&mut out).await.unwrap(), 0);
+        assert!(out.is_empty());
+        assert_eq!(client.pending_frames(), 1);
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::ConnectionError)
+        ));
+        assert_eq!(client.stats().consecutive_connect_failures, 0);
+
+        client.resume();
+        assert!(!client.is_paused());
+        assert_eq!(client.state(), SyncConnectionState::Connected);
+        assert_eq!(client.drain_queue(&mut out).await.unwrap(), 1);
+        assert!(!out.is_empty());
+
+        assert_eq!(
+            seen.lock().unwrap().as_slice(),
+            &[
+                SyncConnectionState::Connecting,
+                SyncConnectionState::Handshaking,
+                SyncConnectionState::Connected,
+                SyncConnectionState::Paused,
+                SyncConnectionState::Connected,
+            ]
+        );
+    }
 
     #[test]
-    fn test_sync_message_ecnp_encode_decode() {
-        let msg = SyncMessage::RemoteExec {
-            command: "uptime".to_string(),
-            args: vec![],
-        };
+    fn test_transitions_while_paused_apply_on_resume() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        client.set_state(SyncConnectionState::Connected);
+        client.pause();
+        client.set_state(SyncConnectionState::Disconnected);
+        assert_eq!(client.state(), SyncConnectionState::Paused);
 
-        let frame = msg.encode_ecnp().unwrap();
-        let (sync_type, decoded) = SyncMessage::decode_ecnp(&frame).unwrap();
+        client.resume();
+        assert_eq!(client.state(), SyncConnectionState::Disconnected);
+        // Resuming twice is harmless
+        client.resume();
+        assert_eq!(client.state(), SyncConnectionState::Disconnected);
+    }
 
-        assert_eq!(sync_type, SYNC_REMOTE_EXEC);
-        match decoded {
-            SyncMessage::RemoteExec { command, args } => {
-                assert_eq!(command, "uptime");
-                assert!(args.is_empty());
-            }
-            _ => panic!("Expected RemoteExec"),
-        }
+    #[tokio::test]
+    async fn test_handshake_rejects_unsigned_or_wrong_key() {
+        let desktop = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let impostor = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+
+        let unsigned = paired_client(spawn_signing_desktop(None).await, desktop.verifying_key());
+        assert!(matches!(
+            unsigned.connect().await,
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert_eq!(unsigned.state(), SyncConnectionState::Error);
+        assert!(!unsigned.is_connected());
+
+        let wrong = paired_client(
+            spawn_signing_desktop(Some(impostor)).await,
+            desktop.verifying_key(),
+        );
+        assert!(matches!(
+            wrong.connect().await,
+            Err(EdgeClawError::CryptoError)
+        ));
     }
 
     #[test]
-    fn test_ecnp_decode_wrong_type_fails() {
-        // Encode as Heartbeat (not Data) — should fail sync decode
-        let payload = b"dummy";
-        let frame = EcnpCodec::encode(MessageType::Heartbeat, payload).unwrap();
-        assert!(SyncMessage::decode_ecnp(&frame).is_err());
+    fn test_circuit_breaker_transitions() {
+        let start = std::time::Instant::now();
+        let mut breaker = CircuitBreaker::new(3, 30);
+        assert!(!breaker.record_failure(start));
+        assert!(!breaker.record_failure(start));
+        assert_eq!(breaker.state(start), BreakerState::Closed);
+        assert!(breaker.record_failure(start));
+        assert_eq!(breaker.state(start), BreakerState::Open);
+        assert!(!breaker.allows(start + std::time::Duration::from_secs(29)));
+
+        // Cooldown over: one trial allowed, and its failure re-opens at once
+        let later = start + std::time::Duration::from_secs(30);
+        assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+        assert!(breaker.allows(later));
+        assert!(breaker.record_failure(later));
+        assert_eq!(breaker.state(later), BreakerState::Open);
+
+        let recovered = later + std::time::Duration::from_secs(30);
+        assert_eq!(breaker.state(recovered), BreakerState::HalfOpen);
+        breaker.record_success();
+        assert_eq!(breaker.state(recovered), BreakerState::Closed);
+        assert_eq!(breaker.consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_replay_cache_window_and_eviction() {
+        let start = std::time::Instant::now();
+        let mut cache = ReplayCache::new(2, 60);
+        assert!(cache.check(b"a", start));
+        assert!(!cache.check(b"a", start));
+        assert!(cache.check(b"a", start + std::time::Duration::from_secs(60)));
+
+        // Capacity 2: "a" is evicted once two newer frames arrive
+        assert!(cache.check(b"b", start));
+        assert!(cache.check(b"c", start));
+        assert!(cache.check(b"a", start));
+
+        let mut disabled = ReplayCache::new(0, 60);
+        assert!(disabled.check(b"a", start));
+        assert!(disabled.check(b"a", start));
+    }
+
+    #[test]
+    fn test_exec_requests_bounded_by_age_and_count() {
+        let start = std::time::Instant::now();
+        let mut requests = ExecRequests::default();
+        requests.insert("old".into(), start);
+        assert!(requests.cancel("old"));
+        requests.insert("new".into(), start + EXEC_REQUEST_TTL);
+        assert_eq!(requests.get("old"), None);
+        assert_eq!(requests.get("new"), Some(ExecRequestState::Pending));
+
+        for i in 0..MAX_TRACKED_EXEC_REQUESTS {
+            let at = start + EXEC_REQUEST_TTL + std::time::Duration::from_secs(i as u64 + 1);
+            requests.insert(format!("r{i}"), at);
+        }
+        assert_eq!(requests.entries.len(), MAX_TRACKED_EXEC_REQUESTS);
+        assert_eq!(requests.get("new"), None);
+        assert!(!requests.cancel("new"));
+        assert_eq!(requests.remove("r0"), Some(ExecRequestState::Pending));
+    }
+
+    #[tokio::test]
+    async fn test_process_incoming_rejects_replayed_frame() {
+        let client = SyncClient::new(SyncClientConfig {
+            replay_cache_size: 16,
+            ..Default::default()
+        });
+        let first = SyncMessage::RemoteExecCancel {
+            request_id: "r1".into(),
+        }
+        .encode_ecnp()
+        .unwrap();
+        let second = SyncMessage::RemoteExecCancel {
+            request_id: "r2".into(),
+        }
+        .encode_ecnp()
+        .unwrap();
+
+        assert!(client.process_incoming(&first).is_ok());
+        assert!(client.process_incoming(&second).is_ok());
+        assert!(matches!(
+            client.process_incoming(&first),
+            Err(EdgeClawError::ReplayDetected)
+        ));
+        assert_eq!(client.stats().messages_received, 2);
+
+        // Frames read from a stream go through the same cache
+        let mut stream: &[u8] = &second;
+        assert!(matches!(
+            client.read_message(&mut stream).await,
+            Err(EdgeClawError::ReplayDetected)
+        ));
+        assert_eq!(client.stats().messages_received, 2);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_on_connect_failures() {
+        // Bind then drop a listener so the port refuses connections
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            breaker_failure_threshold: 2,
+            breaker_cooldown_secs: 1,
+            ..Default::default()
+        });
+
+        assert!(client.connect().await.is_err());
+        assert_eq!(client.state(), SyncConnectionState::Error);
+        assert!(client.connect().await.is_err());
+        assert_eq!(client.state(), SyncConnectionState::CircuitOpen);
+        assert_eq!(client.stats().breaker, BreakerState::Open);
+
+        // Refused without an attempt: the failure count does not move
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::ConnectionError)
+        ));
+        assert_eq!(client.stats().consecutive_connect_failures, 2);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        assert_eq!(client.stats().breaker, BreakerState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_update_address_mid_reconnect() {
+        // Bound first so the stale port cannot be handed out again for it
+        let addr = spawn_signing_desktop(None).await;
+        let stale = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: stale.to_string(),
+            breaker_failure_threshold: 2,
+            ..Default::default()
+        });
+        for _ in 0..2 {
+            assert!(client.connect().await.is_err());
+        }
+        assert_eq!(client.state(), SyncConnectionState::CircuitOpen);
+
+        // Discovery on another handle finds the desktop at a new address
+        let discovery = client.clone();
+        assert!(matches!(
+            discovery.update_address("not-an-address"),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        assert!(discovery.update_address(&addr.to_string()).unwrap());
+        assert!(!discovery.update_address(&addr.to_string()).unwrap());
+
+        assert_eq!(client.desktop_address(), addr.to_string());
+        assert_eq!(client.stats().breaker, BreakerState::Closed);
+        client.connect().await.unwrap();
+        assert_eq!(client.state(), SyncConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn test_proposed_address_adopted_only_after_authentication() {
+        let desktop = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let impostor = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let current = spawn_signing_desktop(Some(desktop.clone())).await;
+        let client = paired_client(current, desktop.verifying_key());
+        client.connect().await.unwrap();
+
+        // A spoofed announcement neither drops the connection nor retargets
+        let spoofed = spawn_signing_desktop(Some(impostor)).await;
+        assert!(client.propose_address(&spoofed.to_string()).unwrap());
+        assert!(client.is_connected());
+        assert_eq!(client.desktop_address(), current.to_string());
+
+        // The next connect falls back once the candidate fails to authenticate
+        let current = spawn_signing_desktop(Some(desktop.clone())).await;
+        client.update_address(&current.to_string()).unwrap();
+        assert!(!client.propose_address(&spoofed.to_string()).unwrap());
+        client.connect().await.unwrap();
+        assert_eq!(client.desktop_address(), current.to_string());
+
+        // The real desktop at its new address is adopted
+        let moved = spawn_signing_desktop(Some(desktop)).await;
+        assert!(client.propose_address(&moved.to_string()).unwrap());
+        client.connect().await.unwrap();
+        assert_eq!(client.desktop_address(), moved.to_string());
+        assert_eq!(client.state(), SyncConnectionState::Connected);
+
+        // Without a pinned key there is nothing to verify against
+        let unpaired = SyncClient::new(SyncClientConfig::default());
+        assert!(matches!(
+            unpaired.propose_address(&moved.to_string()),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_to_new_address() {
+        let old = spawn_signing_desktop(None).await;
+        let new = spawn_signing_desktop(None).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: old.to_string(),
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+
+        client.reconnect_to(&new.to_string()).await.unwrap();
+        assert!(client.is_connected());
+        assert_eq!(client.desktop_address(), new.to_string());
+        assert_eq!(client.stats().reconnect_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_paired_desktop_requires_key() {
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_peer_id: Some("desktop".into()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connect_times_out_without_ack() {
+        // Mock desktop that accepts and reads the handshake but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handshake = ecnp::read_frame(&mut socket).await.unwrap();
+            assert_eq!(handshake.msg_type, MessageType::Handshake as u8);
+            // Keep the socket open until the client gives up
+            let _ = ecnp::read_frame(&mut socket).await;
+        });
+
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            read_timeout_secs: 1,
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::TimeoutError)
+        ));
+        assert_eq!(client.state(), SyncConnectionState::Error);
+        server.abort();
     }
 
-    // ─── SyncClient tests ───
+    /// Mock desktop that reads the handshake, writes `reply` and hangs up
+    async fn spawn_desktop_replying(reply: Vec<u8>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let (mut socket, _) = listener.accept().await.unwrap();
+            ecnp::read_frame(&mut socket).await.unwrap();
+            socket.write_all(&reply).await.unwrap();
+        });
+        addr
+    }
 
-    #[test]
-    fn test_sync_client_creation() {
-        let config = SyncClientConfig::default();
-        let client = SyncClient::new(config);
+    #[tokio::test]
+    async fn test_connect_ack_header_then_close() {
+        // Ack header promising 32 payload bytes, then EOF
+        let addr = spawn_desktop_replying(vec![0x01, MessageType::Ack as u8, 0, 0, 0, 32]).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::IncompleteFrame { received: 6 })
+        ));
+    }
 
-        assert_eq!(client.state(), SyncConnectionState::Disconnected);
-        assert!(!client.is_connected());
-        assert_eq!(client.desktop_address(), "127.0.0.1:8443");
+    #[tokio::test]
+    async fn test_connect_desktop_closes_without_reply() {
+        let addr = spawn_desktop_replying(Vec::new()).await;
+        let client = SyncClient::new(SyncClientConfig {
+            desktop_address: addr.to_string(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.connect().await,
+            Err(EdgeClawError::ConnectionClosed)
+        ));
+        assert_eq!(client.state(), SyncConnectionState::Error);
     }
 
-    #[test]
-    fn test_sync_client_stats() {
+    #[tokio::test(start_paused = true)]
+    async fn test_read_message_times_out() {
         let client = SyncClient::new(SyncClientConfig::default());
-        let stats = client.stats();
-        assert_eq!(stats.messages_sent, 0);
-        assert_eq!(stats.messages_received, 0);
-        assert_eq!(stats.reconnect_count, 0);
-        assert!(stats.last_config_hash.is_none());
+        let (_peer, mut local) = tokio::io::duplex(1024);
+        assert!(matches!(
+            client.read_message(&mut local).await,
+            Err(EdgeClawError::TimeoutError)
+        ));
     }
 
-    #[test]
-    fn test_sync_client_create_remote_exec() {
-        let client = SyncClient::new(SyncClientConfig::default());
-        let frame = client.create_remote_exec("hostname", vec![]).unwrap();
+    #[tokio::test]
+    async fn test_next_message_clean_close_vs_mid_frame() {
+        use tokio::io::AsyncWriteExt;
+        let frame = SyncMessage::config_sync("{}").encode_ecnp().unwrap();
 
-        // Should be a valid ECNP frame
-        let (sync_type, msg) = SyncMessage::decode_ecnp(&frame).unwrap();
-        assert_eq!(sync_type, SYNC_REMOTE_EXEC);
-        match msg {
-            SyncMessage::RemoteExec { command, .. } => assert_eq!(command, "hostname"),
-            _ => panic!("Expected RemoteExec"),
-        }
+        let client = plaintext_client();
+        let (mut peer, mut local) = tokio::io::duplex(1024);
+        peer.write_all(&frame).await.unwrap();
+        drop(peer);
+        assert!(client.next_message(&mut local).await.unwrap().is_some());
+        assert!(client.next_message(&mut local).await.unwrap().is_none());
+        assert_eq!(client.state(), SyncConnectionState::Disconnected);
 
-        // Stats should be updated
-        assert_eq!(client.stats().messages_sent, 1);
+        let (mut peer, mut local) = tokio::io::duplex(1024);
+        peer.write_all(&frame[..frame.len() - 1]).await.unwrap();
+        drop(peer);
+        assert!(matches!(
+            client.next_message(&mut local).await,
+            Err(EdgeClawError::IncompleteFrame { .. })
+        ));
+        assert_eq!(client.state(), SyncConnectionState::Error);
     }
 
-    #[test]
-    fn test_sync_client_process_config_sync() {
-        let client = SyncClient::new(SyncClientConfig::default());
-        let msg = SyncMessage::ConfigSync {
-            config_hash: "sha256:abc".to_string(),
-            config_data: r#"{"agent":{"name":"pc"}}"#.to_string(),
-        };
-        let frame = msg.encode_ecnp().unwrap();
+    #[tokio::test]
+    async fn test_next_message_skips_bad_frames() {
+        use tokio::io::AsyncWriteExt;
+        let oversized = SyncMessage::config_sync(&"x".repeat(4096))
+            .encode_ecnp()
+            .unwrap();
+        let unknown = SyncFrame {
+            sync_type: 0x7f,
+            format: SerializationFormat::Json,
+            body: b"{}".to_vec(),
+        }
+        .encode()
+        .unwrap();
+        let valid = SyncMessage::config_sync("{}").encode_ecnp().unwrap();
 
-        let result = client.process_incoming(&frame).unwrap();
-        match result {
-            SyncMessage::ConfigSync { config_hash, .. } => {
-                assert_eq!(config_hash, "sha256:abc");
-            }
-            _ => panic!("Expected ConfigSync"),
+        let client = SyncClient::new(SyncClientConfig {
+            max_message_bytes: 1024,
+            replay_cache_size: 16,
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        let (mut peer, mut local) = tokio::io::duplex(16 * 1024);
+        for frame in [&oversized, &unknown, &valid, &valid] {
+            peer.write_all(frame).await.unwrap();
         }
+        drop(peer);
 
-        assert_eq!(client.stats().messages_received, 1);
-        assert_eq!(
-            client.stats().last_config_hash,
-            Some("sha256:abc".to_string())
-        );
+        let msg = client.next_message(&mut local).await.unwrap().unwrap();
+        assert!(matches!(msg, SyncMessage::ConfigSync { .. }));
+        // The replayed copy is skipped too
+        assert!(client.next_message(&mut local).await.unwrap().is_none());
+        let stats = client.stats();
+        assert_eq!(stats.messages_skipped, 3);
+        assert_eq!(stats.messages_received, 1);
     }
 
-    #[test]
-    fn test_sync_client_process_status_push() {
-        let client = SyncClient::new(SyncClientConfig::default());
-        let msg = SyncMessage::StatusPush {
-            cpu_usage: 10.0,
-            memory_usage: 50.0,
-            disk_usage: 30.0,
-            uptime_secs: 7200,
-            active_sessions: 2,
-            ai_status: "ollama:running".to_string(),
-        };
-        let frame = msg.encode_ecnp().unwrap();
+    #[tokio::test]
+    async fn test_next_message_disconnect_policy() {
+        use tokio::io::AsyncWriteExt;
+        let oversized = SyncMessage::config_sync(&"x".repeat(4096))
+            .encode_ecnp()
+            .unwrap();
+        let valid = SyncMessage::config_sync("{}").encode_ecnp().unwrap();
 
-        let result = client.process_incoming(&frame).unwrap();
-        match result {
-            SyncMessage::StatusPush { uptime_secs, .. } => {
-                assert_eq!(uptime_secs, 7200);
-            }
-            _ => panic!("Expected StatusPush"),
-        }
+        let client = SyncClient::new(SyncClientConfig {
+            max_message_bytes: 1024,
+            bad_message_policy: BadMessagePolicy::Disconnect,
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        let (mut peer, mut local) = tokio::io::duplex(16 * 1024);
+        peer.write_all(&oversized).await.unwrap();
+        peer.write_all(&valid).await.unwrap();
 
-        assert!(client.stats().last_status_push.is_some());
+        assert!(matches!(
+            client.next_message(&mut local).await,
+            Err(EdgeClawError::PayloadTooLarge { max: 1024, .. })
+        ));
+        // The oversized payload was drained, so the stream is still aligned
+        assert!(client.next_message(&mut local).await.unwrap().is_some());
+        assert_eq!(client.stats().messages_skipped, 0);
     }
 
-    #[test]
-    fn test_sync_client_shutdown() {
+    #[tokio::test]
+    async fn test_read_message_rejects_oversized_frame() {
+        use tokio::io::AsyncWriteExt;
         let client = SyncClient::new(SyncClientConfig::default());
-        assert!(!client.is_shutdown());
-
-        client.shutdown();
-        assert!(client.is_shutdown());
-        assert!(!client.is_connected());
-        assert_eq!(client.state(), SyncConnectionState::Disconnected);
+        let (mut peer, mut local) = tokio::io::duplex(1024);
+        peer.write_all(&[0x01, MessageType::Data as u8, 0x00, 0x20, 0x00, 0x00])
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.read_message(&mut local).await,
+            Err(EdgeClawError::PayloadTooLarge {
+                size: 0x20_0000,
+                max: 0x10_0000
+            })
+        ));
     }
 
-    // ─── Connection strategy tests ───
+    #[tokio::test(start_paused = true)]
+    async fn test_write_to_stalled_socket_times_out() {
+        let client = SyncClient::new(SyncClientConfig::default());
+        let frame = EcnpCodec::encode(MessageType::Data, &[0u8; 256]).unwrap();
+        client.enqueue(frame, SendPriority::Bulk).unwrap();
 
-    #[test]
-    fn test_connection_strategy_auto_both() {
-        let strategy = determine_connection_strategy(
-            TransportPreference::Auto,
-            true,
-            Some("192.168.1.10:8443"),
-        );
-        assert!(strategy.should_use_tcp);
-        assert_eq!(strategy.transport, TransportPreference::Auto);
-        assert_eq!(
-            strategy.desktop_address,
-            Some("192.168.1.10:8443".to_string())
-        );
+        // Peer never reads, so the 64-byte pipe fills and the write stalls
+        let (mut local, _peer) = tokio::io::duplex(64);
+        assert!(matches!(
+            client.drain_queue(&mut local).await,
+            Err(EdgeClawError::TimeoutError)
+        ));
+        assert_eq!(client.pending_frames(), 1);
     }
 
-    #[test]
-    fn test_connection_strategy_auto_tcp_only() {
-        let strategy =
-            determine_connection_strategy(TransportPreference::Auto, false, Some("10.0.0.5:8443"));
-        assert!(strategy.should_use_tcp);
-        assert_eq!(strategy.transport, TransportPreference::TcpLan);
-    }
+    #[tokio::test(start_paused = true)]
+    async fn test_exec_result_wait_times_out() {
+        let client = SyncClient::new(SyncClientConfig {
+            read_timeout_secs: 120,
+            exec_result_timeout_secs: 5,
+            require_encrypted_channel: false,
+            ..Default::default()
+        });
+        let (mut peer, mut local) = tokio::io::duplex(1024);
 
-    #[test]
-    fn test_connection_strategy_auto_ble_only() {
-        let strategy = determine_connection_strategy(TransportPreference::Auto, true, None);
-        assert!(!strategy.should_use_tcp);
-        assert_eq!(strategy.transport, TransportPreference::BleFirst);
-    }
+        // An unrelated result arrives, but never the one we wait for
+        let other = SyncMessage::RemoteExecResult {
+            command: "uptime".into(),
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: "req-other".into(),
+        };
+        ecnp::write_encoded_frame(&mut peer, &other.encode_ecnp().unwrap())
+            .await
+            .unwrap();
 
-    #[test]
-    fn test_connection_strategy_explicit_tcp() {
-        let strategy = determine_connection_strategy(
-            TransportPreference::TcpLan,
-            true,
-            Some("192.168.1.1:8443"),
-        );
-        assert!(strategy.should_use_tcp);
-        assert_eq!(strategy.transport, TransportPreference::TcpLan);
+        assert!(matches!(
+            client.await_exec_result(&mut local, "req-1").await,
+            Err(EdgeClawError::TimeoutError)
+        ));
+        assert_eq!(client.stats().messages_received, 1);
     }
 
-    #[test]
-    fn test_connection_strategy_ble_first_no_ble() {
-        let strategy = determine_connection_strategy(
-            TransportPreference::BleFirst,
-            false,
-            Some("192.168.1.1:8443"),
-        );
-        // Falls back to TCP when BLE not available
-        assert!(strategy.should_use_tcp);
+    #[tokio::test]
+    async fn test_await_exec_result_returns_match() {
+        let client = plaintext_client();
+        let (mut peer, mut local) = tokio::io::duplex(4096);
+
+        // Two identical commands in flight; the later one answers first
+        let (first, _) = client.create_tracked_exec("ls", vec![]).unwrap();
+        let (second, _) = client.create_tracked_exec("ls", vec![]).unwrap();
+        for (id, stdout) in [(&second, "second"), (&first, "first")] {
+            let result = SyncMessage::remote_exec_result(
+                "ls",
+                0,
+                stdout.into(),
+                String::new(),
+                DEFAULT_MAX_EXEC_OUTPUT_BYTES,
+            )
+            .with_request_id(id);
+            ecnp::write_encoded_frame(&mut peer, &result.encode_ecnp().unwrap())
+                .await
+                .unwrap();
+        }
+
+        match client.await_exec_result(&mut local, &first).await.unwrap() {
+            SyncMessage::RemoteExecResult {
+                stdout, request_id, ..
+            } => {
+                assert_eq!(stdout, "first");
+                assert_eq!(request_id, first);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
     }
 
     #[tokio::test]
@@ -797,6 +4366,7 @@ mod tests {
         let client = SyncClient::new(config);
         let result = client.connect().await;
         assert!(result.is_err());
+        assert_eq!(client.state(), SyncConnectionState::Error);
     }
 
     #[test]
@@ -810,6 +4380,8 @@ mod tests {
         assert_eq!(SyncConnectionState::Connected.to_string(), "connected");
         assert_eq!(SyncConnectionState::Syncing.to_string(), "syncing");
         assert_eq!(SyncConnectionState::Error.to_string(), "error");
+        assert_eq!(SyncConnectionState::CircuitOpen.to_string(), "circuit_open");
+        assert_eq!(SyncConnectionState::Paused.to_string(), "paused");
     }
 
     #[test]
@@ -821,6 +4393,75 @@ mod tests {
         assert_eq!(config.connect_timeout_secs, 10);
         assert!(config.auto_reconnect);
         assert_eq!(config.max_reconnect_attempts, 0);
+        assert_eq!(config.compression_min_bytes, DEFAULT_COMPRESSION_MIN_BYTES);
+    }
+
+    #[test]
+    fn test_small_status_push_not_compressed() {
+        let frame = status(12.5)
+            .encode_ecnp_with_threshold(SerializationFormat::Json, Compression::Zstd, 4096)
+            .unwrap();
+        assert_eq!(
+            EcnpCodec::decode(&frame).unwrap().flags & ecnp::FLAG_COMPRESSED,
+            0
+        );
+        assert!(SyncMessage::decode_ecnp(&frame).is_ok());
+    }
+
+    // ─── Compression negotiation tests ───
+
+    #[test]
+    fn test_negotiate_both_support_zstd() {
+        let remote = advertised_compression(br#"{"compression":["zstd","none"]}"#);
+        let agreed = negotiate_compression(&default_compression(), &remote);
+        assert_eq!(agreed, Compression::Zstd);
+    }
+
+    #[test]
+    fn test_negotiate_one_side_none() {
+        // Desktop only offers "none"
+        let remote = advertised_compression(br#"{"compression":["none"]}"#);
+        assert_eq!(
+            negotiate_compression(&default_compression(), &remote),
+            Compression::None
+        );
+
+        // Mobile only offers "none"
+        let remote = advertised_compression(br#"{"compression":["zstd"]}"#);
+        assert_eq!(
+            negotiate_compression(&[Compression::None], &remote),
+            Compression::None
+        );
+
+        // Legacy desktop: empty ack, no compression list
+        let remote = advertised_compression(b"");
+        assert_eq!(
+            negotiate_compression(&default_compression(), &remote),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_compressed_sync_frame_decodes() {
+        let msg = SyncMessage::RemoteExecResult {
+            command: "journalctl".to_string(),
+            exit_code: 0,
+            stdout: "line\n".repeat(500),
+            stderr: String::new(),
+            truncated: false,
+            encoding: OutputEncoding::Utf8,
+            request_id: String::new(),
+        };
+        let plain = msg.encode_ecnp().unwrap();
+        let compressed = msg.encode_ecnp_with(Compression::Zstd).unwrap();
+        assert!(compressed.len() < plain.len());
+
+        let (sync_type, decoded) = SyncMessage::decode_ecnp(&compressed).unwrap();
+        assert_eq!(sync_type, SYNC_REMOTE_EXEC_RESULT);
+        match decoded {
+            SyncMessage::RemoteExecResult { stdout, .. } => assert_eq!(stdout.len(), 2500),
+            _ => panic!("Expected RemoteExecResult"),
+        }
     }
 
     #[test]
@@ -828,4 +4469,299 @@ mod tests {
         let pref = TransportPreference::default();
         assert_eq!(pref, TransportPreference::Auto);
     }
+
+    /// Desktop end of handshake → upgrade over `io`; returns the desktop's
+    /// session. With `signer`, the ack challenge is signed, and the upgrade
+    /// reply too if `sign_upgrade`.
+    async fn accept_upgrade(
+        io: &mut tokio::io::DuplexStream,
+        signer: Option<ed25519_dalek::SigningKey>,
+        sign_upgrade: bool,
+    ) -> (SessionManager, String) {
+        use ed25519_dalek::Signer;
+
+        let handshake = ecnp::read_frame(io).await.unwrap();
+        let request: serde_json::Value = serde_json::from_slice(&handshake.payload).unwrap();
+        let mut ack = serde_json::json!({ "compression": ["none"] });
+        if let (Some(key), Some(challenge)) = (&signer, request["challenge"].as_str()) {
+            let sig = key.sign(&sync_challenge_message(&hex::decode(challenge).unwrap()));
+            ack["challenge_signature"] = hex::encode(sig.to_bytes()).into();
+        }
+        let payload = serde_json::to_vec(&ack).unwrap();
+        ecnp::write_frame(io, MessageType::Ack, &payload)
+            .await
+            .unwrap();
+
+        let upgrade = ecnp::read_frame(io).await.unwrap();
+        assert_eq!(upgrade.msg_type, MessageType::Control as u8);
+        let request: serde_json::Value = serde_json::from_slice(&upgrade.payload).unwrap();
+        assert_eq!(request["upgrade"], SYNC_UPGRADE_SCHEME);
+        let client_public: [u8; 32] =
+            hex::decode(request["ephemeral_public_key"].as_str().unwrap())
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let local = X25519KeyExchange.generate_keypair();
+        let mut reply = serde_json::json!({
+            "upgrade": SYNC_UPGRADE_SCHEME,
+            "ephemeral_public_key": hex::encode(&local.public),
+        });
+        if let (Some(key), true) = (&signer, sign_upgrade) {
+            let sig = key.sign(&sync_upgrade_message(&client_public, &local.public));
+            reply["signature"] = hex::encode(sig.to_bytes()).into();
+        }
+        let payload = serde_json::to_vec(&reply).unwrap();
+        ecnp::write_frame(io, MessageType::Control, &payload)
+            .await
+            .unwrap();
+
+        let mut sessions = SessionManager::new();
+        let session = sessions
+            .accept_session("mobile", &local.secret, &client_public)
+            .unwrap();
+        (sessions, session.session_id)
+    }
+
+    /// `accept_upgrade`, then answer one encrypted remote exec
+    async fn serve_upgrading_desktop(
+        mut io: tokio::io::DuplexStream,
+        signer: Option<ed25519_dalek::SigningKey>,
+        sign_upgrade: bool,
+    ) {
+        let (sessions, session_id) = accept_upgrade(&mut io, signer, sign_upgrade).await;
+
+        let Ok(sealed) = ecnp::read_frame(&mut io).await else {
+            return;
+        };
+        assert_eq!(sealed.msg_type, MessageType::Data as u8);
+        let inner = sessions.decrypt(&session_id, &sealed.payload).unwrap();
+        let (
+            _,
+            SyncMessage::RemoteExec {
+                command,
+                request_id,
+                ..
+            },
+        ) = SyncMessage::decode_ecnp(&inner).unwrap()
+        else {
+            panic!("expected RemoteExec");
+        };
+        let result = SyncMessage::remote_exec_result(
+            &command,
+            0,
+            "up 3 days".into(),
+            String::new(),
+            DEFAULT_MAX_EXEC_OUTPUT_BYTES,
+        )
+        .with_request_id(&request_id);
+        let sealed = sessions
+            .encrypt(&session_id, &result.encode_ecnp().unwrap())
+            .unwrap();
+        ecnp::write_frame(&mut io, MessageType::Data, &sealed)
+            .await
+            .unwrap();
+
+        // A plaintext frame the client must no longer accept
+        let plain = SyncMessage::config_sync("{}").encode_ecnp().unwrap();
+        ecnp::write_encoded_frame(&mut io, &plain).await.unwrap();
+        let _ = ecnp::read_frame(&mut io).await;
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_to_encrypted_channel_over_duplex() {
+        let (mut io, desktop_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve_upgrading_desktop(desktop_io, None, false));
+        let client = SyncClient::new(SyncClientConfig::default());
+
+        client.connect_over(&mut io).await.unwrap();
+        assert_eq!(client.state(), SyncConnectionState::Connected);
+        assert!(!client.is_upgraded());
+        // Sensitive messages wait for the upgrade; others don't
+        assert!(matches!(
+            client.create_remote_exec("uptime", vec![]),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+        assert!(matches!(
+            client.encrypt_to_frame(&SyncMessage::config_sync("{}")),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+        // ...and are not accepted in plaintext either
+        let plain = SyncMessage::config_sync("{}").encode_ecnp().unwrap();
+        assert!(matches!(
+            client.process_incoming(&plain),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+
+        client.upgrade(&mut io).await.unwrap();
+        assert!(client.is_upgraded());
+        let (request_id, frame) = client.create_tracked_exec("uptime", vec![]).unwrap();
+        assert!(SyncMessage::decode_ecnp(&frame).is_err());
+        ecnp::write_encoded_frame(&mut io, &frame).await.unwrap();
+
+        let result = client
+            .await_exec_result(&mut io, &request_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &result,
+            SyncMessage::RemoteExecResult { exit_code: 0, stdout, .. } if stdout == "up 3 days"
+        ));
+        assert_eq!(client.exec_request_state(&request_id), None);
+
+        assert!(matches!(
+            client.read_message(&mut io).await,
+            Err(EdgeClawError::CryptoError)
+        ));
+
+        client.shutdown();
+        assert!(!client.is_upgraded());
+    }
+
+    #[tokio::test]
+    async fn test_upgraded_channel_rejects_replayed_and_reflected_frames() {
+        let (mut io, mut desktop_io) = tokio::io::duplex(64 * 1024);
+        let desktop = tokio::spawn(async move {
+            let session = accept_upgrade(&mut desktop_io, None, false).await;
+            (desktop_io, session)
+        });
+        let client = SyncClient::new(SyncClientConfig::default());
+        client.connect_over(&mut io).await.unwrap();
+        client.upgrade(&mut io).await.unwrap();
+        let (_desktop_io, (sessions, session_id)) = desktop.await.unwrap();
+
+        let seal = |msg: &SyncMessage| {
+            let sealed = sessions
+                .encrypt(&session_id, &msg.encode_ecnp().unwrap())
+                .unwrap();
+            EcnpCodec::encode(MessageType::Data, &sealed).unwrap()
+        };
+        let first = seal(&SyncMessage::config_sync("{\"a\":1}"));
+        let second = seal(&SyncMessage::config_sync("{\"a\":2}"));
+
+        // In order is fine; replaying or going back is not
+        assert!(client.process_incoming(&first).is_ok());
+        assert!(client.process_incoming(&second).is_ok());
+        assert!(matches!(
+            client.process_incoming(&second),
+            Err(EdgeClawError::ReplayDetected)
+        ));
+        assert!(matches!(
+            client.process_incoming(&first),
+            Err(EdgeClawError::ReplayDetected)
+        ));
+
+        // Our own frame reflected back carries the initiator prefix
+        let own = client
+            .encrypt_to_frame(&SyncMessage::config_sync("{}"))
+            .unwrap();
+        assert!(matches!(
+            client.process_incoming(&own),
+            Err(EdgeClawError::CryptoError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_with_caller_driven_frames() {
+        let (mut io, mut desktop_io) = tokio::io::duplex(64 * 1024);
+        let desktop = tokio::spawn(async move {
+            let session = accept_upgrade(&mut desktop_io, None, false).await;
+            (desktop_io, session)
+        });
+        let client = SyncClient::new(SyncClientConfig::default());
+        assert!(matches!(
+            client.finish_upgrade(&[]),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+        client.connect_over(&mut io).await.unwrap();
+
+        // The caller carries the frames, as an FFI host would
+        let request = client.begin_upgrade().unwrap();
+        ecnp::write_encoded_frame(&mut io, &request).await.unwrap();
+        let reply = ecnp::read_frame(&mut io).await.unwrap();
+        let reply = EcnpCodec::encode(MessageType::Control, &reply.payload).unwrap();
+        client.finish_upgrade(&reply).unwrap();
+        assert!(client.is_upgraded());
+        let (_desktop_io, (sessions, session_id)) = desktop.await.unwrap();
+
+        let frame = client.create_remote_exec("uptime", vec![]).unwrap();
+        let sealed = EcnpCodec::decode(&frame).unwrap();
+        let inner = sessions.decrypt(&session_id, &sealed.payload).unwrap();
+        assert!(matches!(
+            SyncMessage::decode_ecnp(&inner).unwrap().1,
+            SyncMessage::RemoteExec { .. }
+        ));
+        // The pending key was used up
+        assert!(matches!(
+            client.finish_upgrade(&reply),
+            Err(EdgeClawError::InvalidParameter)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_authenticates_pinned_desktop() {
+        let signer = ed25519_dalek::SigningKey::from_bytes(&[5; 32]);
+        let config = || SyncClientConfig {
+            desktop_peer_id: Some("desktop-1".into()),
+            ..Default::default()
+        };
+
+        let (mut io, desktop_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve_upgrading_desktop(
+            desktop_io,
+            Some(signer.clone()),
+            true,
+        ));
+        let client = SyncClient::new(config());
+        client.set_desktop_key(signer.verifying_key());
+        client.connect_over(&mut io).await.unwrap();
+        client.upgrade(&mut io).await.unwrap();
+        assert!(client.is_upgraded());
+
+        // Authentic ack, but unsigned upgrade keys could be a MITM swap
+        let (mut io, desktop_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve_upgrading_desktop(
+            desktop_io,
+            Some(signer.clone()),
+            false,
+        ));
+        let client = SyncClient::new(config());
+        client.set_desktop_key(signer.verifying_key());
+        client.connect_over(&mut io).await.unwrap();
+        assert!(matches!(
+            client.upgrade(&mut io).await,
+            Err(EdgeClawError::CryptoError)
+        ));
+        assert!(!client.is_upgraded());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_declined_stays_plaintext() {
+        let (mut io, mut desktop) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            ecnp::read_frame(&mut desktop).await.unwrap();
+            ecnp::write_frame(&mut desktop, MessageType::Ack, b"{}")
+                .await
+                .unwrap();
+            ecnp::read_frame(&mut desktop).await.unwrap();
+            ecnp::write_frame(&mut desktop, MessageType::Error, b"unsupported")
+                .await
+                .unwrap();
+        });
+        let client = SyncClient::new(SyncClientConfig::default());
+        assert!(matches!(
+            client.upgrade(&mut io).await,
+            Err(EdgeClawError::ConnectionError)
+        ));
+
+        client.connect_over(&mut io).await.unwrap();
+        assert!(matches!(
+            client.upgrade(&mut io).await,
+            Err(EdgeClawError::ConnectionError)
+        ));
+        assert!(!client.is_upgraded());
+        assert!(matches!(
+            client.create_remote_exec("ls", vec![]),
+            Err(EdgeClawError::SessionNotEstablished)
+        ));
+    }
 }
@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+/// Where the crate reads the current time from.
+///
+/// Timestamps (`created_at`, `last_seen`, ...) and expiry checks in the
+/// managers all go through one of these, so tests can swap in a
+/// `MockTimeSource` instead of sleeping.
+pub trait TimeSource: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system wall clock (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Shared handle to the system clock
+pub fn system() -> Arc<dyn TimeSource> {
+    Arc::new(SystemTimeSource)
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct MockTimeSource {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockTimeSource {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// Move the clock forward (or back, with a negative duration)
+    pub fn advance(&self, by: chrono::Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+
+    pub fn set(&self, to: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = to;
+    }
+}
+
+impl TimeSource for MockTimeSource {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_time_source() {
+        let start = Utc::now();
+        let clock = MockTimeSource::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(chrono::Duration::seconds(90));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(90));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
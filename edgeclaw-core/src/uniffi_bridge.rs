@@ -6,12 +6,39 @@
 use std::sync::Arc;
 
 use crate::error::EdgeClawError;
-use crate::protocol::MessageType;
+use crate::peer::{PeerLiveness, PeerSortKey};
+use crate::policy::RiskLevel;
+use crate::protocol::{EcmPayload, MessageType};
+use crate::session::SessionOptions;
 use crate::{
-    DeviceIdentity, EcnpMessage, EngineConfig, PeerInfo, PolicyDecision, SessionInfo,
-    SyncClientConfig,
+    AuthChallenge, CapabilityInfo, ConfigUpdateReport, DeviceIdentity, EcnpMessage,
+    EngineCapabilities, EngineConfig, EngineHealth, EngineStats, IssuedAttestation,
+    PeerAttestation, PeerInfo, PermissionMatrix, PolicyAuditEntry, PolicyDecision, RemoteExecGate,
+    RemoteExecRequest, SessionHandshakeAccept, SessionInfo, SessionRekey, SyncClientConfig,
+    SyncMessage,
 };
 
+/// One entry of an `evaluate_capabilities` batch
+#[derive(Debug, Clone)]
+pub struct CapabilityRequest {
+    pub capability_name: String,
+    pub role: String,
+}
+
+/// Per-session outcome of `encrypt_broadcast`: the ciphertext, or the
+/// error message if that session could not encrypt
+#[derive(Debug, Clone)]
+pub struct BroadcastCiphertext {
+    pub session_id: String,
+    pub ciphertext: Option<Vec<u8>>,
+    pub error: Option<String>,
+}
+
+/// Foreign callback for sync connection state changes
+pub trait SyncConnectionListener: Send + Sync {
+    fn on_state_changed(&self, state: String);
+}
+
 /// UniFFI-exported wrapper around `EdgeClawEngine`.
 ///
 /// All methods are `&self` — the inner engine already uses `Mutex` for
@@ -30,7 +57,27 @@ impl EdgeClawEngineFFI {
     // ─── Config ───
 
     pub fn get_config(&self) -> EngineConfig {
-        self.inner.config().clone()
+        self.inner.config()
+    }
+
+    pub fn capabilities(&self) -> EngineCapabilities {
+        self.inner.capabilities()
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+
+    pub fn health(&self) -> EngineHealth {
+        self.inner.health()
+    }
+
+    pub fn stats(&self) -> EngineStats {
+        self.inner.stats()
+    }
+
+    pub fn update_config(&self, config: EngineConfig) -> Result<ConfigUpdateReport, EdgeClawError> {
+        self.inner.update_config(config)
     }
 
     // ─── Identity ───
@@ -43,6 +90,22 @@ impl EdgeClawEngineFFI {
         self.inner.get_identity()
     }
 
+    pub fn generate_identity_for(&self, label: String) -> Result<DeviceIdentity, EdgeClawError> {
+        self.inner.generate_identity_for(&label)
+    }
+
+    pub fn get_identity_for(&self, label: String) -> Result<DeviceIdentity, EdgeClawError> {
+        self.inner.get_identity_for(&label)
+    }
+
+    pub fn list_identities(&self) -> Vec<String> {
+        self.inner.list_identities()
+    }
+
+    pub fn set_active_identity(&self, label: String) -> Result<(), EdgeClawError> {
+        self.inner.set_active_identity(&label)
+    }
+
     // ─── Peers ───
 
     pub fn add_peer(
@@ -57,14 +120,129 @@ impl EdgeClawEngineFFI {
             .add_peer(&peer_id, &device_name, &device_type, &address, capabilities)
     }
 
+    pub fn ingest_discovery(
+        &self,
+        peer_id: String,
+        device_name: String,
+        device_type: String,
+        address: String,
+        capabilities: Vec<String>,
+        source: String,
+    ) -> Result<PeerInfo, EdgeClawError> {
+        self.inner.ingest_discovery(
+            &peer_id,
+            &device_name,
+            &device_type,
+            &address,
+            capabilities,
+            &source,
+        )
+    }
+
     pub fn get_peers(&self) -> Vec<PeerInfo> {
         self.inner.get_peers()
     }
 
+    pub fn get_peers_sorted(&self, by: PeerSortKey) -> Vec<PeerInfo> {
+        self.inner.get_peers_sorted(by)
+    }
+
+    pub fn peer_liveness(&self, peer_id: String) -> Result<PeerLiveness, EdgeClawError> {
+        self.inner.peer_liveness(&peer_id)
+    }
+
     pub fn remove_peer(&self, peer_id: String) -> Result<(), EdgeClawError> {
         self.inner.remove_peer(&peer_id)
     }
 
+    pub fn touch_peer(&self, peer_id: String) -> Result<(), EdgeClawError> {
+        self.inner.touch_peer(&peer_id)
+    }
+
+    pub fn tag_peer(&self, peer_id: String, tag: String) -> Result<(), EdgeClawError> {
+        self.inner.tag_peer(&peer_id, &tag)
+    }
+
+    pub fn untag_peer(&self, peer_id: String, tag: String) -> Result<(), EdgeClawError> {
+        self.inner.untag_peer(&peer_id, &tag)
+    }
+
+    pub fn peers_with_tag(&self, tag: String) -> Vec<PeerInfo> {
+        self.inner.peers_with_tag(&tag)
+    }
+
+    pub fn find_peers_by_public_key(&self, public_key_hex: String) -> Vec<PeerInfo> {
+        self.inner.find_peers_by_public_key(&public_key_hex)
+    }
+
+    pub fn set_peer_public_key(
+        &self,
+        peer_id: String,
+        public_key_hex: String,
+    ) -> Result<(), EdgeClawError> {
+        self.inner.set_peer_public_key(&peer_id, &public_key_hex)
+    }
+
+    pub fn set_peer_role(
+        &self,
+        peer_id: String,
+        role: Option<String>,
+    ) -> Result<(), EdgeClawError> {
+        self.inner.set_peer_role(&peer_id, role.as_deref())
+    }
+
+    pub fn export_peer_manifest(&self) -> Result<String, EdgeClawError> {
+        self.inner.export_peer_manifest()
+    }
+
+    pub fn import_peer_manifest(
+        &self,
+        manifest_json: String,
+        expected_signer_key_hex: String,
+    ) -> Result<u32, EdgeClawError> {
+        self.inner
+            .import_peer_manifest(&manifest_json, &expected_signer_key_hex)
+    }
+
+    // ─── Peer authentication ───
+
+    pub fn create_auth_challenge(&self, peer_id: String) -> Result<AuthChallenge, EdgeClawError> {
+        self.inner.create_auth_challenge(&peer_id)
+    }
+
+    pub fn verify_auth_response(
+        &self,
+        challenge_id: String,
+        signature: Vec<u8>,
+    ) -> Result<(), EdgeClawError> {
+        self.inner.verify_auth_response(&challenge_id, &signature)
+    }
+
+    pub fn attest_peer(&self, peer_id: String) -> Result<String, EdgeClawError> {
+        self.inner.attest_peer(&peer_id)
+    }
+
+    pub fn verify_attestation(
+        &self,
+        attestation_json: String,
+        attester_public_key_hex: String,
+    ) -> Result<PeerAttestation, EdgeClawError> {
+        self.inner
+            .verify_attestation(&attestation_json, &attester_public_key_hex)
+    }
+
+    pub fn list_issued_attestations(&self) -> Vec<IssuedAttestation> {
+        self.inner.list_issued_attestations()
+    }
+
+    pub fn revoke_attestation(&self, attestation_id: String) -> Result<(), EdgeClawError> {
+        self.inner.revoke_attestation(&attestation_id)
+    }
+
+    pub fn sign_auth_challenge(&self, nonce_frame: Vec<u8>) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.sign_auth_challenge(&nonce_frame)
+    }
+
     // ─── Sessions ───
 
     pub fn create_session(
@@ -80,6 +258,104 @@ impl EdgeClawEngineFFI {
         self.inner.create_session(&peer_id, &key)
     }
 
+    pub fn begin_session(&self, peer_id: String) -> Result<String, EdgeClawError> {
+        self.inner.begin_session(&peer_id)
+    }
+
+    pub fn complete_session(
+        &self,
+        pending_id: String,
+        peer_public_key: Vec<u8>,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        if peer_public_key.len() != 32 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&peer_public_key);
+        self.inner.complete_session(&pending_id, &key)
+    }
+
+    pub fn get_or_create_session(
+        &self,
+        peer_id: String,
+        peer_public_key: Vec<u8>,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        if peer_public_key.len() != 32 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&peer_public_key);
+        self.inner.get_or_create_session(&peer_id, &key)
+    }
+
+    pub fn accept_session(
+        &self,
+        peer_id: String,
+        peer_public_key: Vec<u8>,
+    ) -> Result<SessionInfo, EdgeClawError> {
+        if peer_public_key.len() != 32 {
+            return Err(EdgeClawError::InvalidParameter);
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&peer_public_key);
+        self.inner.accept_session(&peer_id, &key)
+    }
+
+    pub fn initiate_session_handshake(
+        &self,
+        peer_id: String,
+        options: SessionOptions,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.initiate_session_handshake(&peer_id, options)
+    }
+
+    pub fn rekey_all_sessions(&self) -> Result<Vec<SessionRekey>, EdgeClawError> {
+        self.inner.rekey_all_sessions()
+    }
+
+    pub fn handle_session_init(
+        &self,
+        frame: Vec<u8>,
+    ) -> Result<SessionHandshakeAccept, EdgeClawError> {
+        self.inner.handle_session_init(&frame)
+    }
+
+    pub fn handle_session_accept(&self, frame: Vec<u8>) -> Result<SessionInfo, EdgeClawError> {
+        self.inner.handle_session_accept(&frame)
+    }
+
+    pub fn all_sessions(&self) -> Vec<SessionInfo> {
+        self.inner.all_sessions()
+    }
+
+    pub fn session_summaries(&self) -> Vec<String> {
+        self.inner.session_summaries()
+    }
+
+    pub fn encrypt_broadcast(
+        &self,
+        session_ids: Vec<String>,
+        plaintext: Vec<u8>,
+    ) -> Vec<BroadcastCiphertext> {
+        let results = self.inner.encrypt_broadcast(&session_ids, &plaintext);
+        session_ids
+            .into_iter()
+            .zip(results)
+            .map(|(session_id, result)| match result {
+                Ok(ciphertext) => BroadcastCiphertext {
+                    session_id,
+                    ciphertext: Some(ciphertext),
+                    error: None,
+                },
+                Err(e) => BroadcastCiphertext {
+                    session_id,
+                    ciphertext: None,
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect()
+    }
+
     pub fn encrypt_message(
         &self,
         session_id: String,
@@ -96,19 +372,106 @@ impl EdgeClawEngineFFI {
         self.inner.decrypt_message(&session_id, &ciphertext)
     }
 
+    /// `message_json` is a serialized `SyncMessage`
+    pub fn encrypt_sync_message(
+        &self,
+        session_id: String,
+        message_json: String,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        let msg: SyncMessage =
+            serde_json::from_str(&message_json).map_err(|_| EdgeClawError::SerializationError)?;
+        self.inner.encrypt_sync_message(&session_id, &msg)
+    }
+
+    pub fn decrypt_sync_frame(
+        &self,
+        session_id: String,
+        encrypted_frame: Vec<u8>,
+    ) -> Result<String, EdgeClawError> {
+        let msg = self
+            .inner
+            .decrypt_sync_frame(&session_id, &encrypted_frame)?;
+        serde_json::to_string(&msg).map_err(|_| EdgeClawError::SerializationError)
+    }
+
+    pub fn sessions_for_peer(&self, peer_id: String) -> Vec<SessionInfo> {
+        self.inner.sessions_for_peer(&peer_id)
+    }
+
+    pub fn encrypt_for_peer(
+        &self,
+        peer_id: String,
+        plaintext: Vec<u8>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.encrypt_for_peer(&peer_id, &plaintext)
+    }
+
+    pub fn decrypt_from_peer(
+        &self,
+        peer_id: String,
+        ciphertext: Vec<u8>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.decrypt_from_peer(&peer_id, &ciphertext)
+    }
+
+    pub fn session_confirmation(&self, session_id: String) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.session_confirmation(&session_id)
+    }
+
+    pub fn verify_session(&self, session_id: String) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.verify_session(&session_id)
+    }
+
+    pub fn echo_session_probe(
+        &self,
+        session_id: String,
+        probe: Vec<u8>,
+    ) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.echo_session_probe(&session_id, &probe)
+    }
+
+    pub fn confirm_session_echo(
+        &self,
+        session_id: String,
+        echo: Vec<u8>,
+    ) -> Result<(), EdgeClawError> {
+        self.inner.confirm_session_echo(&session_id, &echo)
+    }
+
     // ─── Protocol ───
 
+    pub fn ingest_ecm(&self, ecm_json: String, address: String) -> Result<PeerInfo, EdgeClawError> {
+        self.inner.ingest_ecm(&ecm_json, &address)
+    }
+
     pub fn create_ecm(&self) -> Result<String, EdgeClawError> {
         self.inner.create_ecm()
     }
 
+    pub fn create_ecm_struct(&self) -> Result<EcmPayload, EdgeClawError> {
+        self.inner.ecm_payload()
+    }
+
+    pub fn create_ecm_binary(&self) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.create_ecm_binary()
+    }
+
+    pub fn ingest_ecm_binary(
+        &self,
+        ecm: Vec<u8>,
+        address: String,
+    ) -> Result<PeerInfo, EdgeClawError> {
+        self.inner.ingest_ecm_binary(&ecm, &address)
+    }
+
     pub fn create_heartbeat(
         &self,
         uptime_secs: u64,
         cpu_usage: f64,
         memory_usage: f64,
     ) -> Result<String, EdgeClawError> {
-        self.inner.create_heartbeat(uptime_secs, cpu_usage, memory_usage)
+        self.inner
+            .create_heartbeat(uptime_secs, cpu_usage, memory_usage)
     }
 
     // ─── Policy ───
@@ -121,13 +484,85 @@ impl EdgeClawEngineFFI {
         self.inner.evaluate_capability(&capability_name, &role)
     }
 
-    // ─── ECNP ───
+    pub fn evaluate_capabilities(&self, requests: Vec<CapabilityRequest>) -> Vec<PolicyDecision> {
+        self.inner.evaluate_capabilities(
+            requests
+                .into_iter()
+                .map(|r| (r.capability_name, r.role))
+                .collect(),
+        )
+    }
+
+    pub fn capability_infos(&self) -> Vec<CapabilityInfo> {
+        self.inner.capability_infos()
+    }
+
+    pub fn permission_matrix(&self) -> PermissionMatrix {
+        self.inner.permission_matrix()
+    }
+
+    pub fn permission_matrix_csv(&self) -> String {
+        self.inner.permission_matrix().to_csv()
+    }
+
+    pub fn capabilities_at_risk(&self, level: u8) -> Result<Vec<CapabilityInfo>, EdgeClawError> {
+        Ok(self.inner.capabilities_at_risk(RiskLevel::try_from(level)?))
+    }
+
+    pub fn capabilities_up_to(&self, level: u8) -> Result<Vec<CapabilityInfo>, EdgeClawError> {
+        Ok(self.inner.capabilities_up_to(RiskLevel::try_from(level)?))
+    }
 
-    pub fn encode_ecnp(
+    pub fn deny_capability(&self, name: String) -> Result<(), EdgeClawError> {
+        self.inner.deny_capability(&name)
+    }
+
+    pub fn allow_capability(&self, name: String) -> Result<(), EdgeClawError> {
+        self.inner.allow_capability(&name)
+    }
+
+    pub fn broadcast_capability_revocation(
         &self,
-        msg_type: u8,
-        payload: Vec<u8>,
+        capability: String,
+        affected_roles: Vec<String>,
     ) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner
+            .broadcast_capability_revocation(&capability, affected_roles)
+    }
+
+    pub fn handle_control_frame(&self, frame: Vec<u8>) -> Result<(), EdgeClawError> {
+        self.inner.handle_control_frame(&frame).map(|_| ())
+    }
+
+    pub fn set_policy_report_only(&self, enabled: bool) -> Result<(), EdgeClawError> {
+        self.inner.set_policy_report_only(enabled)
+    }
+
+    pub fn policy_audit_log(&self) -> Vec<PolicyAuditEntry> {
+        self.inner.policy_audit_log()
+    }
+
+    pub fn export_policy_audit_ndjson(&self) -> String {
+        self.inner.export_policy_audit_ndjson()
+    }
+
+    pub fn drain_policy_audit_ndjson(&self) -> Result<String, EdgeClawError> {
+        self.inner.drain_policy_audit_ndjson()
+    }
+
+    // ─── Backup ───
+
+    pub fn export_snapshot(&self, passphrase: String) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.export_snapshot(&passphrase)
+    }
+
+    pub fn import_snapshot(&self, blob: Vec<u8>, passphrase: String) -> Result<(), EdgeClawError> {
+        self.inner.import_snapshot(&blob, &passphrase)
+    }
+
+    // ─── ECNP ───
+
+    pub fn encode_ecnp(&self, msg_type: u8, payload: Vec<u8>) -> Result<Vec<u8>, EdgeClawError> {
         let mt = MessageType::try_from(msg_type)?;
         self.inner.encode_ecnp(mt, &payload)
     }
@@ -142,6 +577,14 @@ impl EdgeClawEngineFFI {
         self.inner.init_sync(config)
     }
 
+    pub fn sync_begin_upgrade(&self) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.sync_begin_upgrade()
+    }
+
+    pub fn sync_finish_upgrade(&self, reply: Vec<u8>) -> Result<(), EdgeClawError> {
+        self.inner.sync_finish_upgrade(&reply)
+    }
+
     pub fn sync_remote_exec(
         &self,
         command: String,
@@ -150,6 +593,31 @@ impl EdgeClawEngineFFI {
         self.inner.sync_remote_exec(&command, args)
     }
 
+    pub fn sync_remote_exec_tracked(
+        &self,
+        command: String,
+        args: Vec<String>,
+    ) -> Result<RemoteExecRequest, EdgeClawError> {
+        self.inner.sync_remote_exec_tracked(&command, args)
+    }
+
+    pub fn can_remote_exec(
+        &self,
+        command: String,
+        args: Vec<String>,
+        role: String,
+    ) -> RemoteExecGate {
+        self.inner.can_remote_exec(&command, &args, &role)
+    }
+
+    pub fn sync_cancel_exec(&self, request_id: String) -> Result<Vec<u8>, EdgeClawError> {
+        self.inner.sync_cancel_exec(&request_id)
+    }
+
+    pub fn sync_update_address(&self, address: String) -> Result<bool, EdgeClawError> {
+        self.inner.sync_update_address(&address)
+    }
+
     pub fn sync_process_incoming(&self, frame: Vec<u8>) -> Result<String, EdgeClawError> {
         let msg = self.inner.sync_process_incoming(&frame)?;
         serde_json::to_string(&msg).map_err(|_| EdgeClawError::SerializationError)
@@ -163,11 +631,37 @@ impl EdgeClawEngineFFI {
         self.inner.sync_shutdown()
     }
 
+    pub fn sync_pause(&self) -> Result<(), EdgeClawError> {
+        self.inner.sync_pause()
+    }
+
+    pub fn sync_resume(&self) -> Result<(), EdgeClawError> {
+        self.inner.sync_resume()
+    }
+
+    pub fn sync_set_connection_listener(
+        &self,
+        listener: Box<dyn SyncConnectionListener>,
+    ) -> Result<(), EdgeClawError> {
+        self.inner
+            .sync_set_connection_listener(Box::new(move |state| {
+                listener.on_state_changed(state.to_string())
+            }))
+    }
+
     // ─── Logging ───
 
     pub fn log_event(&self, level: String, message: String) {
         self.inner.log_event(&level, &message)
     }
+
+    pub fn recent_logs(&self) -> Vec<String> {
+        self.inner.recent_logs()
+    }
+
+    pub fn clear_logs(&self) {
+        self.inner.clear_logs()
+    }
 }
 
 /// Top-level factory function exported by UniFFI.
@@ -175,6 +669,21 @@ pub fn create_engine(config: EngineConfig) -> Result<Arc<EdgeClawEngineFFI>, Edg
     Ok(Arc::new(EdgeClawEngineFFI::new(config)?))
 }
 
+/// Numeric pairing PIN from two fingerprints (see `identity::pairing_pin`).
+pub fn pairing_pin(local_fingerprint: String, remote_fingerprint: String, digits: u8) -> String {
+    crate::identity::pairing_pin(&local_fingerprint, &remote_fingerprint, digits)
+}
+
+/// Canonical `ConfigSync` hash (see `sync::compute_config_hash`).
+pub fn compute_config_hash(config_data: String) -> String {
+    crate::sync::compute_config_hash(&config_data)
+}
+
+/// Risk of a remote exec command line (see `sync::classify_command`).
+pub fn classify_command(command: String, args: Vec<String>) -> crate::sync::CommandRisk {
+    crate::sync::classify_command(&command, &args)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +696,13 @@ mod tests {
             max_connections: 8,
             quic_enabled: false,
             log_level: "warn".to_string(),
+            log_ring_capacity: 0,
+            device_id_mode: crate::DeviceIdMode::Uuid,
+            session_duration_secs: 3600,
+            max_active_sessions: 64,
+            discovery_rate_limit: 64,
+            discovery_global_rate_limit: 256,
+            log_format: crate::logging::LogFormat::Json,
         }
     }
 
@@ -242,6 +758,32 @@ mod tests {
         assert!(d.allowed);
     }
 
+    #[test]
+    fn test_ffi_policy_batch() {
+        let engine = create_engine(test_config()).unwrap();
+        let request = |c: &str, r: &str| CapabilityRequest {
+            capability_name: c.into(),
+            role: r.into(),
+        };
+        let decisions = engine.evaluate_capabilities(vec![
+            request("shell_exec", "admin"),
+            request("status_query", "nobody"),
+            request("file_write", "admin"),
+        ]);
+        let allowed: Vec<bool> = decisions.iter().map(|d| d.allowed).collect();
+        assert_eq!(allowed, vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_ffi_encrypt_broadcast_reports_errors() {
+        let engine = create_engine(test_config()).unwrap();
+        let results = engine.encrypt_broadcast(vec!["missing".into()], b"hi".to_vec());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].session_id, "missing");
+        assert!(results[0].ciphertext.is_none());
+        assert!(results[0].error.is_some());
+    }
+
     #[test]
     fn test_ffi_ecnp_roundtrip() {
         let engine = create_engine(test_config()).unwrap();
@@ -250,12 +792,32 @@ mod tests {
         assert_eq!(decoded.payload, b"test");
     }
 
+    #[test]
+    fn test_ffi_ecm_struct_matches_json() {
+        let engine = create_engine(test_config()).unwrap();
+        engine.generate_identity().unwrap();
+
+        let json: EcmPayload = serde_json::from_str(&engine.create_ecm().unwrap()).unwrap();
+        let ecm = engine.create_ecm_struct().unwrap();
+        assert_eq!(ecm.device_id, json.device_id);
+        assert_eq!(ecm.device_type, json.device_type);
+        assert_eq!(ecm.capabilities, json.capabilities);
+        assert_eq!(ecm.os, json.os);
+        assert_eq!(ecm.version, json.version);
+    }
+
     #[test]
     fn test_ffi_sync_lifecycle() {
         let engine = create_engine(test_config()).unwrap();
         let cfg = SyncClientConfig::default();
         engine.init_sync(cfg).unwrap();
         assert!(!engine.sync_is_connected());
+        assert!(engine.sync_finish_upgrade(vec![]).is_err());
+        let request = engine.sync_begin_upgrade().unwrap();
+        assert_eq!(
+            engine.decode_ecnp(request).unwrap().msg_type,
+            MessageType::Control as u8
+        );
         engine.sync_shutdown().unwrap();
     }
 